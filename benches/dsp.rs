@@ -0,0 +1,133 @@
+// Benchmarks for the real-time conversion and mixing hot paths
+//
+// Compares the scalar reference loops with the runtime-dispatched vector
+// paths, per chunk and over a full hour of 48 kHz stereo audio.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use meeting_recorder::dsp;
+
+const SAMPLE_RATE: usize = 48_000;
+const CHANNELS: usize = 2;
+/// One second of interleaved stereo samples
+const SECOND: usize = SAMPLE_RATE * CHANNELS;
+/// A 10 ms callback chunk, matching what the recorder typically receives
+const CHUNK: usize = SECOND / 100;
+
+fn sine(len: usize, freq: f32) -> Vec<f32> {
+    (0..len)
+        .map(|i| (i as f32 * freq * 2.0 * std::f32::consts::PI / SAMPLE_RATE as f32).sin() * 0.8)
+        .collect()
+}
+
+fn bench_convert(c: &mut Criterion) {
+    let input = sine(SECOND, 440.0);
+    let mut output = vec![0i16; SECOND];
+
+    let mut group = c.benchmark_group("convert_f32_to_i16");
+    group.throughput(Throughput::Elements(SECOND as u64));
+    group.bench_function("scalar", |b| {
+        b.iter(|| dsp::convert_f32_to_i16_scalar(black_box(&input), &mut output))
+    });
+    group.bench_function("simd", |b| {
+        b.iter(|| dsp::convert_f32_to_i16(black_box(&input), &mut output))
+    });
+    group.finish();
+}
+
+fn bench_mix(c: &mut Criterion) {
+    let mut a = vec![0i16; SECOND];
+    let mut b = vec![0i16; SECOND];
+    dsp::convert_f32_to_i16(&sine(SECOND, 440.0), &mut a);
+    dsp::convert_f32_to_i16(&sine(SECOND, 660.0), &mut b);
+    let mut output = vec![0i16; SECOND];
+
+    let mut group = c.benchmark_group("mix_saturating");
+    group.throughput(Throughput::Elements(SECOND as u64));
+    group.bench_function("scalar", |bench| {
+        bench.iter(|| dsp::mix_saturating_scalar(black_box(&a), black_box(&b), &mut output))
+    });
+    group.bench_function("simd", |bench| {
+        bench.iter(|| dsp::mix_saturating(black_box(&a), black_box(&b), &mut output))
+    });
+    group.finish();
+}
+
+fn bench_upmix(c: &mut Criterion) {
+    let mut mono = vec![0i16; SAMPLE_RATE];
+    dsp::convert_f32_to_i16(&sine(SAMPLE_RATE, 440.0), &mut mono);
+    let mut output = Vec::with_capacity(SECOND);
+
+    let mut group = c.benchmark_group("append_stereo");
+    group.throughput(Throughput::Elements(SAMPLE_RATE as u64));
+    group.bench_function("mono", |b| {
+        b.iter(|| {
+            output.clear();
+            dsp::append_stereo(&mut output, black_box(&mono), 1);
+        })
+    });
+    group.finish();
+}
+
+fn bench_resample(c: &mut Criterion) {
+    let mut group = c.benchmark_group("resample");
+    for (from, to) in [(44_100, 48_000), (16_000, 48_000), (96_000, 48_000)] {
+        let frames = from as usize;
+        let mut input = vec![0i16; frames * CHANNELS];
+        dsp::convert_f32_to_i16(&sine(frames * CHANNELS, 440.0), &mut input);
+        let mut output = Vec::with_capacity(SECOND + CHANNELS);
+
+        group.throughput(Throughput::Elements(input.len() as u64));
+        group.bench_function(BenchmarkId::from_parameter(format!("{}-{}", from, to)), |b| {
+            let mut resampler = dsp::StereoResampler::new(from, to);
+            b.iter(|| {
+                output.clear();
+                // Feed 10 ms chunks, as the mixer does
+                for chunk in input.chunks(from as usize / 100 * CHANNELS) {
+                    resampler.process(black_box(chunk), &mut output);
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Convert and mix an hour of audio in 10 ms chunks, as a live session would
+fn bench_hour_session(c: &mut Criterion) {
+    let mic = sine(SECOND, 440.0);
+    let sys = sine(SECOND, 660.0);
+    let mut mic_i16 = vec![0i16; CHUNK];
+    let mut sys_i16 = vec![0i16; CHUNK];
+    let mut mixed = vec![0i16; CHUNK];
+
+    let mut group = c.benchmark_group("hour_session");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements((SECOND * 3600) as u64));
+
+    for simd in [false, true] {
+        let name = if simd { "simd" } else { "scalar" };
+        group.bench_with_input(BenchmarkId::from_parameter(name), &simd, |bench, &simd| {
+            bench.iter(|| {
+                for _ in 0..3600 {
+                    for start in (0..SECOND).step_by(CHUNK) {
+                        let mic_chunk = &mic[start..start + CHUNK];
+                        let sys_chunk = &sys[start..start + CHUNK];
+                        if simd {
+                            dsp::convert_f32_to_i16(mic_chunk, &mut mic_i16);
+                            dsp::convert_f32_to_i16(sys_chunk, &mut sys_i16);
+                            dsp::mix_saturating(&mic_i16, &sys_i16, &mut mixed);
+                        } else {
+                            dsp::convert_f32_to_i16_scalar(mic_chunk, &mut mic_i16);
+                            dsp::convert_f32_to_i16_scalar(sys_chunk, &mut sys_i16);
+                            dsp::mix_saturating_scalar(&mic_i16, &sys_i16, &mut mixed);
+                        }
+                        black_box(&mixed);
+                    }
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_convert, bench_upmix, bench_resample, bench_mix, bench_hour_session);
+criterion_main!(benches);
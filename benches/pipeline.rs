@@ -0,0 +1,83 @@
+// End-to-end benchmarks of the real-time path, fed by synthetic sources
+//
+// Each case pushes a minute of audio through conversion, upmixing,
+// resampling and mixing in 10 ms callback chunks, the way a live session
+// with the given pair of devices would. Run with `--features synthetic`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use meeting_recorder::dsp;
+use meeting_recorder::mixer::{Mixer, Source, SourceFormat};
+use meeting_recorder::synthetic::{SyntheticSource, Waveform};
+
+const OUTPUT_RATE: u32 = 48_000;
+const SECONDS: usize = 60;
+const CHUNK_MS: u32 = 10;
+
+/// A synthetic source with one pre-generated callback chunk
+struct Input {
+    source: SyntheticSource,
+    raw: Vec<f32>,
+    converted: Vec<i16>,
+}
+
+impl Input {
+    fn new(source: SyntheticSource) -> Self {
+        let len = source.chunk_len(CHUNK_MS);
+        let mut input = Self {
+            source,
+            raw: vec![0.0; len],
+            converted: vec![0; len],
+        };
+        // Generate once up front so only the real-time path is measured
+        input.source.fill(&mut input.raw);
+        input
+    }
+
+    fn format(&self) -> SourceFormat {
+        SourceFormat {
+            channels: self.source.channels(),
+            sample_rate: self.source.sample_rate(),
+        }
+    }
+
+    /// Convert the chunk as a capture callback would and hand it to the mixer
+    fn process(&mut self, mixer: &mut Mixer, source: Source) {
+        dsp::convert_f32_to_i16(&self.raw, &mut self.converted);
+        mixer.push(source, &self.converted);
+    }
+}
+
+fn bench_sessions(c: &mut Criterion) {
+    let cases = [
+        ("mono-48k+stereo-48k", (48_000, 1), (48_000, 2)),
+        ("mono-44k+stereo-48k", (44_100, 1), (48_000, 2)),
+        ("mono-16k+stereo-44k", (16_000, 1), (44_100, 2)),
+    ];
+
+    let mut group = c.benchmark_group("session_minute");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements((OUTPUT_RATE as usize * 2 * SECONDS) as u64));
+
+    for (name, (mic_rate, mic_channels), (sys_rate, sys_channels)) in cases {
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            let mut mic = Input::new(SyntheticSource::new(Waveform::Sine(440.0), mic_rate, mic_channels));
+            let mut sys = Input::new(SyntheticSource::new(Waveform::Noise, sys_rate, sys_channels));
+            let mut mixer = Mixer::new(mic.format(), Some(sys.format()), OUTPUT_RATE);
+            let mut mixed = Vec::new();
+
+            b.iter(|| {
+                for _ in 0..SECONDS * 1000 / CHUNK_MS as usize {
+                    mic.process(&mut mixer, Source::Microphone);
+                    sys.process(&mut mixer, Source::SystemAudio);
+                    mixed.clear();
+                    mixer.mix(&mut mixed);
+                    black_box(&mixed);
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_sessions);
+criterion_main!(benches);
@@ -0,0 +1,204 @@
+//! Recording whenever there is sound
+//!
+//! `meeting-recorder watch` listens to the system audio source and starts
+//! recording once sound has kept up for `start_after_secs`, then closes the
+//! recording after `stop_after_silence_secs` of silence. Short pauses, as
+//! between words, don't count as silence for starting.
+
+use cpal::traits::{DeviceTrait, StreamTrait};
+use cpal::SupportedStreamConfig;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::dsp::{self, LevelMeter, Levels};
+use crate::error::{Result, StreamError};
+
+/// Quiet that interrupts sound building up to a recording
+const GAP: Duration = Duration::from_secs(1);
+
+/// When `meeting-recorder watch` starts and stops recording
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActivityWatch {
+    /// Peak level in dBFS above which the system audio counts as sound
+    #[serde(default = "default_threshold_db")]
+    pub threshold_db: f32,
+    /// How long sound must keep up before recording starts, in seconds
+    #[serde(default = "default_start_after_secs")]
+    pub start_after_secs: u64,
+    /// How long silence must last before the recording is closed, in seconds
+    #[serde(default = "default_stop_after_silence_secs")]
+    pub stop_after_silence_secs: u64,
+}
+
+fn default_threshold_db() -> f32 {
+    -45.0
+}
+
+fn default_start_after_secs() -> u64 {
+    3
+}
+
+fn default_stop_after_silence_secs() -> u64 {
+    120
+}
+
+impl Default for ActivityWatch {
+    fn default() -> Self {
+        Self {
+            threshold_db: default_threshold_db(),
+            start_after_secs: default_start_after_secs(),
+            stop_after_silence_secs: default_stop_after_silence_secs(),
+        }
+    }
+}
+
+impl ActivityWatch {
+    /// A detector waiting for sound
+    pub fn detector(&self) -> ActivityDetector {
+        ActivityDetector {
+            threshold: 10f32.powf(self.threshold_db / 20.0),
+            start_after: Duration::from_secs(self.start_after_secs),
+            stop_after: Duration::from_secs(self.stop_after_silence_secs),
+            sound_for: Duration::ZERO,
+            quiet_for: Duration::ZERO,
+            active: false,
+        }
+    }
+}
+
+/// A change [`ActivityDetector::update`] reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Activity {
+    /// Sound has kept up long enough to record
+    Started,
+    /// Silence has lasted long enough to stop
+    Stopped,
+}
+
+/// Tracks sound and silence to tell when to start and stop recording
+#[derive(Debug, Clone)]
+pub struct ActivityDetector {
+    /// Peak level, from 0.0 to 1.0, below which the source counts as silent
+    threshold: f32,
+    start_after: Duration,
+    stop_after: Duration,
+    /// Sound so far towards starting
+    sound_for: Duration,
+    /// Silence since the last sound
+    quiet_for: Duration,
+    /// Whether sound has started and not stopped yet
+    active: bool,
+}
+
+impl ActivityDetector {
+    /// Take the levels of the last `elapsed`, returning when activity starts or stops
+    pub fn update(&mut self, levels: Levels, elapsed: Duration) -> Option<Activity> {
+        if levels.peak >= self.threshold {
+            self.quiet_for = Duration::ZERO;
+            self.sound_for += elapsed;
+            if !self.active && self.sound_for >= self.start_after {
+                self.active = true;
+                return Some(Activity::Started);
+            }
+            return None;
+        }
+        self.quiet_for += elapsed;
+        if !self.active && self.quiet_for >= GAP {
+            self.sound_for = Duration::ZERO;
+        }
+        if self.active && self.quiet_for >= self.stop_after {
+            self.active = false;
+            self.sound_for = Duration::ZERO;
+            return Some(Activity::Stopped);
+        }
+        None
+    }
+
+    /// Whether sound has started and not stopped yet
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+}
+
+/// Levels of an input device, measured without recording it
+pub struct LevelMonitor {
+    _stream: cpal::Stream,
+    meter: Arc<Mutex<LevelMeter>>,
+    error: Arc<Mutex<Option<cpal::StreamError>>>,
+}
+
+impl LevelMonitor {
+    /// Start measuring `device` in `config`
+    pub fn start(device: &cpal::Device, config: &SupportedStreamConfig) -> Result<Self> {
+        let meter = Arc::new(Mutex::new(LevelMeter::default()));
+        let error = Arc::new(Mutex::new(None));
+        let (callback_meter, callback_error) = (meter.clone(), error.clone());
+        let mut samples = Vec::new();
+        let stream = device
+            .build_input_stream(
+                &config.clone().into(),
+                move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                    samples.resize(data.len(), 0);
+                    dsp::convert_f32_to_i16(data, &mut samples);
+                    if let Ok(mut meter) = callback_meter.lock() {
+                        meter.add(&samples);
+                    }
+                },
+                move |e| {
+                    if let Ok(mut error) = callback_error.lock() {
+                        error.get_or_insert(e);
+                    }
+                },
+                None,
+            )
+            .map_err(StreamError::from)?;
+        stream.play().map_err(StreamError::from)?;
+        Ok(Self { _stream: stream, meter, error })
+    }
+
+    /// Levels since the last call, or the error that stopped the device
+    pub fn take(&self) -> Result<Levels> {
+        if let Some(e) = self.error.lock().ok().and_then(|mut error| error.take()) {
+            return Err(StreamError::from(e).into());
+        }
+        Ok(self.meter.lock().map_or_else(|_| Levels::default(), |mut meter| meter.take()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn levels(peak: f32) -> Levels {
+        Levels { peak, rms: peak / 2.0 }
+    }
+
+    #[test]
+    fn test_starts_on_sustained_sound_and_stops_after_silence() {
+        let watch = ActivityWatch { threshold_db: -40.0, start_after_secs: 2, stop_after_silence_secs: 5 };
+        let mut detector = watch.detector();
+        let step = Duration::from_millis(500);
+        let quiet = levels(0.001);
+        let loud = levels(0.5);
+
+        // A click followed by a second of quiet doesn't build up
+        assert_eq!(detector.update(loud, step), None);
+        assert_eq!(detector.update(quiet, Duration::from_secs(1)), None);
+        for _ in 0..3 {
+            assert_eq!(detector.update(loud, step), None);
+        }
+        // A pause between words doesn't interrupt it
+        assert_eq!(detector.update(quiet, step), None);
+        assert_eq!(detector.update(loud, step), Some(Activity::Started));
+        assert!(detector.is_active());
+
+        // Silence short of the stop time is bridged
+        assert_eq!(detector.update(quiet, Duration::from_secs(4)), None);
+        assert_eq!(detector.update(loud, step), None);
+        assert_eq!(detector.update(quiet, Duration::from_secs(4)), None);
+        assert_eq!(detector.update(quiet, Duration::from_secs(1)), Some(Activity::Stopped));
+        assert!(!detector.is_active());
+        assert_eq!(detector.update(loud, step), None);
+    }
+}
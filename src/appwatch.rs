@@ -0,0 +1,135 @@
+//! Recording whenever a meeting app uses the microphone
+//!
+//! `meeting-recorder auto --mic DEVICE` checks every `poll_secs` which
+//! programs are capturing from the microphone. When one of the apps in
+//! `meeting_apps` starts to, it starts recording, named after the app, or
+//! with `prompt` asks first on the terminal. Once the app has let go of the
+//! microphone for `stop_delay_secs`, the recording is stopped and saved. A
+//! recording that was declined, or stopped on its own, is not started again
+//! until the app has let go. Lines typed while recording add markers.
+
+use meeting_recorder::detect;
+use meeting_recorder::marker;
+use meeting_recorder::{Config, DeviceManager};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{control, finish_recording, select_device, shutdown, start_recording, Current};
+
+/// How often the stop flag and typed lines are checked between polls
+const TICK: Duration = Duration::from_millis(100);
+
+/// Record meeting apps' calls until a signal arrives, from `mic` and `system`
+pub fn run(mic: &str, system: Option<&str>, prompt: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::load()?;
+    let mut options = config.meeting_apps.clone().unwrap_or_default();
+    options.prompt |= prompt;
+    // Fail now rather than at the first call if a device is missing or apps can't be seen
+    let device_manager = DeviceManager::new()?;
+    select_device(&device_manager, mic)?;
+    if let Some(system) = system {
+        select_device(&device_manager, system)?;
+    }
+    drop(device_manager);
+    detect::microphone_users().map_err(|e| format!("Can't tell which apps use the microphone: {}", e))?;
+
+    let running = Arc::new(AtomicBool::new(true));
+    let _shutdown = shutdown::install(running.clone())?;
+    println!("Waiting for {} to use the microphone; press Ctrl+C to stop", options.apps.join(", "));
+
+    let typed = control::stdin_lines();
+    let poll_interval = Duration::from_secs(options.poll_secs.max(1));
+    let stop_delay = Duration::from_secs(options.stop_delay_secs);
+    // Recording of an app's call, with when the app was last seen without
+    // the microphone if it has let go
+    let mut current: Option<Current<Option<Instant>>> = None;
+    // App not to record until it lets go of the microphone
+    let mut passed_over: Option<String> = None;
+    // App asked about, waiting for an answer
+    let mut asking: Option<String> = None;
+    let mut last_poll: Option<Instant> = None;
+    while running.load(Ordering::SeqCst) {
+        if let Ok(line) = typed.try_recv() {
+            match (&current, asking.take()) {
+                (Some(recording), _) => {
+                    control::mark(&recording.session, marker::typed_label(&line, &config.marker_labels));
+                }
+                (None, Some(app)) if is_yes(&line) => {
+                    current = start(&config, mic, system, &app);
+                    passed_over = current.is_none().then_some(app);
+                }
+                (None, Some(app)) => passed_over = Some(app),
+                (None, None) => {}
+            }
+        }
+        if last_poll.is_some_and(|polled| polled.elapsed() < poll_interval) {
+            thread::sleep(TICK);
+            continue;
+        }
+        last_poll = Some(Instant::now());
+
+        let app = match detect::microphone_users() {
+            Ok(users) => users.iter().find_map(|user| options.app_for(user)).map(str::to_string),
+            Err(e) => {
+                eprintln!("Warning: can't tell which apps use the microphone: {}", e);
+                continue;
+            }
+        };
+        if let Some(recording) = &mut current {
+            let stopped_itself = !recording.session.is_running();
+            recording.state = match (&app, recording.state) {
+                (Some(_), _) => None,
+                (None, released) => released.or_else(|| Some(Instant::now())),
+            };
+            if stopped_itself || recording.state.is_some_and(|released| released.elapsed() >= stop_delay) {
+                if stopped_itself {
+                    passed_over = app.clone();
+                }
+                finish_recording(current.take());
+            }
+        }
+        match app {
+            None => {
+                passed_over = None;
+                if asking.take().is_some() {
+                    println!("\nNo longer in a call; not recording");
+                }
+            }
+            Some(app) if current.is_some() || passed_over.as_ref() == Some(&app) || asking.is_some() => {}
+            Some(app) if options.prompt => {
+                print!("{} is using the microphone. Record? [Y/n] ", app);
+                io::stdout().flush()?;
+                asking = Some(app);
+            }
+            Some(app) => {
+                current = start(&config, mic, system, &app);
+                // Not retried until the next call, rather than every poll
+                passed_over = current.is_none().then_some(app);
+            }
+        }
+    }
+    finish_recording(current);
+    Ok(())
+}
+
+fn is_yes(answer: &str) -> bool {
+    matches!(answer.trim().to_lowercase().as_str(), "" | "y" | "yes")
+}
+
+/// Start recording the call `app` is in, reporting a failure
+fn start(config: &Config, mic: &str, system: Option<&str>, app: &str) -> Option<Current<Option<Instant>>> {
+    match start_recording(config, mic, system, Some(app)) {
+        Ok((session, lock)) => {
+            let path = session.path().map_or("no file".into(), |path| path.display().to_string());
+            println!("{} is using the microphone; recording to {}", app, path);
+            Some(Current { session, _lock: lock, name: Some(format!("the {} call", app)), state: None })
+        }
+        Err(e) => {
+            eprintln!("Error: could not start recording: {}", e);
+            None
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! Async recording API for tokio applications (`tokio` feature)
+//!
+//! Capture still runs on its own threads; these wrappers move the blocking
+//! start-up and finalization onto tokio's blocking pool so async services can
+//! await them, and deliver events and audio as [`Stream`]s.
+
+use futures_core::Stream;
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::error::{RecorderError, Result};
+use crate::events::RecorderEvent;
+use crate::frame::Frame;
+use crate::marker::Marker;
+use crate::mixer::MixLevels;
+use crate::recorder::{Recorder, RecordingResult, RecordingSession};
+use crate::state::RecordingState;
+
+impl Recorder {
+    /// Start recording without blocking the async runtime
+    pub async fn start_async(&self, config: &Config) -> Result<AsyncRecordingSession> {
+        let recorder = self.clone();
+        let config = config.clone();
+        let session = tokio::task::spawn_blocking(move || recorder.start(&config))
+            .await
+            .map_err(|_| RecorderError::Thread("start task failed"))??;
+        Ok(AsyncRecordingSession { session })
+    }
+
+    /// Record until stopped, awaiting the finalized result
+    pub async fn record_async(&self, config: &Config) -> Result<RecordingResult> {
+        self.start_async(config).await?.join().await
+    }
+
+    /// Record for `duration`, awaiting the finalized result
+    pub async fn record_for_async(&self, duration: Duration, config: &Config) -> Result<RecordingResult> {
+        let session = self.start_async(config).await?;
+        session.stop_after(duration);
+        session.join().await
+    }
+
+    /// Receive [`RecorderEvent`]s as an async stream
+    pub fn event_stream(&self) -> EventStream {
+        EventStream {
+            rx: self.events().subscribe_async(),
+        }
+    }
+
+    /// Receive the mixed audio as an async stream of [`Frame`]s
+    pub fn frame_stream(&self) -> FrameStream {
+        FrameStream {
+            rx: self.frame_broadcast().subscribe_async(),
+        }
+    }
+}
+
+/// Async handle to a recording running in the background
+pub struct AsyncRecordingSession {
+    session: RecordingSession,
+}
+
+impl AsyncRecordingSession {
+    /// Unique ID of the session
+    pub fn id(&self) -> Uuid {
+        self.session.id()
+    }
+
+    /// Final path of the recording, unless WAV output is turned off
+    pub fn path(&self) -> Option<&Path> {
+        self.session.path()
+    }
+
+    /// Whether the recording is still capturing audio
+    pub fn is_running(&self) -> bool {
+        self.session.is_running()
+    }
+
+    /// Stop and finalize the recording on its own at `deadline`
+    pub fn stop_at(&self, deadline: Instant) {
+        self.session.stop_at(deadline);
+    }
+
+    /// Stop and finalize the recording on its own once `duration` has passed
+    pub fn stop_after(&self, duration: Duration) {
+        self.session.stop_after(duration);
+    }
+
+    /// What the session is doing
+    pub fn state(&self) -> RecordingState {
+        self.session.state()
+    }
+
+    /// Latest live levels; see [`RecordingSession::levels`]
+    pub fn levels(&self) -> MixLevels {
+        self.session.levels()
+    }
+
+    /// Mark the current point of the recording; see [`RecordingSession::add_marker`]
+    pub fn add_marker(&self, label: impl Into<String>) -> Marker {
+        self.session.add_marker(label)
+    }
+
+    /// Suspend capture; see [`RecordingSession::pause`]
+    pub fn pause(&self) -> bool {
+        self.session.pause()
+    }
+
+    /// Continue capturing after [`pause`](Self::pause)
+    pub fn resume(&self) -> bool {
+        self.session.resume()
+    }
+
+    /// Stop the recording and wait for its file to be finalized
+    pub async fn stop(self) -> Result<RecordingResult> {
+        self.session.stop();
+        self.join().await
+    }
+
+    /// Wait for the recording to end on its own or be stopped elsewhere
+    pub async fn join(self) -> Result<RecordingResult> {
+        let session = self.session;
+        tokio::task::spawn_blocking(move || session.join())
+            .await
+            .map_err(|_| RecorderError::Thread("join task failed"))?
+    }
+}
+
+/// Stream of events from a recorder; ends when the recorder is dropped
+pub struct EventStream {
+    rx: mpsc::Receiver<RecorderEvent>,
+}
+
+impl EventStream {
+    /// Wait for the next event
+    pub async fn next(&mut self) -> Option<RecorderEvent> {
+        self.rx.recv().await
+    }
+}
+
+impl Stream for EventStream {
+    type Item = RecorderEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+/// Stream of mixed audio from a recorder; ends when the recorder is dropped
+pub struct FrameStream {
+    rx: mpsc::Receiver<Frame>,
+}
+
+impl FrameStream {
+    /// Wait for the next block of audio
+    pub async fn next(&mut self) -> Option<Frame> {
+        self.rx.recv().await
+    }
+}
+
+impl Stream for FrameStream {
+    type Item = Frame;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventBus;
+
+    #[test]
+    fn test_event_stream_receives_events() {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let bus = EventBus::default();
+        let mut stream = EventStream { rx: bus.subscribe_async() };
+
+        bus.emit(RecorderEvent::Error { message: "test".into() });
+        drop(bus);
+
+        runtime.block_on(async {
+            assert_eq!(stream.next().await, Some(RecorderEvent::Error { message: "test".into() }));
+            assert_eq!(stream.next().await, None);
+        });
+    }
+}
@@ -0,0 +1,139 @@
+//! Recording the meetings of a calendar
+//!
+//! `meeting-recorder calendar --mic DEVICE` reads the feed set as `calendar`
+//! in the config every `refresh_minutes` and records each event its rules
+//! pick, from `start_early_secs` before the event until `stop_late_secs`
+//! after it, or until the next picked event begins. Recordings are named
+//! after their events. An event is recorded once, even if its recording is
+//! stopped early, and a feed that can't be read is tried again at the next
+//! refresh while the events already known are kept.
+
+use jiff::tz::TimeZone;
+use jiff::Timestamp;
+use meeting_recorder::calendar::{Calendar, Event, Feed};
+use meeting_recorder::{Config, DeviceManager};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::{finish_recording, select_device, shutdown, start_recording, Current};
+
+/// How often the schedule is checked
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// How far around now the feed's events are looked at
+const WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+/// How far ahead `--list` looks
+const LIST_AHEAD: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Record the calendar's events until a signal arrives, from `mic` and `system`
+pub fn run(mic: &str, system: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::load()?;
+    let calendar = configured(&config)?;
+    // Fail now rather than at the first meeting if a device is missing
+    let device_manager = DeviceManager::new()?;
+    select_device(&device_manager, mic)?;
+    if let Some(system) = system {
+        select_device(&device_manager, system)?;
+    }
+    drop(device_manager);
+
+    let running = Arc::new(AtomicBool::new(true));
+    let _shutdown = shutdown::install(running.clone())?;
+    println!("Waiting for meetings in the calendar to record; press Ctrl+C to stop");
+
+    let mut events: Vec<Event> = Vec::new();
+    let mut next_refresh = Instant::now();
+    let mut current: Option<Current<Event>> = None;
+    // Events recorded so far, by UID and start
+    let mut recorded: HashSet<(String, SystemTime)> = HashSet::new();
+    let mut announced: Option<(String, SystemTime)> = None;
+    while running.load(Ordering::SeqCst) {
+        if Instant::now() >= next_refresh {
+            let now = SystemTime::now();
+            match read_feed(&calendar) {
+                Ok(feed) => events = feed.events_between(now - WINDOW, now + WINDOW),
+                Err(e) => eprintln!("Warning: could not read the calendar: {}", e),
+            }
+            next_refresh = Instant::now() + Duration::from_secs(calendar.refresh_minutes.max(1) * 60);
+        }
+
+        let now = SystemTime::now();
+        let due = calendar
+            .due(&events, now)
+            .filter(|event| !recorded.contains(&(event.uid.clone(), event.start)));
+        if let Some(recording) = &current {
+            let overrunning_into_next = recording.state.end <= now && due.is_some();
+            if now >= calendar.stop_time(&recording.state) || overrunning_into_next || !recording.session.is_running() {
+                finish_recording(current.take());
+            }
+        }
+        if let (None, Some(event)) = (&current, due) {
+            recorded.insert((event.uid.clone(), event.start));
+            match start_recording(&config, mic, system, Some(&event.title)) {
+                Ok((session, lock)) => {
+                    let path = session.path().map_or("no file".into(), |path| path.display().to_string());
+                    println!("Recording \"{}\" until {} to {}", event.title, local_time(calendar.stop_time(event)), path);
+                    let name = Some(format!("\"{}\"", event.title));
+                    current = Some(Current { session, _lock: lock, name, state: event.clone() });
+                }
+                Err(e) => eprintln!("Error: could not start recording \"{}\": {}", event.title, e),
+            }
+        }
+
+        let next = events
+            .iter()
+            .filter(|event| event.start > now && calendar.records(event))
+            .find(|event| !recorded.contains(&(event.uid.clone(), event.start)));
+        let next_key = next.map(|event| (event.uid.clone(), event.start));
+        if let (Some(event), true) = (next, next_key != announced) {
+            println!("Next meeting to record: \"{}\" at {}", event.title, local_time(event.start));
+        }
+        announced = next_key;
+
+        thread::sleep(POLL_INTERVAL);
+    }
+    finish_recording(current);
+    Ok(())
+}
+
+/// Print the coming week's events and whether they would be recorded
+pub fn list() -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::load()?;
+    let calendar = configured(&config)?;
+    let now = SystemTime::now();
+    let events = read_feed(&calendar)?.events_between(now, now + LIST_AHEAD);
+    if events.is_empty() {
+        println!("No events in the coming week");
+    }
+    for event in &events {
+        let mark = if calendar.records(event) { "record" } else { "skip" };
+        println!("{:<7}{} - {}  {}", mark, local_time(event.start), local_time(event.end), event.title);
+    }
+    Ok(())
+}
+
+fn configured(config: &Config) -> Result<Calendar, Box<dyn std::error::Error>> {
+    config
+        .calendar
+        .clone()
+        .ok_or_else(|| "No 'calendar' in the config; add one with the feed's 'url' and 'rules' for the events to record".into())
+}
+
+/// Fetch and parse the feed, printing what in it was left out
+fn read_feed(calendar: &Calendar) -> Result<Feed, Box<dyn std::error::Error>> {
+    let feed = Feed::parse(&meeting_recorder::calendar::fetch(&calendar.url)?)?;
+    for warning in feed.warnings() {
+        eprintln!("Warning: {}", warning);
+    }
+    Ok(feed)
+}
+
+/// `time` in the local time zone, e.g. `Tue Mar 5 10:00`
+fn local_time(time: SystemTime) -> String {
+    Timestamp::try_from(time).map_or_else(
+        |_| "an unknown time".to_string(),
+        |time| time.to_zoned(TimeZone::system()).strftime("%a %b %-d %H:%M").to_string(),
+    )
+}
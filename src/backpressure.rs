@@ -0,0 +1,175 @@
+use crossbeam_channel::{Receiver, SendError, Sender, TrySendError};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use crate::pool::BufferPool;
+
+/// What a capture callback does when its queue to the mixer is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackpressurePolicy {
+    /// Discard the oldest queued chunk to make room for the new one
+    #[default]
+    DropOldest,
+    /// Discard the incoming chunk
+    DropNewest,
+    /// Print a warning and block the callback until the mixer catches up
+    BlockWithWarning,
+}
+
+/// What the mixer does when one source's pending audio exceeds the backlog cap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BacklogPolicy {
+    /// Write the oldest excess audio as if the other source were silent
+    #[default]
+    MixWithSilence,
+    /// Discard the oldest excess audio
+    DropOldest,
+}
+
+/// Sending half of a bounded sample queue that applies a backpressure policy
+pub struct SampleSender {
+    tx: Sender<Vec<i16>>,
+    // Held so drop-oldest can evict from the front of the queue
+    rx: Receiver<Vec<i16>>,
+    policy: BackpressurePolicy,
+    source: &'static str,
+    triggered: Arc<AtomicU64>,
+    /// Set while the queue stays full, so a stall is only warned about once
+    stalled: AtomicBool,
+    pool: BufferPool,
+}
+
+/// Create a bounded sample queue holding at most `capacity` chunks
+///
+/// Chunks discarded by the policy are handed back to `pool` for reuse.
+pub fn bounded(
+    capacity: usize,
+    policy: BackpressurePolicy,
+    source: &'static str,
+    pool: BufferPool,
+) -> (SampleSender, Receiver<Vec<i16>>) {
+    let (tx, rx) = crossbeam_channel::bounded(capacity.max(1));
+    let sender = SampleSender {
+        tx,
+        rx: rx.clone(),
+        policy,
+        source,
+        triggered: Arc::new(AtomicU64::new(0)),
+        stalled: AtomicBool::new(false),
+        pool,
+    };
+    (sender, rx)
+}
+
+impl SampleSender {
+    /// Queue a chunk of samples, applying the policy if the queue is full
+    pub fn send(&self, samples: Vec<i16>) -> Result<(), SendError<Vec<i16>>> {
+        let samples = match self.tx.try_send(samples) {
+            Ok(()) => {
+                self.stalled.store(false, Ordering::Relaxed);
+                return Ok(());
+            }
+            Err(TrySendError::Disconnected(samples)) => return Err(SendError(samples)),
+            Err(TrySendError::Full(samples)) => samples,
+        };
+
+        self.triggered.fetch_add(1, Ordering::Relaxed);
+        match self.policy {
+            BackpressurePolicy::DropOldest => {
+                if let Ok(oldest) = self.rx.try_recv() {
+                    self.pool.recycle(oldest);
+                }
+                match self.tx.try_send(samples) {
+                    Ok(()) => Ok(()),
+                    Err(TrySendError::Full(samples)) => {
+                        self.pool.recycle(samples);
+                        Ok(())
+                    }
+                    Err(TrySendError::Disconnected(samples)) => Err(SendError(samples)),
+                }
+            }
+            BackpressurePolicy::DropNewest => {
+                self.pool.recycle(samples);
+                Ok(())
+            }
+            BackpressurePolicy::BlockWithWarning => {
+                // This runs in the capture callback, so one warning per stall;
+                // `triggered` has the total
+                if !self.stalled.swap(true, Ordering::Relaxed) {
+                    console_err!("Warning: {} queue is full, waiting for the mixer to catch up", self.source);
+                }
+                self.tx.send(samples)
+            }
+        }
+    }
+
+    /// Take an empty buffer from the pool backing this queue
+    pub fn take_buffer(&self) -> Vec<i16> {
+        self.pool.take()
+    }
+
+    /// Shared counter of how many times the policy was triggered
+    pub fn triggered(&self) -> Arc<AtomicU64> {
+        self.triggered.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drop_oldest_keeps_latest_chunks() {
+        let (tx, rx) = bounded(2, BackpressurePolicy::DropOldest, "test", BufferPool::new(4, 0));
+        tx.send(vec![1]).unwrap();
+        tx.send(vec![2]).unwrap();
+        tx.send(vec![3]).unwrap();
+
+        assert_eq!(rx.try_recv().unwrap(), vec![2]);
+        assert_eq!(rx.try_recv().unwrap(), vec![3]);
+        assert_eq!(tx.triggered().load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_drop_newest_keeps_earliest_chunks() {
+        let (tx, rx) = bounded(2, BackpressurePolicy::DropNewest, "test", BufferPool::new(4, 0));
+        tx.send(vec![1]).unwrap();
+        tx.send(vec![2]).unwrap();
+        tx.send(vec![3]).unwrap();
+
+        assert_eq!(rx.try_recv().unwrap(), vec![1]);
+        assert_eq!(rx.try_recv().unwrap(), vec![2]);
+        assert!(rx.try_recv().is_err());
+        assert_eq!(tx.triggered().load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_block_waits_for_consumer() {
+        let (tx, rx) = bounded(1, BackpressurePolicy::BlockWithWarning, "test", BufferPool::new(4, 0));
+        tx.send(vec![1]).unwrap();
+
+        let consumer = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            let first = rx.recv().unwrap();
+            let second = rx.recv().unwrap();
+            (first, second)
+        });
+
+        tx.send(vec![2]).unwrap();
+        assert_eq!(consumer.join().unwrap(), (vec![1], vec![2]));
+        assert_eq!(tx.triggered().load(Ordering::Relaxed), 1);
+        assert!(tx.stalled.load(Ordering::Relaxed));
+
+        // Sending without waiting ends the stall, so the next one is warned about again
+        tx.send(vec![3]).unwrap();
+        assert!(!tx.stalled.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_policy_deserializes_from_kebab_case() {
+        let policy: BackpressurePolicy = serde_yaml::from_str("block-with-warning").unwrap();
+        assert_eq!(policy, BackpressurePolicy::BlockWithWarning);
+    }
+}
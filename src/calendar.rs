@@ -0,0 +1,806 @@
+//! Recording meetings from a calendar
+//!
+//! With `calendar` in the config, `meeting-recorder calendar` reads an
+//! iCalendar (ICS) feed, such as the secret address Google Calendar or
+//! Outlook publish for a calendar or a CalDAV server's export URL, and
+//! records the events its rules pick, naming each recording after its event.
+//!
+//! Reading the feed needs the `calendar` feature. Events may be in UTC, in
+//! a named (IANA) time zone or in floating local time; unknown time zones
+//! are taken as local time. Recurring events are expanded for daily,
+//! weekly, monthly and yearly rules, with their exceptions and with
+//! occurrences that were moved or cancelled. All-day events are never
+//! recorded.
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+#[cfg(feature = "calendar")]
+pub use ics::{fetch, Feed};
+
+/// Calendar whose events are recorded, and which of them
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Calendar {
+    /// ICS feed: an `http://`, `https://` or `webcal://` URL, or a file
+    ///
+    /// Secret addresses carry their key, so this is never copied into the sidecar.
+    #[serde(default, skip_serializing)]
+    pub url: String,
+    /// Events to record; an event is recorded if any rule matches it
+    #[serde(default)]
+    pub rules: Vec<CalendarRule>,
+    /// How often the feed is read again, in minutes
+    #[serde(default = "default_refresh_minutes")]
+    pub refresh_minutes: u64,
+    /// How long before an event starts recording begins, in seconds
+    #[serde(default = "default_start_early_secs")]
+    pub start_early_secs: u64,
+    /// How long after an event's scheduled end recording goes on, in seconds
+    #[serde(default = "default_stop_late_secs")]
+    pub stop_late_secs: u64,
+}
+
+fn default_refresh_minutes() -> u64 {
+    5
+}
+
+fn default_start_early_secs() -> u64 {
+    60
+}
+
+fn default_stop_late_secs() -> u64 {
+    300
+}
+
+/// Which events to record: those matching every condition given, ignoring case
+///
+/// A rule without conditions matches every event.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CalendarRule {
+    /// Text the event's title contains, e.g. `1:1`
+    pub title_contains: Option<String>,
+    /// Text the event's location contains, e.g. `zoom.us` for meetings with a Zoom link
+    pub location_contains: Option<String>,
+    /// Text the organizer's name or address contains
+    pub organizer_contains: Option<String>,
+}
+
+/// One occurrence of a calendar event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub uid: String,
+    pub title: String,
+    pub location: Option<String>,
+    /// Organizer's name and address, as `Name <address>` when both are given
+    pub organizer: Option<String>,
+    pub start: SystemTime,
+    pub end: SystemTime,
+}
+
+impl Calendar {
+    /// Whether the rules pick `event` for recording
+    pub fn records(&self, event: &Event) -> bool {
+        self.rules.iter().any(|rule| rule.matches(event))
+    }
+
+    /// The event to record at `now`: of the picked events under way or about
+    /// to start, the one starting first
+    pub fn due<'a>(&self, events: &'a [Event], now: SystemTime) -> Option<&'a Event> {
+        let early = Duration::from_secs(self.start_early_secs);
+        events
+            .iter()
+            .filter(|event| event.start <= now + early && now < event.end && self.records(event))
+            .min_by_key(|event| event.start)
+    }
+
+    /// When the recording of `event` stops
+    pub fn stop_time(&self, event: &Event) -> SystemTime {
+        event.end + Duration::from_secs(self.stop_late_secs)
+    }
+}
+
+impl CalendarRule {
+    pub fn matches(&self, event: &Event) -> bool {
+        let contains = |text: Option<&str>, pattern: &Option<String>| match pattern {
+            Some(pattern) => text.is_some_and(|text| text.to_lowercase().contains(&pattern.to_lowercase())),
+            None => true,
+        };
+        contains(Some(&event.title), &self.title_contains)
+            && contains(event.location.as_deref(), &self.location_contains)
+            && contains(event.organizer.as_deref(), &self.organizer_contains)
+    }
+}
+
+#[cfg(feature = "calendar")]
+mod ics {
+    use jiff::civil::{Date, DateTime, Weekday};
+    use jiff::tz::TimeZone;
+    use jiff::{Span, Timestamp, Zoned};
+    use std::collections::{HashMap, HashSet};
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+
+    use super::Event;
+    use crate::error::CalendarError;
+
+    /// How long a feed may take to download
+    const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+    /// Most periods of a recurring event looked at, so a rule repeating
+    /// since long ago can't stall the recorder
+    const MAX_PERIODS: i64 = 100_000;
+
+    /// Read the feed at `url`: an `http://`, `https://` or `webcal://` URL, or a file
+    pub fn fetch(url: &str) -> Result<String, CalendarError> {
+        let lower = url.to_ascii_lowercase();
+        if lower.starts_with("http://") || lower.starts_with("https://") || lower.starts_with("webcal://") {
+            // webcal:// is how calendar apps are handed feeds served over HTTPS
+            let address = if lower.starts_with("webcal://") {
+                format!("https://{}", &url["webcal://".len()..])
+            } else {
+                url.to_string()
+            };
+            let fetch_error = |message: String| CalendarError::Fetch { url: url.to_string(), message };
+            let response = ureq::get(&address).timeout(FETCH_TIMEOUT).call().map_err(|e| fetch_error(e.to_string()))?;
+            return response.into_string().map_err(|e| fetch_error(e.to_string()));
+        }
+        let path = PathBuf::from(url.strip_prefix("file://").unwrap_or(url));
+        fs::read_to_string(&path).map_err(|source| CalendarError::Read { path, source })
+    }
+
+    /// The events of an iCalendar feed
+    #[derive(Debug, Clone, Default)]
+    pub struct Feed {
+        series: Vec<Series>,
+        warnings: Vec<String>,
+    }
+
+    impl Feed {
+        /// Read the events of an iCalendar document
+        ///
+        /// Events that can't be understood are left out, with a warning.
+        pub fn parse(ics: &str) -> Result<Self, CalendarError> {
+            let lines = unfold(ics);
+            if !lines.iter().any(|line| line.eq_ignore_ascii_case("BEGIN:VCALENDAR")) {
+                return Err(CalendarError::NotACalendar);
+            }
+            let mut feed = Feed::default();
+            let mut zones = Zones::default();
+            // Components nested in the current event, such as its alarms
+            let mut nesting: Option<usize> = None;
+            let mut properties = Vec::new();
+            for line in &lines {
+                let Some(property) = Property::parse(line) else {
+                    continue;
+                };
+                match (property.name.as_str(), nesting) {
+                    ("BEGIN", None) if property.value.eq_ignore_ascii_case("VEVENT") => {
+                        nesting = Some(0);
+                        properties.clear();
+                    }
+                    ("BEGIN", Some(depth)) => nesting = Some(depth + 1),
+                    ("END", Some(0)) => {
+                        nesting = None;
+                        match Series::new(&properties, &mut zones) {
+                            Ok(Some(series)) => feed.series.push(series),
+                            Ok(None) => {}
+                            Err(warning) => feed.warnings.push(warning),
+                        }
+                    }
+                    ("END", Some(depth)) => nesting = Some(depth - 1),
+                    (_, Some(0)) => properties.push(property),
+                    _ => {}
+                }
+            }
+            feed.warnings.append(&mut zones.warnings);
+            Ok(feed)
+        }
+
+        /// Events left out or only partly understood, for the user to check
+        pub fn warnings(&self) -> &[String] {
+            &self.warnings
+        }
+
+        /// Occurrences of the feed's events overlapping `from` to `to`, by start time
+        pub fn events_between(&self, from: SystemTime, to: SystemTime) -> Vec<Event> {
+            let (Ok(from), Ok(to)) = (Timestamp::try_from(from), Timestamp::try_from(to)) else {
+                return Vec::new();
+            };
+            // Occurrences of a recurring event moved or cancelled on their own
+            let replaced: HashSet<(&str, Timestamp)> = self
+                .series
+                .iter()
+                .filter_map(|series| Some((series.uid.as_str(), series.recurrence_id?)))
+                .collect();
+
+            let mut events = Vec::new();
+            for series in self.series.iter().filter(|series| !series.cancelled) {
+                for start in series.starts(from, to) {
+                    let replaced_here = series.recurrence_id.is_none() && replaced.contains(&(series.uid.as_str(), start));
+                    if !replaced_here && !series.exceptions.contains(&start) {
+                        events.push(series.event(start));
+                    }
+                }
+            }
+            events.sort_by_key(|event| event.start);
+            events
+        }
+    }
+
+    /// An event as written in the feed: one occurrence, or a recurring one
+    #[derive(Debug, Clone)]
+    struct Series {
+        uid: String,
+        title: String,
+        location: Option<String>,
+        organizer: Option<String>,
+        start: Zoned,
+        duration: Duration,
+        rule: Option<Rule>,
+        /// Starts of occurrences left out
+        exceptions: HashSet<Timestamp>,
+        /// Start of the occurrence of a recurring event this one replaces
+        recurrence_id: Option<Timestamp>,
+        cancelled: bool,
+    }
+
+    impl Series {
+        /// The event described by `properties`, or `None` for an all-day event
+        fn new(properties: &[Property], zones: &mut Zones) -> Result<Option<Self>, String> {
+            let find = |name: &str| properties.iter().find(|property| property.name == name);
+            let title = find("SUMMARY").map_or_else(|| "Untitled event".to_string(), |property| unescape(&property.value));
+            let uid = find("UID").map_or_else(|| title.clone(), |property| property.value.clone());
+            let Some(start) = find("DTSTART") else {
+                return Err(format!("\"{}\" has no start time and was left out", title));
+            };
+            let start = match parse_when(start, zones) {
+                Some(When::Time(start)) => start,
+                Some(When::Date(_)) => return Ok(None),
+                None => return Err(format!("\"{}\" has a start time that can't be read and was left out", title)),
+            };
+            let end = match (find("DTEND"), find("DURATION")) {
+                (Some(end), _) => match parse_when(end, zones) {
+                    Some(When::Time(end)) => Some(end),
+                    _ => None,
+                },
+                (None, Some(duration)) => duration.value.parse::<Span>().ok().and_then(|span| start.checked_add(span).ok()),
+                (None, None) => None,
+            };
+            let duration = end
+                .and_then(|end| SystemTime::from(end.timestamp()).duration_since(SystemTime::from(start.timestamp())).ok())
+                .unwrap_or_default();
+
+            let mut series = Series {
+                organizer: find("ORGANIZER").map(organizer),
+                location: find("LOCATION").map(|property| unescape(&property.value)).filter(|location| !location.is_empty()),
+                rule: None,
+                exceptions: HashSet::new(),
+                recurrence_id: None,
+                cancelled: find("STATUS").is_some_and(|status| status.value.eq_ignore_ascii_case("CANCELLED")),
+                uid,
+                title,
+                start,
+                duration,
+            };
+            if let Some(recurrence_id) = find("RECURRENCE-ID") {
+                series.recurrence_id = match parse_when(recurrence_id, zones) {
+                    Some(when) => Some(when.timestamp_at(&series.start)),
+                    None => return Err(format!("A change to \"{}\" can't be matched to an occurrence and was left out", series.title)),
+                };
+            }
+            for exception in properties.iter().filter(|property| property.name == "EXDATE") {
+                for value in exception.value.split(',') {
+                    let exception = Property { value: value.to_string(), ..exception.clone() };
+                    if let Some(when) = parse_when(&exception, zones) {
+                        series.exceptions.insert(when.timestamp_at(&series.start));
+                    }
+                }
+            }
+            if let Some(rule) = find("RRULE") {
+                match Rule::parse(&rule.value, &series.start) {
+                    Ok(rule) => series.rule = Some(rule),
+                    Err(part) => zones.warnings.push(format!(
+                        "\"{}\" repeats in a way that isn't supported ({}); only its first occurrence is recorded",
+                        series.title, part
+                    )),
+                }
+            }
+            Ok(Some(series))
+        }
+
+        /// Starts of the occurrences overlapping `from` to `to`
+        fn starts(&self, from: Timestamp, to: Timestamp) -> Vec<Timestamp> {
+            let overlaps = |start: Timestamp| {
+                start < to && SystemTime::from(start) + self.duration > SystemTime::from(from)
+            };
+            let Some(rule) = &self.rule else {
+                let start = self.start.timestamp();
+                return if overlaps(start) { vec![start] } else { Vec::new() };
+            };
+            let mut starts = Vec::new();
+            let mut count = 0;
+            for period in 0..MAX_PERIODS {
+                let Some(dates) = rule.dates(&self.start, period * rule.interval) else {
+                    break;
+                };
+                for date in dates {
+                    let Ok(start) = date.to_datetime(self.start.time()).to_zoned(self.start.time_zone().clone()) else {
+                        continue;
+                    };
+                    let start = start.timestamp();
+                    if start < self.start.timestamp() {
+                        continue;
+                    }
+                    count += 1;
+                    let past_end = rule.count.is_some_and(|limit| count > limit) || rule.until.is_some_and(|until| start > until);
+                    if past_end || start >= to {
+                        return starts;
+                    }
+                    if overlaps(start) {
+                        starts.push(start);
+                    }
+                }
+            }
+            starts
+        }
+
+        fn event(&self, start: Timestamp) -> Event {
+            Event {
+                uid: self.uid.clone(),
+                title: self.title.clone(),
+                location: self.location.clone(),
+                organizer: self.organizer.clone(),
+                start: SystemTime::from(start),
+                end: SystemTime::from(start) + self.duration,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Frequency {
+        Daily,
+        Weekly,
+        Monthly,
+        Yearly,
+    }
+
+    /// How an event repeats, from its RRULE
+    #[derive(Debug, Clone)]
+    struct Rule {
+        frequency: Frequency,
+        interval: i64,
+        count: Option<u32>,
+        until: Option<Timestamp>,
+        /// Weekdays, each with its place in the month (`-1` for the last); 0 for all of them
+        by_day: Vec<(i8, Weekday)>,
+        by_month_day: Vec<i8>,
+        by_month: Vec<i8>,
+        week_start: Weekday,
+    }
+
+    impl Rule {
+        /// Read an RRULE value, or name the part of it that isn't supported
+        fn parse(value: &str, start: &Zoned) -> Result<Self, String> {
+            let mut rule = Rule {
+                frequency: Frequency::Daily,
+                interval: 1,
+                count: None,
+                until: None,
+                by_day: Vec::new(),
+                by_month_day: Vec::new(),
+                by_month: Vec::new(),
+                week_start: Weekday::Monday,
+            };
+            let mut frequency = None;
+            for part in value.split(';').filter(|part| !part.is_empty()) {
+                let unsupported = || part.to_string();
+                let (name, value) = part.split_once('=').ok_or_else(unsupported)?;
+                match name.to_ascii_uppercase().as_str() {
+                    "FREQ" => {
+                        frequency = Some(match value.to_ascii_uppercase().as_str() {
+                            "DAILY" => Frequency::Daily,
+                            "WEEKLY" => Frequency::Weekly,
+                            "MONTHLY" => Frequency::Monthly,
+                            "YEARLY" => Frequency::Yearly,
+                            _ => return Err(unsupported()),
+                        })
+                    }
+                    "INTERVAL" => rule.interval = value.parse().ok().filter(|&interval| interval > 0).ok_or_else(unsupported)?,
+                    "COUNT" => rule.count = Some(value.parse().map_err(|_| unsupported())?),
+                    "UNTIL" => {
+                        rule.until = match parse_when_in(value, start.time_zone().clone()) {
+                            // The whole of the last day
+                            Some(When::Date(date)) => date
+                                .tomorrow()
+                                .ok()
+                                .and_then(|day| day.to_zoned(start.time_zone().clone()).ok())
+                                .and_then(|day| day.timestamp().checked_sub(Span::new().seconds(1)).ok()),
+                            Some(when) => Some(when.timestamp_at(start)),
+                            None => return Err(unsupported()),
+                        };
+                    }
+                    "BYDAY" => {
+                        for day in value.split(',') {
+                            let split = day.len().checked_sub(2).ok_or_else(unsupported)?;
+                            let (nth, weekday) = day.split_at(split);
+                            let nth = if nth.is_empty() { 0 } else { nth.parse().map_err(|_| unsupported())? };
+                            rule.by_day.push((nth, weekday_of(weekday).ok_or_else(unsupported)?));
+                        }
+                    }
+                    "BYMONTHDAY" => rule.by_month_day = numbers(value).ok_or_else(unsupported)?,
+                    "BYMONTH" => rule.by_month = numbers(value).ok_or_else(unsupported)?,
+                    "WKST" => rule.week_start = weekday_of(value).ok_or_else(unsupported)?,
+                    _ => return Err(unsupported()),
+                }
+            }
+            rule.frequency = frequency.ok_or_else(|| value.to_string())?;
+            let ordinals = rule.by_day.iter().any(|&(nth, _)| nth != 0);
+            let by_weekday_in_year = rule.frequency == Frequency::Yearly && !rule.by_day.is_empty() && rule.by_month.is_empty();
+            if by_weekday_in_year || (ordinals && matches!(rule.frequency, Frequency::Daily | Frequency::Weekly)) {
+                return Err(value.to_string());
+            }
+            Ok(rule)
+        }
+
+        /// Dates the event falls on in the period `offset` periods after the
+        /// one it starts in, in order, or `None` past the supported range
+        fn dates(&self, start: &Zoned, offset: i64) -> Option<Vec<Date>> {
+            let first = start.date();
+            let mut dates = match self.frequency {
+                Frequency::Daily => vec![first.checked_add(Span::new().days(offset)).ok()?],
+                Frequency::Weekly => {
+                    let back = (first.weekday().to_monday_zero_offset() - self.week_start.to_monday_zero_offset()).rem_euclid(7);
+                    let week = first.checked_add(Span::new().days(offset * 7 - back as i64)).ok()?;
+                    let weekdays: Vec<Weekday> = if self.by_day.is_empty() {
+                        vec![first.weekday()]
+                    } else {
+                        self.by_day.iter().map(|&(_, weekday)| weekday).collect()
+                    };
+                    (0..7)
+                        .filter_map(|day| week.checked_add(Span::new().days(day)).ok())
+                        .filter(|date| weekdays.contains(&date.weekday()))
+                        .collect()
+                }
+                Frequency::Monthly => {
+                    let month = first.first_of_month().checked_add(Span::new().months(offset)).ok()?;
+                    self.dates_in_month(month, first)
+                }
+                Frequency::Yearly => {
+                    let year = first.first_of_month().checked_add(Span::new().years(offset)).ok()?;
+                    let months = if self.by_month.is_empty() { vec![first.month()] } else { self.by_month.clone() };
+                    let mut dates: Vec<Date> = months
+                        .into_iter()
+                        .filter_map(|month| Date::new(year.year(), month, 1).ok())
+                        .flat_map(|month| self.dates_in_month(month, first))
+                        .collect();
+                    dates.sort();
+                    dates
+                }
+            };
+            // Other frequencies narrow these down; for monthly and yearly rules
+            // they already picked the dates
+            if self.frequency == Frequency::Daily {
+                let weekdays: Vec<Weekday> = self.by_day.iter().map(|&(_, weekday)| weekday).collect();
+                dates.retain(|date| weekdays.is_empty() || weekdays.contains(&date.weekday()));
+                dates.retain(|date| self.by_month_day.is_empty() || self.by_month_day.contains(&date.day()));
+            }
+            if self.frequency != Frequency::Yearly {
+                dates.retain(|date| self.by_month.is_empty() || self.by_month.contains(&date.month()));
+            }
+            Some(dates)
+        }
+
+        /// Dates the event falls on in the month starting at `month`
+        fn dates_in_month(&self, month: Date, first: Date) -> Vec<Date> {
+            let days_in_month = month.days_in_month();
+            let mut dates: Vec<Date> = if !self.by_month_day.is_empty() {
+                self.by_month_day
+                    .iter()
+                    .map(|&day| if day < 0 { days_in_month + day + 1 } else { day })
+                    .filter_map(|day| month.with().day(day).build().ok())
+                    .filter(|date| self.by_day.is_empty() || self.by_day.iter().any(|&(_, weekday)| weekday == date.weekday()))
+                    .collect()
+            } else if !self.by_day.is_empty() {
+                self.by_day
+                    .iter()
+                    .flat_map(|&(nth, weekday)| match nth {
+                        0 => (1..=5).filter_map(|nth| month.nth_weekday_of_month(nth, weekday).ok()).collect(),
+                        nth => month.nth_weekday_of_month(nth, weekday).ok().into_iter().collect::<Vec<_>>(),
+                    })
+                    .filter(|date| date.month() == month.month())
+                    .collect()
+            } else {
+                // Months too short for the day are skipped
+                month.with().day(first.day()).build().ok().into_iter().collect()
+            };
+            dates.sort();
+            dates.dedup();
+            dates
+        }
+    }
+
+    fn weekday_of(code: &str) -> Option<Weekday> {
+        Some(match code.to_ascii_uppercase().as_str() {
+            "MO" => Weekday::Monday,
+            "TU" => Weekday::Tuesday,
+            "WE" => Weekday::Wednesday,
+            "TH" => Weekday::Thursday,
+            "FR" => Weekday::Friday,
+            "SA" => Weekday::Saturday,
+            "SU" => Weekday::Sunday,
+            _ => return None,
+        })
+    }
+
+    fn numbers(list: &str) -> Option<Vec<i8>> {
+        list.split(',').map(|number| number.parse().ok()).collect()
+    }
+
+    /// A DTSTART-like value: a whole day, or a point in time
+    enum When {
+        Date(Date),
+        Time(Zoned),
+    }
+
+    impl When {
+        /// The point in time, with a whole day taken at `start`'s time of day
+        fn timestamp_at(&self, start: &Zoned) -> Timestamp {
+            match self {
+                When::Time(time) => time.timestamp(),
+                When::Date(date) => date
+                    .to_datetime(start.time())
+                    .to_zoned(start.time_zone().clone())
+                    .map_or_else(|_| start.timestamp(), |time| time.timestamp()),
+            }
+        }
+    }
+
+    /// Time zones looked up so far, by TZID
+    #[derive(Default)]
+    struct Zones {
+        zones: HashMap<String, TimeZone>,
+        warnings: Vec<String>,
+    }
+
+    impl Zones {
+        fn get(&mut self, tzid: &str) -> TimeZone {
+            if let Some(zone) = self.zones.get(tzid) {
+                return zone.clone();
+            }
+            // Some calendars prefix the IANA name with a path of their own,
+            // as in /mozilla.org/20050126_1/Europe/Berlin
+            let mut parts = tzid.rsplit('/');
+            let area_and_city = parts.next().zip(parts.next()).map(|(city, area)| format!("{}/{}", area, city));
+            let zone = TimeZone::get(tzid)
+                .ok()
+                .or_else(|| TimeZone::get(&area_and_city?).ok())
+                .unwrap_or_else(|| {
+                    self.warnings.push(format!("Time zone \"{}\" is unknown; its events are taken as local time", tzid));
+                    TimeZone::system()
+                });
+            self.zones.insert(tzid.to_string(), zone.clone());
+            zone
+        }
+    }
+
+    fn parse_when(property: &Property, zones: &mut Zones) -> Option<When> {
+        let zone = match property.param("TZID") {
+            Some(tzid) => zones.get(tzid),
+            None => TimeZone::system(),
+        };
+        parse_when_in(&property.value, zone)
+    }
+
+    /// Read a date or date-time value, taking floating times in `zone`
+    fn parse_when_in(value: &str, zone: TimeZone) -> Option<When> {
+        let value = value.trim();
+        if value.len() == 8 {
+            return Some(When::Date(Date::strptime("%Y%m%d", value).ok()?));
+        }
+        let (local, utc) = match value.strip_suffix(['Z', 'z']) {
+            Some(local) => (local, true),
+            None => (value, false),
+        };
+        let time = DateTime::strptime("%Y%m%dT%H%M%S", local).ok()?;
+        let zone = if utc { TimeZone::UTC } else { zone };
+        time.to_zoned(zone).ok().map(When::Time)
+    }
+
+    /// The organizer as `Name <address>`, or whichever of them is given
+    fn organizer(property: &Property) -> String {
+        let value = &property.value;
+        let address = value.get(..7).filter(|scheme| scheme.eq_ignore_ascii_case("mailto:")).map_or(value.as_str(), |_| &value[7..]);
+        match property.param("CN") {
+            Some(name) if !address.is_empty() => format!("{} <{}>", name, address),
+            Some(name) => name.to_string(),
+            None => address.to_string(),
+        }
+    }
+
+    /// A content line: `NAME;PARAM=value:value`
+    #[derive(Debug, Clone)]
+    struct Property {
+        name: String,
+        params: Vec<(String, String)>,
+        value: String,
+    }
+
+    impl Property {
+        fn parse(line: &str) -> Option<Self> {
+            let name_end = line.find([';', ':'])?;
+            let name = line[..name_end].to_ascii_uppercase();
+            let mut params = Vec::new();
+            let mut rest = &line[name_end..];
+            while let Some(after) = rest.strip_prefix(';') {
+                let (param, after) = after.split_once('=')?;
+                // Quoted values may hold ';' and ':'
+                let (value, after) = match after.strip_prefix('"') {
+                    Some(quoted) => {
+                        let (value, after) = quoted.split_once('"')?;
+                        (value, after)
+                    }
+                    None => after.split_at(after.find([';', ':'])?),
+                };
+                params.push((param.to_ascii_uppercase(), value.to_string()));
+                rest = after;
+            }
+            let value = rest.strip_prefix(':')?.to_string();
+            Some(Self { name, params, value })
+        }
+
+        fn param(&self, name: &str) -> Option<&str> {
+            self.params.iter().find(|(param, _)| param == name).map(|(_, value)| value.as_str())
+        }
+    }
+
+    /// Join lines folded onto several, which start with a space or tab
+    fn unfold(ics: &str) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::new();
+        for line in ics.lines() {
+            match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+                (Some(continued), Some(last)) => last.push_str(continued),
+                _ => lines.push(line.to_string()),
+            }
+        }
+        lines
+    }
+
+    /// A TEXT value with its escapes resolved
+    fn unescape(text: &str) -> String {
+        let mut unescaped = String::with_capacity(text.len());
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                unescaped.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n' | 'N') => unescaped.push('\n'),
+                Some(escaped) => unescaped.push(escaped),
+                None => {}
+            }
+        }
+        unescaped
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn at(time: &str) -> SystemTime {
+            SystemTime::from(time.parse::<Timestamp>().unwrap())
+        }
+
+        const FEED: &str = "BEGIN:VCALENDAR\r\n\
+VERSION:2.0\r\n\
+BEGIN:VEVENT\r\n\
+UID:one-on-one\r\n\
+SUMMARY:1:1 with Sam\\, weekly\r\n\
+DTSTART;TZID=America/New_York:20240305T100000\r\n\
+DTEND;TZID=America/New_York:20240305T103000\r\n\
+RRULE:FREQ=WEEKLY;BYDAY=TU;COUNT=10\r\n\
+EXDATE;TZID=America/New_York:20240319T100000\r\n\
+ORGANIZER;CN=\"Sam: Lead\":mailto:sam@example.com\r\n\
+LOCATION:https://zoom.us/j/12\r\n \
+345\r\n\
+BEGIN:VALARM\r\n\
+SUMMARY:Reminder\r\n\
+END:VALARM\r\n\
+END:VEVENT\r\n\
+BEGIN:VEVENT\r\n\
+UID:one-on-one\r\n\
+SUMMARY:1:1 with Sam (moved)\r\n\
+RECURRENCE-ID;TZID=America/New_York:20240326T100000\r\n\
+DTSTART:20240327T150000Z\r\n\
+DURATION:PT45M\r\n\
+END:VEVENT\r\n\
+BEGIN:VEVENT\r\n\
+UID:holiday\r\n\
+SUMMARY:Holiday\r\n\
+DTSTART;VALUE=DATE:20240312\r\n\
+END:VEVENT\r\n\
+END:VCALENDAR\r\n";
+
+        #[test]
+        fn test_expands_recurring_events_with_exceptions() {
+            let feed = Feed::parse(FEED).unwrap();
+            assert!(feed.warnings().is_empty(), "{:?}", feed.warnings());
+            let events = feed.events_between(at("2024-03-01T00:00:00Z"), at("2024-04-01T00:00:00Z"));
+            let starts: Vec<SystemTime> = events.iter().map(|event| event.start).collect();
+            // Clocks in New York went forward on March 10th; the 19th was skipped
+            // and the 26th moved to the next day
+            assert_eq!(
+                starts,
+                [at("2024-03-05T15:00:00Z"), at("2024-03-12T14:00:00Z"), at("2024-03-27T15:00:00Z")]
+            );
+            assert_eq!(events[0].title, "1:1 with Sam, weekly");
+            assert_eq!(events[0].end, at("2024-03-05T15:30:00Z"));
+            assert_eq!(events[0].location.as_deref(), Some("https://zoom.us/j/12345"));
+            assert_eq!(events[0].organizer.as_deref(), Some("Sam: Lead <sam@example.com>"));
+            assert_eq!(events[2].title, "1:1 with Sam (moved)");
+            assert_eq!(events[2].end, at("2024-03-27T15:45:00Z"));
+
+            // Ten occurrences, the last on May 7th
+            let later = feed.events_between(at("2024-04-01T00:00:00Z"), at("2025-01-01T00:00:00Z"));
+            assert_eq!(later.last().map(|event| event.start), Some(at("2024-05-07T14:00:00Z")));
+        }
+
+        #[test]
+        fn test_monthly_rules() {
+            let start = "2024-01-01T09:00:00[UTC]".parse::<Zoned>().unwrap();
+            let last_friday = Rule::parse("FREQ=MONTHLY;BYDAY=-1FR", &start).unwrap();
+            assert_eq!(last_friday.dates(&start, 1), Some(vec![Date::new(2024, 2, 23).unwrap()]));
+            let thirty_first = Rule::parse("FREQ=MONTHLY;BYMONTHDAY=31", &start).unwrap();
+            assert_eq!(thirty_first.dates(&start, 1), Some(Vec::new()));
+            assert_eq!(thirty_first.dates(&start, 2), Some(vec![Date::new(2024, 3, 31).unwrap()]));
+            assert!(Rule::parse("FREQ=MONTHLY;BYSETPOS=-1;BYDAY=MO,TU", &start).is_err());
+            assert!(Rule::parse("FREQ=HOURLY", &start).is_err());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(title: &str, location: Option<&str>, start_secs: u64, minutes: u64) -> Event {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(start_secs);
+        Event {
+            uid: title.to_string(),
+            title: title.to_string(),
+            location: location.map(str::to_string),
+            organizer: None,
+            start,
+            end: start + Duration::from_secs(minutes * 60),
+        }
+    }
+
+    #[test]
+    fn test_rules_pick_the_events_to_record() {
+        let calendar = Calendar {
+            url: "calendar.ics".to_string(),
+            rules: vec![
+                CalendarRule { title_contains: Some("1:1".to_string()), ..CalendarRule::default() },
+                CalendarRule { location_contains: Some("ZOOM.US".to_string()), ..CalendarRule::default() },
+            ],
+            refresh_minutes: 5,
+            start_early_secs: 60,
+            stop_late_secs: 300,
+        };
+        let events = [
+            event("Lunch", None, 1000, 60),
+            event("Planning", Some("https://zoom.us/j/1"), 4000, 30),
+            event("1:1 with Sam", None, 3000, 30),
+        ];
+        let at = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+
+        assert_eq!(calendar.due(&events, at(1500)), None);
+        assert_eq!(calendar.due(&events, at(2950)).map(|event| event.title.as_str()), Some("1:1 with Sam"));
+        // Both under way; the one that started first is kept
+        assert_eq!(calendar.due(&events, at(4100)).map(|event| event.title.as_str()), Some("1:1 with Sam"));
+        assert_eq!(calendar.due(&events, at(4800)).map(|event| event.title.as_str()), Some("Planning"));
+        assert_eq!(calendar.due(&events, at(5800)), None);
+        assert_eq!(calendar.stop_time(&events[1]), at(6100));
+    }
+}
@@ -0,0 +1,126 @@
+//! SHA-256 checksums of recorded audio, for verifying archived recordings
+//!
+//! The checksum is computed while recording, over the 16-bit little-endian
+//! PCM as it is written, so producing it never needs a second pass over a
+//! long file. That is exactly the content of the recording's WAV `data`
+//! chunk; the header is left out because it is only complete once the file
+//! is finalized. It is stored as a hex digest in `<recording>.sha256`, and
+//! [`verify`] checks a recording against it. Only unencrypted WAV recordings
+//! can be checked this way, so the config rejects `checksum` for others.
+
+use hound::WavReader;
+use sha2::{Digest, Sha256};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::sink::{Sink, SinkContext, SinkError};
+use crate::wav;
+
+/// Lowercase hex form of a digest
+pub fn to_hex(digest: &[u8]) -> String {
+    digest.iter().fold(String::with_capacity(digest.len() * 2), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    })
+}
+
+/// Path of the checksum file for the recording at `recording_path`
+pub fn checksum_path(recording_path: impl AsRef<Path>) -> PathBuf {
+    let mut path = recording_path.as_ref().as_os_str().to_owned();
+    path.push(".sha256");
+    PathBuf::from(path)
+}
+
+/// Sink that hashes the recording's audio and stores the digest next to it
+pub struct ChecksumSink {
+    hasher: Sha256,
+    bytes: Vec<u8>,
+    path: PathBuf,
+}
+
+impl ChecksumSink {
+    /// Hash the audio of the session's recording, for its [`checksum_path`]
+    pub fn new(context: &SinkContext) -> Self {
+        Self {
+            hasher: Sha256::new(),
+            bytes: Vec::new(),
+            path: checksum_path(&context.recording_path),
+        }
+    }
+}
+
+impl Sink for ChecksumSink {
+    fn write_frames(&mut self, samples: &[i16]) -> Result<(), SinkError> {
+        self.bytes.clear();
+        self.bytes.extend(samples.iter().flat_map(|sample| sample.to_le_bytes()));
+        self.hasher.update(&self.bytes);
+        Ok(())
+    }
+
+    fn finalize(self: Box<Self>) -> Result<(), SinkError> {
+        let part_path = wav::partial_path(&self.path);
+        fs::write(&part_path, format!("{}\n", to_hex(&self.hasher.finalize())))?;
+        fs::rename(&part_path, &self.path)?;
+        Ok(())
+    }
+}
+
+/// SHA-256 of a WAV file's audio data, as stored by [`ChecksumSink`]
+pub fn audio_sha256(path: impl AsRef<Path>) -> hound::Result<[u8; 32]> {
+    let mut reader = WavReader::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut bytes = Vec::new();
+    let mut samples = reader.samples::<i16>();
+    loop {
+        bytes.clear();
+        for sample in samples.by_ref().take(64 * 1024) {
+            bytes.extend_from_slice(&sample?.to_le_bytes());
+        }
+        if bytes.is_empty() {
+            break;
+        }
+        hasher.update(&bytes);
+    }
+    Ok(hasher.finalize().into())
+}
+
+/// Check a WAV recording against its `.sha256` file
+///
+/// Returns whether the audio still matches the stored checksum.
+pub fn verify(recording_path: impl AsRef<Path>) -> Result<bool, Box<dyn std::error::Error>> {
+    let recording_path = recording_path.as_ref();
+    let expected = fs::read_to_string(checksum_path(recording_path))?;
+    let actual = to_hex(&audio_sha256(recording_path)?);
+    Ok(expected.trim().eq_ignore_ascii_case(&actual))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hound::{SampleFormat, WavSpec};
+
+    #[test]
+    fn test_checksum_covers_the_little_endian_samples() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let context = SinkContext {
+            recording_path: temp_dir.path().join("meeting.wav"),
+            spec: WavSpec {
+                channels: 1,
+                sample_rate: 2,
+                bits_per_sample: 16,
+                sample_format: SampleFormat::Int,
+            },
+            session_id: Default::default(),
+        };
+        let mut sink: Box<dyn Sink> = Box::new(ChecksumSink::new(&context));
+        sink.write_frames(&[1]).unwrap();
+        sink.write_frames(&[-1]).unwrap();
+        sink.finalize().unwrap();
+        // SHA-256 of 01 00 ff ff
+        assert_eq!(
+            fs::read_to_string(checksum_path(&context.recording_path)).unwrap(),
+            "16b8cb1fe734fbc60c6763c94c9e4cc55840ae966e7e508ba82f539d82702511\n"
+        );
+    }
+}
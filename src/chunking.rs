@@ -0,0 +1,289 @@
+//! Fixed-length, overlapping chunks of a recording for speech recognition
+//!
+//! Streaming speech-to-text pipelines consume audio in pieces of a fixed
+//! length, e.g. 30 seconds, that overlap by a couple of seconds so words on
+//! a boundary aren't cut in half. A [`ChunkSink`] cuts the mixed audio into
+//! such chunks while recording and hands each one over as soon as it is
+//! complete: to a callback through the sink API, or as WAV files with
+//! [`ChunkSink::files`]. Chunk `n` starts `n * (length - overlap)` into the
+//! recording; the last one is shorter unless the recording happens to end
+//! on a boundary.
+
+use hound::{WavSpec, WavWriter};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::encryption::{self, Encryption};
+use crate::sink::{Sink, SinkContext, SinkError};
+use crate::wav;
+
+/// Length and overlap of chunks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkOptions {
+    /// Length of each chunk in seconds
+    #[serde(default = "default_length_secs")]
+    pub length_secs: u64,
+    /// Seconds at the end of a chunk repeated at the start of the next
+    #[serde(default = "default_overlap_secs")]
+    pub overlap_secs: u64,
+}
+
+fn default_length_secs() -> u64 {
+    30
+}
+
+fn default_overlap_secs() -> u64 {
+    2
+}
+
+impl Default for ChunkOptions {
+    fn default() -> Self {
+        Self {
+            length_secs: default_length_secs(),
+            overlap_secs: default_overlap_secs(),
+        }
+    }
+}
+
+impl ChunkOptions {
+    /// Whether chunks would advance through the recording at all
+    pub fn is_valid(&self) -> bool {
+        self.overlap_secs < self.length_secs
+    }
+}
+
+/// A complete chunk of mixed audio
+#[derive(Debug, Clone, Copy)]
+pub struct AudioChunk<'a> {
+    /// Position of the chunk in the recording, from 0
+    pub index: u64,
+    /// Sample frame of the recording the chunk starts at
+    pub start_frame: u64,
+    /// Interleaved samples in the session's format
+    pub samples: &'a [i16],
+}
+
+impl AudioChunk<'_> {
+    /// Offset of the chunk from the start of a recording at `sample_rate`
+    pub fn start(&self, sample_rate: u32) -> Duration {
+        Duration::from_secs_f64(self.start_frame as f64 / sample_rate as f64)
+    }
+}
+
+/// Sink that cuts the recording into overlapping chunks and passes each to a callback
+///
+/// The callback runs on the mixer thread like any sink; an error from it is
+/// reported as a failure of this sink.
+pub struct ChunkSink<F> {
+    on_chunk: F,
+    channels: usize,
+    /// Interleaved samples per chunk
+    length: usize,
+    /// Interleaved samples between the starts of consecutive chunks
+    step: usize,
+    buffer: Vec<i16>,
+    index: u64,
+    /// Sample frame of the recording at the start of `buffer`
+    start_frame: u64,
+}
+
+impl<F> ChunkSink<F>
+where
+    F: FnMut(AudioChunk<'_>) -> Result<(), SinkError> + Send,
+{
+    /// Cut audio of `spec` into chunks as `options` say, passing each to `on_chunk`
+    ///
+    /// Panics if `options` are not [valid](ChunkOptions::is_valid).
+    pub fn new(spec: &WavSpec, options: &ChunkOptions, on_chunk: F) -> Self {
+        assert!(options.is_valid(), "chunk overlap must be shorter than the chunk");
+        let channels = spec.channels.max(1) as usize;
+        let samples_per_sec = spec.sample_rate as usize * channels;
+        let length = options.length_secs as usize * samples_per_sec;
+        Self {
+            on_chunk,
+            channels,
+            length,
+            step: length - options.overlap_secs as usize * samples_per_sec,
+            buffer: Vec::with_capacity(length),
+            index: 0,
+            start_frame: 0,
+        }
+    }
+
+    fn emit(&mut self, len: usize) -> Result<(), SinkError> {
+        (self.on_chunk)(AudioChunk {
+            index: self.index,
+            start_frame: self.start_frame,
+            samples: &self.buffer[..len],
+        })?;
+        self.index += 1;
+        Ok(())
+    }
+}
+
+impl<F> Sink for ChunkSink<F>
+where
+    F: FnMut(AudioChunk<'_>) -> Result<(), SinkError> + Send,
+{
+    fn write_frames(&mut self, samples: &[i16]) -> Result<(), SinkError> {
+        self.buffer.extend_from_slice(samples);
+        while self.buffer.len() >= self.length {
+            self.emit(self.length)?;
+            // Keep the overlap for the start of the next chunk
+            self.buffer.drain(..self.step);
+            self.start_frame += (self.step / self.channels) as u64;
+        }
+        Ok(())
+    }
+
+    fn finalize(mut self: Box<Self>) -> Result<(), SinkError> {
+        // Past the first chunk, the start of the buffer was already sent as overlap
+        let sent = if self.index == 0 { 0 } else { self.length - self.step };
+        if self.buffer.len() > sent {
+            self.emit(self.buffer.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Directory the chunks of the recording at `recording_path` are written to
+pub fn chunk_directory(recording_path: impl AsRef<Path>) -> PathBuf {
+    let path = recording_path.as_ref();
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}-chunks", stem))
+}
+
+impl ChunkSink<()> {
+    /// Write the session's chunks as numbered WAV files in its [`chunk_directory`]
+    ///
+    /// Each file is written under a `.part` name and renamed once complete,
+    /// so a pipeline watching the directory only ever sees whole chunks.
+    /// With `encryption`, each chunk is encrypted to `<chunk>.wav.age` as it
+    /// closes and its plaintext removed.
+    pub fn files(
+        context: &SinkContext,
+        options: &ChunkOptions,
+        encryption: Option<&Encryption>,
+    ) -> io::Result<ChunkSink<impl FnMut(AudioChunk<'_>) -> Result<(), SinkError> + Send>> {
+        Self::files_then(context, options, encryption, |_, _| {})
+    }
+
+    /// Like [`files`](Self::files), calling `on_closed` with each chunk and the path of its complete file
+    pub(crate) fn files_then(
+        context: &SinkContext,
+        options: &ChunkOptions,
+        encryption: Option<&Encryption>,
+        mut on_closed: impl FnMut(&AudioChunk<'_>, PathBuf) + Send,
+    ) -> io::Result<ChunkSink<impl FnMut(AudioChunk<'_>) -> Result<(), SinkError> + Send>> {
+        let directory = chunk_directory(&context.recording_path);
+        fs::create_dir_all(&directory)?;
+        let stem = context.recording_path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        let spec = context.spec;
+        let encryption = encryption.cloned();
+        Ok(ChunkSink::new(&spec, options, move |chunk: AudioChunk<'_>| {
+            let path = directory.join(format!("{}-chunk-{:04}.wav", stem, chunk.index));
+            let part_path = wav::partial_path(&path);
+            let mut writer = WavWriter::create(&part_path, spec)?;
+            wav::write_samples(&mut writer, chunk.samples)?;
+            writer.finalize()?;
+            let path = match &encryption {
+                Some(encryption) => {
+                    // Only the encrypted chunk ever appears under a complete name
+                    let encrypted = encryption::encrypted_path(&path);
+                    let result = encryption.encrypt_to(&part_path, &encrypted);
+                    fs::remove_file(&part_path)?;
+                    result?;
+                    encrypted
+                }
+                None => {
+                    fs::rename(&part_path, &path)?;
+                    path
+                }
+            };
+            on_closed(&chunk, path);
+            Ok(())
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hound::SampleFormat;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_chunks_overlap_and_the_last_one_is_short() {
+        // One frame per second keeps the numbers small
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 1,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let options = ChunkOptions { length_secs: 4, overlap_secs: 1 };
+        let chunks = Arc::new(Mutex::new(Vec::new()));
+        let received = Arc::clone(&chunks);
+        let mut sink: Box<dyn Sink> = Box::new(ChunkSink::new(&spec, &options, move |chunk: AudioChunk<'_>| {
+            received.lock().unwrap().push((chunk.index, chunk.start_frame, chunk.samples.to_vec()));
+            Ok(())
+        }));
+
+        // Frames 0..=8, each sample holding its frame number, in uneven writes
+        let samples: Vec<i16> = (0..9).flat_map(|frame| [frame, frame]).collect();
+        sink.write_frames(&samples[..5]).unwrap();
+        sink.write_frames(&samples[5..]).unwrap();
+        sink.finalize().unwrap();
+
+        let frames = |range: std::ops::Range<i16>| range.flat_map(|frame| [frame, frame]).collect::<Vec<_>>();
+        assert_eq!(
+            *chunks.lock().unwrap(),
+            [(0, 0, frames(0..4)), (1, 3, frames(3..7)), (2, 6, frames(6..9))]
+        );
+    }
+
+    #[test]
+    fn test_nothing_new_after_the_overlap_sends_no_chunk() {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 1,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let options = ChunkOptions { length_secs: 3, overlap_secs: 1 };
+        let count = Arc::new(Mutex::new(0));
+        let counter = Arc::clone(&count);
+        let mut sink: Box<dyn Sink> = Box::new(ChunkSink::new(&spec, &options, move |_: AudioChunk<'_>| {
+            *counter.lock().unwrap() += 1;
+            Ok(())
+        }));
+        sink.write_frames(&[1, 2, 3, 4, 5]).unwrap();
+        sink.finalize().unwrap();
+        assert_eq!(*count.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_encrypted_chunks_never_leave_plaintext() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let context = SinkContext {
+            recording_path: temp_dir.path().join("meeting.wav"),
+            spec: WavSpec {
+                channels: 1,
+                sample_rate: 2,
+                bits_per_sample: 16,
+                sample_format: SampleFormat::Int,
+            },
+            session_id: Default::default(),
+        };
+        // Not a valid recipient, so `age` fails whether or not it is installed
+        let encryption = Encryption { recipients: vec!["not-a-recipient".to_string()], ..Encryption::default() };
+        let options = ChunkOptions { length_secs: 1, overlap_secs: 0 };
+        let mut sink = ChunkSink::files(&context, &options, Some(&encryption)).unwrap();
+        assert!(sink.write_frames(&[1, 2]).is_err());
+        let left: Vec<_> = fs::read_dir(chunk_directory(&context.recording_path)).unwrap().collect();
+        assert!(left.is_empty(), "{:?}", left);
+    }
+}
@@ -0,0 +1,203 @@
+//! Receiving recordings from other machines
+//!
+//! `meeting-recorder collect` listens for recorders with `collector` in their
+//! config and writes each session to `<session id>.wav` in the output
+//! directory, under a temporary name until the recorder ends it. A session
+//! whose connection drops stays open for the recorder to reconnect: audio
+//! that never arrived is filled in with silence, and audio sent again is
+//! skipped. Sessions still open when the collector stops are saved as they
+//! are.
+
+use hound::{SampleFormat, WavSpec, WavWriter};
+use meeting_recorder::collector::{parse_audio, read_message, MessageKind, StreamStart};
+use meeting_recorder::{wav, Config};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::shutdown;
+
+/// How often new connections are accepted and the stop flag checked
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+/// Longest gap filled with silence; anything larger is taken for a broken sender
+const MAX_GAP_SECS: u64 = 3600;
+
+/// Sessions being received, by session ID
+type Sessions = Arc<Mutex<HashMap<Uuid, Incoming>>>;
+
+/// Receive recordings on `listen` until stopped
+pub fn run(listen: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::load()?;
+    let directory = PathBuf::from(&config.output_directory);
+    let listener = TcpListener::bind(listen).map_err(|e| format!("Could not listen on {}: {}", listen, e))?;
+    listener.set_nonblocking(true)?;
+    let running = Arc::new(AtomicBool::new(true));
+    let _shutdown = shutdown::install(running.clone())?;
+    println!("Collecting recordings on {} into {}", listen, directory.display());
+    println!("Press Ctrl+C to stop");
+
+    let sessions = Sessions::default();
+    while running.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, peer)) => {
+                let (sessions, directory) = (sessions.clone(), directory.clone());
+                thread::spawn(move || {
+                    if let Err(e) = receive(stream, peer, &sessions, &directory) {
+                        eprintln!("Warning: the connection from {} failed: {}", peer, e);
+                    }
+                });
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(ACCEPT_INTERVAL),
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let open: Vec<Incoming> = sessions.lock().expect("sessions lock").drain().map(|(_, incoming)| incoming).collect();
+    for incoming in open {
+        match incoming.finish() {
+            Ok(path) => println!("Saved {} before its recorder finished", path.display()),
+            Err(e) => eprintln!("Warning: could not save a recording: {}", e),
+        }
+    }
+    Ok(())
+}
+
+/// Take the audio of one connection into its session's file
+fn receive(
+    mut stream: TcpStream,
+    peer: SocketAddr,
+    sessions: &Mutex<HashMap<Uuid, Incoming>>,
+    directory: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Accepted sockets may inherit the listener's non-blocking mode
+    stream.set_nonblocking(false)?;
+    let Some((MessageKind::Start, payload)) = read_message(&mut stream)? else {
+        return Err("the connection did not start with a session".into());
+    };
+    let start: StreamStart = serde_json::from_slice(&payload)?;
+    let id = start.session_id;
+    {
+        let mut sessions = sessions.lock().expect("sessions lock");
+        match sessions.get(&id) {
+            Some(incoming) if incoming.spec != spec(&start)? => {
+                return Err(format!("session {} changed its format on reconnecting", id).into())
+            }
+            Some(_) => println!("{} reconnected for session {}", peer, id),
+            None => {
+                let incoming = Incoming::create(directory, &start)?;
+                println!("Receiving {} from {}", incoming.path.display(), peer);
+                sessions.insert(id, incoming);
+            }
+        }
+    }
+    loop {
+        match read_message(&mut stream)? {
+            Some((MessageKind::Audio, payload)) => {
+                let (start_frame, samples) = parse_audio(&payload)?;
+                if let Some(incoming) = sessions.lock().expect("sessions lock").get_mut(&id) {
+                    incoming.append(start_frame, &samples)?;
+                }
+            }
+            Some((MessageKind::End, _)) => {
+                if let Some(incoming) = sessions.lock().expect("sessions lock").remove(&id) {
+                    println!("Saved {}", incoming.finish()?.display());
+                }
+                return Ok(());
+            }
+            Some((MessageKind::Start, _)) => return Err("unexpected second start message".into()),
+            None => {
+                println!("{} disconnected; keeping session {} open for it to reconnect", peer, id);
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Format of the audio a session announces
+fn spec(start: &StreamStart) -> Result<WavSpec, Box<dyn std::error::Error>> {
+    if start.channels == 0 || start.sample_rate == 0 {
+        return Err("the session announced no channels or no sample rate".into());
+    }
+    Ok(WavSpec {
+        channels: start.channels,
+        sample_rate: start.sample_rate,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    })
+}
+
+/// A session's recording as it arrives
+struct Incoming {
+    writer: WavWriter<BufWriter<File>>,
+    spec: WavSpec,
+    path: PathBuf,
+    /// Frames written so far
+    frames: u64,
+}
+
+impl Incoming {
+    fn create(directory: &Path, start: &StreamStart) -> Result<Self, Box<dyn std::error::Error>> {
+        let spec = spec(start)?;
+        let path = directory.join(format!("{}.wav", start.session_id));
+        let writer = WavWriter::create(wav::partial_path(&path), spec)?;
+        Ok(Self { writer, spec, path, frames: 0 })
+    }
+
+    /// Write the samples of a chunk starting at `start_frame`, filling any gap
+    /// before it with silence and skipping what is already written
+    fn append(&mut self, start_frame: u64, samples: &[i16]) -> Result<(), Box<dyn std::error::Error>> {
+        let channels = self.spec.channels as u64;
+        if start_frame > self.frames {
+            let gap = start_frame - self.frames;
+            if gap > MAX_GAP_SECS * self.spec.sample_rate as u64 {
+                return Err(format!("audio jumped ahead by {} frames", gap).into());
+            }
+            for _ in 0..gap * channels {
+                self.writer.write_sample(0i16)?;
+            }
+            self.frames = start_frame;
+        }
+        let skip = ((self.frames - start_frame) * channels).min(samples.len() as u64) as usize;
+        for &sample in &samples[skip..] {
+            self.writer.write_sample(sample)?;
+        }
+        self.frames = self.frames.max(start_frame + samples.len() as u64 / channels);
+        Ok(())
+    }
+
+    /// Finalize the file and move it into place
+    fn finish(self) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        self.writer.finalize()?;
+        fs::rename(wav::partial_path(&self.path), &self.path)?;
+        Ok(self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_incoming_fills_gaps_and_skips_repeats() {
+        let dir = tempfile::tempdir().unwrap();
+        let start = StreamStart { session_id: Uuid::new_v4(), sample_rate: 8000, channels: 2 };
+        let mut incoming = Incoming::create(dir.path(), &start).unwrap();
+        incoming.append(0, &[1, 1, 2, 2]).unwrap();
+        // Sent again after a reconnect, overlapping what arrived
+        incoming.append(1, &[2, 2, 3, 3]).unwrap();
+        // Frame 3 never arrived
+        incoming.append(4, &[5, 5]).unwrap();
+        let path = incoming.finish().unwrap();
+
+        assert_eq!(path, dir.path().join(format!("{}.wav", start.session_id)));
+        let samples: Vec<i16> = hound::WavReader::open(&path).unwrap().samples().map(Result::unwrap).collect();
+        assert_eq!(samples, [1, 1, 2, 2, 3, 3, 0, 0, 5, 5]);
+    }
+}
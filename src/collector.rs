@@ -0,0 +1,400 @@
+//! Sending the mixed audio to a remote collector over TCP
+//!
+//! With `collector` in the config, a [`CollectorSink`] sends the recording
+//! to a machine running `meeting-recorder collect`, which writes it to a
+//! WAV file of its own, so meeting-room machines can keep their recordings
+//! on a central server. Sending happens on a thread of its own. When the
+//! connection drops, the sink reconnects with growing pauses; audio waits
+//! for it, up to `max_queued_secs`, and is sent once the connection is back.
+//!
+//! Each connection carries messages of a one-byte kind and a four-byte
+//! big-endian payload length, followed by the payload:
+//!
+//! - [`MessageKind::Start`] opens every connection, with a JSON [`StreamStart`].
+//! - [`MessageKind::Audio`] carries a chunk: the index of its first frame
+//!   in the recording as a big-endian `u64`, then interleaved signed 16-bit
+//!   little-endian samples. The frame index lets the collector fill gaps
+//!   with silence and skip audio it already has after a reconnect.
+//! - [`MessageKind::End`], with no payload, closes the session.
+
+use crossbeam_channel::{Receiver, Sender, TrySendError};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::sink::{Chunk, Chunker, Sink, SinkContext, SinkError};
+
+/// Where the audio is sent during a recording
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Collector {
+    /// Host and port of the collector, e.g. `recordings.example.com:9100`
+    pub address: String,
+    /// Milliseconds of audio sent to the collector at a time
+    #[serde(default = "default_chunk_ms")]
+    pub chunk_ms: u64,
+    /// Most audio, in seconds, held while the collector is unreachable before chunks are dropped
+    #[serde(default = "default_max_queued_secs")]
+    pub max_queued_secs: u64,
+}
+
+fn default_chunk_ms() -> u64 {
+    500
+}
+
+impl Collector {
+    /// Whether `address` has a host and a port
+    pub fn is_valid(&self) -> bool {
+        self.address
+            .rsplit_once(':')
+            .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok())
+    }
+}
+
+fn default_max_queued_secs() -> u64 {
+    300
+}
+
+/// Kinds of message on a collector connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MessageKind {
+    Start = 1,
+    Audio = 2,
+    End = 3,
+}
+
+impl MessageKind {
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(Self::Start),
+            2 => Some(Self::Audio),
+            3 => Some(Self::End),
+            _ => None,
+        }
+    }
+}
+
+/// Largest payload a collector accepts, well above any sane chunk
+pub const MAX_PAYLOAD: usize = 16 * 1024 * 1024;
+
+/// Payload of the message opening a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StreamStart {
+    /// Recording session the audio belongs to; the same on every reconnect
+    pub session_id: Uuid,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+/// Write one message
+pub fn write_message(writer: &mut impl Write, kind: MessageKind, payload: &[u8]) -> io::Result<()> {
+    let mut header = [0; 5];
+    header[0] = kind as u8;
+    header[1..].copy_from_slice(&(payload.len() as u32).to_be_bytes());
+    writer.write_all(&header)?;
+    writer.write_all(payload)
+}
+
+/// Read the next message, or `None` if the connection was closed between messages
+pub fn read_message(reader: &mut impl Read) -> io::Result<Option<(MessageKind, Vec<u8>)>> {
+    let mut header = [0; 5];
+    match reader.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let kind = MessageKind::from_byte(header[0])
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("unknown message kind {}", header[0])))?;
+    let len = u32::from_be_bytes(header[1..].try_into().expect("four length bytes")) as usize;
+    if len > MAX_PAYLOAD {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("message of {} bytes is too large", len)));
+    }
+    let mut payload = vec![0; len];
+    reader.read_exact(&mut payload)?;
+    Ok(Some((kind, payload)))
+}
+
+/// Payload of an audio message
+pub fn audio_payload(start_frame: u64, samples: &[i16]) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(8 + samples.len() * 2);
+    payload.extend_from_slice(&start_frame.to_be_bytes());
+    payload.extend(samples.iter().flat_map(|sample| sample.to_le_bytes()));
+    payload
+}
+
+/// First frame and samples of an audio message's payload
+pub fn parse_audio(payload: &[u8]) -> io::Result<(u64, Vec<i16>)> {
+    if payload.len() < 8 || !payload.len().is_multiple_of(2) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "malformed audio message"));
+    }
+    let start_frame = u64::from_be_bytes(payload[..8].try_into().expect("eight frame bytes"));
+    let samples = payload[8..].chunks_exact(2).map(|pair| i16::from_le_bytes([pair[0], pair[1]])).collect();
+    Ok((start_frame, samples))
+}
+
+/// How long connecting or sending one chunk may take before it counts as failed
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+/// First pause before reconnecting; it doubles up to `MAX_RETRY_DELAY`
+const MIN_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+/// How long the end of a recording waits for an unreachable collector
+const FINISH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Chunks sent and given up on by the sender thread
+#[derive(Default)]
+struct Delivery {
+    sent: u64,
+    failed: u64,
+}
+
+/// Sink that sends the recording to a collector
+pub struct CollectorSink {
+    address: String,
+    sender: Option<Sender<Chunk>>,
+    thread: Option<JoinHandle<Delivery>>,
+    /// Set when the recording ends, so the sender stops waiting for the collector
+    finishing: Arc<AtomicBool>,
+    chunker: Chunker,
+    dropped: u64,
+}
+
+impl CollectorSink {
+    /// Start sending the session's audio as `options` say
+    ///
+    /// The collector is only contacted once the first chunk is ready,
+    /// so a collector that is down doesn't stop the recording starting.
+    pub fn spawn(options: &Collector, context: &SinkContext) -> Result<Self, SinkError> {
+        let spec = context.spec;
+        let chunker = Chunker::new(&spec, options.chunk_ms);
+        let (sender, receiver) = crossbeam_channel::bounded(chunker.chunks_in(options.max_queued_secs));
+        let finishing = Arc::new(AtomicBool::new(false));
+        let connection = Connection {
+            address: options.address.clone(),
+            start: StreamStart {
+                session_id: context.session_id,
+                sample_rate: spec.sample_rate,
+                channels: spec.channels,
+            },
+            finishing: finishing.clone(),
+        };
+        let thread = thread::Builder::new()
+            .name("collector-sender".to_string())
+            .spawn(move || connection.run(receiver))?;
+        Ok(Self {
+            address: options.address.clone(),
+            sender: Some(sender),
+            thread: Some(thread),
+            finishing,
+            chunker,
+            dropped: 0,
+        })
+    }
+
+    /// Queue a chunk for sending, dropping it if the queue is full
+    fn queue(&mut self, chunk: Chunk) {
+        if let Some(sender) = &self.sender {
+            if let Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) = sender.try_send(chunk) {
+                if self.dropped == 0 {
+                    console_err!("Warning: the collector at {} fell too far behind; dropping audio", self.address);
+                }
+                self.dropped += 1;
+            }
+        }
+    }
+}
+
+impl Sink for CollectorSink {
+    fn write_frames(&mut self, mut samples: &[i16]) -> Result<(), SinkError> {
+        while let Some(chunk) = self.chunker.fill(&mut samples) {
+            self.queue(chunk);
+        }
+        Ok(())
+    }
+
+    fn finalize(mut self: Box<Self>) -> Result<(), SinkError> {
+        if let Some(chunk) = self.chunker.rest() {
+            self.queue(chunk);
+        }
+        // Hanging up lets the sender finish the queue and end the session
+        self.sender = None;
+        self.finishing.store(true, Ordering::SeqCst);
+        let delivery = self
+            .thread
+            .take()
+            .expect("sender thread is only joined here")
+            .join()
+            .map_err(|_| "collector sender thread panicked")?;
+        let lost = delivery.failed + self.dropped;
+        if lost > 0 {
+            return Err(format!(
+                "{} of {} chunks did not reach the collector at {} ({} dropped while it was unreachable)",
+                lost,
+                delivery.sent + lost,
+                self.address,
+                self.dropped
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+/// The sender thread's end of the connection
+struct Connection {
+    address: String,
+    start: StreamStart,
+    finishing: Arc<AtomicBool>,
+}
+
+impl Connection {
+    /// Send every chunk, in order, reconnecting and retrying until the recording has ended
+    fn run(self, chunks: Receiver<Chunk>) -> Delivery {
+        let mut delivery = Delivery::default();
+        let mut stream = None;
+        let mut delay = MIN_RETRY_DELAY;
+        let mut deadline = None;
+        for chunk in &chunks {
+            loop {
+                match self.send(&mut stream, &chunk) {
+                    Ok(()) => {
+                        if delay > MIN_RETRY_DELAY {
+                            console_err!("Reconnected to the collector at {}", self.address);
+                        }
+                        delay = MIN_RETRY_DELAY;
+                        delivery.sent += 1;
+                        break;
+                    }
+                    Err(e) => {
+                        stream = None;
+                        if delay == MIN_RETRY_DELAY {
+                            console_err!(
+                                "Warning: lost the collector at {}: {}; reconnecting",
+                                self.address,
+                                e
+                            );
+                        }
+                        if !self.pause(delay, &mut deadline) {
+                            // The recording has ended and the collector is still away
+                            delivery.failed += 1 + chunks.try_iter().count() as u64;
+                            return delivery;
+                        }
+                        delay = (delay * 2).min(MAX_RETRY_DELAY);
+                    }
+                }
+            }
+        }
+        if let Some(mut stream) = stream {
+            let _ = write_message(&mut stream, MessageKind::End, &[]);
+            let _ = stream.shutdown(Shutdown::Write);
+        }
+        delivery
+    }
+
+    /// Send a chunk, connecting first if needed
+    fn send(&self, stream: &mut Option<TcpStream>, chunk: &Chunk) -> io::Result<()> {
+        let open = match stream {
+            Some(open) => open,
+            None => stream.insert(self.connect()?),
+        };
+        write_message(open, MessageKind::Audio, &audio_payload(chunk.start_frame, &chunk.samples))
+    }
+
+    /// Connect and announce the session
+    fn connect(&self) -> io::Result<TcpStream> {
+        let mut last_error = io::Error::new(io::ErrorKind::NotFound, "the address resolved to nothing");
+        for address in self.address.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&address, IO_TIMEOUT) {
+                Ok(mut stream) => {
+                    stream.set_nodelay(true)?;
+                    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+                    let start = serde_json::to_vec(&self.start).map_err(io::Error::other)?;
+                    write_message(&mut stream, MessageKind::Start, &start)?;
+                    return Ok(stream);
+                }
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Wait `delay` before reconnecting; false once the recording has
+    /// ended more than `FINISH_TIMEOUT` ago
+    fn pause(&self, delay: Duration, deadline: &mut Option<Instant>) -> bool {
+        let until = Instant::now() + delay;
+        loop {
+            let now = Instant::now();
+            if self.finishing.load(Ordering::SeqCst) {
+                let deadline = *deadline.get_or_insert(now + FINISH_TIMEOUT);
+                if now >= deadline {
+                    return false;
+                }
+            }
+            if now >= until {
+                return true;
+            }
+            // Short steps, so the end of the recording is noticed
+            thread::sleep((until - now).min(Duration::from_millis(100)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_messages_round_trip() {
+        let mut bytes = Vec::new();
+        write_message(&mut bytes, MessageKind::Audio, &audio_payload(480, &[1, -2, 300])).unwrap();
+        write_message(&mut bytes, MessageKind::End, &[]).unwrap();
+        let mut reader = bytes.as_slice();
+        let (kind, payload) = read_message(&mut reader).unwrap().unwrap();
+        assert_eq!(kind, MessageKind::Audio);
+        assert_eq!(parse_audio(&payload).unwrap(), (480, vec![1, -2, 300]));
+        assert_eq!(read_message(&mut reader).unwrap().unwrap(), (MessageKind::End, Vec::new()));
+        assert!(read_message(&mut reader).unwrap().is_none());
+        assert!(read_message(&mut [9u8, 0, 0, 0, 0].as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_sink_sends_the_session() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let options = Collector {
+            address: listener.local_addr().unwrap().to_string(),
+            chunk_ms: 10,
+            max_queued_secs: default_max_queued_secs(),
+        };
+        let context = SinkContext {
+            recording_path: "meeting.wav".into(),
+            spec: hound::WavSpec {
+                channels: 2,
+                sample_rate: 8000,
+                bits_per_sample: 16,
+                sample_format: hound::SampleFormat::Int,
+            },
+            session_id: Uuid::new_v4(),
+        };
+        let mut sink: Box<dyn Sink> = Box::new(CollectorSink::spawn(&options, &context).unwrap());
+        // Two full chunks of 80 frames, then a partial one
+        sink.write_frames(&[7; 400]).unwrap();
+        sink.finalize().unwrap();
+
+        let (mut stream, _) = listener.accept().unwrap();
+        let (kind, payload) = read_message(&mut stream).unwrap().unwrap();
+        assert_eq!(kind, MessageKind::Start);
+        let start: StreamStart = serde_json::from_slice(&payload).unwrap();
+        assert_eq!((start.session_id, start.sample_rate, start.channels), (context.session_id, 8000, 2));
+        let mut frames = Vec::new();
+        while let Some((MessageKind::Audio, payload)) = read_message(&mut stream).unwrap() {
+            let (start_frame, samples) = parse_audio(&payload).unwrap();
+            frames.push((start_frame, samples.len()));
+        }
+        assert_eq!(frames, vec![(0, 160), (80, 160), (160, 80)]);
+    }
+}
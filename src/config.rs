@@ -1,26 +1,271 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
+use crate::activity::ActivityWatch;
+use crate::backpressure::{BacklogPolicy, BackpressurePolicy};
+use crate::calendar::Calendar;
+use crate::chunking::ChunkOptions;
+use crate::detect::MeetingApps;
+use crate::encoder::{Bitrate, CompressedFormat, ExternalEncoder};
+use crate::encryption::Encryption;
+use crate::error::{ConfigError, Result};
+use crate::hotkey::Hotkeys;
+use crate::marker::MarkerFormat;
+use crate::midi::{MidiControls, MidiTrigger};
+use crate::mute::MuteDetector;
+use crate::priority::ThreadPriority;
+use crate::sink::RecordingFormat;
+use crate::collector::Collector;
+use crate::live::LiveServer;
+use crate::streaming::Streaming;
+use crate::transcription::Transcription;
+use crate::upload::Upload;
+use crate::webhook::Webhook;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 /// Application configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     /// Directory where recordings are saved
     pub output_directory: String,
+
+    /// Maximum number of audio chunks queued per source before backpressure applies
+    #[serde(default = "default_channel_capacity")]
+    pub channel_capacity: usize,
+
+    /// What to do when a source queue is full
+    #[serde(default)]
+    pub backpressure_policy: BackpressurePolicy,
+
+    /// Most audio, in seconds, the mixer holds for one source while waiting on the other (0 disables)
+    #[serde(default = "default_max_backlog_secs")]
+    pub max_backlog_secs: u64,
+
+    /// What to do when a source's backlog in the mixer exceeds `max_backlog_secs`
+    #[serde(default)]
+    pub backlog_policy: BacklogPolicy,
+
+    /// Scheduling priority for the capture and mixer threads
+    #[serde(default)]
+    pub thread_priority: ThreadPriority,
+
+    /// Size of the in-memory write buffer in front of the output file, in KiB
+    #[serde(default = "default_write_buffer_kb")]
+    pub write_buffer_kb: usize,
+
+    /// Seconds between flushes of buffered audio to disk (0 flushes only at the end)
+    #[serde(default = "default_flush_interval_secs")]
+    pub flush_interval_secs: u64,
+
+    /// Force data to stable storage on each flush, so power loss can't lose it either
+    #[serde(default = "default_sync_on_flush")]
+    pub sync_on_flush: bool,
+
+    /// Stop recording when free space on the output volume falls below this many MiB (0 disables)
+    #[serde(default = "default_min_free_space_mb")]
+    pub min_free_space_mb: u64,
+
+    /// Typical meeting length in minutes, used to warn up front when it won't fit on disk
+    #[serde(default = "default_expected_meeting_minutes")]
+    pub expected_meeting_minutes: u64,
+
+    /// Warn when the microphone stays silent this many seconds while system audio plays (0 disables)
+    #[serde(default = "default_mic_silence_alert_secs")]
+    pub mic_silence_alert_secs: u64,
+
+    /// Microphone peak level in dBFS below which it counts as silent for that warning
+    #[serde(default = "default_mic_silence_threshold_db")]
+    pub mic_silence_threshold_db: f32,
+
+    /// Format of the recording file
+    #[serde(default)]
+    pub format: RecordingFormat,
+
+    /// Also write a compressed copy in this format alongside the recording
+    #[serde(default)]
+    pub compressed_format: Option<CompressedFormat>,
+
+    /// Bitrate of lossy output: the recording in `opus` format and the compressed copy
+    #[serde(default)]
+    pub bitrate: Bitrate,
+
+    /// Command that produces recordings in `external` format
+    #[serde(default)]
+    pub external_encoder: Option<ExternalEncoder>,
+
+    /// Append each session's UUID to its file name
+    #[serde(default)]
+    pub session_id_in_filename: bool,
+
+    /// Encrypt finished recordings with `age` to these recipients
+    #[serde(default)]
+    pub encryption: Option<Encryption>,
+
+    /// Store a SHA-256 of each recording's audio in `<recording>.sha256`
+    #[serde(default)]
+    pub checksum: bool,
+
+    /// Write a JSON sidecar with the session's metadata next to each recording
+    #[serde(default = "default_sidecar")]
+    pub sidecar: bool,
+
+    /// Marker labels by the short name typed for them while recording, e.g. `a: action item`
+    #[serde(default)]
+    pub marker_labels: BTreeMap<String, String>,
+
+    /// Also save the markers next to each recording as an Audacity label track and/or FFmpeg chapters
+    #[serde(default)]
+    pub marker_exports: Vec<MarkerFormat>,
+
+    /// Also write the recording as overlapping fixed-length WAV chunks while recording
+    #[serde(default)]
+    pub chunks: Option<ChunkOptions>,
+
+    /// Stream the audio to an HTTP or WebSocket endpoint while recording (needs the `streaming` feature)
+    #[serde(default)]
+    pub streaming: Option<Streaming>,
+
+    /// Serve the audio to WebSocket clients while recording (needs the `live` feature)
+    #[serde(default)]
+    pub live: Option<LiveServer>,
+
+    /// Send the audio to a `meeting-recorder collect` server while recording
+    #[serde(default)]
+    pub collector: Option<Collector>,
+
+    /// Transcribe finished recordings with Whisper (needs the `whisper` feature)
+    #[serde(default)]
+    pub transcription: Option<Transcription>,
+
+    /// Upload finished recordings and their metadata to S3 or a server over SSH (needs the `upload` feature)
+    #[serde(default)]
+    pub upload: Option<Upload>,
+
+    /// Announce finished and failed recordings to a webhook, e.g. a Slack channel (needs the `webhook` feature)
+    #[serde(default)]
+    pub webhook: Option<Webhook>,
+
+    /// Global keyboard shortcuts to start and stop recording and add markers (needs the `hotkeys` feature)
+    #[serde(default)]
+    pub hotkeys: Option<Hotkeys>,
+
+    /// Buttons on a MIDI controller that start and stop, pause and mark the recording (needs the `midi` feature)
+    #[serde(default)]
+    pub midi: Option<MidiControls>,
+
+    /// Show desktop notifications when recording starts and ends, and on problems (needs the `notifications` feature)
+    #[serde(default)]
+    pub notifications: bool,
+
+    /// Record the meetings of a calendar with `meeting-recorder calendar` (needs the `calendar` feature)
+    #[serde(default)]
+    pub calendar: Option<Calendar>,
+
+    /// Which apps start a recording under `meeting-recorder auto` by using the microphone
+    #[serde(default)]
+    pub meeting_apps: Option<MeetingApps>,
+
+    /// When `meeting-recorder watch` starts and stops recording on the system audio's activity
+    #[serde(default)]
+    pub activity_watch: Option<ActivityWatch>,
+}
+
+fn default_channel_capacity() -> usize {
+    256
+}
+
+fn default_max_backlog_secs() -> u64 {
+    10
+}
+
+fn default_write_buffer_kb() -> usize {
+    256
+}
+
+fn default_flush_interval_secs() -> u64 {
+    5
+}
+
+fn default_sync_on_flush() -> bool {
+    true
+}
+
+fn default_min_free_space_mb() -> u64 {
+    500
+}
+
+fn default_expected_meeting_minutes() -> u64 {
+    60
+}
+
+fn default_mic_silence_alert_secs() -> u64 {
+    60
+}
+
+fn default_mic_silence_threshold_db() -> f32 {
+    -50.0
+}
+
+fn default_sidecar() -> bool {
+    true
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            output_directory: String::new(),
+            channel_capacity: default_channel_capacity(),
+            backpressure_policy: BackpressurePolicy::default(),
+            max_backlog_secs: default_max_backlog_secs(),
+            backlog_policy: BacklogPolicy::default(),
+            thread_priority: ThreadPriority::default(),
+            write_buffer_kb: default_write_buffer_kb(),
+            flush_interval_secs: default_flush_interval_secs(),
+            sync_on_flush: default_sync_on_flush(),
+            min_free_space_mb: default_min_free_space_mb(),
+            expected_meeting_minutes: default_expected_meeting_minutes(),
+            mic_silence_alert_secs: default_mic_silence_alert_secs(),
+            mic_silence_threshold_db: default_mic_silence_threshold_db(),
+            format: RecordingFormat::default(),
+            compressed_format: None,
+            bitrate: Bitrate::default(),
+            external_encoder: None,
+            session_id_in_filename: false,
+            encryption: None,
+            checksum: false,
+            sidecar: default_sidecar(),
+            marker_labels: BTreeMap::new(),
+            marker_exports: Vec::new(),
+            chunks: None,
+            streaming: None,
+            live: None,
+            collector: None,
+            transcription: None,
+            upload: None,
+            webhook: None,
+            hotkeys: None,
+            midi: None,
+            notifications: false,
+            calendar: None,
+            meeting_apps: None,
+            activity_watch: None,
+        }
+    }
 }
 
 impl Config {
     /// Load configuration from platform-specific default location
     /// - Windows: %PROGRAMDATA%\meeting-recorder\config.yaml
     /// - macOS/Linux: /opt/meeting-recorder/config.yaml
-    pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn load() -> Result<Self> {
         let config_path = Self::default_config_path()?;
         Self::load_from_path(config_path)
     }
     
     /// Get the default config path for the current platform
     /// This is public for testing purposes
-    pub fn default_config_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    pub fn default_config_path() -> Result<PathBuf> {
         #[cfg(target_os = "windows")]
         {
             use std::env;
@@ -36,39 +281,157 @@ impl Config {
     }
     
     /// Load configuration from a specific path (useful for testing)
-    pub fn load_from_path(config_path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn load_from_path(config_path: impl AsRef<Path>) -> Result<Self> {
         let config_path = config_path.as_ref();
         
         if !config_path.exists() {
-            return Err(format!(
-                "Config file not found at {}. Please create it with an 'output_directory' field.",
-                config_path.display()
-            ).into());
+            return Err(ConfigError::NotFound(config_path.to_path_buf()).into());
         }
         
-        let contents = fs::read_to_string(config_path)?;
-        let config: Config = serde_yaml::from_str(&contents)?;
+        let contents = fs::read_to_string(config_path).map_err(|source| ConfigError::Read {
+            path: config_path.to_path_buf(),
+            source,
+        })?;
+        let config: Config = serde_yaml::from_str(&contents).map_err(|source| ConfigError::Parse {
+            path: config_path.to_path_buf(),
+            source,
+        })?;
         
         // Validate that the output directory exists or can be created
         let output_path = Path::new(&config.output_directory);
         if !output_path.exists() {
-            fs::create_dir_all(output_path)?;
+            fs::create_dir_all(output_path).map_err(|source| ConfigError::CreateOutputDirectory {
+                path: config.output_directory.clone(),
+                source,
+            })?;
         }
         
         if !output_path.is_dir() {
-            return Err(format!(
-                "Output directory '{}' exists but is not a directory",
-                config.output_directory
-            ).into());
+            return Err(ConfigError::NotADirectory(config.output_directory).into());
         }
-        
+
+        config.validate()?;
         Ok(config)
     }
+
+    /// Check that recordings can be produced as configured
+    pub fn validate(&self) -> Result<()> {
+        if self.format == RecordingFormat::External
+            && self.external_encoder.as_ref().is_none_or(|encoder| encoder.command.is_empty())
+        {
+            return Err(ConfigError::MissingExternalEncoder.into());
+        }
+        if self.encryption.as_ref().is_some_and(|encryption| !encryption.has_recipients()) {
+            return Err(ConfigError::MissingEncryptionRecipients.into());
+        }
+        if self.chunks.is_some_and(|chunks| !chunks.is_valid()) {
+            return Err(ConfigError::InvalidChunks.into());
+        }
+        if let Some(streaming) = &self.streaming {
+            if !cfg!(feature = "streaming") {
+                return Err(ConfigError::StreamingUnavailable.into());
+            }
+            if streaming.transport().is_none() {
+                return Err(ConfigError::InvalidStreamingUrl(streaming.url.clone()).into());
+            }
+        }
+        if let Some(live) = &self.live {
+            if !cfg!(feature = "live") {
+                return Err(ConfigError::LiveUnavailable.into());
+            }
+            if live.address().is_none() {
+                return Err(ConfigError::InvalidLiveAddress(live.listen.clone()).into());
+            }
+        }
+        if self.collector.as_ref().is_some_and(|collector| !collector.is_valid()) {
+            return Err(ConfigError::InvalidCollectorAddress.into());
+        }
+        if self.transcription.is_some() {
+            if !cfg!(feature = "whisper") {
+                return Err(ConfigError::TranscriptionUnavailable.into());
+            }
+            if self.format != RecordingFormat::Wav {
+                return Err(ConfigError::TranscriptionNeedsWav.into());
+            }
+        }
+        if self.checksum {
+            if self.format != RecordingFormat::Wav {
+                return Err(ConfigError::ChecksumNeedsWav.into());
+            }
+            if self.encryption.is_some() {
+                return Err(ConfigError::ChecksumWithEncryption.into());
+            }
+        }
+        if self.upload.is_some() && !cfg!(feature = "upload") {
+            return Err(ConfigError::UploadUnavailable.into());
+        }
+        if let Some(webhook) = &self.webhook {
+            if !cfg!(feature = "webhook") {
+                return Err(ConfigError::WebhookUnavailable.into());
+            }
+            if !webhook.has_valid_url() {
+                return Err(ConfigError::InvalidWebhookUrl(webhook.url.clone()).into());
+            }
+        }
+        if self.hotkeys.is_some() && !cfg!(feature = "hotkeys") {
+            return Err(ConfigError::HotkeysUnavailable.into());
+        }
+        #[cfg(feature = "hotkeys")]
+        if let Some(shortcut) = self.hotkeys.as_ref().and_then(Hotkeys::invalid_shortcut) {
+            return Err(ConfigError::InvalidHotkey(shortcut.to_string()).into());
+        }
+        if let Some(midi) = &self.midi {
+            if !cfg!(feature = "midi") {
+                return Err(ConfigError::MidiUnavailable.into());
+            }
+            if let Some(Err(e)) = midi.invalid_control().map(str::parse::<MidiTrigger>) {
+                return Err(ConfigError::InvalidMidiControl(e).into());
+            }
+        }
+        if self.notifications && !cfg!(feature = "notifications") {
+            return Err(ConfigError::NotificationsUnavailable.into());
+        }
+        if self.calendar.as_ref().is_some_and(|calendar| calendar.url.is_empty() || calendar.rules.is_empty()) {
+            return Err(ConfigError::IncompleteCalendar.into());
+        }
+        Ok(())
+    }
+
+    /// Extension of recording files, without the dot
+    pub fn recording_extension(&self) -> &str {
+        match (self.format, &self.external_encoder) {
+            (RecordingFormat::External, Some(encoder)) => &encoder.extension,
+            (format, _) => format.extension(),
+        }
+    }
     
     /// Get the full path for a recording file
     pub fn recording_path(&self, filename: &str) -> PathBuf {
         Path::new(&self.output_directory).join(filename)
     }
+    
+    /// Free space low-water mark in bytes, or `None` if disk monitoring is disabled
+    pub fn min_free_space_bytes(&self) -> Option<u64> {
+        (self.min_free_space_mb > 0).then(|| self.min_free_space_mb * 1024 * 1024)
+    }
+    
+    /// Backlog cap in interleaved samples for a stream of `spec`, or `None` if uncapped
+    pub fn max_backlog_samples(&self, spec: &hound::WavSpec) -> Option<usize> {
+        (self.max_backlog_secs > 0).then(|| {
+            (self.max_backlog_secs * spec.sample_rate as u64 * spec.channels as u64) as usize
+        })
+    }
+    
+    /// Detector for a muted microphone, or `None` if the warning is disabled
+    pub fn mute_detector(&self) -> Option<MuteDetector> {
+        (self.mic_silence_alert_secs > 0)
+            .then(|| MuteDetector::new(self.mic_silence_threshold_db, Duration::from_secs(self.mic_silence_alert_secs)))
+    }
+    
+    /// Interval between periodic flushes, or `None` if only flushing at the end
+    pub fn flush_interval(&self) -> Option<Duration> {
+        (self.flush_interval_secs > 0).then(|| Duration::from_secs(self.flush_interval_secs))
+    }
 }
 
 #[cfg(test)]
@@ -79,11 +442,36 @@ mod tests {
     fn test_recording_path() {
         let config = Config {
             output_directory: "/tmp/recordings".to_string(),
+            ..Default::default()
         };
         
         let path = config.recording_path("test.wav");
         assert!(path.to_string_lossy().contains("test.wav"));
         assert!(path.to_string_lossy().contains("/tmp/recordings"));
     }
+    
+    #[test]
+    fn test_max_backlog_samples() {
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 48000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut config = Config::default();
+        assert_eq!(config.max_backlog_samples(&spec), Some(960_000));
+        
+        config.max_backlog_secs = 0;
+        assert_eq!(config.max_backlog_samples(&spec), None);
+    }
+    
+    #[test]
+    fn test_flush_interval() {
+        let mut config = Config::default();
+        assert_eq!(config.flush_interval(), Some(Duration::from_secs(5)));
+        
+        config.flush_interval_secs = 0;
+        assert_eq!(config.flush_interval(), None);
+    }
 }
 
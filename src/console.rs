@@ -0,0 +1,49 @@
+//! Terminal output from the library, compiled out without the `console` feature
+//!
+//! Write errors are ignored: after a terminal hangup printing can fail, and
+//! that must never take a recording down with it.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Turn the library's terminal output off or back on, e.g. while a
+/// full-screen interface owns the terminal
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether the library prints to the terminal
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Print a line of progress to stdout
+macro_rules! console_out {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "console")]
+        if crate::console::enabled() {
+            use std::io::Write as _;
+            let _ = writeln!(std::io::stdout(), $($arg)*);
+        }
+        #[cfg(not(feature = "console"))]
+        {
+            let _ = format_args!($($arg)*);
+        }
+    }};
+}
+
+/// Print a line of warnings or diagnostics to stderr
+macro_rules! console_err {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "console")]
+        if crate::console::enabled() {
+            use std::io::Write as _;
+            let _ = writeln!(std::io::stderr(), $($arg)*);
+        }
+        #[cfg(not(feature = "console"))]
+        {
+            let _ = format_args!($($arg)*);
+        }
+    }};
+}
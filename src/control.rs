@@ -0,0 +1,196 @@
+//! Adding markers to a running recording
+//!
+//! While recording, every line typed on stdin adds a marker labelled with
+//! the text, or with the label it is short for in `marker_labels`. On Unix,
+//! a control socket in the output directory takes the same from other
+//! processes, which is how `meeting-recorder marker` reaches the session.
+//! Each connection sends one line, `marker [LABEL]`, and is answered with
+//! `ok <seconds> <label>` or `error <message>`.
+
+use meeting_recorder::marker;
+use meeting_recorder::recorder::RecordingSession;
+use std::collections::BTreeMap;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use crossbeam_channel::Receiver;
+
+/// Name of the control socket created in the output directory
+pub const SOCKET_FILE_NAME: &str = ".meeting-recorder.sock";
+
+/// Path of the control socket of sessions recording into `output_directory`
+pub fn socket_path(output_directory: impl AsRef<Path>) -> PathBuf {
+    output_directory.as_ref().join(SOCKET_FILE_NAME)
+}
+
+/// Lines typed on stdin, read by a thread of its own until input ends
+///
+/// The thread is never joined, as a read from a terminal can't be interrupted.
+pub fn stdin_lines() -> Receiver<String> {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else { return };
+            if tx.send(line).is_err() {
+                return;
+            }
+        }
+    });
+    rx
+}
+
+/// Mark the current point of `session`, returning how far into the recording it is
+pub fn mark(session: &RecordingSession, label: String) -> Duration {
+    let marker = session.add_marker(label);
+    let time = marker.time(session.sample_rate().max(1));
+    println!("Marker at {}: {}", timestamp(time), marker.label);
+    time
+}
+
+/// `1:02:03`, or `2:03` under an hour
+pub fn timestamp(time: Duration) -> String {
+    let secs = time.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+/// Label asked for by a `marker` command, or None if `line` isn't one
+#[cfg_attr(not(unix), allow(dead_code))]
+fn marker_command(line: &str) -> Option<&str> {
+    let line = line.trim();
+    match line.split_once(' ') {
+        Some(("marker", label)) => Some(label),
+        None if line == "marker" => Some(""),
+        _ => None,
+    }
+}
+
+/// A listening control socket, removed again when dropped
+#[cfg(unix)]
+pub struct ControlSocket {
+    listener: std::os::unix::net::UnixListener,
+    path: PathBuf,
+}
+
+#[cfg(unix)]
+impl ControlSocket {
+    /// Listen in `output_directory`
+    ///
+    /// Only the holder of the session lock may call this: a socket file left
+    /// by a session that crashed is replaced.
+    pub fn bind(output_directory: impl AsRef<Path>) -> io::Result<Self> {
+        let path = socket_path(output_directory);
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        let listener = std::os::unix::net::UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self { listener, path })
+    }
+
+    /// Answer the connections waiting, without blocking for new ones
+    ///
+    /// `mark` adds each marker asked for, as [`mark`] does.
+    pub fn poll(&self, labels: &BTreeMap<String, String>, mut mark: impl FnMut(String) -> Duration) {
+        use std::io::Write;
+
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(false).is_err() || stream.set_read_timeout(Some(Duration::from_secs(1))).is_err() {
+                continue;
+            }
+            let mut line = String::new();
+            if io::BufReader::new(io::Read::take(&stream, 1024)).read_line(&mut line).is_err() {
+                continue;
+            }
+            let reply = match marker_command(&line) {
+                Some(typed) => {
+                    let label = marker::typed_label(typed, labels);
+                    let time = mark(label.clone());
+                    format!("ok {:.3} {}", time.as_secs_f64(), label)
+                }
+                None => format!("error unknown command '{}'", line.trim()),
+            };
+            let _ = writeln!(&stream, "{}", reply);
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Add a marker to the session recording into `output_directory`
+///
+/// Returns where in the recording it went and its label.
+#[cfg(unix)]
+pub fn send_marker(output_directory: impl AsRef<Path>, label: &str) -> Result<(Duration, String), Box<dyn std::error::Error>> {
+    use std::io::{Read, Write};
+
+    let path = socket_path(output_directory);
+    let mut stream = std::os::unix::net::UnixStream::connect(&path)
+        .map_err(|e| format!("No recording to mark ({}: {})", path.display(), e))?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    writeln!(stream, "marker {}", label.trim())?;
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply)?;
+    parse_reply(&reply)
+}
+
+/// Markers can only be sent over a Unix socket
+#[cfg(not(unix))]
+pub fn send_marker(_output_directory: impl AsRef<Path>, _label: &str) -> Result<(Duration, String), Box<dyn std::error::Error>> {
+    Err("Adding markers from another process is only supported on Unix; type them into the recorder's terminal instead".into())
+}
+
+/// Without Unix sockets there is nothing to listen on; markers are typed instead
+#[cfg(not(unix))]
+pub struct ControlSocket;
+
+#[cfg(not(unix))]
+impl ControlSocket {
+    pub fn bind(_output_directory: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self)
+    }
+
+    pub fn poll(&self, _labels: &BTreeMap<String, String>, _mark: impl FnMut(String) -> Duration) {}
+}
+
+#[cfg_attr(not(unix), allow(dead_code))]
+fn parse_reply(reply: &str) -> Result<(Duration, String), Box<dyn std::error::Error>> {
+    let reply = reply.trim();
+    if let Some(message) = reply.strip_prefix("error ") {
+        return Err(message.into());
+    }
+    let (secs, label) = reply
+        .strip_prefix("ok ")
+        .and_then(|rest| rest.split_once(' '))
+        .ok_or_else(|| format!("Unexpected reply from the recorder: '{}'", reply))?;
+    let secs: f64 = secs.parse().map_err(|_| format!("Unexpected reply from the recorder: '{}'", reply))?;
+    Ok((Duration::from_secs_f64(secs), label.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_marker_commands_and_replies() {
+        assert_eq!(marker_command("marker action item\n"), Some("action item"));
+        assert_eq!(marker_command("marker\n"), Some(""));
+        assert_eq!(marker_command("stop\n"), None);
+
+        let (time, label) = parse_reply("ok 754.250 decision\n").unwrap();
+        assert_eq!((timestamp(time).as_str(), label.as_str()), ("12:34", "decision"));
+        assert_eq!(parse_reply("error unknown command 'x'").unwrap_err().to_string(), "unknown command 'x'");
+        assert_eq!(timestamp(Duration::from_secs(3723)), "1:02:03");
+    }
+}
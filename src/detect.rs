@@ -0,0 +1,373 @@
+//! Noticing meeting apps that are using the microphone
+//!
+//! `meeting-recorder auto` asks [`microphone_users`] which programs are
+//! capturing from the microphone and records while one of the apps in
+//! `meeting_apps` is. How that is found out differs per platform:
+//!
+//! - Linux: the capture streams PulseAudio, or PipeWire through
+//!   pipewire-pulse, lists with `pactl`, and the program behind each.
+//! - macOS 14.2 and later: the processes CoreAudio reports as taking input.
+//! - Windows: the apps the microphone privacy settings show as using it.
+//!
+//! The recorder's own capture is never counted.
+
+use serde::{Deserialize, Serialize};
+use std::io;
+
+/// Which apps start a recording by using the microphone, and how
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MeetingApps {
+    /// Names of the apps, each matched ignoring case against the program's name or path
+    #[serde(default = "default_apps")]
+    pub apps: Vec<String>,
+    /// Ask on the terminal before recording instead of starting right away
+    #[serde(default)]
+    pub prompt: bool,
+    /// How often the microphone is checked, in seconds
+    #[serde(default = "default_poll_secs")]
+    pub poll_secs: u64,
+    /// How long an app must have let go of the microphone before recording stops, in seconds
+    #[serde(default = "default_stop_delay_secs")]
+    pub stop_delay_secs: u64,
+}
+
+fn default_apps() -> Vec<String> {
+    // Webex's audio runs in CiscoCollabHost
+    ["zoom", "teams", "webex", "CiscoCollabHost"].map(String::from).to_vec()
+}
+
+fn default_poll_secs() -> u64 {
+    2
+}
+
+fn default_stop_delay_secs() -> u64 {
+    10
+}
+
+impl Default for MeetingApps {
+    fn default() -> Self {
+        Self {
+            apps: default_apps(),
+            prompt: false,
+            poll_secs: default_poll_secs(),
+            stop_delay_secs: default_stop_delay_secs(),
+        }
+    }
+}
+
+impl MeetingApps {
+    /// The configured app `user` is, if any
+    pub fn app_for(&self, user: &MicrophoneUser) -> Option<&str> {
+        let name = user.name.to_lowercase();
+        self.apps.iter().find(|app| name.contains(&app.to_lowercase())).map(String::as_str)
+    }
+}
+
+/// A program capturing from the microphone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MicrophoneUser {
+    /// Process ID, where the platform tells
+    pub pid: Option<u32>,
+    /// Name or path of the program, such as `zoom` or `C:\Program Files\Zoom\bin\Zoom.exe`
+    pub name: String,
+}
+
+/// Programs other than this one capturing from the microphone now
+pub fn microphone_users() -> io::Result<Vec<MicrophoneUser>> {
+    let own_pid = std::process::id();
+    let own_path = std::env::current_exe().ok();
+    let mut users = platform::microphone_users()?;
+    users.retain(|user| {
+        user.pid != Some(own_pid) && own_path.as_ref().is_none_or(|path| path.as_os_str() != user.name.as_str())
+    });
+    Ok(users)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod platform {
+    use super::MicrophoneUser;
+    use std::io;
+    use std::process::Command;
+
+    pub fn microphone_users() -> io::Result<Vec<MicrophoneUser>> {
+        let output = Command::new("pactl")
+            .args(["list", "source-outputs"])
+            .env("LC_ALL", "C")
+            .output()
+            .map_err(|e| io::Error::new(e.kind(), format!("could not run pactl: {}", e)))?;
+        if !output.status.success() {
+            let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            return Err(io::Error::other(format!("pactl failed: {}", message)));
+        }
+        Ok(parse_source_outputs(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// The programs behind the capture streams in `pactl list source-outputs`
+    pub(super) fn parse_source_outputs(listing: &str) -> Vec<MicrophoneUser> {
+        let mut users = Vec::new();
+        // Each stream starts with an unindented "Source Output #N" line
+        for stream in listing.split("\nSource Output #").map(|stream| stream.trim_start_matches("Source Output #")) {
+            let property = |name: &str| {
+                stream.lines().find_map(|line| {
+                    let value = line.trim().strip_prefix(name)?.trim_start().strip_prefix('=')?;
+                    Some(value.trim().trim_matches('"').to_string())
+                })
+            };
+            let name = property("application.process.binary").or_else(|| property("application.name"));
+            if let Some(name) = name {
+                let pid = property("application.process.id").and_then(|pid| pid.parse().ok());
+                users.push(MicrophoneUser { pid, name });
+            }
+        }
+        users
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::MicrophoneUser;
+    use std::ffi::c_void;
+    use std::io;
+    use std::ptr;
+
+    #[repr(C)]
+    struct AudioObjectPropertyAddress {
+        selector: u32,
+        scope: u32,
+        element: u32,
+    }
+
+    #[link(name = "CoreAudio", kind = "framework")]
+    extern "C" {
+        fn AudioObjectGetPropertyDataSize(
+            object: u32,
+            address: *const AudioObjectPropertyAddress,
+            qualifier_size: u32,
+            qualifier: *const c_void,
+            data_size: *mut u32,
+        ) -> i32;
+        fn AudioObjectGetPropertyData(
+            object: u32,
+            address: *const AudioObjectPropertyAddress,
+            qualifier_size: u32,
+            qualifier: *const c_void,
+            data_size: *mut u32,
+            data: *mut c_void,
+        ) -> i32;
+    }
+
+    const SYSTEM_OBJECT: u32 = 1;
+    const SCOPE_GLOBAL: u32 = u32::from_be_bytes(*b"glob");
+    const ELEMENT_MAIN: u32 = 0;
+    /// Audio process objects, from macOS 14.2
+    const PROCESS_OBJECT_LIST: u32 = u32::from_be_bytes(*b"prs#");
+    const PROCESS_PID: u32 = u32::from_be_bytes(*b"ppid");
+    const PROCESS_IS_RUNNING_INPUT: u32 = u32::from_be_bytes(*b"piri");
+
+    pub fn microphone_users() -> io::Result<Vec<MicrophoneUser>> {
+        let processes = property_size(SYSTEM_OBJECT, PROCESS_OBJECT_LIST)
+            .and_then(|size| {
+                let mut processes = vec![0u32; size as usize / std::mem::size_of::<u32>()];
+                property(SYSTEM_OBJECT, PROCESS_OBJECT_LIST, &mut processes)?;
+                Ok(processes)
+            })
+            .map_err(|e| io::Error::new(e.kind(), format!("CoreAudio can't list the processes using audio (needs macOS 14.2 or later): {}", e)))?;
+        let mut users = Vec::new();
+        for process in processes {
+            let mut running_input = [0u32];
+            let mut pid = [0i32];
+            if property(process, PROCESS_IS_RUNNING_INPUT, &mut running_input).is_err() || running_input[0] == 0 {
+                continue;
+            }
+            if property(process, PROCESS_PID, &mut pid).is_ok() {
+                users.push(MicrophoneUser { pid: Some(pid[0] as u32), name: process_path(pid[0]) });
+            }
+        }
+        Ok(users)
+    }
+
+    fn address(selector: u32) -> AudioObjectPropertyAddress {
+        AudioObjectPropertyAddress { selector, scope: SCOPE_GLOBAL, element: ELEMENT_MAIN }
+    }
+
+    fn property_size(object: u32, selector: u32) -> io::Result<u32> {
+        let mut size = 0;
+        match unsafe { AudioObjectGetPropertyDataSize(object, &address(selector), 0, ptr::null(), &mut size) } {
+            0 => Ok(size),
+            status => Err(io::Error::other(format!("OSStatus {}", status))),
+        }
+    }
+
+    fn property<T: Copy>(object: u32, selector: u32, data: &mut [T]) -> io::Result<()> {
+        let mut size = std::mem::size_of_val(data) as u32;
+        let status = unsafe {
+            AudioObjectGetPropertyData(object, &address(selector), 0, ptr::null(), &mut size, data.as_mut_ptr().cast())
+        };
+        match status {
+            0 => Ok(()),
+            status => Err(io::Error::other(format!("OSStatus {}", status))),
+        }
+    }
+
+    /// Path of the executable of process `pid`, e.g. `/Applications/zoom.us.app/Contents/MacOS/zoom.us`
+    fn process_path(pid: i32) -> String {
+        let mut path = vec![0u8; libc::PROC_PIDPATHINFO_MAXSIZE as usize];
+        let length = unsafe { libc::proc_pidpath(pid, path.as_mut_ptr().cast(), path.len() as u32) };
+        if length <= 0 {
+            return format!("process {}", pid);
+        }
+        String::from_utf8_lossy(&path[..length as usize]).into_owned()
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::MicrophoneUser;
+    use std::ffi::c_void;
+    use std::io;
+    use std::ptr;
+    use windows_sys::Win32::System::Registry::{
+        RegCloseKey, RegEnumKeyExW, RegGetValueW, RegOpenKeyExW, HKEY, HKEY_CURRENT_USER, KEY_READ, RRF_RT_REG_QWORD,
+    };
+
+    /// Where Windows records which apps use the microphone, for its privacy settings
+    const CONSENT_STORE: &str = r"Software\Microsoft\Windows\CurrentVersion\CapabilityAccessManager\ConsentStore\microphone";
+
+    pub fn microphone_users() -> io::Result<Vec<MicrophoneUser>> {
+        let store = Key::open(HKEY_CURRENT_USER, CONSENT_STORE)?;
+        let mut users = Vec::new();
+        for name in store.subkeys() {
+            // Desktop apps are kept by path, with '#' for '\'; Store apps by package name
+            if name == "NonPackaged" {
+                let desktop = Key::open(store.0, &name)?;
+                users.extend(
+                    desktop
+                        .subkeys()
+                        .into_iter()
+                        .filter(|app| desktop.in_use(app))
+                        .map(|app| MicrophoneUser { pid: None, name: app.replace('#', "\\") }),
+                );
+            } else if store.in_use(&name) {
+                users.push(MicrophoneUser { pid: None, name });
+            }
+        }
+        Ok(users)
+    }
+
+    /// An open registry key
+    struct Key(HKEY);
+
+    impl Key {
+        fn open(parent: HKEY, path: &str) -> io::Result<Self> {
+            let mut key: HKEY = ptr::null_mut();
+            match unsafe { RegOpenKeyExW(parent, wide(path).as_ptr(), 0, KEY_READ, &mut key) } {
+                0 => Ok(Self(key)),
+                error => Err(io::Error::from_raw_os_error(error as i32)),
+            }
+        }
+
+        fn subkeys(&self) -> Vec<String> {
+            let mut names = Vec::new();
+            let mut name = [0u16; 512];
+            for index in 0.. {
+                let mut length = name.len() as u32;
+                let status = unsafe {
+                    RegEnumKeyExW(
+                        self.0,
+                        index,
+                        name.as_mut_ptr(),
+                        &mut length,
+                        ptr::null(),
+                        ptr::null_mut(),
+                        ptr::null_mut(),
+                        ptr::null_mut(),
+                    )
+                };
+                if status != 0 {
+                    break;
+                }
+                names.push(String::from_utf16_lossy(&name[..length as usize]));
+            }
+            names
+        }
+
+        /// Whether the app under `subkey` is using the microphone: it has
+        /// started to and not yet stopped
+        fn in_use(&self, subkey: &str) -> bool {
+            let mut stopped = u64::MAX;
+            let mut size = std::mem::size_of::<u64>() as u32;
+            let status = unsafe {
+                RegGetValueW(
+                    self.0,
+                    wide(subkey).as_ptr(),
+                    wide("LastUsedTimeStop").as_ptr(),
+                    RRF_RT_REG_QWORD,
+                    ptr::null_mut(),
+                    (&mut stopped as *mut u64).cast::<c_void>(),
+                    &mut size,
+                )
+            };
+            status == 0 && stopped == 0
+        }
+    }
+
+    impl Drop for Key {
+        fn drop(&mut self) {
+            unsafe {
+                RegCloseKey(self.0);
+            }
+        }
+    }
+
+    fn wide(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain(Some(0)).collect()
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod platform {
+    use super::MicrophoneUser;
+    use std::io;
+
+    pub fn microphone_users() -> io::Result<Vec<MicrophoneUser>> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "finding apps that use the microphone is not supported on this platform"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apps_match_programs_ignoring_case() {
+        let apps = MeetingApps::default();
+        let user = |name: &str| MicrophoneUser { pid: None, name: name.to_string() };
+        assert_eq!(apps.app_for(&user("/Applications/zoom.us.app/Contents/MacOS/zoom.us")), Some("zoom"));
+        assert_eq!(apps.app_for(&user(r"C:\Program Files\WindowsApps\MSTeams_8wekyb3d8bbwe\ms-teams.exe")), Some("teams"));
+        assert_eq!(apps.app_for(&user("firefox")), None);
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn test_parses_pactl_source_outputs() {
+        let listing = "Source Output #41\n\
+\tDriver: PipeWire\n\
+\tProperties:\n\
+\t\tapplication.name = \"ZOOM VoiceEngine\"\n\
+\t\tapplication.process.id = \"4242\"\n\
+\t\tapplication.process.binary = \"zoom\"\n\
+\n\
+Source Output #57\n\
+\tDriver: PipeWire\n\
+\tProperties:\n\
+\t\tapplication.name = \"Firefox\"\n";
+        assert_eq!(
+            platform::parse_source_outputs(listing),
+            [
+                MicrophoneUser { pid: Some(4242), name: "zoom".to_string() },
+                MicrophoneUser { pid: None, name: "Firefox".to_string() },
+            ]
+        );
+        assert!(platform::parse_source_outputs("").is_empty());
+    }
+}
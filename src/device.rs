@@ -1,5 +1,6 @@
 use cpal::traits::{DeviceTrait, HostTrait};
 use cpal::SupportedStreamConfig;
+use crate::error::{DeviceError, Result};
 
 /// Manages audio device enumeration and selection
 pub struct DeviceManager {
@@ -8,22 +9,23 @@ pub struct DeviceManager {
 
 impl DeviceManager {
     /// Create a new DeviceManager
-    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn new() -> Result<Self> {
         let host = cpal::default_host();
-        let devices: Vec<_> = host.input_devices()?.collect();
+        let devices: Vec<_> = host.input_devices().map_err(DeviceError::from)?.collect();
         
         if devices.is_empty() {
-            return Err("No input devices found".into());
+            return Err(DeviceError::NoInputDevices.into());
         }
         
         Ok(Self { devices })
     }
     
-    /// List all available input devices
-    pub fn list_devices(&self) -> Result<(), Box<dyn std::error::Error>> {
+    /// Print all available input devices (`console` feature)
+    #[cfg(feature = "console")]
+    pub fn list_devices(&self) -> Result<()> {
         println!("Available input devices:");
         for (i, device) in self.devices.iter().enumerate() {
-            let name = device.name()?;
+            let name = device.name().map_err(DeviceError::from)?;
             let config = device.default_input_config().ok();
             let info = if let Some(cfg) = config {
                 format!(" ({} ch, {} Hz)", cfg.channels(), cfg.sample_rate().0)
@@ -55,19 +57,71 @@ impl DeviceManager {
     }
     
     /// Get device name
-    pub fn device_name(&self, index: usize) -> Result<String, Box<dyn std::error::Error>> {
-        self.devices
-            .get(index)
-            .ok_or_else(|| format!("Device index {} out of range", index).into())
-            .and_then(|d| d.name().map_err(|e| e.into()))
+    pub fn device_name(&self, index: usize) -> Result<String> {
+        let device = self.devices.get(index).ok_or(DeviceError::IndexOutOfRange(index))?;
+        Ok(device.name().map_err(DeviceError::from)?)
     }
     
     /// Get device configuration
-    pub fn device_config(&self, index: usize) -> Result<SupportedStreamConfig, Box<dyn std::error::Error>> {
-        self.devices
-            .get(index)
-            .ok_or_else(|| format!("Device index {} out of range", index).into())
-            .and_then(|d| d.default_input_config().map_err(|e| e.into()))
+    pub fn device_config(&self, index: usize) -> Result<SupportedStreamConfig> {
+        let device = self.devices.get(index).ok_or(DeviceError::IndexOutOfRange(index))?;
+        Ok(device.default_input_config().map_err(DeviceError::from)?)
+    }
+    
+    /// Index of the device called `name`
+    ///
+    /// An exact match wins; otherwise `name` may be any case-insensitive part
+    /// of a single device's name.
+    pub fn find_device(&self, name: &str) -> Result<usize> {
+        let names = self.devices.iter()
+            .map(|device| device.name().map_err(DeviceError::from))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(match_name(&names, name)?)
+    }
+    
+    /// The device called `name` (see [`find_device`](Self::find_device)) and its default configuration
+    pub fn device_by_name(&self, name: &str) -> Result<(cpal::Device, SupportedStreamConfig)> {
+        let index = self.find_device(name)?;
+        Ok((self.devices[index].clone(), self.device_config(index)?))
+    }
+}
+
+/// Pick the one entry of `names` that `query` refers to
+fn match_name(names: &[String], query: &str) -> std::result::Result<usize, DeviceError> {
+    if let Some(index) = names.iter().position(|name| name == query) {
+        return Ok(index);
+    }
+    let query_lower = query.to_lowercase();
+    let matches: Vec<usize> = names.iter()
+        .enumerate()
+        .filter(|(_, name)| name.to_lowercase().contains(&query_lower))
+        .map(|(index, _)| index)
+        .collect();
+    match matches.as_slice() {
+        [index] => Ok(*index),
+        [] => Err(DeviceError::NotFound(query.to_string())),
+        _ => Err(DeviceError::Ambiguous {
+            name: query.to_string(),
+            matches: matches.iter().map(|&index| names[index].clone()).collect(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_match_name_prefers_exact_then_unique_substring() {
+        let names: Vec<String> = ["MacBook Pro Microphone", "BlackHole 2ch", "BlackHole 16ch", "BlackHole"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        assert_eq!(match_name(&names, "BlackHole").unwrap(), 3);
+        assert_eq!(match_name(&names, "microphone").unwrap(), 0);
+        assert!(matches!(match_name(&names, "blackhole 1"), Ok(2)));
+        assert!(matches!(match_name(&names, "ch"), Err(DeviceError::Ambiguous { .. })));
+        assert!(matches!(match_name(&names, "USB"), Err(DeviceError::NotFound(_))));
     }
 }
 
@@ -0,0 +1,52 @@
+use hound::WavSpec;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// Space available to the current user on the volume holding `path`, in bytes
+pub fn available_space(path: impl AsRef<Path>) -> io::Result<u64> {
+    fs4::available_space(path)
+}
+
+/// Bytes of sample data a recording with `spec` produces per second
+pub fn bytes_per_second(spec: &WavSpec) -> u64 {
+    spec.sample_rate as u64 * spec.channels as u64 * (spec.bits_per_sample as u64 / 8)
+}
+
+/// How long a recording with `spec` can run before free space drops to `reserve` bytes
+pub fn time_remaining(available: u64, reserve: u64, spec: &WavSpec) -> Duration {
+    let rate = bytes_per_second(spec).max(1);
+    Duration::from_secs(available.saturating_sub(reserve) / rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hound::SampleFormat;
+
+    fn spec() -> WavSpec {
+        WavSpec {
+            channels: 2,
+            sample_rate: 48000,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        }
+    }
+
+    #[test]
+    fn test_bytes_per_second() {
+        assert_eq!(bytes_per_second(&spec()), 192_000);
+    }
+
+    #[test]
+    fn test_time_remaining_respects_reserve() {
+        let hour = 192_000 * 3600;
+        assert_eq!(time_remaining(hour + 1000, 1000, &spec()), Duration::from_secs(3600));
+        assert_eq!(time_remaining(500, 1000, &spec()), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_available_space_of_temp_dir() {
+        assert!(available_space(std::env::temp_dir()).unwrap() > 0);
+    }
+}
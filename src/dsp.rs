@@ -0,0 +1,367 @@
+//! Hot-path sample conversion, channel mapping, resampling and mixing
+//!
+//! Conversion and mixing have a scalar reference implementation and, on
+//! x86_64, SSE2 and AVX2 paths selected at runtime. The vector paths produce
+//! bit-identical output to the scalar ones.
+
+/// Convert f32 samples in [-1.0, 1.0] to i16, writing into `output`
+///
+/// Values outside the range are clamped and NaN becomes 0.
+/// Panics if `input` and `output` differ in length.
+pub fn convert_f32_to_i16(input: &[f32], output: &mut [i16]) {
+    assert_eq!(input.len(), output.len(), "input and output lengths differ");
+
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: AVX2 support was just checked
+            unsafe { x86::convert_f32_to_i16_avx2(input, output) };
+        } else {
+            // SAFETY: SSE2 is part of the x86_64 baseline
+            unsafe { x86::convert_f32_to_i16_sse2(input, output) };
+        }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    convert_f32_to_i16_scalar(input, output);
+}
+
+/// Scalar reference for [`convert_f32_to_i16`]
+pub fn convert_f32_to_i16_scalar(input: &[f32], output: &mut [i16]) {
+    for (out, &s) in output.iter_mut().zip(input) {
+        *out = (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+    }
+}
+
+/// Mix two sample slices with saturating addition, writing into `output`
+///
+/// Panics if the three slices differ in length.
+pub fn mix_saturating(a: &[i16], b: &[i16], output: &mut [i16]) {
+    assert!(a.len() == b.len() && a.len() == output.len(), "slice lengths differ");
+
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: AVX2 support was just checked
+            unsafe { x86::mix_saturating_avx2(a, b, output) };
+        } else {
+            // SAFETY: SSE2 is part of the x86_64 baseline
+            unsafe { x86::mix_saturating_sse2(a, b, output) };
+        }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    mix_saturating_scalar(a, b, output);
+}
+
+/// Scalar reference for [`mix_saturating`]
+pub fn mix_saturating_scalar(a: &[i16], b: &[i16], output: &mut [i16]) {
+    for ((out, &x), &y) in output.iter_mut().zip(a).zip(b) {
+        *out = x.saturating_add(y);
+    }
+}
+
+/// Peak level of `samples` from 0.0 (silence) to 1.0 (full scale)
+pub fn peak_level(samples: &[i16]) -> f32 {
+    let peak = samples.iter().map(|s| s.unsigned_abs()).max().unwrap_or(0);
+    (peak as f32 / i16::MAX as f32).min(1.0)
+}
+
+/// Peak and RMS level of a stretch of audio, from 0.0 (silence) to 1.0 (full scale)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Levels {
+    pub peak: f32,
+    pub rms: f32,
+}
+
+/// Accumulates [`Levels`] over any number of chunks
+#[derive(Debug, Clone, Default)]
+pub struct LevelMeter {
+    peak: f32,
+    sum_squares: f64,
+    samples: u64,
+}
+
+impl LevelMeter {
+    pub fn add(&mut self, samples: &[i16]) {
+        self.peak = self.peak.max(peak_level(samples));
+        self.sum_squares += samples.iter().map(|&s| (s as f64) * (s as f64)).sum::<f64>();
+        self.samples += samples.len() as u64;
+    }
+
+    /// Levels since the last call, starting a new measurement
+    pub fn take(&mut self) -> Levels {
+        std::mem::take(self).levels()
+    }
+
+    /// Levels over everything added so far
+    pub fn levels(&self) -> Levels {
+        let mean_square = self.sum_squares / self.samples.max(1) as f64;
+        Levels {
+            peak: self.peak,
+            rms: (mean_square.sqrt() / i16::MAX as f64).min(1.0) as f32,
+        }
+    }
+}
+
+/// Append interleaved samples with `channels` channels to a stereo buffer
+///
+/// Mono is duplicated to both channels; anything else is assumed to already
+/// be stereo.
+pub fn append_stereo(buffer: &mut Vec<i16>, samples: &[i16], channels: u16) {
+    if channels == 1 {
+        buffer.extend(samples.iter().flat_map(|&s| [s, s]));
+    } else {
+        buffer.extend_from_slice(samples);
+    }
+}
+
+/// Streaming linear-interpolation resampler for interleaved stereo
+///
+/// Keeps the last frame and fractional position between calls, so chunks of
+/// any size can be fed in without clicks at the boundaries.
+#[derive(Debug, Clone)]
+pub struct StereoResampler {
+    /// Input frames advanced per output frame
+    step: f64,
+    /// Position of the next output frame, where 0 is `last`
+    pos: f64,
+    last: [i16; 2],
+    primed: bool,
+}
+
+impl StereoResampler {
+    pub fn new(input_rate: u32, output_rate: u32) -> Self {
+        Self {
+            step: input_rate as f64 / output_rate.max(1) as f64,
+            pos: 0.0,
+            last: [0; 2],
+            primed: false,
+        }
+    }
+
+    /// Resample `input` and append the result to `output`
+    pub fn process(&mut self, input: &[i16], output: &mut Vec<i16>) {
+        let frames = input.len() / 2;
+        if frames == 0 {
+            return;
+        }
+        if !self.primed {
+            self.last = [input[0], input[1]];
+            self.primed = true;
+            // Start on the first real frame rather than the primed copy
+            self.pos = 1.0;
+        }
+
+        // Frame 0 is the last frame of the previous chunk, 1..=frames this one
+        let frame = |i: usize| -> [i16; 2] {
+            if i == 0 {
+                self.last
+            } else {
+                [input[(i - 1) * 2], input[(i - 1) * 2 + 1]]
+            }
+        };
+
+        output.reserve(((frames as f64 / self.step) as usize + 1) * 2);
+        let mut pos = self.pos;
+        while pos < frames as f64 {
+            let index = pos as usize;
+            let frac = pos - index as f64;
+            let (a, b) = (frame(index), frame(index + 1));
+            for ch in 0..2 {
+                let value = a[ch] as f64 + (b[ch] as f64 - a[ch] as f64) * frac;
+                output.push(value.round() as i16);
+            }
+            pos += self.step;
+        }
+
+        self.pos = pos - frames as f64;
+        self.last = frame(frames);
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn convert_f32_to_i16_sse2(input: &[f32], output: &mut [i16]) {
+        let lo = _mm_set1_ps(-1.0);
+        let hi = _mm_set1_ps(1.0);
+        let scale = _mm_set1_ps(i16::MAX as f32);
+        let chunks = input.len() / 8;
+
+        for i in 0..chunks {
+            let src = input.as_ptr().add(i * 8);
+            let mut a = _mm_loadu_ps(src);
+            let mut b = _mm_loadu_ps(src.add(4));
+            // Zero NaNs to match `NaN as i16 == 0`
+            a = _mm_and_ps(a, _mm_cmpord_ps(a, a));
+            b = _mm_and_ps(b, _mm_cmpord_ps(b, b));
+            a = _mm_mul_ps(_mm_min_ps(_mm_max_ps(a, lo), hi), scale);
+            b = _mm_mul_ps(_mm_min_ps(_mm_max_ps(b, lo), hi), scale);
+            let packed = _mm_packs_epi32(_mm_cvttps_epi32(a), _mm_cvttps_epi32(b));
+            _mm_storeu_si128(output.as_mut_ptr().add(i * 8) as *mut __m128i, packed);
+        }
+
+        let done = chunks * 8;
+        super::convert_f32_to_i16_scalar(&input[done..], &mut output[done..]);
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn convert_f32_to_i16_avx2(input: &[f32], output: &mut [i16]) {
+        let lo = _mm256_set1_ps(-1.0);
+        let hi = _mm256_set1_ps(1.0);
+        let scale = _mm256_set1_ps(i16::MAX as f32);
+        let chunks = input.len() / 16;
+
+        for i in 0..chunks {
+            let src = input.as_ptr().add(i * 16);
+            let mut a = _mm256_loadu_ps(src);
+            let mut b = _mm256_loadu_ps(src.add(8));
+            a = _mm256_and_ps(a, _mm256_cmp_ps::<_CMP_ORD_Q>(a, a));
+            b = _mm256_and_ps(b, _mm256_cmp_ps::<_CMP_ORD_Q>(b, b));
+            a = _mm256_mul_ps(_mm256_min_ps(_mm256_max_ps(a, lo), hi), scale);
+            b = _mm256_mul_ps(_mm256_min_ps(_mm256_max_ps(b, lo), hi), scale);
+            // packs works per 128-bit lane, so restore sample order afterwards
+            let packed = _mm256_packs_epi32(_mm256_cvttps_epi32(a), _mm256_cvttps_epi32(b));
+            let ordered = _mm256_permute4x64_epi64::<0b11_01_10_00>(packed);
+            _mm256_storeu_si256(output.as_mut_ptr().add(i * 16) as *mut __m256i, ordered);
+        }
+
+        let done = chunks * 16;
+        convert_f32_to_i16_sse2(&input[done..], &mut output[done..]);
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn mix_saturating_sse2(a: &[i16], b: &[i16], output: &mut [i16]) {
+        let chunks = a.len() / 8;
+
+        for i in 0..chunks {
+            let x = _mm_loadu_si128(a.as_ptr().add(i * 8) as *const __m128i);
+            let y = _mm_loadu_si128(b.as_ptr().add(i * 8) as *const __m128i);
+            _mm_storeu_si128(output.as_mut_ptr().add(i * 8) as *mut __m128i, _mm_adds_epi16(x, y));
+        }
+
+        let done = chunks * 8;
+        super::mix_saturating_scalar(&a[done..], &b[done..], &mut output[done..]);
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn mix_saturating_avx2(a: &[i16], b: &[i16], output: &mut [i16]) {
+        let chunks = a.len() / 16;
+
+        for i in 0..chunks {
+            let x = _mm256_loadu_si256(a.as_ptr().add(i * 16) as *const __m256i);
+            let y = _mm256_loadu_si256(b.as_ptr().add(i * 16) as *const __m256i);
+            _mm256_storeu_si256(output.as_mut_ptr().add(i * 16) as *mut __m256i, _mm256_adds_epi16(x, y));
+        }
+
+        let done = chunks * 16;
+        mix_saturating_sse2(&a[done..], &b[done..], &mut output[done..]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_signal(len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| match i % 7 {
+                0 => f32::NAN,
+                1 => 1.5,
+                2 => -1.5,
+                _ => ((i as f32) * 0.37).sin(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_conversion_matches_scalar() {
+        // Odd length exercises the scalar tail after the vector chunks
+        let input = test_signal(1003);
+        let mut expected = vec![0i16; input.len()];
+        let mut actual = vec![0i16; input.len()];
+
+        convert_f32_to_i16_scalar(&input, &mut expected);
+        convert_f32_to_i16(&input, &mut actual);
+
+        assert_eq!(actual, expected);
+        assert_eq!(expected[0], 0, "NaN should convert to silence");
+        assert_eq!(expected[1], i16::MAX);
+        assert_eq!(expected[2], -i16::MAX);
+    }
+
+    #[test]
+    fn test_mixing_matches_scalar() {
+        let a: Vec<i16> = (0..1003).map(|i| (i * 97 % 65536 - 32768) as i16).collect();
+        let b: Vec<i16> = (0..1003).map(|i| (i * 53 % 65536 - 32768) as i16).collect();
+        let mut expected = vec![0i16; a.len()];
+        let mut actual = vec![0i16; a.len()];
+
+        mix_saturating_scalar(&a, &b, &mut expected);
+        mix_saturating(&a, &b, &mut actual);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_peak_level() {
+        assert_eq!(peak_level(&[]), 0.0);
+        assert_eq!(peak_level(&[100, -i16::MAX, 5]), 1.0);
+        assert_eq!(peak_level(&[i16::MIN]), 1.0);
+    }
+
+    #[test]
+    fn test_level_meter_accumulates_across_chunks() {
+        let mut meter = LevelMeter::default();
+        assert_eq!(meter.levels(), Levels::default());
+
+        meter.add(&[i16::MAX, -i16::MAX]);
+        meter.add(&[0, 0]);
+        let levels = meter.levels();
+        assert_eq!(levels.peak, 1.0);
+        assert!((levels.rms - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_append_stereo_upmixes_mono() {
+        let mut buffer = vec![];
+        append_stereo(&mut buffer, &[1, 2], 1);
+        append_stereo(&mut buffer, &[3, 4], 2);
+        assert_eq!(buffer, [1, 1, 2, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_resampler_produces_expected_length() {
+        // One second at 44.1 kHz in 10 ms chunks becomes one second at 48 kHz
+        let mut resampler = StereoResampler::new(44100, 48000);
+        let chunk: Vec<i16> = (0..882).map(|i| (i % 100) as i16).collect();
+        let mut output = vec![];
+        for _ in 0..100 {
+            resampler.process(&chunk, &mut output);
+        }
+        let frames = output.len() / 2;
+        assert!((47_990..=48_010).contains(&frames), "got {} frames", frames);
+    }
+
+    #[test]
+    fn test_resampler_interpolates_across_chunks() {
+        // Upsampling a ramp by 2x should fill in the midpoints, including
+        // between the last frame of one chunk and the first of the next. The
+        // final frame is held back until the next chunk arrives
+        let mut resampler = StereoResampler::new(24000, 48000);
+        let mut output = vec![];
+        resampler.process(&[0, 0, 100, -100], &mut output);
+        resampler.process(&[200, -200], &mut output);
+        assert_eq!(output, [0, 0, 50, -50, 100, -100, 150, -150]);
+    }
+
+    #[test]
+    fn test_mixing_saturates() {
+        let mut out = [0i16; 2];
+        mix_saturating(&[20000, -20000], &[20000, -20000], &mut out);
+        assert_eq!(out, [i16::MAX, i16::MIN]);
+    }
+}
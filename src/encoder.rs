@@ -0,0 +1,302 @@
+//! Compressed output made by an external encoder
+//!
+//! Raw PCM is piped into a command-line encoder (`opusenc` or `lame`) while
+//! recording, either as the recording itself or as a compressed copy next
+//! to the master, so a small file to share is ready as soon as the
+//! recording stops.
+
+use hound::WavSpec;
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+use crate::sink::{Sink, SinkContext, SinkError};
+use crate::wav;
+
+/// Size of the buffer in front of the encoder's stdin, in bytes
+const PIPE_BUFFER_LEN: usize = 64 * 1024;
+
+/// Bitrate of lossy output, as a named preset or in kbps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Bitrate {
+    Preset(BitratePreset),
+    Kbps(u32),
+}
+
+/// Bitrates suited to typical meeting audio, scaled to each codec
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BitratePreset {
+    /// Clear speech at the smallest size: 32 kbps Opus, 64 kbps MP3
+    Voice,
+    /// Speech with more room for shared audio: 48 kbps Opus, 96 kbps MP3
+    VoiceHq,
+    /// Music and other wideband audio: 64 kbps Opus, 128 kbps MP3
+    Music,
+}
+
+impl Default for Bitrate {
+    fn default() -> Self {
+        Self::Preset(BitratePreset::Voice)
+    }
+}
+
+impl Bitrate {
+    /// Bitrate in kbps when encoding to `format`
+    ///
+    /// MP3 needs about twice Opus' bitrate for the same quality, so presets
+    /// are doubled for it.
+    pub fn kbps(self, format: CompressedFormat) -> u32 {
+        let scale = match format {
+            CompressedFormat::Opus => 1,
+            CompressedFormat::Mp3 => 2,
+        };
+        match self {
+            Self::Preset(BitratePreset::Voice) => 32 * scale,
+            Self::Preset(BitratePreset::VoiceHq) => 48 * scale,
+            Self::Preset(BitratePreset::Music) => 64 * scale,
+            Self::Kbps(kbps) => kbps,
+        }
+    }
+}
+
+/// Format of the compressed copy written next to the WAV file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CompressedFormat {
+    /// Opus in Ogg, encoded with `opusenc` (opus-tools)
+    Opus,
+    /// MP3, encoded with `lame`
+    Mp3,
+}
+
+impl CompressedFormat {
+    /// File extension of the compressed copy
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Opus => "opus",
+            Self::Mp3 => "mp3",
+        }
+    }
+
+    /// Name of the encoder program
+    pub fn program(self) -> &'static str {
+        match self {
+            Self::Opus => "opusenc",
+            Self::Mp3 => "lame",
+        }
+    }
+
+    /// Encoder invocation that reads raw 16-bit PCM of `spec` on stdin and writes `output` at `bitrate`
+    pub fn command(self, spec: &WavSpec, bitrate: Bitrate, output: &Path) -> Command {
+        let mut command = Command::new(self.program());
+        match self {
+            Self::Opus => {
+                command
+                    .args(["--quiet", "--raw", "--raw-bits", "16", "--raw-endianness", "0"])
+                    .arg("--raw-rate")
+                    .arg(spec.sample_rate.to_string())
+                    .arg("--raw-chan")
+                    .arg(spec.channels.to_string())
+                    .arg("--bitrate")
+                    .arg(bitrate.kbps(self).to_string());
+            }
+            Self::Mp3 => {
+                let mode = if spec.channels == 1 { "m" } else { "j" };
+                command
+                    .args(["--quiet", "-r", "--bitwidth", "16", "--signed", "--little-endian"])
+                    .arg("-s")
+                    .arg((spec.sample_rate as f64 / 1000.0).to_string())
+                    .args(["-m", mode])
+                    .arg("-b")
+                    .arg(bitrate.kbps(self).to_string());
+            }
+        }
+        command.arg("-").arg(output);
+        command
+    }
+}
+
+/// A user-supplied encoder command, for formats the recorder has no encoder for
+///
+/// The command reads raw little-endian 16-bit PCM on stdin. In its
+/// arguments, `{sample_rate}`, `{channels}` and `{output}` are replaced with
+/// the stream's sample rate, channel count and the file to write.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExternalEncoder {
+    /// Program followed by its arguments
+    pub command: Vec<String>,
+    /// Extension of the files it produces, without the dot
+    pub extension: String,
+}
+
+impl ExternalEncoder {
+    /// The configured invocation with its placeholders filled in
+    ///
+    /// `None` if no program is configured.
+    pub fn command(&self, spec: &WavSpec, output: &Path) -> Option<Command> {
+        let (program, args) = self.command.split_first()?;
+        let mut command = Command::new(program);
+        for arg in args {
+            command.arg(
+                arg.replace("{sample_rate}", &spec.sample_rate.to_string())
+                    .replace("{channels}", &spec.channels.to_string())
+                    .replace("{output}", &output.to_string_lossy()),
+            );
+        }
+        Some(command)
+    }
+}
+
+/// Sink that pipes the recording into an encoder process
+pub struct EncoderSink {
+    program: String,
+    child: Child,
+    stdin: Option<BufWriter<ChildStdin>>,
+    output: PathBuf,
+    /// Where to move the output once the encoder has exited successfully
+    rename_to: Option<PathBuf>,
+}
+
+impl EncoderSink {
+    /// Start the encoder for `format`, writing a copy next to the session's recording
+    ///
+    /// The encoder writes under a `.part` name that is renamed into place once
+    /// it exits successfully, like the recording itself.
+    pub fn spawn(format: CompressedFormat, bitrate: Bitrate, context: &SinkContext) -> io::Result<Self> {
+        let path = context.recording_path.with_extension(format.extension());
+        let mut sink = Self::create(format, bitrate, &context.spec, &wav::partial_path(&path))?;
+        sink.rename_to = Some(path);
+        Ok(sink)
+    }
+
+    /// Start the encoder for `format`, writing audio in `spec` to exactly `output`
+    pub fn create(format: CompressedFormat, bitrate: Bitrate, spec: &WavSpec, output: &Path) -> io::Result<Self> {
+        Self::run(format.command(spec, bitrate, output), output)
+    }
+
+    /// Start a user-supplied encoder, writing audio in `spec` to exactly `output`
+    pub fn create_external(encoder: &ExternalEncoder, spec: &WavSpec, output: &Path) -> io::Result<Self> {
+        let command = encoder
+            .command(spec, output)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "the external encoder command is empty"))?;
+        Self::run(command, output)
+    }
+
+    /// Start `command`, which writes `output`
+    fn run(mut command: Command, output: &Path) -> io::Result<Self> {
+        let program = command.get_program().to_string_lossy().into_owned();
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|e| io::Error::new(e.kind(), format!("could not run `{}`: {}", program, e)))?;
+        let stdin = child.stdin.take().map(|stdin| BufWriter::with_capacity(PIPE_BUFFER_LEN, stdin));
+        Ok(Self {
+            program,
+            child,
+            stdin,
+            output: output.to_path_buf(),
+            rename_to: None,
+        })
+    }
+
+    /// Where the encoded file ends up once finalized
+    pub fn path(&self) -> &Path {
+        self.rename_to.as_deref().unwrap_or(&self.output)
+    }
+}
+
+impl Sink for EncoderSink {
+    fn write_frames(&mut self, samples: &[i16]) -> Result<(), SinkError> {
+        let stdin = self.stdin.as_mut().ok_or("encoder input already closed")?;
+        for sample in samples {
+            stdin.write_all(&sample.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn finalize(mut self: Box<Self>) -> Result<(), SinkError> {
+        // Closing stdin tells the encoder the recording is over
+        if let Some(mut stdin) = self.stdin.take() {
+            stdin.flush()?;
+        }
+        let status = self.child.wait()?;
+        if !status.success() {
+            return Err(format!("{} exited with {}", self.program, status).into());
+        }
+        if let Some(path) = &self.rename_to {
+            std::fs::rename(&self.output, path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for EncoderSink {
+    fn drop(&mut self) {
+        // Dropped without finalizing, after a failure: don't leave the encoder running
+        if self.stdin.take().is_some() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hound::SampleFormat;
+
+    #[test]
+    fn test_mp3_command_gives_rate_in_khz() {
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let command = CompressedFormat::Mp3.command(&spec, Bitrate::default(), Path::new("out.mp3"));
+        let args: Vec<_> = command.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect();
+        assert_eq!(command.get_program(), "lame");
+        assert!(args.windows(2).any(|pair| pair == ["-s", "44.1"]));
+        assert!(args.windows(2).any(|pair| pair == ["-b", "64"]));
+        assert_eq!(args[args.len() - 2..], ["-", "out.mp3"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_external_encoder_receives_raw_pcm() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let output = temp_dir.path().join("out.raw");
+        let encoder = ExternalEncoder {
+            command: ["sh", "-c", "test \"$1\" = 16000x1 && cat > \"$0\"", "{output}", "{sample_rate}x{channels}"]
+                .map(String::from)
+                .to_vec(),
+            extension: "raw".to_string(),
+        };
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 16000,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+
+        let mut sink: Box<dyn Sink> = Box::new(EncoderSink::create_external(&encoder, &spec, &output).unwrap());
+        sink.write_frames(&[1, -2]).unwrap();
+        sink.finalize().unwrap();
+        assert_eq!(std::fs::read(&output).unwrap(), [1, 0, 0xFE, 0xFF]);
+    }
+
+    #[test]
+    fn test_bitrate_is_a_preset_or_kbps() {
+        let preset: Bitrate = serde_yaml::from_str("voice-hq").unwrap();
+        assert_eq!(preset.kbps(CompressedFormat::Opus), 48);
+        assert_eq!(preset.kbps(CompressedFormat::Mp3), 96);
+        let custom: Bitrate = serde_yaml::from_str("40").unwrap();
+        assert_eq!(custom, Bitrate::Kbps(40));
+        assert_eq!(custom.kbps(CompressedFormat::Mp3), 40);
+        assert_eq!(Bitrate::default().kbps(CompressedFormat::Opus), 32);
+    }
+}
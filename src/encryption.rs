@@ -0,0 +1,120 @@
+//! Encryption of finished recordings with `age`
+//!
+//! Once a recording is finalized, its audio files (the recording and any
+//! compressed copy) are encrypted to the configured recipients with the
+//! [`age`](https://age-encryption.org) command, and the plaintext is
+//! removed. While recording, the audio is on disk unencrypted under its
+//! `.part` name, since the WAV header can only be completed at the end.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::wav;
+
+/// Extension added to encrypted files
+pub const ENCRYPTED_EXTENSION: &str = "age";
+
+/// Who finished recordings are encrypted to
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Encryption {
+    /// age recipients: public keys (`age1...`) or SSH public keys
+    #[serde(default)]
+    pub recipients: Vec<String>,
+    /// Files listing recipients, one per line
+    #[serde(default)]
+    pub recipients_files: Vec<String>,
+}
+
+impl Encryption {
+    /// Whether any recipient is configured
+    pub fn has_recipients(&self) -> bool {
+        !self.recipients.is_empty() || !self.recipients_files.is_empty()
+    }
+
+    /// `age` invocation that encrypts `input` into `output`
+    pub fn command(&self, input: &Path, output: &Path) -> Command {
+        let mut command = Command::new("age");
+        command.arg("--encrypt");
+        for recipient in &self.recipients {
+            command.arg("--recipient").arg(recipient);
+        }
+        for file in &self.recipients_files {
+            command.arg("--recipients-file").arg(file);
+        }
+        command.arg("--output").arg(output).arg(input);
+        command
+    }
+
+    /// Replace the file at `path` with an encrypted copy named `<path>.age`
+    ///
+    /// The plaintext is only removed once the encrypted file is complete;
+    /// on failure it is left in place. Returns the encrypted file's path.
+    pub fn encrypt_file(&self, path: &Path) -> io::Result<PathBuf> {
+        let encrypted = encrypted_path(path);
+        self.encrypt_to(path, &encrypted)?;
+        fs::remove_file(path)?;
+        Ok(encrypted)
+    }
+
+    /// Encrypt the file at `input` into `output`, leaving `input` in place
+    ///
+    /// `output` is written under its `.part` name and only appears once complete.
+    pub fn encrypt_to(&self, input: &Path, output: &Path) -> io::Result<()> {
+        let part_path = wav::partial_path(output);
+        let result = self
+            .command(input, &part_path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .output()
+            .map_err(|e| io::Error::new(e.kind(), format!("could not run `age`: {}", e)))?;
+        if !result.status.success() {
+            let _ = fs::remove_file(&part_path);
+            let stderr = String::from_utf8_lossy(&result.stderr);
+            return Err(io::Error::other(format!("`age` exited with {}: {}", result.status, stderr.trim())));
+        }
+        fs::rename(&part_path, output)
+    }
+}
+
+/// Path of the encrypted copy of the file at `path`
+pub fn encrypted_path(path: impl AsRef<Path>) -> PathBuf {
+    let mut encrypted = path.as_ref().as_os_str().to_owned();
+    encrypted.push(".");
+    encrypted.push(ENCRYPTED_EXTENSION);
+    PathBuf::from(encrypted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_age_command_lists_every_recipient() {
+        let encryption = Encryption {
+            recipients: vec!["age1abc".to_string(), "ssh-ed25519 AAAA".to_string()],
+            recipients_files: vec!["/etc/recorder/recipients.txt".to_string()],
+        };
+        let command = encryption.command(Path::new("in.wav"), Path::new("in.wav.age.part"));
+        let args: Vec<_> = command.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect();
+        assert_eq!(command.get_program(), "age");
+        assert_eq!(
+            args,
+            [
+                "--encrypt",
+                "--recipient",
+                "age1abc",
+                "--recipient",
+                "ssh-ed25519 AAAA",
+                "--recipients-file",
+                "/etc/recorder/recipients.txt",
+                "--output",
+                "in.wav.age.part",
+                "in.wav",
+            ]
+        );
+        assert_eq!(encrypted_path("in.wav"), Path::new("in.wav.age"));
+    }
+}
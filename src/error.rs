@@ -0,0 +1,225 @@
+//! Error types returned by the recorder library
+
+use std::io;
+use std::path::PathBuf;
+use thiserror::Error;
+
+use crate::recorder::IncompleteRecording;
+use crate::sink::SinkError;
+
+/// Result type used throughout the library
+pub type Result<T, E = RecorderError> = std::result::Result<T, E>;
+
+/// Any failure from loading configuration, opening devices or recording
+#[derive(Debug, Error)]
+pub enum RecorderError {
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+
+    #[error(transparent)]
+    Device(#[from] DeviceError),
+
+    #[error(transparent)]
+    Stream(#[from] StreamError),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// Creating or writing the output file failed before recording started
+    #[error("WAV error: {0}")]
+    Wav(#[from] hound::Error),
+
+    /// Too little free space on the output volume to start recording
+    #[error("Only {available_mib} MiB free in {directory}, below the configured minimum of {minimum_mib} MiB")]
+    InsufficientSpace {
+        directory: String,
+        available_mib: u64,
+        minimum_mib: u64,
+    },
+
+    /// An output sink could not be created when the session started
+    #[error("Failed to open output sink: {0}")]
+    Sink(#[source] SinkError),
+
+    /// A session was started while another one from the same recorder was still running
+    #[error("A recording is already in progress on this recorder")]
+    Busy,
+
+    /// A recording or mixer thread panicked or exited without reporting
+    #[error("Recording thread failed: {0}")]
+    Thread(&'static str),
+
+    /// The recording ended early; the audio captured so far was kept
+    #[error(transparent)]
+    Incomplete(#[from] IncompleteRecording),
+}
+
+/// Problems with the configuration file
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Config file not found at {}. Please create it with an 'output_directory' field.", .0.display())]
+    NotFound(PathBuf),
+
+    #[error("Failed to read config file {}: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Invalid config file {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: serde_yaml::Error,
+    },
+
+    #[error("Failed to create output directory '{path}': {source}")]
+    CreateOutputDirectory {
+        path: String,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Output directory '{0}' exists but is not a directory")]
+    NotADirectory(String),
+
+    #[error("The 'external' format needs an 'external_encoder' with a command")]
+    MissingExternalEncoder,
+
+    #[error("'encryption' needs at least one recipient or recipients file")]
+    MissingEncryptionRecipients,
+
+    #[error("'chunks' needs an 'overlap_secs' shorter than its 'length_secs'")]
+    InvalidChunks,
+
+    #[error("'streaming' needs a build with the 'streaming' feature")]
+    StreamingUnavailable,
+
+    #[error("Streaming URL '{0}' must start with http://, https://, ws:// or wss://")]
+    InvalidStreamingUrl(String),
+
+    #[error("'live' needs a build with the 'live' feature")]
+    LiveUnavailable,
+
+    #[error("Live address '{0}' must be an IP address and port, e.g. 127.0.0.1:8765")]
+    InvalidLiveAddress(String),
+
+    #[error("'collector' needs an 'address' with a host and port, e.g. recordings.example.com:9100")]
+    InvalidCollectorAddress,
+
+    #[error("'transcription' needs a build with the 'whisper' feature")]
+    TranscriptionUnavailable,
+
+    #[error("'transcription' only works with the 'wav' format")]
+    TranscriptionNeedsWav,
+
+    #[error("'checksum' only works with the 'wav' format")]
+    ChecksumNeedsWav,
+
+    #[error("'checksum' can't be verified on encrypted recordings")]
+    ChecksumWithEncryption,
+
+    #[error("'upload' needs a build with the 'upload' feature")]
+    UploadUnavailable,
+
+    #[error("'webhook' needs a build with the 'webhook' feature")]
+    WebhookUnavailable,
+
+    #[error("Webhook URL '{0}' must start with http:// or https://")]
+    InvalidWebhookUrl(String),
+
+    #[error("'hotkeys' needs a build with the 'hotkeys' feature")]
+    HotkeysUnavailable,
+
+    #[error("Hotkey '{0}' is not a valid shortcut, e.g. ctrl+alt+R")]
+    InvalidHotkey(String),
+
+    #[error("'midi' needs a build with the 'midi' feature")]
+    MidiUnavailable,
+
+    #[error("{0}")]
+    InvalidMidiControl(String),
+
+    #[error("'notifications' needs a build with the 'notifications' feature")]
+    NotificationsUnavailable,
+
+    #[error("'calendar' needs a 'url' and at least one rule in 'rules'")]
+    IncompleteCalendar,
+}
+
+/// Problems finding or querying audio devices
+#[derive(Debug, Error)]
+pub enum DeviceError {
+    #[error("No input devices found")]
+    NoInputDevices,
+
+    #[error("Device index {0} out of range")]
+    IndexOutOfRange(usize),
+
+    #[error("No input device named \"{0}\"")]
+    NotFound(String),
+
+    #[error("\"{name}\" matches several input devices: {}", matches.join(", "))]
+    Ambiguous { name: String, matches: Vec<String> },
+
+    #[error("Failed to list input devices: {0}")]
+    Enumerate(#[from] cpal::DevicesError),
+
+    #[error("Failed to read device name: {0}")]
+    Name(#[from] cpal::DeviceNameError),
+
+    #[error("Failed to read device configuration: {0}")]
+    Config(#[from] cpal::DefaultStreamConfigError),
+
+    #[error("Invalid RTP input \"{0}\": expected rtp://host:port, optionally with ?codec=l16|opus&rate=...&channels=... (Opus needs rate 48000 and 1 or 2 channels)")]
+    InvalidRtpUrl(String),
+
+    #[error("Opus RTP input needs a build with the 'opus' feature")]
+    OpusUnavailable,
+}
+
+/// Problems reading a calendar feed
+#[derive(Debug, Error)]
+pub enum CalendarError {
+    #[error("Failed to fetch calendar {url}: {message}")]
+    Fetch { url: String, message: String },
+
+    #[error("Failed to read calendar file {}: {source}", path.display())]
+    Read { path: PathBuf, source: io::Error },
+
+    #[error("The calendar is not an iCalendar (ICS) feed")]
+    NotACalendar,
+}
+
+/// Problems listening to a MIDI controller
+#[derive(Debug, Error)]
+pub enum MidiError {
+    #[error("MIDI is not available: {0}")]
+    Unavailable(String),
+
+    #[error("No MIDI inputs found")]
+    NoInputs,
+
+    #[error("No MIDI input matching '{port}'; available: {available}")]
+    NotFound { port: String, available: String },
+
+    #[error("Failed to connect to MIDI input {port}: {message}")]
+    Connect { port: String, message: String },
+}
+
+/// Problems opening or controlling a capture stream
+#[derive(Debug, Error)]
+pub enum StreamError {
+    #[error("Failed to open capture stream: {0}")]
+    Build(#[from] cpal::BuildStreamError),
+
+    #[error("Failed to start capture stream: {0}")]
+    Play(#[from] cpal::PlayStreamError),
+
+    #[error("Failed to stop capture stream: {0}")]
+    Pause(#[from] cpal::PauseStreamError),
+
+    #[error("Capture device was lost: {0}")]
+    Lost(#[from] cpal::StreamError),
+}
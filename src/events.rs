@@ -0,0 +1,145 @@
+//! Live notifications from a running recorder
+//!
+//! Front-ends subscribe with [`Recorder::subscribe`](crate::Recorder::subscribe)
+//! and receive [`RecorderEvent`]s on a channel instead of parsing stdout.
+
+use crossbeam_channel::{Receiver, Sender, TrySendError};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::dsp::Levels;
+use crate::recorder::StopReason;
+
+/// Events queued per subscriber before new ones are dropped for it
+const SUBSCRIBER_CAPACITY: usize = 256;
+
+/// Something that happened during a recording session
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum RecorderEvent {
+    /// Capture is running for session `id`, and audio is being written to `path` unless WAV output is off
+    Started { id: Uuid, path: Option<PathBuf> },
+    /// Peak and RMS levels of each source and the mix since the last update
+    LevelUpdate { mic: Levels, sys: Option<Levels>, mix: Levels },
+    /// Audio was discarded or delayed because a source fell behind
+    Dropout { source: &'static str, count: u64 },
+    /// The microphone has been silent for `duration` while system audio played; it may be muted
+    MicSilent { duration: Duration },
+    /// Chunk `index`, starting `start` into the recording, was closed and written to `path`
+    SegmentRotated { index: u64, path: PathBuf, start: Duration },
+    /// A non-fatal or fatal problem; fatal ones are followed by `Stopped`
+    Error { message: String },
+    /// The session ended and its file was finalized (or left for repair)
+    Stopped { reason: StopReason, duration: Duration },
+}
+
+/// Fan-out of events to every subscriber
+///
+/// Sending never blocks: a subscriber that stops reading loses events rather
+/// than stalling capture, and one that hangs up is forgotten.
+pub(crate) struct Broadcast<T> {
+    subscribers: Arc<Mutex<Vec<Subscriber<T>>>>,
+}
+
+/// Broadcast of [`RecorderEvent`]s
+pub(crate) type EventBus = Broadcast<RecorderEvent>;
+
+impl<T> Default for Broadcast<T> {
+    fn default() -> Self {
+        Self {
+            subscribers: Arc::default(),
+        }
+    }
+}
+
+impl<T> Clone for Broadcast<T> {
+    fn clone(&self) -> Self {
+        Self {
+            subscribers: self.subscribers.clone(),
+        }
+    }
+}
+
+enum Subscriber<T> {
+    Channel(Sender<T>),
+    #[cfg(feature = "tokio")]
+    Async(tokio::sync::mpsc::Sender<T>),
+}
+
+impl<T: Clone> Subscriber<T> {
+    /// Deliver without blocking; false once the subscriber has hung up
+    fn offer(&self, event: &T) -> bool {
+        match self {
+            Self::Channel(tx) => {
+                !matches!(tx.try_send(event.clone()), Err(TrySendError::Disconnected(_)))
+            }
+            #[cfg(feature = "tokio")]
+            Self::Async(tx) => !matches!(
+                tx.try_send(event.clone()),
+                Err(tokio::sync::mpsc::error::TrySendError::Closed(_))
+            ),
+        }
+    }
+}
+
+impl<T: Clone> Broadcast<T> {
+    pub fn subscribe(&self) -> Receiver<T> {
+        let (tx, rx) = crossbeam_channel::bounded(SUBSCRIBER_CAPACITY);
+        self.add(Subscriber::Channel(tx));
+        rx
+    }
+
+    #[cfg(feature = "tokio")]
+    pub fn subscribe_async(&self) -> tokio::sync::mpsc::Receiver<T> {
+        let (tx, rx) = tokio::sync::mpsc::channel(SUBSCRIBER_CAPACITY);
+        self.add(Subscriber::Async(tx));
+        rx
+    }
+
+    fn add(&self, subscriber: Subscriber<T>) {
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.push(subscriber);
+        }
+    }
+
+    /// Whether anyone is listening, to skip work that only feeds events
+    pub fn has_subscribers(&self) -> bool {
+        self.subscribers.lock().is_ok_and(|subscribers| !subscribers.is_empty())
+    }
+
+    pub fn emit(&self, event: T) {
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.retain(|subscriber| subscriber.offer(&event));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_reach_every_subscriber() {
+        let bus = EventBus::default();
+        let first = bus.subscribe();
+        let second = bus.subscribe();
+
+        bus.emit(RecorderEvent::Dropout { source: "microphone", count: 3 });
+
+        for rx in [first, second] {
+            assert_eq!(rx.try_recv().unwrap(), RecorderEvent::Dropout { source: "microphone", count: 3 });
+        }
+    }
+
+    #[test]
+    fn test_disconnected_subscribers_are_dropped() {
+        let bus = EventBus::default();
+        drop(bus.subscribe());
+        assert!(bus.has_subscribers());
+
+        bus.emit(RecorderEvent::Error { message: "test".into() });
+        assert!(!bus.has_subscribers());
+    }
+}
@@ -0,0 +1,47 @@
+//! Live mixed audio for downstream processing
+//!
+//! [`Recorder::frames`](crate::Recorder::frames) delivers the same samples
+//! that go into the WAV file, so callers can feed speech recognition or
+//! effects while recording, or instead of it.
+
+use std::time::Duration;
+
+/// A block of mixed audio, in the format the recording is written in
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// Offset of the first sample from the start of the recording
+    pub timestamp: Duration,
+    /// Interleaved samples, `channels` per frame
+    pub samples: Vec<i16>,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl Frame {
+    /// Number of sample frames (one sample per channel) in the block
+    pub fn frame_count(&self) -> usize {
+        self.samples.len() / self.channels.max(1) as usize
+    }
+
+    /// Length of audio the block covers
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.frame_count() as f64 / self.sample_rate.max(1) as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_duration() {
+        let frame = Frame {
+            timestamp: Duration::ZERO,
+            samples: vec![0; 960],
+            sample_rate: 48000,
+            channels: 2,
+        };
+        assert_eq!(frame.frame_count(), 480);
+        assert_eq!(frame.duration(), Duration::from_millis(10));
+    }
+}
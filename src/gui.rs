@@ -0,0 +1,467 @@
+//! Desktop window
+//!
+//! `meeting-recorder gui` opens a small window for people who never use a
+//! terminal: pick the microphone and system audio device from drop-downs,
+//! record, pause and stop with buttons while a meter for each source shows
+//! that audio arrives, and open the latest recordings from a list. Closing
+//! the window while recording stops the recording and closes once the file
+//! is finalized.
+
+use crossbeam_channel::Receiver;
+use eframe::egui::{self, Color32, RichText, ViewportCommand};
+use meeting_recorder::dsp::Levels;
+use meeting_recorder::events::RecorderEvent;
+use meeting_recorder::lock::SessionLock;
+use meeting_recorder::marker;
+use meeting_recorder::recorder::{RecordingResult, RecordingSession};
+use meeting_recorder::state::RecordingState;
+use meeting_recorder::{Config, DeviceManager, Recorder};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::meter::{clock, db, file_size, meter_ratio, size_text, DiskUsage};
+use crate::{launch, select_device, shutdown};
+
+/// How often the window is redrawn while recording
+const REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+/// How often the window is redrawn while idle, to notice signals
+const IDLE_REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+/// Recordings listed
+const RECENT_RECORDINGS: usize = 10;
+/// Words in the names of devices that capture system audio
+const SYSTEM_AUDIO_HINTS: [&str; 5] = ["monitor", "loopback", "blackhole", "stereo mix", "soundflower"];
+
+/// Open the window and run it until closed
+pub fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::load()?;
+    let running = Arc::new(AtomicBool::new(true));
+    let _shutdown = shutdown::install(running.clone())?;
+    let mut app = GuiApp::new(config, running);
+    app.refresh_devices();
+
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title("Meeting Recorder")
+            .with_inner_size([440.0, 560.0])
+            .with_min_inner_size([360.0, 420.0]),
+        ..Default::default()
+    };
+    eframe::run_native("Meeting Recorder", options, Box::new(|_| Ok(Box::new(app))))?;
+    Ok(())
+}
+
+/// A session being recorded, with what the window shows about it
+struct Recording {
+    session: RecordingSession,
+    lock: SessionLock,
+    events: Receiver<RecorderEvent>,
+    /// Audio chunks lost or delayed so far
+    dropouts: u64,
+    /// The latest error or warning
+    problem: Option<String>,
+}
+
+/// A finished recording in the output directory
+struct RecentRecording {
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+struct GuiApp {
+    config: Config,
+    /// Cleared by Ctrl+C and termination signals
+    running: Arc<AtomicBool>,
+    devices: Vec<String>,
+    mic: usize,
+    system: Option<usize>,
+    recording: Option<Recording>,
+    /// Reports the outcome of a stopped recording once it is finalized
+    saving: Option<Receiver<String>>,
+    /// Outcome of the last recording, or why it couldn't start
+    message: Option<String>,
+    recent: Vec<RecentRecording>,
+    disk: DiskUsage,
+    /// Whether to close once the recording is saved
+    closing: bool,
+}
+
+impl GuiApp {
+    fn new(config: Config, running: Arc<AtomicBool>) -> Self {
+        Self {
+            disk: DiskUsage::new(&config),
+            recent: recent_recordings(Path::new(&config.output_directory), config.recording_extension(), RECENT_RECORDINGS),
+            config,
+            running,
+            devices: Vec::new(),
+            mic: 0,
+            system: None,
+            recording: None,
+            saving: None,
+            message: None,
+            closing: false,
+        }
+    }
+
+    fn refresh_devices(&mut self) {
+        let names = DeviceManager::new().and_then(|manager| {
+            (0..manager.device_count()).map(|index| manager.device_name(index)).collect::<Result<Vec<_>, _>>()
+        });
+        match names {
+            Ok(names) => {
+                self.mic = self.mic.min(names.len().saturating_sub(1));
+                self.system = self.system.filter(|&index| index < names.len()).or_else(|| likely_system_device(&names));
+                self.devices = names;
+            }
+            Err(e) => self.message = Some(format!("Could not list devices: {}", e)),
+        }
+    }
+
+    fn start(&mut self) {
+        match self.open_session() {
+            Ok(recording) => {
+                self.message = None;
+                self.recording = Some(recording);
+            }
+            Err(e) => self.message = Some(format!("Could not start recording: {}", e)),
+        }
+    }
+
+    fn open_session(&self) -> Result<Recording, Box<dyn std::error::Error>> {
+        let mic = self.devices.get(self.mic).ok_or("No microphone selected")?;
+        let lock = SessionLock::acquire(&self.config.output_directory)?;
+        let mut device_manager = DeviceManager::new()?;
+        let mic_idx = select_device(&device_manager, mic)?;
+        let sys_idx = self
+            .system
+            .and_then(|index| self.devices.get(index))
+            .map(|name| select_device(&device_manager, name))
+            .transpose()?;
+        let mic_config = device_manager.device_config(mic_idx)?;
+        let sys_config = sys_idx.map(|idx| device_manager.device_config(idx)).transpose()?;
+        let mic_device = device_manager
+            .take_device(mic_idx)
+            .ok_or_else(|| format!("Failed to get microphone device at index {}", mic_idx))?;
+        let sys_device = sys_idx.and_then(|idx| device_manager.take_device(idx));
+        let recorder = Recorder::new(mic_device, mic_config, sys_device, sys_config);
+        let events = recorder.subscribe();
+        let session = recorder.start(&self.config)?;
+        Ok(Recording {
+            session,
+            lock,
+            events,
+            dropouts: 0,
+            problem: None,
+        })
+    }
+
+    /// Hand a stopped recording to a thread of its own to be finalized
+    fn finalize(&mut self) {
+        let Some(Recording { session, lock, .. }) = self.recording.take() else {
+            return;
+        };
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        thread::spawn(move || {
+            let outcome = outcome_text(session.join());
+            // Release the lock first, so a new recording can start once this one is reported
+            drop(lock);
+            let _ = tx.send(outcome);
+        });
+        self.saving = Some(rx);
+    }
+
+    fn stop(&mut self) {
+        if let Some(recording) = &self.recording {
+            recording.session.stop();
+        }
+    }
+
+    /// Catch up with the recording and the thread saving the last one
+    fn poll(&mut self) {
+        if let Some(recording) = &mut self.recording {
+            for event in recording.events.try_iter() {
+                match event {
+                    RecorderEvent::Dropout { count, .. } => recording.dropouts += count,
+                    RecorderEvent::MicSilent { duration } => {
+                        recording.problem = Some(format!(
+                            "The microphone has been silent for {} s while system audio plays; is it muted?",
+                            duration.as_secs()
+                        ))
+                    }
+                    RecorderEvent::Error { message } => recording.problem = Some(message),
+                    _ => {}
+                }
+            }
+        }
+        if !self.running.load(Ordering::SeqCst) {
+            self.closing = true;
+            self.stop();
+        }
+        if self.recording.as_ref().is_some_and(|recording| !recording.session.is_running()) {
+            self.finalize();
+        }
+        if let Some(outcome) = self.saving.as_ref().and_then(|saving| saving.try_recv().ok()) {
+            self.message = Some(outcome);
+            self.saving = None;
+            self.recent = recent_recordings(
+                Path::new(&self.config.output_directory),
+                self.config.recording_extension(),
+                RECENT_RECORDINGS,
+            );
+        }
+    }
+
+    fn devices_ui(&mut self, ui: &mut egui::Ui) {
+        let idle = self.recording.is_none() && self.saving.is_none();
+        ui.add_enabled_ui(idle, |ui| {
+            egui::Grid::new("devices").num_columns(2).spacing([8.0, 6.0]).show(ui, |ui| {
+                ui.label("Microphone");
+                let selected = self.devices.get(self.mic).map_or("No devices", String::as_str);
+                egui::ComboBox::from_id_salt("mic").width(260.0).selected_text(selected).show_ui(ui, |ui| {
+                    for (index, name) in self.devices.iter().enumerate() {
+                        ui.selectable_value(&mut self.mic, index, name);
+                    }
+                });
+                ui.end_row();
+
+                ui.label("System audio");
+                let selected = self.system.and_then(|index| self.devices.get(index)).map_or("None", String::as_str);
+                egui::ComboBox::from_id_salt("system").width(260.0).selected_text(selected).show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.system, None, "None");
+                    for (index, name) in self.devices.iter().enumerate() {
+                        ui.selectable_value(&mut self.system, Some(index), name);
+                    }
+                });
+                ui.end_row();
+            });
+            if ui.button("Refresh devices").clicked() {
+                self.refresh_devices();
+            }
+        });
+    }
+
+    fn controls_ui(&mut self, ui: &mut egui::Ui) {
+        let state = self.recording.as_ref().map(|recording| recording.session.state());
+        ui.horizontal(|ui| {
+            let can_start = state.is_none() && self.saving.is_none() && !self.devices.is_empty();
+            if ui.add_enabled(can_start, egui::Button::new("⏺ Record")).clicked() {
+                self.start();
+            }
+            let pause_text = if state == Some(RecordingState::Paused) { "▶ Resume" } else { "⏸ Pause" };
+            if ui.add_enabled(state.is_some(), egui::Button::new(pause_text)).clicked() {
+                if let Some(recording) = &self.recording {
+                    let _ = recording.session.pause() || recording.session.resume();
+                }
+            }
+            if ui.add_enabled(state == Some(RecordingState::Recording), egui::Button::new("Marker")).clicked() {
+                if let Some(recording) = &self.recording {
+                    recording.session.add_marker(marker::DEFAULT_LABEL);
+                }
+            }
+            if ui.add_enabled(state.is_some(), egui::Button::new("⏹ Stop")).clicked() {
+                self.stop();
+            }
+        });
+    }
+
+    fn status_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(recording) = &self.recording else {
+            if self.saving.is_some() {
+                ui.label("Saving the recording...");
+            } else if let Some(message) = &self.message {
+                ui.label(message);
+            }
+            return;
+        };
+        let session = &recording.session;
+        let (state, color) = match session.state() {
+            RecordingState::Paused => ("PAUSED", Color32::from_rgb(0xff, 0xb3, 0x00)),
+            _ => ("● REC", Color32::from_rgb(0xe5, 0x39, 0x35)),
+        };
+        ui.label(RichText::new(format!("{}   {}", state, clock(session.duration()))).color(color).strong().size(18.0));
+        let mut size = session.path().and_then(file_size).map_or_else(String::new, size_text);
+        if let Some(usage) = self.disk.text(session) {
+            size.push_str(&format!(" @ {}", usage));
+        }
+        ui.label(size);
+
+        let levels = session.levels();
+        meter(ui, "Microphone", Some(levels.mic));
+        meter(ui, "System audio", levels.sys);
+        meter(ui, "Mix", Some(levels.mix));
+        if recording.dropouts > 0 {
+            ui.colored_label(Color32::YELLOW, format!("{} dropouts", recording.dropouts));
+        }
+        if let Some(problem) = &recording.problem {
+            ui.colored_label(Color32::YELLOW, problem);
+        }
+    }
+
+    fn recent_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.heading("Recent recordings");
+            if ui.button("Open folder").clicked() {
+                if let Err(e) = launch::open(Path::new(&self.config.output_directory)) {
+                    self.message = Some(format!("Could not open {}: {}", self.config.output_directory, e));
+                }
+            }
+        });
+        if self.recent.is_empty() {
+            ui.label("None yet");
+        }
+        let now = SystemTime::now();
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for recording in &self.recent {
+                let name = recording.path.file_name().unwrap_or_default().to_string_lossy();
+                let age = now.duration_since(recording.modified).unwrap_or_default();
+                ui.horizontal(|ui| {
+                    if ui.link(name).on_hover_text("Open").clicked() {
+                        if let Err(e) = launch::open(&recording.path) {
+                            self.message = Some(format!("Could not open {}: {}", recording.path.display(), e));
+                        }
+                    }
+                    ui.label(format!("{}, {}", size_text(recording.size), age_text(age)));
+                });
+            }
+        });
+    }
+}
+
+impl eframe::App for GuiApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.poll();
+        // Keep the window until the recording is saved
+        if ctx.input(|input| input.viewport().close_requested()) && (self.recording.is_some() || self.saving.is_some()) {
+            ctx.send_viewport_cmd(ViewportCommand::CancelClose);
+            self.closing = true;
+            self.stop();
+        }
+        if self.closing && self.recording.is_none() && self.saving.is_none() {
+            ctx.send_viewport_cmd(ViewportCommand::Close);
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            self.devices_ui(ui);
+            ui.separator();
+            self.controls_ui(ui);
+            ui.add_space(4.0);
+            self.status_ui(ui);
+            ui.separator();
+            self.recent_ui(ui);
+        });
+        let busy = self.recording.is_some() || self.saving.is_some();
+        ctx.request_repaint_after(if busy { REFRESH_INTERVAL } else { IDLE_REFRESH_INTERVAL });
+    }
+}
+
+fn meter(ui: &mut egui::Ui, name: &str, levels: Option<Levels>) {
+    let Some(levels) = levels else {
+        ui.label(format!("{}: not recorded", name));
+        return;
+    };
+    let peak_db = db(levels.peak);
+    let color = if peak_db >= -1.0 {
+        Color32::from_rgb(0xe5, 0x39, 0x35)
+    } else if peak_db >= -6.0 {
+        Color32::from_rgb(0xff, 0xb3, 0x00)
+    } else {
+        Color32::from_rgb(0x43, 0xa0, 0x47)
+    };
+    ui.label(name);
+    ui.add(
+        egui::ProgressBar::new(meter_ratio(levels.peak) as f32)
+            .fill(color)
+            .text(format!("peak {:.1} dBFS   RMS {:.1} dBFS", peak_db, db(levels.rms))),
+    );
+}
+
+/// `Saved meeting.wav (00:42:17)`, or why the recording failed
+fn outcome_text(outcome: meeting_recorder::error::Result<RecordingResult>) -> String {
+    match outcome {
+        Ok(recording) => format!(
+            "Saved {} ({})",
+            recording.filename.as_deref().map_or("the recording", |path| path.rsplit(['/', '\\']).next().unwrap_or(path)),
+            clock(recording.duration)
+        ),
+        Err(e) => format!("Recording failed: {}", e),
+    }
+}
+
+/// The device that most likely captures system audio, by its name
+fn likely_system_device(names: &[String]) -> Option<usize> {
+    names.iter().position(|name| {
+        let name = name.to_lowercase();
+        SYSTEM_AUDIO_HINTS.iter().any(|hint| name.contains(hint))
+    })
+}
+
+/// The newest finished recordings in `directory`, encrypted ones included
+fn recent_recordings(directory: &Path, extension: &str, limit: usize) -> Vec<RecentRecording> {
+    let suffixes = [format!(".{}", extension), format!(".{}.age", extension)];
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return Vec::new();
+    };
+    let mut recordings: Vec<RecentRecording> = entries
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            suffixes.iter().any(|suffix| name.ends_with(suffix.as_str()))
+        })
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            Some(RecentRecording {
+                path: entry.path(),
+                size: metadata.len(),
+                modified: metadata.modified().ok()?,
+            })
+        })
+        .collect();
+    recordings.sort_by_key(|recording| std::cmp::Reverse(recording.modified));
+    recordings.truncate(limit);
+    recordings
+}
+
+/// `5 min ago`, `3 h ago`, `2 d ago`
+fn age_text(age: Duration) -> String {
+    let minutes = age.as_secs() / 60;
+    match minutes {
+        0 => "just now".to_string(),
+        1..=59 => format!("{} min ago", minutes),
+        60..=1439 => format!("{} h ago", minutes / 60),
+        _ => format!("{} d ago", minutes / 1440),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_recent_recordings() {
+        let dir = TempDir::new().unwrap();
+        for name in ["a.wav", "b.wav.age", "c.wav.part", "a.json", "d.flac"] {
+            std::fs::write(dir.path().join(name), b"data").unwrap();
+        }
+        let mut names: Vec<_> = recent_recordings(dir.path(), "wav", 10)
+            .into_iter()
+            .map(|recording| recording.path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["a.wav", "b.wav.age"]);
+        assert_eq!(recent_recordings(dir.path(), "wav", 1).len(), 1);
+    }
+
+    #[test]
+    fn test_device_hints_and_ages() {
+        let names = ["MacBook Pro Microphone", "BlackHole 2ch"].map(str::to_string);
+        assert_eq!(likely_system_device(&names), Some(1));
+        assert_eq!(likely_system_device(&names[..1]), None);
+        assert_eq!(age_text(Duration::from_secs(30)), "just now");
+        assert_eq!(age_text(Duration::from_secs(3 * 3600)), "3 h ago");
+    }
+}
@@ -0,0 +1,214 @@
+//! Global keyboard shortcuts
+//!
+//! With the `hotkeys` feature, configured shortcuts work while any window
+//! has focus: one stops the recording (or, with `record --wait-for-hotkey`,
+//! starts it), one pauses and resumes it, and one marks the current point. Shortcuts are written like
+//! `ctrl+alt+R`: modifiers (`ctrl`, `alt`/`option`, `shift`, `super`/`cmd`)
+//! followed by one key. On Linux they need an X11 session; Wayland
+//! compositors don't let applications grab keys.
+
+use serde::{Deserialize, Serialize};
+
+/// Shortcuts read from the configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hotkeys {
+    /// Starts a recording that waits for it, and stops a running one
+    #[serde(default)]
+    pub start_stop: Option<String>,
+    /// Pauses the recording, and resumes it when paused
+    #[serde(default)]
+    pub pause: Option<String>,
+    /// Marks the current point of the recording
+    #[serde(default)]
+    pub marker: Option<String>,
+    /// Label of markers added with the shortcut
+    #[serde(default = "default_marker_label")]
+    pub marker_label: String,
+}
+
+fn default_marker_label() -> String {
+    crate::marker::DEFAULT_LABEL.to_string()
+}
+
+/// What a shortcut, or a MIDI control, does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HotkeyAction {
+    StartStop,
+    Pause,
+    Marker,
+}
+
+impl Hotkeys {
+    /// Each configured shortcut with its action
+    pub fn bindings(&self) -> impl Iterator<Item = (&str, HotkeyAction)> {
+        [
+            (&self.start_stop, HotkeyAction::StartStop),
+            (&self.pause, HotkeyAction::Pause),
+            (&self.marker, HotkeyAction::Marker),
+        ]
+        .into_iter()
+        .filter_map(|(shortcut, action)| Some((shortcut.as_deref()?, action)))
+    }
+}
+
+#[cfg(feature = "hotkeys")]
+pub use listener::HotkeyListener;
+
+#[cfg(feature = "hotkeys")]
+mod listener {
+    use super::{HotkeyAction, Hotkeys};
+    use crossbeam_channel::Receiver;
+    use global_hotkey::hotkey::{HotKey, HotKeyParseError};
+    use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::{Duration, Instant};
+
+    /// How long to wait for platform events between checks
+    const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+    impl Hotkeys {
+        /// The first shortcut that isn't a valid key combination, if any
+        pub fn invalid_shortcut(&self) -> Option<&str> {
+            self.bindings()
+                .map(|(shortcut, _)| shortcut)
+                .find(|shortcut| shortcut.parse::<HotKey>().is_err())
+        }
+    }
+
+    /// Registered shortcuts and the events they send
+    ///
+    /// macOS only delivers the events to the main thread and Windows to the
+    /// thread that registered them, so the listener must be created and
+    /// polled on the main thread.
+    pub struct HotkeyListener {
+        _manager: GlobalHotKeyManager,
+        bindings: Vec<(u32, HotkeyAction)>,
+        events: Receiver<GlobalHotKeyEvent>,
+    }
+
+    impl HotkeyListener {
+        /// Register the configured shortcuts with the system
+        pub fn register(hotkeys: &Hotkeys) -> Result<Self, global_hotkey::Error> {
+            // Without a display the X11 backend fails quietly, so say so here
+            #[cfg(target_os = "linux")]
+            if std::env::var_os("DISPLAY").is_none() {
+                return Err(global_hotkey::Error::FailedToRegister(
+                    "no X11 display (DISPLAY is not set)".to_string(),
+                ));
+            }
+            let manager = GlobalHotKeyManager::new()?;
+            let mut bindings = Vec::new();
+            for (shortcut, action) in hotkeys.bindings() {
+                let hotkey: HotKey = shortcut
+                    .parse()
+                    .map_err(|e: HotKeyParseError| global_hotkey::Error::HotKeyParseError(e.to_string()))?;
+                manager.register(hotkey)?;
+                bindings.push((hotkey.id(), action));
+            }
+            Ok(Self {
+                _manager: manager,
+                bindings,
+                events: GlobalHotKeyEvent::receiver().clone(),
+            })
+        }
+
+        /// The next shortcut pressed within `timeout`, if any
+        pub fn next(&self, timeout: Duration) -> Option<HotkeyAction> {
+            let deadline = Instant::now() + timeout;
+            loop {
+                while let Ok(event) = self.events.try_recv() {
+                    if event.state != HotKeyState::Pressed {
+                        continue;
+                    }
+                    let action = self.bindings.iter().find(|(id, _)| *id == event.id);
+                    if let Some((_, action)) = action {
+                        return Some(*action);
+                    }
+                }
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return None;
+                }
+                platform::wait(remaining.min(POLL_INTERVAL));
+            }
+        }
+
+        /// Wait for the start shortcut; false if `running` was cleared first
+        pub fn wait_for_start(&self, running: &AtomicBool) -> bool {
+            while running.load(Ordering::SeqCst) {
+                if self.next(Duration::from_millis(100)) == Some(HotkeyAction::StartStop) {
+                    return true;
+                }
+            }
+            false
+        }
+    }
+
+    /// Waiting while the platform delivers shortcut events
+    mod platform {
+        use std::time::Duration;
+
+        /// Dispatch the messages of this thread's hidden hotkey window
+        #[cfg(windows)]
+        pub fn wait(duration: Duration) {
+            use windows_sys::Win32::UI::WindowsAndMessaging::{
+                DispatchMessageW, PeekMessageW, TranslateMessage, MSG, PM_REMOVE,
+            };
+            unsafe {
+                let mut message: MSG = std::mem::zeroed();
+                while PeekMessageW(&mut message, std::ptr::null_mut(), 0, 0, PM_REMOVE) != 0 {
+                    TranslateMessage(&message);
+                    DispatchMessageW(&message);
+                }
+            }
+            std::thread::sleep(duration);
+        }
+
+        /// Run the main run loop, which delivers the hotkey events
+        #[cfg(target_os = "macos")]
+        pub fn wait(duration: Duration) {
+            use std::ffi::c_void;
+
+            #[link(name = "CoreFoundation", kind = "framework")]
+            extern "C" {
+                static kCFRunLoopDefaultMode: *const c_void;
+                fn CFRunLoopRunInMode(mode: *const c_void, seconds: f64, return_after_source_handled: u8) -> i32;
+            }
+            unsafe {
+                CFRunLoopRunInMode(kCFRunLoopDefaultMode, duration.as_secs_f64(), 1);
+            }
+        }
+
+        /// The X11 backend reads events on a thread of its own
+        #[cfg(not(any(windows, target_os = "macos")))]
+        pub fn wait(duration: Duration) {
+            std::thread::sleep(duration);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hotkeys_config() {
+        let hotkeys: Hotkeys = serde_yaml::from_str("start_stop: ctrl+alt+R\n").unwrap();
+        assert_eq!(hotkeys.marker_label, "marker");
+        assert_eq!(hotkeys.bindings().collect::<Vec<_>>(), [("ctrl+alt+R", HotkeyAction::StartStop)]);
+    }
+
+    #[cfg(feature = "hotkeys")]
+    #[test]
+    fn test_invalid_shortcuts() {
+        let mut hotkeys = Hotkeys {
+            start_stop: Some("ctrl+alt+R".to_string()),
+            pause: None,
+            marker: Some("ctrl+shift+M".to_string()),
+            marker_label: default_marker_label(),
+        };
+        assert_eq!(hotkeys.invalid_shortcut(), None);
+        hotkeys.marker = Some("ctrl+M+shift".to_string());
+        assert_eq!(hotkeys.invalid_shortcut(), Some("ctrl+M+shift"));
+    }
+}
@@ -0,0 +1,15 @@
+//! Opening files and folders for desktop front ends
+
+use std::path::Path;
+
+/// Show `path`, a file or a folder, with the platform's default application
+pub fn open(path: &Path) -> std::io::Result<()> {
+    let program = if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(windows) {
+        "explorer"
+    } else {
+        "xdg-open"
+    };
+    std::process::Command::new(program).arg(path).spawn().map(|_| ())
+}
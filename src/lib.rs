@@ -1,10 +1,56 @@
+#[macro_use]
+pub mod console;
+
+pub mod activity;
+#[cfg(feature = "tokio")]
+pub mod async_api;
+pub mod backpressure;
+pub mod calendar;
+pub mod checksum;
+pub mod chunking;
+pub mod collector;
 pub mod config;
+pub mod detect;
 pub mod device;
+pub mod disk;
+pub mod dsp;
+pub mod encoder;
+pub mod encryption;
+pub mod error;
+pub mod events;
+pub mod flac;
+pub mod frame;
+pub mod hotkey;
+#[cfg(feature = "input")]
 pub mod input;
+pub mod live;
+pub mod lock;
+pub mod marker;
+pub mod midi;
+pub mod mixer;
+pub mod mute;
+pub mod notification;
+pub mod pool;
+pub mod priority;
 pub mod recorder;
+pub mod rtp;
+pub mod sidecar;
+pub mod sink;
+pub mod source;
+pub mod state;
+pub mod streaming;
+pub mod summary;
+#[cfg(feature = "synthetic")]
+pub mod synthetic;
+pub mod transcription;
+pub mod upload;
 pub mod wav;
+pub mod waveform;
+pub mod webhook;
 
 pub use recorder::Recorder;
 pub use device::DeviceManager;
 pub use config::Config;
+pub use error::RecorderError;
+pub use uuid::Uuid;
 
@@ -0,0 +1,418 @@
+//! Serving the live mix over WebSocket
+//!
+//! With the `live` feature, `live` in the config makes the recorder listen
+//! for WebSocket connections while it records, so a browser or a dashboard
+//! can listen in. Each client gets a JSON text message describing the
+//! stream, as the `streaming` WebSocket does, then a binary message per
+//! chunk from the moment it connects, and `{"event":"end"}` when recording
+//! stops. A plain HTTP request for any path gets a small page that plays
+//! the stream. A client that falls behind loses chunks rather than holding
+//! up the others or the recording.
+
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+
+use crate::streaming::StreamFormat;
+
+/// Where and how the live mix is served
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LiveServer {
+    /// Address and port to listen on; only this machine can connect to the default
+    #[serde(default = "default_listen")]
+    pub listen: String,
+    /// Encoding of each chunk
+    #[serde(default)]
+    pub format: StreamFormat,
+    /// Milliseconds of audio per message to clients; shorter chunks reach them sooner
+    #[serde(default = "default_chunk_ms")]
+    pub chunk_ms: u64,
+    /// Secret clients must pass as `?token=...`; anyone who can connect may listen without it
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+fn default_listen() -> String {
+    "127.0.0.1:8765".to_string()
+}
+
+fn default_chunk_ms() -> u64 {
+    200
+}
+
+impl LiveServer {
+    /// The address to listen on, if `listen` is a valid `ip:port`
+    pub fn address(&self) -> Option<SocketAddr> {
+        self.listen.parse().ok()
+    }
+
+    /// Whether the query string of a request carries the configured token
+    pub fn authorizes(&self, query: Option<&str>) -> bool {
+        let Some(token) = &self.token else {
+            return true;
+        };
+        query
+            .unwrap_or_default()
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .any(|(name, value)| name == "token" && value == token)
+    }
+}
+
+/// Page served to plain HTTP requests: plays the stream through Web Audio
+#[cfg_attr(not(feature = "live"), allow(dead_code))]
+const PLAYER_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>Meeting Recorder live</title></head>
+<body style="font-family: sans-serif; margin: 2em">
+<h1>Meeting Recorder</h1>
+<button id="listen">Listen</button>
+<p id="status">Press Listen to hear the recording live.</p>
+<script>
+document.getElementById("listen").onclick = function () {
+  this.disabled = true;
+  const status = document.getElementById("status");
+  const url = (location.protocol === "https:" ? "wss://" : "ws://") + location.host + "/" + location.search;
+  const socket = new WebSocket(url);
+  socket.binaryType = "arraybuffer";
+  let audio, stream, next = 0;
+  const play = (buffer) => {
+    const source = audio.createBufferSource();
+    source.buffer = buffer;
+    source.connect(audio.destination);
+    next = Math.max(next, audio.currentTime + 0.2);
+    source.start(next);
+    next += buffer.duration;
+  };
+  socket.onmessage = (message) => {
+    if (typeof message.data === "string") {
+      const event = JSON.parse(message.data);
+      if (event.event === "start") {
+        stream = event;
+        audio = new AudioContext({ sampleRate: event.sample_rate });
+        status.textContent = "Listening (" + event.sample_rate + " Hz, " + event.channels + " channels)";
+      } else if (event.event === "end") {
+        status.textContent = "Recording stopped.";
+      }
+    } else if (stream.format === "pcm") {
+      const samples = new Int16Array(message.data);
+      const frames = samples.length / stream.channels;
+      const buffer = audio.createBuffer(stream.channels, frames, stream.sample_rate);
+      for (let channel = 0; channel < stream.channels; channel++) {
+        const data = buffer.getChannelData(channel);
+        for (let i = 0; i < frames; i++) data[i] = samples[i * stream.channels + channel] / 32768;
+      }
+      play(buffer);
+    } else {
+      audio.decodeAudioData(message.data).then(play);
+    }
+  };
+  socket.onclose = () => { if (!status.textContent.startsWith("Recording stopped")) status.textContent = "Disconnected."; };
+};
+</script>
+</body>
+</html>
+"#;
+
+#[cfg(feature = "live")]
+pub use self::sink::LiveSink;
+
+#[cfg(feature = "live")]
+mod sink {
+    use crossbeam_channel::{Receiver, RecvTimeoutError, Sender, TrySendError};
+    use hound::WavSpec;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::Arc;
+    use std::thread::{self, JoinHandle};
+    use std::time::{Duration, Instant};
+    use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+    use tungstenite::http::StatusCode;
+    use tungstenite::Message;
+    use uuid::Uuid;
+
+    use super::{LiveServer, PLAYER_PAGE};
+    use crate::encoder::Bitrate;
+    use crate::sink::{Chunker, Sink, SinkContext, SinkError};
+
+    /// How often new connections are accepted while no audio arrives
+    const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+    /// How long a client may take to send its request or take a message
+    const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+    /// Chunks queued for a client before new ones are dropped for it
+    const CLIENT_QUEUE: usize = 64;
+    /// Chunks queued for the server thread before new ones are dropped
+    const SERVER_QUEUE: usize = 64;
+
+    /// An encoded chunk, shared by every client
+    type Chunk = Arc<Vec<u8>>;
+
+    /// Sink that serves the recording to WebSocket clients
+    pub struct LiveSink {
+        sender: Option<Sender<Vec<i16>>>,
+        thread: Option<JoinHandle<()>>,
+        chunker: Chunker,
+        dropped: u64,
+    }
+
+    impl LiveSink {
+        /// Start listening as `options` say; fails if the address can't be bound
+        pub fn spawn(options: &LiveServer, bitrate: Bitrate, context: &SinkContext) -> Result<Self, SinkError> {
+            let address = options.address().ok_or_else(|| format!("invalid live address {}", options.listen))?;
+            let listener = TcpListener::bind(address)
+                .map_err(|e| format!("could not listen for live clients on {}: {}", address, e))?;
+            listener.set_nonblocking(true)?;
+            let spec = context.spec;
+            let (sender, receiver) = crossbeam_channel::bounded(SERVER_QUEUE);
+            let server = Server {
+                options: options.clone(),
+                bitrate,
+                spec,
+                session_id: context.session_id,
+            };
+            let thread = thread::Builder::new()
+                .name("live-server".to_string())
+                .spawn(move || server.run(listener, receiver))?;
+            Ok(Self {
+                sender: Some(sender),
+                thread: Some(thread),
+                chunker: Chunker::new(&spec, options.chunk_ms),
+                dropped: 0,
+            })
+        }
+
+        fn queue(&mut self, samples: Vec<i16>) {
+            if let Some(sender) = &self.sender {
+                if let Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) = sender.try_send(samples) {
+                    if self.dropped == 0 {
+                        console_err!("Warning: the live server fell behind; dropping audio for its clients");
+                    }
+                    self.dropped += 1;
+                }
+            }
+        }
+    }
+
+    impl Sink for LiveSink {
+        fn write_frames(&mut self, mut samples: &[i16]) -> Result<(), SinkError> {
+            while let Some(chunk) = self.chunker.fill(&mut samples) {
+                self.queue(chunk.samples);
+            }
+            Ok(())
+        }
+
+        fn finalize(mut self: Box<Self>) -> Result<(), SinkError> {
+            if let Some(chunk) = self.chunker.rest() {
+                self.queue(chunk.samples);
+            }
+            // Hanging up tells the server to send the clients the end of the stream
+            self.sender = None;
+            self.thread
+                .take()
+                .expect("server thread is only joined here")
+                .join()
+                .map_err(|_| "live server thread panicked")?;
+            Ok(())
+        }
+    }
+
+    /// The server thread: accepts clients and hands each chunk to all of them
+    struct Server {
+        options: LiveServer,
+        bitrate: Bitrate,
+        spec: WavSpec,
+        session_id: Uuid,
+    }
+
+    impl Server {
+        fn run(self, listener: TcpListener, chunks: Receiver<Vec<i16>>) {
+            let start = serde_json::json!({
+                "event": "start",
+                "session_id": self.session_id,
+                "format": self.options.format,
+                "content_type": self.options.format.content_type(),
+                "sample_rate": self.spec.sample_rate,
+                "channels": self.spec.channels,
+            })
+            .to_string();
+            let options = Arc::new(self.options.clone());
+            let mut clients: Vec<(Sender<Chunk>, JoinHandle<()>)> = Vec::new();
+            let mut encode_failed = false;
+            loop {
+                match chunks.recv_timeout(ACCEPT_INTERVAL) {
+                    Ok(samples) => match self.options.format.encode(&samples, &self.spec, self.bitrate) {
+                        Ok(bytes) => {
+                            let bytes = Arc::new(bytes);
+                            // A full queue drops the chunk for that client; a closed one is a client gone
+                            clients.retain(|(client, _)| {
+                                !matches!(client.try_send(bytes.clone()), Err(TrySendError::Disconnected(_)))
+                            });
+                        }
+                        Err(e) => {
+                            if !std::mem::replace(&mut encode_failed, true) {
+                                console_err!("Warning: could not encode live audio: {}", e);
+                            }
+                        }
+                    },
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+                while let Ok((stream, _)) = listener.accept() {
+                    let (sender, receiver) = crossbeam_channel::bounded(CLIENT_QUEUE);
+                    let (options, start) = (options.clone(), start.clone());
+                    let client = thread::spawn(move || serve(stream, &options, start, receiver));
+                    clients.push((sender, client));
+                }
+            }
+            // Hanging up on the clients makes them send the end of the stream and close
+            let handles: Vec<_> = clients.into_iter().map(|(_, handle)| handle).collect();
+            for handle in handles {
+                let _ = handle.join();
+            }
+        }
+    }
+
+    /// Answer one connection: the player page, or the stream over a WebSocket
+    fn serve(stream: TcpStream, options: &LiveServer, start: String, chunks: Receiver<Chunk>) {
+        if stream.set_nonblocking(false).is_err()
+            || stream.set_read_timeout(Some(CLIENT_TIMEOUT)).is_err()
+            || stream.set_write_timeout(Some(CLIENT_TIMEOUT)).is_err()
+        {
+            return;
+        }
+        match is_websocket_request(&stream) {
+            Some(true) => {}
+            Some(false) => return send_page(stream),
+            None => return,
+        }
+        // The callback's error type is tungstenite's, however large
+        #[allow(clippy::result_large_err)]
+        let check = |request: &Request, response: Response| -> Result<Response, ErrorResponse> {
+            if options.authorizes(request.uri().query()) {
+                Ok(response)
+            } else {
+                let mut refusal = ErrorResponse::new(Some("A valid token is required".to_string()));
+                *refusal.status_mut() = StatusCode::UNAUTHORIZED;
+                Err(refusal)
+            }
+        };
+        let Ok(mut socket) = tungstenite::accept_hdr(stream, check) else {
+            return;
+        };
+        if socket.send(Message::Text(start)).is_err() {
+            return;
+        }
+        for chunk in chunks {
+            if socket.send(Message::Binary(chunk.to_vec())).is_err() {
+                return;
+            }
+        }
+        let _ = socket.send(Message::Text(r#"{"event":"end"}"#.to_string()));
+        let _ = socket.close(None);
+        let _ = socket.flush();
+    }
+
+    /// Whether the request waiting on `stream` asks for a WebSocket, without
+    /// consuming it; None if the headers don't arrive in time
+    fn is_websocket_request(stream: &TcpStream) -> Option<bool> {
+        let deadline = Instant::now() + CLIENT_TIMEOUT;
+        let mut buffer = [0; 4096];
+        loop {
+            let peeked = stream.peek(&mut buffer).ok()?;
+            let request = String::from_utf8_lossy(&buffer[..peeked]).to_ascii_lowercase();
+            if request.contains("\r\n\r\n") || peeked == buffer.len() {
+                return Some(request.lines().any(|line| {
+                    line.split_once(':')
+                        .is_some_and(|(name, value)| name.trim() == "upgrade" && value.trim() == "websocket")
+                }));
+            }
+            if peeked == 0 || Instant::now() >= deadline {
+                return None;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn send_page(mut stream: TcpStream) {
+        // Read the request so closing doesn't reset the connection under the reply
+        let mut request = [0; 4096];
+        let _ = stream.read(&mut request);
+        let _ = write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            PLAYER_PAGE.len(),
+            PLAYER_PAGE
+        );
+        let _ = stream.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server(token: Option<&str>) -> LiveServer {
+        LiveServer {
+            listen: default_listen(),
+            format: StreamFormat::Pcm,
+            chunk_ms: default_chunk_ms(),
+            token: token.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_address_and_token() {
+        assert_eq!(server(None).address(), Some("127.0.0.1:8765".parse().unwrap()));
+        assert!(server(None).authorizes(None));
+        let secured = server(Some("s3cret"));
+        assert!(secured.authorizes(Some("room=a&token=s3cret")));
+        assert!(!secured.authorizes(Some("token=wrong")));
+        assert!(!secured.authorizes(None));
+    }
+
+    #[cfg(feature = "live")]
+    #[test]
+    fn test_clients_get_the_stream_and_the_page() {
+        use crate::encoder::Bitrate;
+        use crate::sink::{Sink, SinkContext};
+        use std::io::{Read, Write};
+        use tungstenite::Message;
+
+        // Find a free port, then serve on it
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let options = LiveServer {
+            listen: format!("127.0.0.1:{}", port),
+            chunk_ms: 10,
+            ..server(Some("s3cret"))
+        };
+        let context = SinkContext {
+            recording_path: "meeting.wav".into(),
+            spec: hound::WavSpec {
+                channels: 1,
+                sample_rate: 8000,
+                bits_per_sample: 16,
+                sample_format: hound::SampleFormat::Int,
+            },
+            session_id: uuid::Uuid::new_v4(),
+        };
+        let mut sink: Box<dyn Sink> = Box::new(LiveSink::spawn(&options, Bitrate::default(), &context).unwrap());
+
+        let mut page = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+        page.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut reply = String::new();
+        page.read_to_string(&mut reply).unwrap();
+        assert!(reply.starts_with("HTTP/1.1 200 OK") && reply.contains("new WebSocket"));
+
+        let refused = tungstenite::connect(format!("ws://127.0.0.1:{}/", port));
+        assert!(refused.is_err());
+        let (mut socket, _) = tungstenite::connect(format!("ws://127.0.0.1:{}/?token=s3cret", port)).unwrap();
+        let Message::Text(start) = socket.read().unwrap() else { panic!("expected the start message") };
+        assert!(start.contains(r#""sample_rate":8000"#));
+
+        // Give the server a moment to register the client, then send one chunk of 80 frames
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        sink.write_frames(&[5; 80]).unwrap();
+        sink.finalize().unwrap();
+        assert_eq!(socket.read().unwrap(), Message::Binary([5, 0].repeat(80)));
+        assert_eq!(socket.read().unwrap(), Message::Text(r#"{"event":"end"}"#.to_string()));
+    }
+}
@@ -0,0 +1,167 @@
+//! Single-instance guard for recording sessions
+//!
+//! A lock file in the output directory is held (with an OS-level exclusive
+//! lock) for as long as a session runs, so a second invocation can refuse to
+//! start instead of fighting over the same devices. The file records who holds
+//! it, which lets other processes report on the running session.
+
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Name of the lock file created in the output directory
+pub const LOCK_FILE_NAME: &str = ".meeting-recorder.lock";
+
+/// Details of the process holding the session lock
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionInfo {
+    pub pid: u32,
+    /// When the session started
+    pub started: SystemTime,
+}
+
+impl SessionInfo {
+    fn current() -> Self {
+        Self {
+            pid: std::process::id(),
+            started: SystemTime::now(),
+        }
+    }
+
+    fn to_contents(&self) -> String {
+        let started = self.started.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        format!("pid={}\nstarted={}\n", self.pid, started)
+    }
+
+    fn parse(contents: &str) -> Option<Self> {
+        let mut pid = None;
+        let mut started = None;
+        for line in contents.lines() {
+            match line.split_once('=') {
+                Some(("pid", value)) => pid = value.trim().parse().ok(),
+                Some(("started", value)) => started = value.trim().parse().ok(),
+                _ => {}
+            }
+        }
+        Some(Self {
+            pid: pid?,
+            started: UNIX_EPOCH + Duration::from_secs(started?),
+        })
+    }
+}
+
+/// An exclusive claim on a directory for one recording session
+///
+/// Released when dropped; the OS also drops the lock if the process dies, so
+/// a crash never leaves a stale lock behind.
+#[derive(Debug)]
+pub struct SessionLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl SessionLock {
+    /// Take the session lock for `dir`, failing if another session holds it
+    pub fn acquire(dir: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = dir.as_ref().join(LOCK_FILE_NAME);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let holder = read_info(&mut file)
+                    .map(|info| format!(" (pid {})", info.pid))
+                    .unwrap_or_default();
+                return Err(format!(
+                    "Another recording is already running{}; lock file: {}",
+                    holder,
+                    path.display()
+                ).into());
+            }
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+
+        file.set_len(0)?;
+        file.write_all(SessionInfo::current().to_contents().as_bytes())?;
+        file.sync_data()?;
+        Ok(Self { file, path })
+    }
+
+    /// Path of the lock file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for SessionLock {
+    fn drop(&mut self) {
+        // Clear the details first so readers never see a finished session as live
+        let _ = self.file.set_len(0);
+        let _ = self.file.unlock();
+    }
+}
+
+/// The session currently recording into `dir`, if any
+pub fn running_session(dir: impl AsRef<Path>) -> io::Result<Option<SessionInfo>> {
+    let path = dir.as_ref().join(LOCK_FILE_NAME);
+    let mut file = match File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    // If the lock can be taken, nobody is recording
+    match file.try_lock_shared() {
+        Ok(()) => {
+            file.unlock()?;
+            Ok(None)
+        }
+        Err(TryLockError::WouldBlock) => Ok(read_info(&mut file)),
+        Err(TryLockError::Error(e)) => Err(e),
+    }
+}
+
+fn read_info(file: &mut File) -> Option<SessionInfo> {
+    let mut contents = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut contents).ok()?;
+    SessionInfo::parse(&contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_second_acquire_is_refused() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock = SessionLock::acquire(temp_dir.path()).unwrap();
+
+        let err = SessionLock::acquire(temp_dir.path()).unwrap_err();
+        assert!(err.to_string().contains("already running"));
+        assert!(err.to_string().contains(&std::process::id().to_string()));
+
+        drop(lock);
+        assert!(SessionLock::acquire(temp_dir.path()).is_ok());
+    }
+
+    #[test]
+    fn test_running_session_reports_holder() {
+        let temp_dir = TempDir::new().unwrap();
+        assert_eq!(running_session(temp_dir.path()).unwrap(), None);
+
+        let lock = SessionLock::acquire(temp_dir.path()).unwrap();
+        let info = running_session(temp_dir.path()).unwrap().expect("session should be running");
+        assert_eq!(info.pid, std::process::id());
+
+        drop(lock);
+        assert_eq!(running_session(temp_dir.path()).unwrap(), None);
+    }
+}
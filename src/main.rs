@@ -1,27 +1,528 @@
-use meeting_recorder::{DeviceManager, Recorder, Config};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use meeting_recorder::{DeviceManager, Recorder, RecorderError, Config};
+use meeting_recorder::checksum;
+use meeting_recorder::dsp::Levels;
+use meeting_recorder::hotkey::HotkeyAction;
+#[cfg(feature = "hotkeys")]
+use meeting_recorder::hotkey::HotkeyListener;
+use meeting_recorder::marker;
+#[cfg(feature = "midi")]
+use meeting_recorder::midi::MidiListener;
 use meeting_recorder::input::{read_index, read_index_optional};
+use meeting_recorder::lock::{self, SessionLock};
+use meeting_recorder::rtp::RtpSource;
+use meeting_recorder::source::Input;
+use meeting_recorder::recorder::{IncompleteRecording, RecordingResult, RecordingSession, StopReason};
+use meeting_recorder::wav;
+use meeting_recorder::waveform::{self, WaveformOptions};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+use meter::StatusLine;
+use pipe::PipeSink;
+
+mod appwatch;
+#[cfg(feature = "calendar")]
+mod autorecord;
+mod collect;
+mod control;
+#[cfg(feature = "gui")]
+mod gui;
+#[cfg(any(feature = "gui", feature = "tray"))]
+mod launch;
+mod meter;
+mod pipe;
+mod service;
+mod shutdown;
+mod systemd;
+#[cfg(feature = "tray")]
+mod tray;
+#[cfg(feature = "tui")]
+mod tui;
+mod watch;
+
+/// Exit codes, so scripts can tell how a recording ended
+mod exit_code {
+    /// Recording could not start, or another command failed
+    pub const FAILED: u8 = 1;
+    // 2 is used by clap for invalid arguments
+    /// Stopped early because the disk was nearly full; the file was saved
+    pub const LOW_DISK_SPACE: u8 = 3;
+    /// Stopped early because a capture device went away; the file was saved
+    pub const DEVICE_LOST: u8 = 4;
+    /// Stopped early because writing failed; the file may need `repair`
+    pub const WRITE_ERROR: u8 = 5;
+}
+
+/// Record microphone and system audio into a single WAV file
+#[derive(Parser)]
+#[command(name = "meeting-recorder", version, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    
+    #[command(flatten)]
+    record: RecordArgs,
+}
+
+#[derive(Args, Default)]
+struct RecordArgs {
+    /// Format of the report printed when recording ends, or `-` to write the audio to stdout
+    #[arg(long, value_enum, default_value_t)]
+    output: OutputFormat,
+    /// With `--output -`, start the audio with a WAV header for programs that expect one
+    #[arg(long)]
+    wav_header: bool,
+    /// Microphone to record, by index or name, instead of asking; needed when running as a service
+    #[arg(long, value_name = "DEVICE")]
+    mic: Option<String>,
+    /// System audio device to record, by index or name, or an rtp:// stream to receive; with --mic, system audio is skipped without it
+    #[arg(long, value_name = "DEVICE", requires = "mic")]
+    system: Option<String>,
+    /// Select devices, then start recording only when the start/stop hotkey or MIDI control is pressed
+    #[arg(long)]
+    wait_for_hotkey: bool,
+    /// Don't show the live status line with the elapsed time, levels and file size
+    #[arg(long)]
+    no_meters: bool,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Human-readable progress and summary
+    #[default]
+    Text,
+    /// A single JSON object on the last line of stdout
+    Json,
+    /// Raw 16-bit little-endian PCM on stdout, with progress and the summary on stderr
+    #[value(name = "-")]
+    Pipe,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Record a meeting (the default when no command is given)
+    Record(RecordArgs),
+    /// Record a meeting in a full-screen interface with live level meters
+    #[cfg(feature = "tui")]
+    Tui,
+    /// Record in a desktop window with device lists, level meters and recent recordings
+    #[cfg(feature = "gui")]
+    Gui,
+    /// Sit in the system tray, recording from fixed devices when started from its menu
+    #[cfg(feature = "tray")]
+    Tray {
+        /// Microphone to record, by index or name
+        #[arg(long, value_name = "DEVICE")]
+        mic: String,
+        /// System audio device to record, by index or name
+        #[arg(long, value_name = "DEVICE")]
+        system: Option<String>,
+    },
+    /// Record the events of the configured calendar that match its rules
+    #[cfg(feature = "calendar")]
+    Calendar {
+        /// Microphone to record, by index or name
+        #[arg(long, value_name = "DEVICE", required_unless_present = "list")]
+        mic: Option<String>,
+        /// System audio device to record, by index or name
+        #[arg(long, value_name = "DEVICE")]
+        system: Option<String>,
+        /// List the events of the coming week and whether they would be recorded, then exit
+        #[arg(long)]
+        list: bool,
+    },
+    /// Record whenever a meeting app such as Zoom or Teams uses the microphone
+    Auto {
+        /// Microphone to record, by index or name
+        #[arg(long, value_name = "DEVICE")]
+        mic: String,
+        /// System audio device to record, by index or name
+        #[arg(long, value_name = "DEVICE")]
+        system: Option<String>,
+        /// Ask before each recording instead of starting right away
+        #[arg(long)]
+        prompt: bool,
+    },
+    /// Record whenever there is sound on the system audio device, until it has been silent for a while
+    Watch {
+        /// Microphone to record, by index or name
+        #[arg(long, value_name = "DEVICE")]
+        mic: String,
+        /// System audio device to listen to and record, by index or name
+        #[arg(long, value_name = "DEVICE")]
+        system: String,
+    },
+    /// Show what each button of the MIDI controller sends, for the controls under 'midi' in the config
+    #[cfg(feature = "midi")]
+    Midi,
+    /// Rebuild the header of a truncated or unfinalized WAV file in place
+    Repair {
+        /// WAV file to repair
+        file: PathBuf,
+    },
+    /// Report whether a recording is in progress
+    Status,
+    /// Receive recordings sent by other machines with `collector` in their config
+    Collect {
+        /// Address and port to listen on
+        #[arg(long, default_value = "0.0.0.0:9100")]
+        listen: String,
+    },
+    /// Mark the current point of the running recording
+    Marker {
+        /// What the marker is for, or a short name from `marker_labels`
+        label: Vec<String>,
+    },
+    /// Describe the format, chunks and any problems of a WAV file
+    Inspect {
+        /// WAV file to inspect
+        file: PathBuf,
+    },
+    /// Measure a WAV file's levels, clipping and silence
+    Analyze {
+        /// WAV file to analyze
+        file: PathBuf,
+    },
+    /// Render an overview waveform of a WAV file as a PNG
+    Waveform {
+        /// WAV file to render
+        file: PathBuf,
+        /// PNG file to write
+        #[arg(long)]
+        out: PathBuf,
+        /// Image width in pixels
+        #[arg(long, default_value_t = 1200)]
+        width: u32,
+        /// Image height in pixels
+        #[arg(long, default_value_t = 200)]
+        height: u32,
+        /// Draw each channel separately
+        #[arg(long)]
+        per_channel: bool,
+    },
+    /// Check a WAV recording's audio against its .sha256 checksum
+    Verify {
+        /// WAV file to check
+        file: PathBuf,
+    },
+    /// Register a service that records with fixed devices (systemd, launchd or Task Scheduler)
+    InstallService {
+        /// Microphone the service records, by index or name
+        #[arg(long, value_name = "DEVICE")]
+        mic: String,
+        /// System audio device the service records, by index or name
+        #[arg(long, value_name = "DEVICE")]
+        system: Option<String>,
+        /// Name of the service
+        #[arg(long, default_value = "meeting-recorder")]
+        name: String,
+        /// Install for the current user instead of system-wide
+        #[arg(long)]
+        user: bool,
+        /// Print the service definition instead of installing it
+        #[arg(long)]
+        print: bool,
+    },
+    /// Stop and remove a service registered with install-service
+    UninstallService {
+        /// Name of the service
+        #[arg(long, default_value = "meeting-recorder")]
+        name: String,
+        /// Remove the current user's service instead of the system-wide one
+        #[arg(long)]
+        user: bool,
+    },
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    
+    let result = match cli.command.unwrap_or(Command::Record(cli.record)) {
+        Command::Record(args) => return finish(run_recording(&args), args.output),
+        #[cfg(feature = "tui")]
+        Command::Tui => return finish(tui::run(), OutputFormat::Text),
+        #[cfg(feature = "gui")]
+        Command::Gui => gui::run(),
+        #[cfg(feature = "tray")]
+        Command::Tray { mic, system } => tray::run(&mic, system.as_deref()),
+        #[cfg(feature = "calendar")]
+        Command::Calendar { list: true, .. } => autorecord::list(),
+        #[cfg(feature = "calendar")]
+        Command::Calendar { mic, system, .. } => autorecord::run(mic.as_deref().unwrap_or_default(), system.as_deref()),
+        Command::Auto { mic, system, prompt } => appwatch::run(&mic, system.as_deref(), prompt),
+        Command::Watch { mic, system } => watch::run(&mic, &system),
+        #[cfg(feature = "midi")]
+        Command::Midi => midi_presses(),
+        Command::Repair { file } => repair(&file),
+        Command::Status => status(),
+        Command::Collect { listen } => collect::run(&listen),
+        Command::Marker { label } => marker(&label.join(" ")),
+        Command::Inspect { file } => inspect(&file),
+        Command::Analyze { file } => analyze(&file),
+        Command::Waveform { file, out, width, height, per_channel } => {
+            let options = WaveformOptions { width, height, per_channel };
+            waveform::render_png(&file, &out, &options)
+        }
+        Command::Verify { file } => verify(&file),
+        Command::InstallService { mic, system, name, user, print } => install_service(mic, system, name, user, print),
+        Command::UninstallService { name, user } => {
+            service::Platform::current().and_then(|platform| service::uninstall(platform, &name, user))
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(exit_code::FAILED)
+        }
+    }
+}
+
+/// Report how a recording ended, returning the matching exit code
+fn finish(result: Result<RecordingResult, Box<dyn std::error::Error>>, output: OutputFormat) -> ExitCode {
+    let code = match &result {
+        Ok(recording) if recording.stop_reason == StopReason::LowDiskSpace => exit_code::LOW_DISK_SPACE,
+        Ok(_) => 0,
+        Err(e) => match incomplete(e.as_ref()).map(|incomplete| incomplete.reason) {
+            Some(StopReason::DeviceLost) => exit_code::DEVICE_LOST,
+            Some(StopReason::WriteError) => exit_code::WRITE_ERROR,
+            Some(StopReason::LowDiskSpace) => exit_code::LOW_DISK_SPACE,
+            Some(StopReason::Requested | StopReason::TimeLimit | StopReason::EndOfInput) | None => exit_code::FAILED,
+        },
+    };
+    
+    match (&result, output) {
+        (Ok(_), OutputFormat::Text | OutputFormat::Pipe) => {}
+        (Err(e), OutputFormat::Text | OutputFormat::Pipe) => eprintln!("Error: {}", e),
+        (result, OutputFormat::Json) => println!("{}", json_report(result, code)),
+    }
+    ExitCode::from(code)
+}
+
+fn levels_json(levels: &Levels) -> serde_json::Value {
+    json!({ "peak": levels.peak, "rms": levels.rms })
+}
+
+/// Final machine-readable report for `--output json`
+fn json_report(result: &Result<RecordingResult, Box<dyn std::error::Error>>, code: u8) -> serde_json::Value {
+    match result {
+        Ok(recording) => json!({
+            "status": "completed",
+            "session_id": recording.id,
+            "exit_code": code,
+            "exit_reason": recording.stop_reason,
+            "file": recording.filename,
+            "finalized": true,
+            "duration_secs": recording.duration.as_secs_f64(),
+            "format": {
+                "sample_rate": recording.spec.sample_rate,
+                "channels": recording.spec.channels,
+                "bits_per_sample": recording.spec.bits_per_sample,
+            },
+            "samples": {
+                "microphone": recording.mic_samples,
+                "system_audio": recording.sys_samples,
+                "written": recording.samples_written,
+                "frames": recording.frames_written,
+            },
+            "levels": {
+                "microphone": levels_json(&recording.mic_levels),
+                "system_audio": recording.sys_levels.as_ref().map(levels_json),
+                "mix": levels_json(&recording.mix_levels),
+            },
+            "summary": {
+                "microphone": recording.mic_summary,
+                "system_audio": recording.sys_summary,
+            },
+            "dropouts": {
+                "total": recording.dropouts(),
+                "backpressure_events": recording.backpressure_events,
+                "backlog_events": recording.backlog_events,
+                "stream_errors": recording.stream_errors,
+            },
+            "markers": recording.markers.iter().map(|marker| json!({
+                "position": marker.position,
+                "time_secs": marker.time(recording.spec.sample_rate).as_secs_f64(),
+                "label": marker.label,
+            })).collect::<Vec<_>>(),
+            "marker_exports": recording.marker_exports,
+            "transcripts": recording.transcripts,
+            "upload": recording.upload,
+            "error": null,
+        }),
+        Err(e) => match incomplete(e.as_ref()) {
+            Some(incomplete) => json!({
+                "status": "incomplete",
+                "session_id": incomplete.id,
+                "exit_code": code,
+                "exit_reason": incomplete.reason,
+                "file": incomplete.path,
+                "finalized": incomplete.finalized,
+                "duration_secs": incomplete.duration.as_secs_f64(),
+                "error": e.to_string(),
+            }),
+            None => json!({
+                "status": "failed",
+                "exit_code": code,
+                "exit_reason": null,
+                "file": null,
+                "error": e.to_string(),
+            }),
+        },
+    }
+}
+
+fn status() -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::load()?;
+    match lock::running_session(&config.output_directory)? {
+        Some(session) => {
+            let elapsed = session.started.elapsed().unwrap_or_default().as_secs();
+            println!(
+                "Recording in progress (pid {}, running for {}m {:02}s)",
+                session.pid,
+                elapsed / 60,
+                elapsed % 60
+            );
+        }
+        None => println!("No recording in progress"),
+    }
+    Ok(())
+}
+
+fn repair(file: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let summary = wav::repair(file)?;
+    
+    if !summary.changed() {
+        println!("{}: header is already consistent, nothing to repair", file.display());
+    } else {
+        println!("Repaired {}", file.display());
+        println!(
+            "Data size: {} -> {} bytes",
+            summary.original_data_size, summary.repaired_data_size
+        );
+        if summary.truncated_bytes > 0 {
+            println!("Dropped {} bytes of an incomplete trailing frame", summary.truncated_bytes);
+        }
+    }
+    
+    // A crashed recording is left under its temporary .part name
+    if let Some(completed) = wav::completed_path(file) {
+        if completed.exists() {
+            println!("Not renaming: {} already exists", completed.display());
+        } else {
+            std::fs::rename(file, &completed)?;
+            println!("Renamed to {}", completed.display());
+        }
+    }
+    Ok(())
+}
+
+fn inspect(file: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let report = wav::validate_wav_file(file)?;
+    println!("{}", file.display());
+    println!("{}", report);
+    Ok(())
+}
+
+fn analyze(file: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let stats = wav::analyze(file)?;
+    println!("{}", file.display());
+    println!("{}", stats);
+    Ok(())
+}
+
+fn verify(file: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    if !checksum::verify(file)? {
+        return Err(format!("{}: audio does not match its checksum", file.display()).into());
+    }
+    println!("{}: OK", file.display());
+    Ok(())
+}
+
+fn install_service(
+    mic: String,
+    system: Option<String>,
+    name: String,
+    user: bool,
+    print: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let platform = service::Platform::current()?;
+    let mut args = vec!["record".to_string(), "--mic".to_string(), mic];
+    if let Some(system) = system {
+        args.extend(["--system".to_string(), system]);
+    }
+    // The JSON report is the one thing worth keeping in the service log
+    args.extend(["--output".to_string(), "json".to_string()]);
+    let options = service::ServiceOptions {
+        name,
+        user,
+        executable: std::env::current_exe()?,
+        args,
+    };
+    if print {
+        print!("{}", platform.definition(&options));
+        return Ok(());
+    }
+    service::install(platform, &options)
+}
+
+fn run_recording(args: &RecordArgs) -> Result<RecordingResult, Box<dyn std::error::Error>> {
+    // Audio gets stdout to itself; everything printed from here on goes to stderr
+    let pipe = (args.output == OutputFormat::Pipe).then(pipe::take_stdout).transpose()?;
     println!("Meeting Recorder - Capturing microphone and system audio");
     println!("========================================================\n");
     
     // Load configuration
     let config = Config::load()?;
     println!("Output directory: {}\n", config.output_directory);
+    let start_controls: Vec<String> = config.hotkeys.iter()
+        .filter_map(|hotkeys| hotkeys.start_stop.clone())
+        .chain(config.midi.iter().filter_map(|midi| Some(format!("MIDI {}", midi.start_stop.as_ref()?))))
+        .collect();
+    if args.wait_for_hotkey && start_controls.is_empty() {
+        return Err("--wait-for-hotkey needs a 'start_stop' shortcut under 'hotkeys', or control under 'midi', in the config".into());
+    }
+    
+    // Refuse to start if another session is already recording here
+    let _session_lock = SessionLock::acquire(&config.output_directory)?;
 
     let device_manager = DeviceManager::new()?;
     device_manager.list_devices()?;
 
-    // Get device selections
-    println!("\nSelect microphone device (index):");
-    let mic_idx = read_index(device_manager.device_count())?;
+    // Get device selections, from the command line or else interactively
+    let mic_idx = match &args.mic {
+        Some(device) => select_device(&device_manager, device)?,
+        None => {
+            println!("\nSelect microphone device (index):");
+            read_index(device_manager.device_count())?
+        }
+    };
     let mic_name = device_manager.device_name(mic_idx)?;
     println!("Selected microphone: {}\n", mic_name);
 
-    println!("Select system audio device (index, or -1 to skip):");
-    let sys_idx = read_index_optional(device_manager.device_count())?;
+    // System audio can also come over the network as an RTP stream
+    let sys_rtp = args.system.as_deref()
+        .filter(|device| device.starts_with("rtp://"))
+        .map(RtpSource::from_url)
+        .transpose()?;
+    let sys_idx = match (&args.mic, &args.system) {
+        (_, Some(_)) if sys_rtp.is_some() => None,
+        (_, Some(device)) => Some(select_device(&device_manager, device)?),
+        (Some(_), None) => None,
+        (None, None) => {
+            println!("Select system audio device (index, or -1 to skip):");
+            read_index_optional(device_manager.device_count())?
+        }
+    };
     
-    if let Some(idx) = sys_idx {
+    if let Some(rtp) = &sys_rtp {
+        println!("Receiving system audio over RTP on {}\n", rtp.address());
+    } else if let Some(idx) = sys_idx {
         let name = device_manager.device_name(idx)?;
         println!("Selected system audio: {}\n", name);
     } else {
@@ -42,6 +543,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         let sys_sample_rate = config.sample_rate().0;
         let sys_channels = config.channels() as u16;
         println!("System audio config: {} channels, {} Hz", sys_channels, sys_sample_rate);
+    } else if let Some(format) = sys_rtp.as_ref().map(RtpSource::format) {
+        println!("System audio stream: {} channels, {} Hz", format.channels, format.sample_rate);
     }
 
     // Create recorder and start recording
@@ -55,15 +558,290 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     } else {
         None
     };
+    let mic = Input::Device { device: mic_device, config: mic_config };
+    let sys = match sys_rtp {
+        Some(rtp) => Some(Input::from(rtp)),
+        None => sys_device.zip(sys_config).map(|(device, config)| Input::Device { device, config }),
+    };
+    
+    // Stop and finalize on Ctrl+C, termination signals and console close.
+    // The guard tells the platform handler once the file is finalized
+    let running = Arc::new(AtomicBool::new(true));
+    let _shutdown = shutdown::install(running.clone())?;
+    
+    let mut recorder = Recorder::from_inputs(mic, sys).with_running_flag(running.clone());
+    if let Some(out) = pipe {
+        let (running, wav_header) = (running.clone(), args.wav_header);
+        recorder = recorder.with_sink(move |context| {
+            let spec = context.spec;
+            println!(
+                "Writing {} to stdout: {} Hz, {} channels, 16-bit little-endian",
+                if wav_header { "WAV" } else { "raw PCM" },
+                spec.sample_rate,
+                spec.channels
+            );
+            Ok(Box::new(PipeSink::spawn(out.try_clone()?, spec, wav_header, running.clone())?))
+        });
+    }
     
-    let recorder = Recorder::new(
-        mic_device,
-        mic_config,
-        sys_device,
-        sys_config,
-    );
+    // Under systemd, report readiness and progress and announce the stop
+    if let Some(notifier) = systemd::Notifier::from_env() {
+        notifier.follow(recorder.subscribe(), running.clone());
+    }
+
+    // Shortcuts are registered and polled on the main thread, the only one
+    // macOS delivers them to
+    #[cfg(feature = "hotkeys")]
+    let hotkeys = config.hotkeys.as_ref()
+        .map(HotkeyListener::register)
+        .transpose()
+        .map_err(|e| format!("Could not register hotkeys: {}", e))?;
+    #[cfg(feature = "midi")]
+    let midi = config.midi.as_ref()
+        .map(MidiListener::connect)
+        .transpose()
+        .map_err(|e| format!("Could not open the MIDI controller: {}", e))?;
+    #[cfg(feature = "midi")]
+    if let Some(midi) = &midi {
+        println!("Listening for MIDI controls on {}", midi.port());
+    }
+
+    #[cfg(feature = "hotkeys")]
+    let hotkey_label = config.hotkeys.as_ref().map_or(marker::DEFAULT_LABEL, |hotkeys| &hotkeys.marker_label);
+    #[cfg(feature = "hotkeys")]
+    let wait_for_hotkey = |timeout| match &hotkeys {
+        Some(hotkeys) => hotkeys.next(timeout).map(|action| (action, hotkey_label)),
+        None => {
+            std::thread::sleep(timeout);
+            None
+        }
+    };
+    #[cfg(not(feature = "hotkeys"))]
+    let wait_for_hotkey = |timeout| {
+        std::thread::sleep(timeout);
+        None
+    };
+    // MIDI controls arrive on a thread of the MIDI backend and are picked up
+    // between waits for shortcuts
+    #[cfg(feature = "midi")]
+    let midi_label = config.midi.as_ref().map_or(marker::DEFAULT_LABEL, |midi| &midi.marker_label);
+    #[cfg(feature = "midi")]
+    let wait = |timeout| match midi.as_ref().and_then(MidiListener::try_next) {
+        Some(action) => Some((action, midi_label)),
+        None => wait_for_hotkey(timeout),
+    };
+    #[cfg(not(feature = "midi"))]
+    let wait = wait_for_hotkey;
+
+    if args.wait_for_hotkey {
+        println!("\nPress {} to start recording...", start_controls.join(" or "));
+        while !matches!(wait(Duration::from_millis(100)), Some((HotkeyAction::StartStop, _))) {
+            if !running.load(Ordering::SeqCst) {
+                return Err("Stopped before recording started".into());
+            }
+        }
+    }
     
-    recorder.record(&config)?;
+    // Markers can be added from other processes once the socket is there
+    let control = match control::ControlSocket::bind(&config.output_directory) {
+        Ok(control) => Some(control),
+        Err(e) => {
+            eprintln!("Warning: markers can only be typed here, the control socket failed: {}", e);
+            None
+        }
+    };
+
+    let session = recorder.start(&config)?;
+    println!("\nPress Ctrl+C to stop recording...");
+    println!("Type a label and press Enter to add a marker\n");
+    #[cfg(feature = "hotkeys")]
+    if let Some(config) = &config.hotkeys {
+        for (shortcut, action) in config.bindings() {
+            println!("{} {}", shortcut, control_description(action));
+        }
+    }
+    #[cfg(feature = "midi")]
+    if let Some(config) = &config.midi {
+        for (control, action) in config.bindings() {
+            println!("MIDI {} {}", control, control_description(action));
+        }
+    }
+
+    let status = (!args.no_meters && args.output != OutputFormat::Json).then(|| StatusLine::new(&config));
+    follow_session(&session, &config, control.as_ref(), status, wait);
+    Ok(session.join()?)
+}
 
+/// What a shortcut or MIDI control does while recording
+#[cfg(any(feature = "hotkeys", feature = "midi"))]
+fn control_description(action: HotkeyAction) -> &'static str {
+    match action {
+        HotkeyAction::StartStop => "stops recording",
+        HotkeyAction::Pause => "pauses and resumes recording",
+        HotkeyAction::Marker => "adds a marker",
+    }
+}
+
+/// Add markers typed, sent over the control socket or set with a hotkey or
+/// MIDI control, pause on the pause control, and stop on the stop control,
+/// until `session` stops
+///
+/// `wait` waits up to the given time for a control, returning its action
+/// with the label of markers it adds. The status line, if any, is cleared
+/// before anything else is printed.
+fn follow_session<'a>(
+    session: &RecordingSession,
+    config: &Config,
+    control: Option<&control::ControlSocket>,
+    mut status: Option<StatusLine>,
+    mut wait: impl FnMut(Duration) -> Option<(HotkeyAction, &'a str)>,
+) {
+    let typed = control::stdin_lines();
+    let mark = |status: &mut Option<StatusLine>, label: String| {
+        if let Some(status) = status {
+            status.clear();
+        }
+        control::mark(session, label)
+    };
+    while session.is_running() {
+        match wait(Duration::from_millis(100)) {
+            Some((HotkeyAction::StartStop, _)) => {
+                if let Some(status) = &mut status {
+                    status.clear();
+                }
+                println!("\nStopping recording...");
+                session.stop();
+            }
+            Some((HotkeyAction::Pause, _)) => {
+                if let Some(status) = &mut status {
+                    status.clear();
+                }
+                if session.pause() {
+                    println!("\nPaused; press it again to resume");
+                } else if session.resume() {
+                    println!("\nResumed");
+                }
+            }
+            Some((HotkeyAction::Marker, label)) => {
+                mark(&mut status, label.to_string());
+            }
+            None => {}
+        }
+        for line in typed.try_iter() {
+            mark(&mut status, marker::typed_label(&line, &config.marker_labels));
+        }
+        if let Some(control) = control {
+            control.poll(&config.marker_labels, |label| mark(&mut status, label));
+        }
+        if let Some(status) = &mut status {
+            status.update(session);
+        }
+    }
+    if let Some(status) = &mut status {
+        status.clear();
+    }
+}
+
+/// Print each button pressed on the MIDI controller as a control for the config
+#[cfg(feature = "midi")]
+fn midi_presses() -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::load()?;
+    let listener = MidiListener::connect(&config.midi.unwrap_or_default())?;
+    let running = Arc::new(AtomicBool::new(true));
+    let _shutdown = shutdown::install(running.clone())?;
+    println!("Listening to {}; press the buttons to use, then Ctrl+C to stop", listener.port());
+    while running.load(Ordering::SeqCst) {
+        let Some(press) = listener.next_press(Duration::from_millis(100)) else {
+            continue;
+        };
+        match listener.action_for(&press) {
+            Some(action) => println!("{}  ({})", press, control_description(action)),
+            None => println!("{}", press),
+        }
+    }
+    Ok(())
+}
+
+fn marker(label: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::load()?;
+    let (time, label) = control::send_marker(&config.output_directory, label)?;
+    println!("Marker at {}: {}", control::timestamp(time), label);
     Ok(())
 }
+
+/// Index of the device given on the command line as an index or (part of) a name
+fn select_device(device_manager: &DeviceManager, device: &str) -> Result<usize, Box<dyn std::error::Error>> {
+    match device.parse::<usize>() {
+        Ok(index) if index < device_manager.device_count() => Ok(index),
+        Ok(index) => Err(format!("No device with index {}", index).into()),
+        Err(_) => Ok(device_manager.find_device(device)?),
+    }
+}
+
+/// Open the devices and start recording into the configured directory,
+/// naming the recording after `title` if given
+fn start_recording(
+    config: &Config,
+    mic: &str,
+    system: Option<&str>,
+    title: Option<&str>,
+) -> Result<(RecordingSession, SessionLock), Box<dyn std::error::Error>> {
+    let session_lock = SessionLock::acquire(&config.output_directory)?;
+    // Devices come and go, so they are looked up again for each recording
+    let mut device_manager = DeviceManager::new()?;
+    let mic_idx = select_device(&device_manager, mic)?;
+    let sys_idx = system.map(|system| select_device(&device_manager, system)).transpose()?;
+    let mic_config = device_manager.device_config(mic_idx)?;
+    let sys_config = sys_idx.map(|idx| device_manager.device_config(idx)).transpose()?;
+    let mic_device = device_manager
+        .take_device(mic_idx)
+        .ok_or_else(|| format!("Failed to get microphone device at index {}", mic_idx))?;
+    let sys_device = sys_idx.and_then(|idx| device_manager.take_device(idx));
+    let mut recorder = Recorder::new(mic_device, mic_config, sys_device, sys_config);
+    if let Some(title) = title {
+        recorder = recorder.with_title(title);
+    }
+    Ok((recorder.start(config)?, session_lock))
+}
+
+/// A recording that a waiting mode (`auto`, `calendar`, `watch`) started on its own
+struct Current<T = ()> {
+    session: RecordingSession,
+    _lock: SessionLock,
+    /// How messages refer to the recording, e.g. `the Zoom call`
+    name: Option<String>,
+    /// What the mode keeps track of while it records
+    state: T,
+}
+
+/// Stop a recording, if there is one, and report how it turned out
+fn finish_recording<T>(current: Option<Current<T>>) {
+    let Some(Current { session, _lock, name, .. }) = current else {
+        return;
+    };
+    session.stop();
+    let result = session.join();
+    match (result, name) {
+        (Ok(recording), Some(name)) => println!(
+            "Saved {} to {} ({})",
+            name,
+            recording.filename.as_deref().unwrap_or("no file"),
+            meter::clock(recording.duration)
+        ),
+        (Ok(recording), None) => println!(
+            "Saved {} ({})",
+            recording.filename.as_deref().unwrap_or("no file"),
+            meter::clock(recording.duration)
+        ),
+        (Err(e), Some(name)) => eprintln!("Error: recording {} failed: {}", name, e),
+        (Err(e), None) => eprintln!("Error: recording failed: {}", e),
+    }
+}
+
+/// The saved part of a recording that stopped early, if that is what `error` is
+fn incomplete<'a>(error: &'a (dyn std::error::Error + 'static)) -> Option<&'a IncompleteRecording> {
+    match error.downcast_ref::<RecorderError>()? {
+        RecorderError::Incomplete(incomplete) => Some(incomplete),
+        _ => None,
+    }
+}
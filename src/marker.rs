@@ -0,0 +1,197 @@
+//! Points of interest marked during a recording
+//!
+//! Markers are added with
+//! [`RecordingSession::add_marker`](crate::recorder::RecordingSession::add_marker)
+//! while a session runs. Each one points at the sample frame of the recording
+//! that was being written when it was set, and WAV recordings carry them as
+//! cue points that editors show on the timeline. They can also be exported
+//! next to the recording as an Audacity label track or as FFmpeg chapters.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::wav;
+
+/// Label of markers set without one
+pub const DEFAULT_LABEL: &str = "marker";
+
+/// A labelled position in a recording
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Marker {
+    /// Sample frame of the recording the marker points at
+    pub position: u64,
+    /// What the marker is for, e.g. "decision"
+    pub label: String,
+}
+
+impl Marker {
+    /// Offset of the marker from the start of a recording at `sample_rate`
+    pub fn time(&self, sample_rate: u32) -> Duration {
+        Duration::from_secs_f64(self.position as f64 / sample_rate as f64)
+    }
+}
+
+/// A file format markers are exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MarkerFormat {
+    /// Audacity label track: start, end and label per line, tab-separated, in seconds
+    Audacity,
+    /// FFmpeg metadata with a chapter starting at each marker
+    Ffmetadata,
+}
+
+impl MarkerFormat {
+    /// What replaces the recording's extension in the export's file name
+    pub fn extension(self) -> &'static str {
+        match self {
+            MarkerFormat::Audacity => "labels.txt",
+            MarkerFormat::Ffmetadata => "ffmetadata",
+        }
+    }
+
+    /// Render the `markers` of a recording of `frames` sample frames at `sample_rate`
+    pub fn render(self, markers: &[Marker], sample_rate: u32, frames: u64) -> String {
+        match self {
+            MarkerFormat::Audacity => to_audacity(markers, sample_rate),
+            MarkerFormat::Ffmetadata => to_ffmetadata(markers, sample_rate, frames),
+        }
+    }
+}
+
+fn to_audacity(markers: &[Marker], sample_rate: u32) -> String {
+    let mut labels = String::new();
+    for marker in markers {
+        let time = marker.time(sample_rate).as_secs_f64();
+        let label = marker.label.replace(['\t', '\r', '\n'], " ");
+        let _ = writeln!(labels, "{:.6}\t{:.6}\t{}", time, time, label);
+    }
+    labels
+}
+
+/// Chapters running from each marker to the next, timed in sample frames
+///
+/// Audio before the first marker becomes a chapter of its own, so players
+/// can still jump back to the start.
+fn to_ffmetadata(markers: &[Marker], sample_rate: u32, frames: u64) -> String {
+    let mut chapters: Vec<(u64, &str)> = markers.iter().map(|marker| (marker.position, marker.label.as_str())).collect();
+    if chapters.first().is_some_and(|&(start, _)| start > 0) {
+        chapters.insert(0, (0, "Start"));
+    }
+    let mut metadata = String::from(";FFMETADATA1\n");
+    for (i, &(start, title)) in chapters.iter().enumerate() {
+        let end = chapters.get(i + 1).map_or(frames, |&(next, _)| next).max(start);
+        let _ = write!(
+            metadata,
+            "\n[CHAPTER]\nTIMEBASE=1/{}\nSTART={}\nEND={}\ntitle={}\n",
+            sample_rate,
+            start,
+            end,
+            ffmetadata_escape(title)
+        );
+    }
+    metadata
+}
+
+/// Backslash-escape the characters FFMETADATA gives a meaning
+fn ffmetadata_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Path of the marker export of the recording at `recording_path` in `format`
+pub fn export_path(recording_path: impl AsRef<Path>, format: MarkerFormat) -> PathBuf {
+    recording_path.as_ref().with_extension(format.extension())
+}
+
+/// Write `markers` next to the recording at `recording_path` in each of `formats`
+///
+/// `frames` and `sample_rate` describe the recording. Each file is written
+/// under a `.part` name and renamed into place. Returns the paths written.
+pub fn write_exports(
+    recording_path: impl AsRef<Path>,
+    markers: &[Marker],
+    sample_rate: u32,
+    frames: u64,
+    formats: &[MarkerFormat],
+) -> io::Result<Vec<PathBuf>> {
+    formats
+        .iter()
+        .map(|&format| {
+            let path = export_path(recording_path.as_ref(), format);
+            let part_path = wav::partial_path(&path);
+            fs::write(&part_path, format.render(markers, sample_rate, frames))?;
+            fs::rename(&part_path, &path)?;
+            Ok(path)
+        })
+        .collect()
+}
+
+/// Label for a marker typed as `typed`
+///
+/// A short name from `labels` (the config's `marker_labels`) stands for its
+/// label, any other text is the label itself, and nothing at all gives
+/// [`DEFAULT_LABEL`].
+pub fn typed_label(typed: &str, labels: &BTreeMap<String, String>) -> String {
+    let typed = typed.trim();
+    match labels.get(typed) {
+        Some(label) => label.clone(),
+        None if typed.is_empty() => DEFAULT_LABEL.to_string(),
+        None => typed.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_typed_label() {
+        let labels = BTreeMap::from([("a".to_string(), "action item".to_string())]);
+        assert_eq!(typed_label("a\n", &labels), "action item");
+        assert_eq!(typed_label(" decision ", &labels), "decision");
+        assert_eq!(typed_label("", &labels), "marker");
+    }
+
+    fn markers() -> Vec<Marker> {
+        vec![
+            Marker { position: 72_000, label: "decision".to_string() },
+            Marker { position: 144_000, label: "action item; owner=Sam".to_string() },
+        ]
+    }
+
+    #[test]
+    fn test_audacity_labels() {
+        assert_eq!(
+            MarkerFormat::Audacity.render(&markers(), 48_000, 480_000),
+            "1.500000\t1.500000\tdecision\n3.000000\t3.000000\taction item; owner=Sam\n"
+        );
+        assert_eq!(
+            export_path("/rec/meeting.wav", MarkerFormat::Audacity),
+            Path::new("/rec/meeting.labels.txt")
+        );
+    }
+
+    #[test]
+    fn test_ffmetadata_chapters() {
+        assert_eq!(
+            MarkerFormat::Ffmetadata.render(&markers(), 48_000, 480_000),
+            ";FFMETADATA1\n\
+             \n[CHAPTER]\nTIMEBASE=1/48000\nSTART=0\nEND=72000\ntitle=Start\n\
+             \n[CHAPTER]\nTIMEBASE=1/48000\nSTART=72000\nEND=144000\ntitle=decision\n\
+             \n[CHAPTER]\nTIMEBASE=1/48000\nSTART=144000\nEND=480000\ntitle=action item\\; owner\\=Sam\n"
+        );
+        assert_eq!(MarkerFormat::Ffmetadata.render(&[], 48_000, 480_000), ";FFMETADATA1\n");
+    }
+}
@@ -0,0 +1,220 @@
+//! Level meters and the live status line of plain recordings
+//!
+//! While recording in a terminal, a status line shows the elapsed time, a
+//! peak meter for each source, the size of the file so far, how fast it
+//! grows and how long the free space lasts at that rate, refreshed in place
+//! twice a second. When stdout is not a terminal (a log file, a service),
+//! the same line is printed once a minute instead.
+
+use meeting_recorder::disk;
+use meeting_recorder::dsp::Levels;
+use meeting_recorder::recorder::RecordingSession;
+use meeting_recorder::state::RecordingState;
+use meeting_recorder::{wav, Config};
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Quietest level the meters show
+pub const METER_FLOOR_DB: f32 = -60.0;
+/// Width of a status line meter in characters
+const BAR_WIDTH: usize = 10;
+/// Audio recorded before the write rate is estimated, so headers and
+/// buffered writes don't skew it
+const RATE_WARMUP: Duration = Duration::from_secs(10);
+/// How often the free space of the output volume is checked again
+const SPACE_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// `level` (0 to 1) in dBFS, no lower than the meter's floor
+pub fn db(level: f32) -> f32 {
+    if level <= 0.0 {
+        METER_FLOOR_DB
+    } else {
+        (20.0 * level.log10()).max(METER_FLOOR_DB)
+    }
+}
+
+/// How full a meter showing `level` is, on a dB scale from the floor to 0 dBFS
+pub fn meter_ratio(level: f32) -> f64 {
+    ((db(level) - METER_FLOOR_DB) / -METER_FLOOR_DB).clamp(0.0, 1.0) as f64
+}
+
+/// `[######....] -18 dB`, the peak of `levels` as a text meter
+fn bar(levels: Levels) -> String {
+    let filled = (meter_ratio(levels.peak) * BAR_WIDTH as f64).round() as usize;
+    format!("[{}{}] {:>3.0} dB", "#".repeat(filled), ".".repeat(BAR_WIDTH - filled), db(levels.peak))
+}
+
+/// Size of the recording so far, written under its partial name until finalized
+pub fn file_size(path: &Path) -> Option<u64> {
+    std::fs::metadata(wav::partial_path(path)).or_else(|_| std::fs::metadata(path)).ok().map(|metadata| metadata.len())
+}
+
+/// `12.3 MB`
+pub fn size_text(bytes: u64) -> String {
+    let mb = bytes as f64 / 1_000_000.0;
+    if mb >= 1000.0 {
+        format!("{:.2} GB", mb / 1000.0)
+    } else {
+        format!("{:.1} MB", mb)
+    }
+}
+
+/// `01:02:03`
+pub fn clock(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// Average bytes per second of audio written, once enough is recorded to tell
+///
+/// Measured from the file rather than the spec, so compressed formats are
+/// estimated as well as WAV.
+fn write_rate(bytes: u64, duration: Duration) -> Option<f64> {
+    (duration >= RATE_WARMUP && bytes > 0).then(|| bytes as f64 / duration.as_secs_f64())
+}
+
+/// `11.5 MB/min, 41.5 h left`: the write rate and how long `free` bytes last at it
+fn usage_text(rate: f64, free: u64) -> String {
+    let remaining = Duration::from_secs_f64(free as f64 / rate);
+    let left = if remaining >= Duration::from_secs(3600) {
+        format!("{:.1} h left", remaining.as_secs_f64() / 3600.0)
+    } else {
+        format!("{} min left", remaining.as_secs() / 60)
+    };
+    format!("{:.1} MB/min, {}", rate * 60.0 / 1_000_000.0, left)
+}
+
+/// Write rate of a recording and the room left for it on the output volume
+pub struct DiskUsage {
+    directory: PathBuf,
+    /// Free space recording stops at
+    reserve: u64,
+    /// Free space when last checked, and when that was
+    available: Option<(u64, Instant)>,
+}
+
+impl DiskUsage {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            directory: PathBuf::from(&config.output_directory),
+            reserve: config.min_free_space_bytes().unwrap_or(0),
+            available: None,
+        }
+    }
+
+    /// `11.5 MB/min, 41.5 h left` for `session`, once the rate can be told
+    pub fn text(&mut self, session: &RecordingSession) -> Option<String> {
+        let rate = write_rate(session.path().and_then(file_size)?, session.duration())?;
+        if self.available.is_none_or(|(_, checked)| checked.elapsed() >= SPACE_CHECK_INTERVAL) {
+            let available = disk::available_space(&self.directory).ok()?;
+            self.available = Some((available, Instant::now()));
+        }
+        let (available, _) = self.available?;
+        Some(usage_text(rate, available.saturating_sub(self.reserve)))
+    }
+}
+
+/// The status line of a running session
+pub struct StatusLine {
+    /// Whether the line is redrawn in place
+    in_place: bool,
+    interval: Duration,
+    last: Option<Instant>,
+    /// Characters shown by the last in-place update, to blank them out
+    shown: usize,
+    disk: DiskUsage,
+}
+
+impl StatusLine {
+    pub fn new(config: &Config) -> Self {
+        let in_place = std::io::stdout().is_terminal();
+        Self {
+            in_place,
+            interval: if in_place { Duration::from_millis(500) } else { Duration::from_secs(60) },
+            last: None,
+            shown: 0,
+            disk: DiskUsage::new(config),
+        }
+    }
+
+    /// Show the state of `session`, unless that was done less than an interval ago
+    pub fn update(&mut self, session: &RecordingSession) {
+        if self.last.is_some_and(|last| last.elapsed() < self.interval) {
+            return;
+        }
+        self.last = Some(Instant::now());
+        let mut line = status_text(session);
+        if let Some(usage) = self.disk.text(session) {
+            line.push_str(&format!(" @ {}", usage));
+        }
+        let mut stdout = std::io::stdout().lock();
+        if self.in_place {
+            let _ = write!(stdout, "\r{:<width$}", line, width = self.shown);
+            self.shown = line.chars().count();
+        } else {
+            let _ = writeln!(stdout, "{}", line);
+        }
+        let _ = stdout.flush();
+    }
+
+    /// Blank out the line so other output starts on a clean one
+    pub fn clear(&mut self) {
+        if self.shown > 0 {
+            let mut stdout = std::io::stdout().lock();
+            let _ = write!(stdout, "\r{}\r", " ".repeat(self.shown));
+            let _ = stdout.flush();
+            self.shown = 0;
+            self.last = None;
+        }
+    }
+}
+
+fn status_text(session: &RecordingSession) -> String {
+    let levels = session.levels();
+    let state = match session.state() {
+        RecordingState::Paused => "PAUSED",
+        _ => "REC",
+    };
+    let mut line = format!("{} {}  mic {}", state, clock(session.duration()), bar(levels.mic));
+    if let Some(sys) = levels.sys {
+        line.push_str(&format!("  sys {}", bar(sys)));
+    }
+    if let Some(bytes) = session.path().and_then(file_size) {
+        line.push_str(&format!("  {}", size_text(bytes)));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_meter_scale() {
+        assert_eq!(db(1.0), 0.0);
+        assert_eq!(db(0.0), METER_FLOOR_DB);
+        assert!((db(0.5) + 6.02).abs() < 0.01);
+        assert_eq!(meter_ratio(1.0), 1.0);
+        assert!((meter_ratio(10f32.powf(-1.5)) - 0.5).abs() < 1e-6);
+        assert_eq!(meter_ratio(0.0), 0.0);
+        assert_eq!(bar(Levels { peak: 0.125, rms: 0.05 }), "[#######...] -18 dB");
+        assert_eq!(bar(Levels::default()), "[..........] -60 dB");
+    }
+
+    #[test]
+    fn test_status_text() {
+        assert_eq!(clock(Duration::from_secs(3723)), "01:02:03");
+        assert_eq!(size_text(12_345_678), "12.3 MB");
+        assert_eq!(size_text(2_500_000_000), "2.50 GB");
+    }
+
+    #[test]
+    fn test_disk_usage_text() {
+        assert_eq!(write_rate(1_000_000, Duration::from_secs(5)), None);
+        let rate = write_rate(1_920_000, Duration::from_secs(10)).unwrap();
+        assert_eq!(rate, 192_000.0);
+        assert_eq!(usage_text(rate, 192_000 * 5400), "11.5 MB/min, 1.5 h left");
+        assert_eq!(usage_text(rate, 192_000 * 1500), "11.5 MB/min, 25 min left");
+    }
+}
@@ -0,0 +1,308 @@
+//! Control from MIDI pads, keys and button boxes
+//!
+//! With the `midi` feature, buttons on a MIDI controller start and stop,
+//! pause and resume the recording and add markers, like the global
+//! shortcuts. Each control is written as the message the button sends:
+//! `note 36` (a pad or key being pressed), `cc 20` (a controller going to
+//! 64 or above, as buttons send when pressed) or `program 3`, optionally
+//! followed by `channel 10` to only take it from one channel (1 to 16).
+//! `meeting-recorder midi` shows what each button sends.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::hotkey::HotkeyAction;
+
+/// MIDI controls read from the configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MidiControls {
+    /// Part of the name of the MIDI input to listen to, ignoring case; the first input if not set
+    #[serde(default)]
+    pub port: Option<String>,
+    /// Starts a recording that waits for it, and stops a running one
+    #[serde(default)]
+    pub start_stop: Option<String>,
+    /// Pauses the recording, and resumes it when paused
+    #[serde(default)]
+    pub pause: Option<String>,
+    /// Marks the current point of the recording
+    #[serde(default)]
+    pub marker: Option<String>,
+    /// Label of markers added with the control
+    #[serde(default = "default_marker_label")]
+    pub marker_label: String,
+}
+
+fn default_marker_label() -> String {
+    crate::marker::DEFAULT_LABEL.to_string()
+}
+
+impl Default for MidiControls {
+    fn default() -> Self {
+        Self {
+            port: None,
+            start_stop: None,
+            pause: None,
+            marker: None,
+            marker_label: default_marker_label(),
+        }
+    }
+}
+
+impl MidiControls {
+    /// Each configured control with its action
+    pub fn bindings(&self) -> impl Iterator<Item = (&str, HotkeyAction)> {
+        [
+            (&self.start_stop, HotkeyAction::StartStop),
+            (&self.pause, HotkeyAction::Pause),
+            (&self.marker, HotkeyAction::Marker),
+        ]
+        .into_iter()
+        .filter_map(|(control, action)| Some((control.as_deref()?, action)))
+    }
+
+    /// The first control that isn't a valid MIDI message, if any
+    pub fn invalid_control(&self) -> Option<&str> {
+        self.bindings()
+            .map(|(control, _)| control)
+            .find(|control| control.parse::<MidiTrigger>().is_err())
+    }
+
+    /// Each valid control, parsed, with its action
+    pub fn triggers(&self) -> Vec<(MidiTrigger, HotkeyAction)> {
+        self.bindings()
+            .filter_map(|(control, action)| Some((control.parse().ok()?, action)))
+            .collect()
+    }
+}
+
+/// Kind of MIDI message a control is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MidiKind {
+    Note,
+    ControlChange,
+    ProgramChange,
+}
+
+/// A MIDI message that sets off an action, such as `note 36 channel 10`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MidiTrigger {
+    pub kind: MidiKind,
+    /// Note, controller or program number, from 0 to 127
+    pub number: u8,
+    /// Channel from 1 to 16, or `None` for any
+    pub channel: Option<u8>,
+}
+
+impl std::str::FromStr for MidiTrigger {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("'{}' is not a MIDI control, e.g. 'note 36' or 'cc 20 channel 2'", s);
+        let words: Vec<String> = s.split_whitespace().map(str::to_lowercase).collect();
+        let (kind, number, channel) = match words.as_slice() {
+            [kind, number] => (kind, number, None),
+            [kind, number, channel_word, channel] if channel_word == "channel" || channel_word == "ch" => {
+                (kind, number, Some(channel))
+            }
+            _ => return Err(invalid()),
+        };
+        let kind = match kind.as_str() {
+            "note" => MidiKind::Note,
+            "cc" | "control" => MidiKind::ControlChange,
+            "program" | "pc" => MidiKind::ProgramChange,
+            _ => return Err(invalid()),
+        };
+        let number = number.parse::<u8>().ok().filter(|number| *number <= 127).ok_or_else(invalid)?;
+        let channel = match channel {
+            Some(channel) => {
+                let channel = channel.parse::<u8>().ok().filter(|channel| (1..=16).contains(channel));
+                Some(channel.ok_or_else(invalid)?)
+            }
+            None => None,
+        };
+        Ok(Self { kind, number, channel })
+    }
+}
+
+impl fmt::Display for MidiTrigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            MidiKind::Note => "note",
+            MidiKind::ControlChange => "cc",
+            MidiKind::ProgramChange => "program",
+        };
+        write!(f, "{} {}", kind, self.number)?;
+        if let Some(channel) = self.channel {
+            write!(f, " channel {}", channel)?;
+        }
+        Ok(())
+    }
+}
+
+impl MidiTrigger {
+    /// The button press the raw MIDI `message` is, with its channel, if it is one
+    ///
+    /// Releases (note off, or a note on with velocity 0, and controllers
+    /// going below 64) aren't presses.
+    pub fn pressed(message: &[u8]) -> Option<Self> {
+        let (kind, number) = match *message {
+            [status, number, velocity, ..] if status & 0xf0 == 0x90 && velocity > 0 => (MidiKind::Note, number),
+            [status, number, value, ..] if status & 0xf0 == 0xb0 && value >= 64 => (MidiKind::ControlChange, number),
+            [status, number, ..] if status & 0xf0 == 0xc0 => (MidiKind::ProgramChange, number),
+            _ => return None,
+        };
+        Some(Self { kind, number, channel: Some((message[0] & 0x0f) + 1) })
+    }
+
+    /// Whether `press` is this control, on its channel if it has one
+    pub fn covers(&self, press: &MidiTrigger) -> bool {
+        self.kind == press.kind
+            && self.number == press.number
+            && self.channel.is_none_or(|channel| press.channel == Some(channel))
+    }
+}
+
+#[cfg(feature = "midi")]
+pub use listener::MidiListener;
+
+#[cfg(feature = "midi")]
+mod listener {
+    use super::{MidiControls, MidiTrigger};
+    use crate::error::MidiError;
+    use crate::hotkey::HotkeyAction;
+    use crossbeam_channel::Receiver;
+    use midir::{Ignore, MidiInput, MidiInputConnection};
+    use std::time::{Duration, Instant};
+
+    /// Name the recorder shows up as to the MIDI system
+    const CLIENT_NAME: &str = "meeting-recorder";
+
+    /// A connection to a MIDI input and the buttons pressed on it
+    ///
+    /// Messages arrive on a thread of the MIDI backend, so unlike the
+    /// hotkey listener this can be polled from any thread.
+    pub struct MidiListener {
+        _connection: MidiInputConnection<()>,
+        port: String,
+        presses: Receiver<MidiTrigger>,
+        triggers: Vec<(MidiTrigger, HotkeyAction)>,
+    }
+
+    impl MidiListener {
+        /// Listen to the configured input for the configured controls
+        pub fn connect(controls: &MidiControls) -> Result<Self, MidiError> {
+            let mut input = MidiInput::new(CLIENT_NAME).map_err(|e| MidiError::Unavailable(e.to_string()))?;
+            input.ignore(Ignore::All);
+            let ports: Vec<_> = input
+                .ports()
+                .into_iter()
+                .filter_map(|port| Some((input.port_name(&port).ok()?, port)))
+                .collect();
+            if ports.is_empty() {
+                return Err(MidiError::NoInputs);
+            }
+            let wanted = controls.port.as_deref().map(str::to_lowercase);
+            let (port_name, port) = ports
+                .iter()
+                .find(|(name, _)| wanted.as_ref().is_none_or(|wanted| name.to_lowercase().contains(wanted)))
+                .cloned()
+                .ok_or_else(|| MidiError::NotFound {
+                    port: controls.port.clone().unwrap_or_default(),
+                    available: ports.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>().join(", "),
+                })?;
+
+            let (tx, presses) = crossbeam_channel::unbounded();
+            let connection = input
+                .connect(
+                    &port,
+                    CLIENT_NAME,
+                    move |_, message, _| {
+                        if let Some(press) = MidiTrigger::pressed(message) {
+                            let _ = tx.send(press);
+                        }
+                    },
+                    (),
+                )
+                .map_err(|e| MidiError::Connect { port: port_name.clone(), message: e.to_string() })?;
+            Ok(Self {
+                _connection: connection,
+                port: port_name,
+                presses,
+                triggers: controls.triggers(),
+            })
+        }
+
+        /// Name of the input listened to
+        pub fn port(&self) -> &str {
+            &self.port
+        }
+
+        /// The next button pressed within `timeout`, whether or not it is a control
+        pub fn next_press(&self, timeout: Duration) -> Option<MidiTrigger> {
+            self.presses.recv_timeout(timeout).ok()
+        }
+
+        /// The action of `press`, if it is one of the controls
+        pub fn action_for(&self, press: &MidiTrigger) -> Option<HotkeyAction> {
+            self.triggers.iter().find(|(trigger, _)| trigger.covers(press)).map(|(_, action)| *action)
+        }
+
+        /// The next control pressed within `timeout`, if any
+        pub fn next(&self, timeout: Duration) -> Option<HotkeyAction> {
+            let deadline = Instant::now() + timeout;
+            while let Some(press) = self.next_press(deadline.saturating_duration_since(Instant::now())) {
+                if let Some(action) = self.action_for(&press) {
+                    return Some(action);
+                }
+            }
+            None
+        }
+
+        /// A control pressed since the last call, if any, without waiting
+        pub fn try_next(&self) -> Option<HotkeyAction> {
+            self.presses.try_iter().find_map(|press| self.action_for(&press))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_controls() {
+        let trigger: MidiTrigger = "note 36".parse().unwrap();
+        assert_eq!(trigger, MidiTrigger { kind: MidiKind::Note, number: 36, channel: None });
+        let trigger: MidiTrigger = "CC 20 channel 10".parse().unwrap();
+        assert_eq!(trigger, MidiTrigger { kind: MidiKind::ControlChange, number: 20, channel: Some(10) });
+        assert_eq!(trigger.to_string(), "cc 20 channel 10");
+        for invalid in ["note", "note 128", "cc 20 channel 0", "key 36", "note 36 channel"] {
+            assert!(invalid.parse::<MidiTrigger>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_presses_match_controls() {
+        let controls: MidiControls =
+            serde_yaml::from_str("start_stop: note 36\npause: cc 64 channel 2\nmarker: program 5\n").unwrap();
+        assert_eq!(controls.invalid_control(), None);
+        assert_eq!(controls.marker_label, "marker");
+        let action_for = |message: &[u8]| {
+            let press = MidiTrigger::pressed(message)?;
+            controls.triggers().into_iter().find(|(trigger, _)| trigger.covers(&press)).map(|(_, action)| action)
+        };
+
+        assert_eq!(action_for(&[0x90, 36, 100]), Some(HotkeyAction::StartStop));
+        assert_eq!(action_for(&[0x99, 36, 100]), Some(HotkeyAction::StartStop));
+        // Note off, and note on with velocity 0, are releases
+        assert_eq!(action_for(&[0x80, 36, 0]), None);
+        assert_eq!(action_for(&[0x90, 36, 0]), None);
+        assert_eq!(action_for(&[0xb1, 64, 127]), Some(HotkeyAction::Pause));
+        assert_eq!(action_for(&[0xb1, 64, 0]), None);
+        // Channel 3
+        assert_eq!(action_for(&[0xb2, 64, 127]), None);
+        assert_eq!(action_for(&[0xc0, 5]), Some(HotkeyAction::Marker));
+        assert_eq!(MidiTrigger::pressed(&[0x99, 36, 100]).unwrap().to_string(), "note 36 channel 10");
+    }
+}
@@ -0,0 +1,461 @@
+//! Mixing of the microphone and system audio into one stereo stream
+//!
+//! Feed each source's chunks to a [`Mixer`] as they arrive, in the source's
+//! own channel count and sample rate, then pull out whatever can be mixed.
+//! The recorder drives one of these on its mixer thread; it is public so the
+//! same logic can be reused and tested without audio devices.
+
+use std::time::Duration;
+
+use crate::backpressure::BacklogPolicy;
+use crate::dsp::{self, LevelMeter, Levels};
+use crate::summary::{AudioSummary, SummaryMeter};
+
+/// How far a source may run ahead of the other before it is written on its own
+///
+/// Two live sources deliver their chunks at slightly different moments, so
+/// one is usually a little ahead; it waits this long for the other to catch up.
+pub const LONE_SOURCE_WAIT: Duration = Duration::from_millis(200);
+
+/// One of the mixer's inputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Microphone,
+    SystemAudio,
+}
+
+/// Peak and RMS levels of each source and of the mix
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MixLevels {
+    pub mic: Levels,
+    /// `None` when the mixer has no system audio source
+    pub sys: Option<Levels>,
+    pub mix: Levels,
+}
+
+/// Channel count and sample rate of a source's chunks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceFormat {
+    pub channels: u16,
+    pub sample_rate: u32,
+}
+
+/// Mixes two sources into interleaved stereo at a fixed output rate
+///
+/// Samples present for both sources are mixed with saturating addition. A
+/// source that gets ahead of the other waits up to [`LONE_SOURCE_WAIT`] for
+/// it, and only beyond that passes through on its own, so a stalled source
+/// doesn't stall the output. Once a source has [ended](Self::end), the other
+/// passes through right away.
+pub struct Mixer {
+    mic: SourceInput,
+    sys: SourceInput,
+    has_sys: bool,
+    mic_ended: bool,
+    sys_ended: bool,
+    /// [`LONE_SOURCE_WAIT`] in stereo samples at the output rate
+    lone_wait: usize,
+    max_backlog: Option<usize>,
+    backlog_policy: BacklogPolicy,
+    mix_meter: LevelMeter,
+    /// Mix levels since the last [`take_levels`](Self::take_levels)
+    mix_recent: LevelMeter,
+}
+
+impl Mixer {
+    /// Mix `mic` and, if present, `sys` into stereo at `output_rate`
+    pub fn new(mic: SourceFormat, sys: Option<SourceFormat>, output_rate: u32) -> Self {
+        Self {
+            mic: SourceInput::new(mic, output_rate),
+            sys: SourceInput::new(sys.unwrap_or(SourceFormat { channels: 2, sample_rate: output_rate }), output_rate),
+            has_sys: sys.is_some(),
+            mic_ended: false,
+            sys_ended: sys.is_none(),
+            lone_wait: (LONE_SOURCE_WAIT.as_secs_f64() * output_rate as f64) as usize * 2,
+            max_backlog: None,
+            backlog_policy: BacklogPolicy::default(),
+            mix_meter: LevelMeter::default(),
+            mix_recent: LevelMeter::default(),
+        }
+    }
+
+    /// Cap how many samples one source may run ahead of the other
+    ///
+    /// Anything beyond `max_backlog` is mixed with silence or dropped, as
+    /// `policy` says. `None` (the default) lets a source run ahead without bound.
+    pub fn with_backlog_limit(mut self, max_backlog: Option<usize>, policy: BacklogPolicy) -> Self {
+        self.max_backlog = max_backlog;
+        self.backlog_policy = policy;
+        self
+    }
+
+    fn input(&mut self, source: Source) -> &mut SourceInput {
+        match source {
+            Source::Microphone => &mut self.mic,
+            Source::SystemAudio => &mut self.sys,
+        }
+    }
+
+    /// Queue a chunk of samples from `source`, in its own format
+    pub fn push(&mut self, source: Source, samples: &[i16]) {
+        self.input(source).push(samples);
+    }
+
+    /// Note that `source` will deliver nothing more, so the other no longer waits for it
+    pub fn end(&mut self, source: Source) {
+        match source {
+            Source::Microphone => self.mic_ended = true,
+            Source::SystemAudio => self.sys_ended = true,
+        }
+    }
+
+    /// Stereo samples from `source` waiting to be mixed
+    pub fn pending(&self, source: Source) -> usize {
+        match source {
+            Source::Microphone => self.mic.buffer.len(),
+            Source::SystemAudio => self.sys.buffer.len(),
+        }
+    }
+
+    /// Append as much mixed audio as is ready to `out`
+    ///
+    /// Returns how many samples the backlog limit trimmed from a source that
+    /// ran too far ahead, 0 if none.
+    pub fn mix(&mut self, out: &mut Vec<i16>) -> u64 {
+        let start = out.len();
+        let trimmed = cap_backlog(
+            &mut self.mic.buffer,
+            &mut self.sys.buffer,
+            self.max_backlog,
+            self.backlog_policy,
+            out,
+        );
+        // A lone source is held back for as long as the other may still catch up
+        let mic_wait = if self.sys_ended { 0 } else { self.lone_wait };
+        let sys_wait = if self.mic_ended { 0 } else { self.lone_wait };
+        mix_available(&mut self.mic.buffer, &mut self.sys.buffer, (mic_wait, sys_wait), out);
+        self.mix_meter.add(&out[start..]);
+        self.mix_recent.add(&out[start..]);
+        trimmed
+    }
+
+    /// Append everything still pending to `out`, once both sources have stopped
+    ///
+    /// A source that ended early is padded with silence.
+    pub fn finish(&mut self, out: &mut Vec<i16>) {
+        let start = out.len();
+        mix_remaining(&self.mic.buffer, &self.sys.buffer, out);
+        self.mix_meter.add(&out[start..]);
+        self.mix_recent.add(&out[start..]);
+        self.clear();
+    }
+
+    /// Discard everything pending without mixing it
+    pub fn clear(&mut self) {
+        self.mic.buffer.clear();
+        self.sys.buffer.clear();
+    }
+
+    /// Levels since the last call, for live meters, starting a new measurement
+    pub fn take_levels(&mut self) -> MixLevels {
+        let sys = self.sys.recent.take();
+        MixLevels {
+            mic: self.mic.recent.take(),
+            sys: self.has_sys.then_some(sys),
+            mix: self.mix_recent.take(),
+        }
+    }
+
+    /// Levels of everything pushed for `source` so far
+    pub fn levels(&self, source: Source) -> Levels {
+        match source {
+            Source::Microphone => self.mic.meter.levels(),
+            Source::SystemAudio => self.sys.meter.levels(),
+        }
+    }
+
+    /// Peak, loudness, clipping and silence of everything pushed for `source` so far
+    pub fn summary(&self, source: Source) -> AudioSummary {
+        match source {
+            Source::Microphone => self.mic.summary.summary(),
+            Source::SystemAudio => self.sys.summary.summary(),
+        }
+    }
+
+    /// Levels of everything mixed so far
+    pub fn mix_levels(&self) -> Levels {
+        self.mix_meter.levels()
+    }
+}
+
+/// Converts one source's chunks to stereo at the output sample rate
+struct SourceInput {
+    channels: u16,
+    resampler: Option<dsp::StereoResampler>,
+    scratch: Vec<i16>,
+    /// Stereo samples waiting to be mixed
+    buffer: Vec<i16>,
+    /// Levels since the last [`Mixer::take_levels`]
+    recent: LevelMeter,
+    /// Levels over the whole session
+    meter: LevelMeter,
+    summary: SummaryMeter,
+}
+
+impl SourceInput {
+    fn new(format: SourceFormat, output_rate: u32) -> Self {
+        Self {
+            channels: format.channels,
+            resampler: (format.sample_rate != output_rate)
+                .then(|| dsp::StereoResampler::new(format.sample_rate, output_rate)),
+            scratch: Vec::new(),
+            buffer: Vec::new(),
+            recent: LevelMeter::default(),
+            meter: LevelMeter::default(),
+            summary: SummaryMeter::new(format.channels, format.sample_rate),
+        }
+    }
+
+    /// Append a chunk of source samples to the stereo buffer
+    fn push(&mut self, samples: &[i16]) {
+        self.recent.add(samples);
+        self.meter.add(samples);
+        self.summary.add(samples);
+        match &mut self.resampler {
+            None => dsp::append_stereo(&mut self.buffer, samples, self.channels),
+            Some(resampler) => {
+                self.scratch.clear();
+                dsp::append_stereo(&mut self.scratch, samples, self.channels);
+                resampler.process(&self.scratch, &mut self.buffer);
+            }
+        }
+    }
+}
+
+/// Mix as much audio as is ready, appending it to `out`
+///
+/// `wait` is how many samples of the microphone and of system audio to keep
+/// back when that source is the only one with anything pending.
+fn mix_available(mic_buffer: &mut Vec<i16>, sys_buffer: &mut Vec<i16>, wait: (usize, usize), out: &mut Vec<i16>) {
+    // Mix left with left, right with right, in whole stereo pairs
+    let min_len = mic_buffer.len().min(sys_buffer.len());
+    if min_len >= 2 {
+        let len = min_len / 2 * 2;
+        let start = out.len();
+        out.resize(start + len, 0);
+        dsp::mix_saturating(&mic_buffer[..len], &sys_buffer[..len], &mut out[start..]);
+        mic_buffer.drain(0..len);
+        sys_buffer.drain(0..len);
+    }
+
+    // If one buffer has run too far ahead of the other, pass its lead through
+    let single = if sys_buffer.is_empty() {
+        Some((mic_buffer, wait.0))
+    } else if mic_buffer.is_empty() {
+        Some((sys_buffer, wait.1))
+    } else {
+        None
+    };
+    if let Some((buffer, wait)) = single {
+        let len = buffer.len().saturating_sub(wait) / 2 * 2;
+        out.extend(buffer.drain(0..len));
+    }
+}
+
+/// Trim a buffer that has run more than `max_backlog` samples ahead of the other
+///
+/// Returns how many samples were cut from the front; the backlog policy
+/// decides whether they are appended to `out` or dropped.
+fn cap_backlog(
+    mic_buffer: &mut Vec<i16>,
+    sys_buffer: &mut Vec<i16>,
+    max_backlog: Option<usize>,
+    policy: BacklogPolicy,
+    out: &mut Vec<i16>,
+) -> u64 {
+    let Some(max_backlog) = max_backlog else {
+        return 0;
+    };
+    let (ahead, behind) = if mic_buffer.len() >= sys_buffer.len() {
+        (mic_buffer, sys_buffer)
+    } else {
+        (sys_buffer, mic_buffer)
+    };
+
+    // Only the lead over the other source is backlog; the rest gets mixed.
+    // Cut whole stereo pairs so channels stay aligned
+    let lead = ahead.len() - behind.len();
+    let excess = lead.saturating_sub(max_backlog).div_ceil(2) * 2;
+    if excess == 0 {
+        return 0;
+    }
+
+    let cut = ahead.drain(0..excess);
+    if policy == BacklogPolicy::MixWithSilence {
+        out.extend(cut);
+    }
+    excess as u64
+}
+
+/// Mix everything left in the buffers once both sources have stopped
+fn mix_remaining(mic_buffer: &[i16], sys_buffer: &[i16], out: &mut Vec<i16>) {
+    // Mix any remaining pairs, treating a missing sample as silence
+    let pairs = mic_buffer.len().max(sys_buffer.len()) / 2;
+    out.extend((0..pairs * 2).map(|i| {
+        let mic = mic_buffer.get(i).copied().unwrap_or(0);
+        let sys = sys_buffer.get(i).copied().unwrap_or(0);
+        mic.saturating_add(sys)
+    }));
+
+    // Pass through any remaining unpaired samples
+    out.extend(mic_buffer.iter().skip(pairs * 2).chain(sys_buffer.iter().skip(pairs * 2)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEREO_48K: SourceFormat = SourceFormat { channels: 2, sample_rate: 48000 };
+
+    #[test]
+    fn test_cap_backlog_trims_only_the_lead() {
+        let mut out = Vec::new();
+        let mut mic = vec![1i16; 100];
+        let mut sys = vec![2i16; 20];
+        assert_eq!(cap_backlog(&mut mic, &mut sys, Some(50), BacklogPolicy::DropOldest, &mut out), 30);
+        assert_eq!((mic.len(), sys.len(), out.len()), (70, 20, 0));
+
+        assert_eq!(cap_backlog(&mut sys, &mut mic, Some(20), BacklogPolicy::MixWithSilence, &mut out), 30);
+        assert_eq!((mic.len(), sys.len(), out.len()), (40, 20, 30));
+
+        assert_eq!(cap_backlog(&mut mic, &mut sys, None, BacklogPolicy::DropOldest, &mut out), 0);
+    }
+
+    #[test]
+    fn test_mixing_passes_through_a_lone_source() {
+        let mut out = Vec::new();
+        let mut mic = vec![100i16; 6];
+        let mut sys = vec![20i16; 4];
+        mix_available(&mut mic, &mut sys, (0, 0), &mut out);
+        assert_eq!(out, vec![120, 120, 120, 120, 100, 100]);
+        assert!(mic.is_empty() && sys.is_empty());
+
+        // An odd leftover sample waits for its pair
+        let mut mic = vec![7i16; 3];
+        mix_available(&mut mic, &mut sys, (0, 0), &mut out);
+        assert_eq!(out.len(), 8);
+        assert_eq!(mic, vec![7]);
+
+        // Only the lead beyond the wait passes through
+        let mut mic = vec![7i16; 10];
+        mix_available(&mut mic, &mut sys, (4, 0), &mut out);
+        assert_eq!(out.len(), 14);
+        assert_eq!(mic.len(), 4);
+    }
+
+    #[test]
+    fn test_mixer_sums_sources_that_arrive_out_of_step() {
+        let mut mixer = Mixer::new(STEREO_48K, Some(STEREO_48K), 48000);
+        let mut out = Vec::new();
+        // The microphone runs up to 50 ms ahead, as two devices' callbacks do
+        for _ in 0..5 {
+            mixer.push(Source::Microphone, &[1000; 960]);
+            mixer.mix(&mut out);
+        }
+        for _ in 0..95 {
+            mixer.push(Source::Microphone, &[1000; 960]);
+            mixer.mix(&mut out);
+            mixer.push(Source::SystemAudio, &[2000; 960]);
+            mixer.mix(&mut out);
+        }
+        for _ in 0..5 {
+            mixer.push(Source::SystemAudio, &[2000; 960]);
+            mixer.mix(&mut out);
+        }
+        mixer.end(Source::Microphone);
+        mixer.end(Source::SystemAudio);
+        mixer.finish(&mut out);
+        assert_eq!(out.len(), 100 * 960);
+        assert!(out.iter().all(|&sample| sample == 3000));
+    }
+
+    #[test]
+    fn test_mixer_passes_through_once_the_other_source_ends() {
+        let mut mixer = Mixer::new(STEREO_48K, Some(STEREO_48K), 48000);
+        mixer.push(Source::Microphone, &[1; 8]);
+        let mut out = Vec::new();
+        mixer.mix(&mut out);
+        assert!(out.is_empty());
+
+        mixer.end(Source::SystemAudio);
+        mixer.mix(&mut out);
+        assert_eq!(out, vec![1; 8]);
+    }
+
+    #[test]
+    fn test_mixer_upmixes_and_mixes_sources() {
+        let mono = SourceFormat { channels: 1, sample_rate: 48000 };
+        let mut mixer = Mixer::new(mono, Some(STEREO_48K), 48000);
+        mixer.push(Source::Microphone, &[1000, 2000]);
+        mixer.push(Source::SystemAudio, &[3000, 4000, i16::MAX, 1]);
+
+        let mut out = Vec::new();
+        assert_eq!(mixer.mix(&mut out), 0);
+        assert_eq!(out, vec![4000, 5000, i16::MAX, 2001]);
+        assert_eq!(mixer.pending(Source::Microphone), 0);
+        assert_eq!(mixer.mix_levels().peak, 1.0);
+    }
+
+    #[test]
+    fn test_mixer_finish_pads_the_shorter_source() {
+        let mut mixer = Mixer::new(STEREO_48K, Some(STEREO_48K), 48000);
+        mixer.push(Source::Microphone, &[10, 20, 30, 40]);
+        mixer.push(Source::SystemAudio, &[1, 2]);
+
+        let mut out = Vec::new();
+        mixer.mix(&mut out);
+        // The microphone's lead waits for system audio to catch up
+        assert_eq!(out, vec![11, 22]);
+
+        mixer.push(Source::Microphone, &[5, 6]);
+        mixer.push(Source::SystemAudio, &[7, 8, 9, 10, 11, 12]);
+        out.clear();
+        mixer.finish(&mut out);
+        assert_eq!(out, vec![37, 48, 14, 16, 11, 12]);
+        assert_eq!(mixer.pending(Source::SystemAudio), 0);
+    }
+
+    #[test]
+    fn test_mixer_resamples_to_the_output_rate() {
+        let mut mixer = Mixer::new(SourceFormat { channels: 2, sample_rate: 24000 }, None, 48000);
+        mixer.push(Source::Microphone, &[100; 480]);
+        let mut out = Vec::new();
+        mixer.mix(&mut out);
+        // 10 ms in, about 10 ms out; the resampler holds back its last frame
+        assert!((956..=960).contains(&out.len()), "got {} samples", out.len());
+        let levels = mixer.take_levels();
+        assert_eq!(levels.mic.peak, 100.0 / i16::MAX as f32);
+        assert_eq!(levels.sys, None);
+        assert!(levels.mix.rms > 0.0);
+        assert_eq!(mixer.take_levels(), MixLevels::default());
+    }
+
+    #[test]
+    fn test_backlog_limit_applies_when_mixing() {
+        let mut mixer = Mixer::new(STEREO_48K, Some(STEREO_48K), 48000)
+            .with_backlog_limit(Some(4), BacklogPolicy::DropOldest);
+        mixer.push(Source::Microphone, &[1; 10]);
+        mixer.push(Source::SystemAudio, &[2; 2]);
+
+        let mut out = Vec::new();
+        assert_eq!(mixer.mix(&mut out), 4);
+        assert_eq!(out, vec![3, 3]);
+        assert_eq!(mixer.pending(Source::Microphone), 4);
+
+        // Once system audio has ended, the rest of the microphone passes through
+        mixer.end(Source::SystemAudio);
+        mixer.mix(&mut out);
+        assert_eq!(out, vec![3, 3, 1, 1, 1, 1]);
+        assert_eq!(mixer.pending(Source::Microphone), 0);
+    }
+}
@@ -0,0 +1,89 @@
+//! Noticing a microphone that records nothing
+//!
+//! A hardware-muted headset still delivers audio, just silence, so nothing
+//! fails and the recording looks fine until it is played back. While system
+//! audio is playing, a microphone that stays below a threshold for a while
+//! is most likely muted, and the recorder warns about it once per stretch.
+
+use std::time::Duration;
+
+use crate::dsp::Levels;
+
+/// Tracks how long the microphone has been silent while system audio played
+#[derive(Debug, Clone)]
+pub struct MuteDetector {
+    /// Peak level, from 0.0 to 1.0, below which a source counts as silent
+    threshold: f32,
+    /// Silence that triggers the alert
+    after: Duration,
+    /// Silence so far while system audio was playing
+    silent_for: Duration,
+    /// Whether this stretch of silence was already reported
+    alerted: bool,
+}
+
+impl MuteDetector {
+    /// Alert after `after` of microphone levels below `threshold_db` dBFS
+    pub fn new(threshold_db: f32, after: Duration) -> Self {
+        Self {
+            threshold: 10f32.powf(threshold_db / 20.0),
+            after,
+            silent_for: Duration::ZERO,
+            alerted: false,
+        }
+    }
+
+    /// Take the levels of the last `elapsed`, returning how long the
+    /// microphone has been silent when that first reaches the alert time
+    ///
+    /// Time when both sources are quiet doesn't count, but doesn't reset the
+    /// silence either: only sound from the microphone does.
+    pub fn update(&mut self, mic: Levels, sys: Option<Levels>, elapsed: Duration) -> Option<Duration> {
+        if mic.peak >= self.threshold {
+            self.silent_for = Duration::ZERO;
+            self.alerted = false;
+            return None;
+        }
+        if sys.is_none_or(|sys| sys.peak < self.threshold) {
+            return None;
+        }
+        self.silent_for += elapsed;
+        if self.silent_for >= self.after && !self.alerted {
+            self.alerted = true;
+            return Some(self.silent_for);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn levels(peak: f32) -> Levels {
+        Levels { peak, rms: peak / 2.0 }
+    }
+
+    #[test]
+    fn test_alerts_once_per_stretch_of_silence() {
+        let mut detector = MuteDetector::new(-50.0, Duration::from_secs(2));
+        let step = Duration::from_secs(1);
+        let quiet = levels(0.001);
+        let loud = levels(0.5);
+
+        assert_eq!(detector.update(quiet, Some(loud), step), None);
+        // Both quiet: doesn't count, doesn't reset
+        assert_eq!(detector.update(quiet, Some(quiet), step), None);
+        assert_eq!(detector.update(quiet, Some(loud), step), Some(Duration::from_secs(2)));
+        assert_eq!(detector.update(quiet, Some(loud), step), None);
+
+        // Speaking resets it
+        assert_eq!(detector.update(loud, Some(loud), step), None);
+        assert_eq!(detector.update(quiet, Some(loud), step), None);
+        assert_eq!(detector.update(quiet, Some(loud), step), Some(Duration::from_secs(2)));
+
+        // Without system audio there is nothing to compare against
+        let mut detector = MuteDetector::new(-50.0, Duration::from_secs(1));
+        assert_eq!(detector.update(quiet, None, Duration::from_secs(5)), None);
+    }
+}
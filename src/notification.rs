@@ -0,0 +1,174 @@
+//! Desktop notifications
+//!
+//! With the `notifications` feature and `notifications: true`, the recorder
+//! shows a native notification (the freedesktop notification service on
+//! Linux, Notification Center on macOS, a toast on Windows) when recording
+//! starts, when it is saved or fails, and on problems along the way such as
+//! low disk space or a microphone that seems muted. The terminal is usually
+//! hidden behind the meeting window; these are not.
+
+use std::path::Path;
+
+use crate::error::RecorderError;
+use crate::events::RecorderEvent;
+use crate::recorder::{RecordingResult, StopReason};
+use crate::webhook::duration_text;
+
+/// Title and text of a notification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub summary: String,
+    pub body: String,
+}
+
+impl Notification {
+    fn new(summary: impl Into<String>, body: impl Into<String>) -> Self {
+        Self {
+            summary: summary.into(),
+            body: body.into(),
+        }
+    }
+}
+
+/// What to show for `event`, if anything; the end of a session is announced
+/// from its result instead
+pub fn event_notification(event: &RecorderEvent) -> Option<Notification> {
+    match event {
+        RecorderEvent::Started { path, .. } => Some(Notification::new(
+            "Recording started",
+            path.as_deref().map_or_else(|| "Recording without a WAV file".to_string(), file_name),
+        )),
+        RecorderEvent::MicSilent { duration } => Some(Notification::new(
+            "Is your microphone muted?",
+            format!("It has been silent for {} while system audio is playing", duration_text(*duration)),
+        )),
+        RecorderEvent::Error { message } => Some(Notification::new("Recording problem", message.clone())),
+        _ => None,
+    }
+}
+
+/// What to show once a session has ended
+pub fn outcome_notification(outcome: &Result<RecordingResult, RecorderError>) -> Notification {
+    match outcome {
+        Ok(result) => {
+            let file = result.filename.as_deref().map_or_else(|| "no WAV file".to_string(), |path| file_name(Path::new(path)));
+            let mut body = format!("{} ({})", file, duration_text(result.duration));
+            let reason = match result.stop_reason {
+                StopReason::LowDiskSpace => Some("free disk space ran low"),
+                StopReason::DeviceLost => Some("a device was disconnected"),
+                StopReason::WriteError => Some("the file could not be written"),
+                StopReason::Requested | StopReason::TimeLimit | StopReason::EndOfInput => None,
+            };
+            if let Some(reason) = reason {
+                body.push_str(&format!("\nStopped early: {}", reason));
+                return Notification::new("Recording stopped", body);
+            }
+            Notification::new("Recording saved", body)
+        }
+        Err(e @ RecorderError::Incomplete(_)) => Notification::new("Recording stopped", e.to_string()),
+        Err(e) => Notification::new("Recording failed", e.to_string()),
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().into_owned()
+}
+
+#[cfg(feature = "notifications")]
+pub use notifier::Notifier;
+
+#[cfg(feature = "notifications")]
+mod notifier {
+    use super::{event_notification, outcome_notification, Notification};
+    use crate::error::RecorderError;
+    use crate::events::RecorderEvent;
+    use crate::recorder::RecordingResult;
+    use crossbeam_channel::{Receiver, Sender};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    /// Problems reported within this long of the last one are left to the console
+    const ERROR_INTERVAL: Duration = Duration::from_secs(60);
+
+    /// Shows the notifications of one session from a thread of its own
+    pub struct Notifier {
+        outcome: Sender<Notification>,
+        handle: thread::JoinHandle<()>,
+    }
+
+    impl Notifier {
+        /// Announce `events` as they arrive
+        pub fn spawn(events: Receiver<RecorderEvent>) -> Self {
+            let (outcome, outcome_rx) = crossbeam_channel::bounded::<Notification>(1);
+            let handle = thread::spawn(move || {
+                let mut last_error: Option<Instant> = None;
+                loop {
+                    crossbeam_channel::select! {
+                        recv(events) -> event => {
+                            let Ok(event) = event else { continue };
+                            if let RecorderEvent::Error { .. } = event {
+                                if last_error.is_some_and(|last| last.elapsed() < ERROR_INTERVAL) {
+                                    continue;
+                                }
+                                last_error = Some(Instant::now());
+                            }
+                            if let Some(notification) = event_notification(&event) {
+                                show(&notification);
+                            }
+                        }
+                        recv(outcome_rx) -> notification => {
+                            if let Ok(notification) = notification {
+                                show(&notification);
+                            }
+                            return;
+                        }
+                    }
+                }
+            });
+            Self { outcome, handle }
+        }
+
+        /// Announce how the session ended and wait for that to be shown
+        pub fn finish(self, outcome: &Result<RecordingResult, RecorderError>) {
+            let _ = self.outcome.send(outcome_notification(outcome));
+            let _ = self.handle.join();
+        }
+    }
+
+    fn show(notification: &Notification) {
+        let shown = notify_rust::Notification::new()
+            .appname("Meeting Recorder")
+            .summary(&notification.summary)
+            .body(&notification.body)
+            .show();
+        if let Err(e) = shown {
+            console_err!("Warning: could not show a desktop notification: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    #[test]
+    fn test_event_notifications() {
+        let started = RecorderEvent::Started {
+            id: uuid::Uuid::nil(),
+            path: Some(PathBuf::from("/recordings/meeting_1700000000.wav")),
+        };
+        assert_eq!(
+            event_notification(&started),
+            Some(Notification::new("Recording started", "meeting_1700000000.wav"))
+        );
+        let silent = RecorderEvent::MicSilent { duration: Duration::from_secs(60) };
+        assert_eq!(
+            event_notification(&silent).unwrap().body,
+            "It has been silent for 1m 00s while system audio is playing"
+        );
+        assert_eq!(event_notification(&RecorderEvent::Dropout { source: "microphone", count: 1 }), None);
+        assert_eq!(outcome_notification(&Err(RecorderError::Busy)).summary, "Recording failed");
+    }
+}
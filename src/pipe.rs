@@ -0,0 +1,192 @@
+//! Recording to stdout, for piping into another program
+//!
+//! With `--output -` the mix goes to stdout as it is recorded: signed 16-bit
+//! little-endian samples, interleaved, at the session's sample rate and
+//! channel count, which are printed to stderr as recording starts. With
+//! `--wav-header` the samples follow a WAV header whose sizes are left at
+//! their maximum, since the length isn't known yet. Everything the recorder
+//! would otherwise print goes to stderr.
+
+use crossbeam_channel::{Sender, TrySendError};
+use hound::WavSpec;
+use meeting_recorder::sink::{Sink, SinkError};
+use std::fs::File;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// Chunks of audio queued for the reader before new ones are dropped
+const QUEUE_CHUNKS: usize = 1000;
+
+/// Take stdout for audio, pointing the process's own stdout at stderr
+pub fn take_stdout() -> io::Result<File> {
+    io::stdout().flush()?;
+    platform::take_stdout()
+}
+
+/// Sink writing the mix to the original stdout from a thread of its own
+///
+/// If the reader falls behind, audio is dropped from the pipe but never
+/// from the recording. When the reader goes away, recording stops.
+pub struct PipeSink {
+    sender: Option<Sender<Vec<u8>>>,
+    thread: Option<JoinHandle<io::Result<()>>>,
+    dropped: u64,
+}
+
+impl PipeSink {
+    /// Start writing audio of `spec` to `out`, clearing `running` if it is closed
+    pub fn spawn(mut out: File, spec: WavSpec, wav_header: bool, running: Arc<AtomicBool>) -> io::Result<Self> {
+        let (sender, receiver) = crossbeam_channel::bounded::<Vec<u8>>(QUEUE_CHUNKS);
+        let thread = thread::Builder::new().name("stdout-writer".to_string()).spawn(move || {
+            let result = (|| {
+                if wav_header {
+                    out.write_all(&streaming_wav_header(&spec))?;
+                }
+                for bytes in receiver {
+                    out.write_all(&bytes)?;
+                }
+                out.flush()
+            })();
+            if let Err(e) = &result {
+                if e.kind() == io::ErrorKind::BrokenPipe {
+                    eprintln!("\nThe program reading the audio has exited; stopping recording...");
+                    running.store(false, Ordering::SeqCst);
+                }
+            }
+            result
+        })?;
+        Ok(Self { sender: Some(sender), thread: Some(thread), dropped: 0 })
+    }
+
+    /// Wait for the writer thread, returning how it ended
+    fn join(&mut self) -> Result<(), SinkError> {
+        self.sender = None;
+        match self.thread.take().map(JoinHandle::join) {
+            Some(Ok(result)) => Ok(result?),
+            Some(Err(_)) => Err("stdout writer thread panicked".into()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Sink for PipeSink {
+    fn write_frames(&mut self, samples: &[i16]) -> Result<(), SinkError> {
+        let Some(sender) = &self.sender else {
+            return Ok(());
+        };
+        let bytes = samples.iter().flat_map(|sample| sample.to_le_bytes()).collect();
+        match sender.try_send(bytes) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                if self.dropped == 0 {
+                    eprintln!("\nWarning: the program reading the audio is falling behind; dropping audio from stdout");
+                }
+                self.dropped += 1;
+                Ok(())
+            }
+            // The writer has stopped; surface why
+            Err(TrySendError::Disconnected(_)) => {
+                self.join()?;
+                Err("stdout closed".into())
+            }
+        }
+    }
+
+    fn finalize(mut self: Box<Self>) -> Result<(), SinkError> {
+        if self.dropped > 0 {
+            eprintln!("Warning: {} chunk(s) of audio were dropped from stdout", self.dropped);
+        }
+        self.join()
+    }
+}
+
+/// WAV header for a stream of unknown length, with both sizes at their maximum
+fn streaming_wav_header(spec: &WavSpec) -> Vec<u8> {
+    let block_align = spec.channels * spec.bits_per_sample / 8;
+    let mut header = Vec::with_capacity(44);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&u32::MAX.to_le_bytes());
+    header.extend_from_slice(b"WAVEfmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&spec.channels.to_le_bytes());
+    header.extend_from_slice(&spec.sample_rate.to_le_bytes());
+    header.extend_from_slice(&(spec.sample_rate * block_align as u32).to_le_bytes());
+    header.extend_from_slice(&block_align.to_le_bytes());
+    header.extend_from_slice(&spec.bits_per_sample.to_le_bytes());
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&u32::MAX.to_le_bytes());
+    header
+}
+
+#[cfg(unix)]
+mod platform {
+    use std::fs::File;
+    use std::io;
+    use std::os::fd::AsFd;
+
+    pub fn take_stdout() -> io::Result<File> {
+        let audio = io::stdout().as_fd().try_clone_to_owned()?;
+        if unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(File::from(audio))
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::fs::File;
+    use std::io;
+    use std::os::windows::io::AsHandle;
+    use windows_sys::Win32::System::Console::{GetStdHandle, SetStdHandle, STD_ERROR_HANDLE, STD_OUTPUT_HANDLE};
+
+    pub fn take_stdout() -> io::Result<File> {
+        let audio = io::stdout().as_handle().try_clone_to_owned()?;
+        // The standard library looks the handle up on every write
+        if unsafe { SetStdHandle(STD_OUTPUT_HANDLE, GetStdHandle(STD_ERROR_HANDLE)) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(File::from(audio))
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod platform {
+    use std::fs::File;
+    use std::io;
+
+    pub fn take_stdout() -> io::Result<File> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "recording to stdout is not supported on this platform"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Seek};
+
+    #[test]
+    fn test_pipe_sink_writes_a_readable_stream() {
+        let spec = WavSpec { channels: 2, sample_rate: 16000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        let mut file = tempfile::tempfile().unwrap();
+        let running = Arc::new(AtomicBool::new(true));
+        let mut sink: Box<dyn Sink> = Box::new(PipeSink::spawn(file.try_clone().unwrap(), spec, true, running).unwrap());
+        sink.write_frames(&[1, -1, 256, -256]).unwrap();
+        sink.finalize().unwrap();
+
+        let mut bytes = Vec::new();
+        file.rewind().unwrap();
+        file.read_to_end(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 44 + 8);
+        assert_eq!(&bytes[44..], &[1, 0, 255, 255, 0, 1, 0, 255]);
+        assert_eq!(&bytes[..4], b"RIFF");
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(bytes[22..24], 2u16.to_le_bytes());
+        assert_eq!(bytes[24..28], 16000u32.to_le_bytes());
+        assert_eq!(bytes[28..32], 64000u32.to_le_bytes());
+        assert_eq!(&bytes[36..40], b"data");
+    }
+}
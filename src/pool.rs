@@ -0,0 +1,77 @@
+use crossbeam_channel::{Receiver, Sender};
+
+/// Number of buffers allocated up front so the first callbacks don't allocate
+const PREALLOCATED_BUFFERS: usize = 16;
+
+/// Pool of reusable sample buffers shared between capture callbacks and the mixer
+///
+/// Callbacks take a cleared buffer, fill it and send it to the mixer, which
+/// hands it back once consumed. Buffers keep their capacity across trips, so
+/// once the pool has warmed up no heap allocation happens per callback.
+#[derive(Clone)]
+pub struct BufferPool {
+    free_tx: Sender<Vec<i16>>,
+    free_rx: Receiver<Vec<i16>>,
+    buffer_len: usize,
+}
+
+impl BufferPool {
+    /// Create a pool retaining at most `max_buffers` idle buffers of `buffer_len` samples
+    pub fn new(max_buffers: usize, buffer_len: usize) -> Self {
+        let (free_tx, free_rx) = crossbeam_channel::bounded(max_buffers.max(1));
+        for _ in 0..max_buffers.min(PREALLOCATED_BUFFERS) {
+            let _ = free_tx.try_send(Vec::with_capacity(buffer_len));
+        }
+        Self { free_tx, free_rx, buffer_len }
+    }
+
+    /// Take an empty buffer, allocating only if the pool has run dry
+    pub fn take(&self) -> Vec<i16> {
+        self.free_rx
+            .try_recv()
+            .unwrap_or_else(|_| Vec::with_capacity(self.buffer_len))
+    }
+
+    /// Return a buffer to the pool; it is dropped if the pool is already full
+    pub fn recycle(&self, mut buffer: Vec<i16>) {
+        buffer.clear();
+        let _ = self.free_tx.try_send(buffer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recycled_buffer_is_reused() {
+        let pool = BufferPool::new(1, 64);
+        let mut buffer = pool.take();
+        buffer.extend_from_slice(&[1, 2, 3]);
+        let ptr = buffer.as_ptr();
+        pool.recycle(buffer);
+
+        let reused = pool.take();
+        assert!(reused.is_empty());
+        assert_eq!(reused.as_ptr(), ptr);
+        assert!(reused.capacity() >= 64);
+    }
+
+    #[test]
+    fn test_take_allocates_when_empty() {
+        let pool = BufferPool::new(1, 32);
+        let first = pool.take();
+        let second = pool.take();
+        assert!(first.capacity() >= 32);
+        assert!(second.capacity() >= 32);
+    }
+
+    #[test]
+    fn test_grown_buffer_keeps_capacity() {
+        let pool = BufferPool::new(1, 4);
+        let mut buffer = pool.take();
+        buffer.extend(std::iter::repeat_n(0, 1024));
+        pool.recycle(buffer);
+        assert!(pool.take().capacity() >= 1024);
+    }
+}
@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+
+/// Scheduling priority requested for the capture callback and mixer threads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThreadPriority {
+    /// Leave the OS default scheduling untouched
+    #[default]
+    Normal,
+    /// Raise the thread above normal time-sharing priority
+    High,
+    /// Use the platform's real-time scheduling class (may need privileges)
+    Realtime,
+}
+
+/// Apply `priority` to the calling thread
+///
+/// Failures are returned rather than fatal: recording still works at normal
+/// priority, it is just more prone to dropouts on a loaded machine.
+pub fn promote_current_thread(priority: ThreadPriority) -> Result<(), String> {
+    match priority {
+        ThreadPriority::Normal => Ok(()),
+        ThreadPriority::High | ThreadPriority::Realtime => platform::promote(priority),
+    }
+}
+
+#[cfg(unix)]
+mod platform {
+    use super::ThreadPriority;
+    use std::io;
+
+    /// Nice value used for `ThreadPriority::High`
+    #[cfg(target_os = "linux")]
+    const HIGH_NICE: libc::c_int = -10;
+
+    pub fn promote(priority: ThreadPriority) -> Result<(), String> {
+        match priority {
+            ThreadPriority::Realtime => unsafe {
+                let min = libc::sched_get_priority_min(libc::SCHED_RR);
+                let max = libc::sched_get_priority_max(libc::SCHED_RR);
+                let param = libc::sched_param {
+                    sched_priority: min + (max - min) / 2,
+                };
+                match libc::pthread_setschedparam(libc::pthread_self(), libc::SCHED_RR, &param) {
+                    0 => Ok(()),
+                    err => Err(format!(
+                        "Failed to enable real-time scheduling: {}",
+                        io::Error::from_raw_os_error(err)
+                    )),
+                }
+            },
+            _ => raise(),
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn raise() -> Result<(), String> {
+        // On Linux, PRIO_PROCESS with who=0 targets the calling thread
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS as _, 0, HIGH_NICE) } == 0 {
+            Ok(())
+        } else {
+            Err(format!(
+                "Failed to raise thread priority: {}",
+                io::Error::last_os_error()
+            ))
+        }
+    }
+
+    #[cfg(target_vendor = "apple")]
+    fn raise() -> Result<(), String> {
+        // setpriority would renice the whole process here; a QoS class only covers this thread
+        match unsafe { libc::pthread_set_qos_class_self_np(libc::qos_class_t::QOS_CLASS_USER_INTERACTIVE, 0) } {
+            0 => Ok(()),
+            err => Err(format!(
+                "Failed to raise thread priority: {}",
+                io::Error::from_raw_os_error(err)
+            )),
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_vendor = "apple")))]
+    fn raise() -> Result<(), String> {
+        // No per-thread nice value here, and renicing the whole process would defeat the point
+        Err("High thread priority is not supported on this platform; use realtime".to_string())
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::ThreadPriority;
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentThread, SetThreadPriority, THREAD_PRIORITY_HIGHEST,
+        THREAD_PRIORITY_TIME_CRITICAL,
+    };
+
+    pub fn promote(priority: ThreadPriority) -> Result<(), String> {
+        let level = match priority {
+            ThreadPriority::Realtime => THREAD_PRIORITY_TIME_CRITICAL,
+            _ => THREAD_PRIORITY_HIGHEST,
+        };
+        if unsafe { SetThreadPriority(GetCurrentThread(), level) } != 0 {
+            Ok(())
+        } else {
+            Err(format!(
+                "Failed to raise thread priority: {}",
+                std::io::Error::last_os_error()
+            ))
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod platform {
+    use super::ThreadPriority;
+
+    pub fn promote(_priority: ThreadPriority) -> Result<(), String> {
+        Err("Thread priority is not supported on this platform".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normal_priority_is_a_no_op() {
+        assert!(promote_current_thread(ThreadPriority::Normal).is_ok());
+    }
+
+    #[test]
+    fn test_priority_deserializes_from_config() {
+        let priority: ThreadPriority = serde_yaml::from_str("realtime").unwrap();
+        assert_eq!(priority, ThreadPriority::Realtime);
+    }
+}
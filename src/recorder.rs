@@ -1,20 +1,96 @@
 use cpal::traits::{DeviceTrait, StreamTrait};
 use cpal::SupportedStreamConfig;
-use hound::{WavSpec, WavWriter, SampleFormat};
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc;
+use hound::{WavSpec, SampleFormat};
+use serde::Serialize;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::thread;
-use std::time::SystemTime;
+use std::time::{Duration, Instant, SystemTime};
+use crate::backpressure::{self, SampleSender};
+use crate::pool::BufferPool;
+use crate::sidecar;
+use crate::priority::{self, ThreadPriority};
+use crate::rtp;
+use crate::wav::{self, WavMetadata};
+use crate::checksum::{self, ChecksumSink};
+use crate::chunking::{self, ChunkSink};
+use crate::collector::CollectorSink;
 use crate::config::Config;
+use crate::device::DeviceManager;
+use crate::disk;
+use crate::dsp;
+use crate::encoder::{CompressedFormat, EncoderSink};
+use crate::mixer::{MixLevels, Mixer, Source};
+use crate::error::{ConfigError, RecorderError, Result, StreamError};
+use crate::events::{Broadcast, EventBus, RecorderEvent};
+use crate::frame::Frame;
+use crate::live::LiveServer;
+#[cfg(feature = "live")]
+use crate::live::LiveSink;
+use crate::marker::{self, Marker};
+#[cfg(feature = "notifications")]
+use crate::notification::Notifier;
+use crate::state::{RecordingState, SharedState};
+use crate::summary::AudioSummary;
+use crate::streaming::Streaming;
+#[cfg(feature = "streaming")]
+use crate::streaming::StreamSink;
+#[cfg(feature = "whisper")]
+use crate::transcription;
+use crate::source::{Input, FILE_CHUNK};
+#[cfg(feature = "upload")]
+use crate::upload;
+use crate::upload::{FileUpload, Upload, UploadReport};
+use crate::flac::FlacSink;
+use crate::sink::{RecordingFormat, Sink, SinkContext, SinkError, SinkFactory, WavSink};
+use crossbeam_channel::Receiver;
+use uuid::Uuid;
+
+/// How often the mixer reports input levels to subscribers
+const LEVEL_UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How often the mixer checks free space on the output volume
+const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Initial capacity of pooled sample buffers (samples per callback chunk)
+const POOL_BUFFER_LEN: usize = 4096;
+
+/// Longest title, in characters, that goes into a file name
+const MAX_TITLE_CHARS: usize = 60;
+
+/// How often the session thread checks for stop requests and stream errors
+const STATUS_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// State a session thread shares with its handle
+#[derive(Default)]
+struct SessionShared {
+    /// When the session should stop on its own
+    deadline: Mutex<Option<Instant>>,
+    /// Latest live levels from the mixer
+    levels: Mutex<MixLevels>,
+    /// Sample frames written so far, where a marker set now points
+    frames_written: AtomicU64,
+    /// Markers set so far, in order
+    markers: Mutex<Vec<Marker>>,
+    /// Sample rate of the recording, once capture has started
+    sample_rate: AtomicU32,
+}
 
 /// Main recorder that handles audio recording from devices
+#[derive(Clone)]
 pub struct Recorder {
-    mic_device: cpal::Device,
-    mic_config: SupportedStreamConfig,
-    sys_device: Option<cpal::Device>,
-    sys_config: Option<SupportedStreamConfig>,
+    mic: Input,
+    sys: Option<Input>,
     running: Arc<AtomicBool>,
+    state: SharedState,
+    events: EventBus,
+    frames: Broadcast<Frame>,
+    wav_output: bool,
+    sinks: Vec<Arc<SinkFactory>>,
+    title: Option<String>,
 }
 
 impl Recorder {
@@ -25,20 +101,201 @@ impl Recorder {
         sys_device: Option<cpal::Device>,
         sys_config: Option<SupportedStreamConfig>,
     ) -> Self {
+        let mic = Input::Device { device: mic_device, config: mic_config };
+        let sys = sys_device.zip(sys_config).map(|(device, config)| Input::Device { device, config });
+        Self::from_inputs(mic, sys)
+    }
+    
+    /// Create a Recorder for any mix of capture devices and [`FileSource`](crate::source::FileSource)s
+    ///
+    /// A session whose inputs are all files stops on its own once they have
+    /// been played through.
+    pub fn from_inputs(mic: Input, sys: Option<Input>) -> Self {
         Self {
-            mic_device,
-            mic_config,
-            sys_device,
-            sys_config,
+            mic,
+            sys,
             running: Arc::new(AtomicBool::new(true)),
+            state: SharedState::default(),
+            events: EventBus::default(),
+            frames: Broadcast::default(),
+            wav_output: true,
+            sinks: Vec::new(),
+            title: None,
+        }
+    }
+    
+    /// Create a Recorder for the input devices called `mic` and, optionally, `system`
+    ///
+    /// Devices are looked up by name as [`DeviceManager::find_device`] does
+    /// and recorded in their default configuration.
+    pub fn from_names(mic: &str, system: Option<&str>) -> Result<Self> {
+        let devices = DeviceManager::new()?;
+        let (mic_device, mic_config) = devices.device_by_name(mic)?;
+        let (sys_device, sys_config) = match system {
+            Some(name) => {
+                let (device, config) = devices.device_by_name(name)?;
+                (Some(device), Some(config))
+            }
+            None => (None, None),
+        };
+        Ok(Self::new(mic_device, mic_config, sys_device, sys_config))
+    }
+    
+    /// Receive [`RecorderEvent`]s from every session this recorder runs
+    ///
+    /// Events are dropped for a subscriber that falls too far behind, so a
+    /// slow reader can never stall capture.
+    pub fn subscribe(&self) -> Receiver<RecorderEvent> {
+        self.events.subscribe()
+    }
+    
+    /// What the recorder's current (or last) session is doing
+    pub fn state(&self) -> RecordingState {
+        self.state.get()
+    }
+    
+    /// Receive the mixed audio of every session this recorder runs, as it is captured
+    ///
+    /// Like events, frames are dropped for a subscriber that falls too far
+    /// behind rather than stalling capture.
+    pub fn frames(&self) -> Receiver<Frame> {
+        self.frames.subscribe()
+    }
+    
+    #[cfg(feature = "tokio")]
+    pub(crate) fn events(&self) -> &EventBus {
+        &self.events
+    }
+    
+    #[cfg(feature = "tokio")]
+    pub(crate) fn frame_broadcast(&self) -> &Broadcast<Frame> {
+        &self.frames
+    }
+    
+    /// Also send each session's audio to a sink created by `factory`
+    ///
+    /// The factory runs as a session starts, and the session fails to start
+    /// if it does. A sink that fails later is dropped with an
+    /// [`Error`](RecorderEvent::Error) event while the recording carries on.
+    pub fn with_sink<F>(mut self, factory: F) -> Self
+    where
+        F: Fn(&SinkContext) -> std::result::Result<Box<dyn Sink>, SinkError> + Send + Sync + 'static,
+    {
+        self.sinks.push(Arc::new(factory));
+        self
+    }
+    
+    /// Whether sessions write a recording file, in the configured format (the default)
+    ///
+    /// Turn this off to only deliver audio through [`frames`](Self::frames);
+    /// no file is created and the free space checks are skipped.
+    pub fn with_wav_output(mut self, enabled: bool) -> Self {
+        self.wav_output = enabled;
+        self
+    }
+    
+    /// Name recordings after `title`, such as a calendar event, instead of just "recording"
+    ///
+    /// The title goes into the file name in a form safe for file systems,
+    /// and as it is into the WAV metadata and the sidecar.
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+    
+    /// Record until stopped, using `running` as the stop flag
+    ///
+    /// Recording continues while the flag is true; clearing it (from a signal
+    /// handler, a UI thread, or anywhere else) stops and finalizes the file.
+    /// The library never installs signal handlers itself.
+    pub fn with_running_flag(mut self, running: Arc<AtomicBool>) -> Self {
+        self.running = running;
+        self
+    }
+    
+    /// Record audio to a single combined WAV file, blocking until stopped
+    pub fn record(&self, config: &Config) -> Result<RecordingResult> {
+        self.start(config)?.join()
+    }
+    
+    /// Record for `duration`, then stop and finalize on its own
+    ///
+    /// Stopping earlier through the running flag still works; the result's
+    /// stop reason tells which happened.
+    pub fn record_for(&self, duration: Duration, config: &Config) -> Result<RecordingResult> {
+        let session = self.start(config)?;
+        session.stop_after(duration);
+        session.join()
+    }
+    
+    /// Start recording in the background and return a handle to the session
+    ///
+    /// Returns once capture is running, or with the error that prevented it
+    /// from starting. The recording continues until [`RecordingSession::stop`]
+    /// (or [`Recorder::stop`]) is called or it ends on its own.
+    ///
+    /// A recorder runs one session at a time but can start any number of them
+    /// back to back; each start re-arms the running flag.
+    pub fn start(&self, config: &Config) -> Result<RecordingSession> {
+        let idle = self.state.transition(RecordingState::Idle, RecordingState::Armed)
+            || self.state.transition(RecordingState::Finished, RecordingState::Armed);
+        if !idle {
+            return Err(RecorderError::Busy);
+        }
+        self.running.store(true, Ordering::SeqCst);
+        let recorder = self.clone();
+        let config = config.clone();
+        let (ready_tx, ready_rx) = crossbeam_channel::bounded(1);
+        let shared = Arc::new(SessionShared::default());
+        let session_shared = shared.clone();
+        let id = Uuid::new_v4();
+        #[cfg(feature = "notifications")]
+        let notifier = config.notifications.then(|| Notifier::spawn(self.subscribe()));
+        let handle = thread::spawn(move || {
+            let result = recorder.run(id, &config, ready_tx, &session_shared);
+            recorder.state.set(RecordingState::Finished);
+            #[cfg(feature = "notifications")]
+            if let Some(notifier) = notifier {
+                notifier.finish(&result);
+            }
+            recorder.notify(&config, id, &result);
+            result
+        });
+        
+        match ready_rx.recv() {
+            Ok(path) => Ok(RecordingSession {
+                id,
+                path,
+                running: self.running.clone(),
+                state: self.state.clone(),
+                shared,
+                handle,
+            }),
+            // The session thread gave up before capture started
+            Err(_) => match handle.join() {
+                Ok(Err(e)) => Err(e),
+                Ok(Ok(_)) | Err(_) => Err(RecorderError::Thread("session thread exited before recording started")),
+            },
         }
     }
     
-    /// Record audio to a single combined WAV file
-    pub fn record(&self, config: &Config) -> Result<RecordingResult, Box<dyn std::error::Error>> {
+    /// Run a whole session on the calling thread, which owns the streams
+    ///
+    /// Sends the output path, if any, on `ready` once capture has started,
+    /// stops on its own once the shared deadline passes, and publishes live
+    /// levels to `shared`.
+    fn run(
+        &self,
+        id: Uuid,
+        config: &Config,
+        ready: crossbeam_channel::Sender<Option<PathBuf>>,
+        shared: &Arc<SessionShared>,
+    ) -> Result<RecordingResult> {
+        config.validate()?;
+
         // Format timestamp as dd-mm-yyyy-hh-mm
         let now = SystemTime::now();
-        let datetime = now.duration_since(std::time::UNIX_EPOCH)?;
+        let datetime = now.duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
         let secs = datetime.as_secs();
         
         // Convert to local time components
@@ -84,22 +341,26 @@ impl Recorder {
         let hours = (secs_in_day / 3600) as u32;
         let minutes = ((secs_in_day % 3600) / 60) as u32;
         
-        // Format as mm-dd-yyyy-24h-m-recording.wav
-        let filename = format!("{:02}-{:02}-{}-{:02}-{:02}-recording.wav", month, day, year, hours, minutes);
-        let combined_path = config.recording_path(&filename);
+        // Format as mm-dd-yyyy-24h-m-recording.wav, or after the title if there is one,
+        // optionally tagged with the session ID
+        let name = self.title.as_deref().map(file_name_part).filter(|name| !name.is_empty());
+        let mut stem = format!(
+            "{:02}-{:02}-{}-{:02}-{:02}-{}",
+            month, day, year, hours, minutes, name.as_deref().unwrap_or("recording")
+        );
+        if config.session_id_in_filename {
+            stem = format!("{}-{}", stem, id);
+        }
+        let combined_path = unused_recording_path(config, &stem, config.recording_extension());
         let combined_filename = combined_path.to_string_lossy().to_string();
+        // Write under a temporary name until finalized so watchers never see a partial file
+        let part_path = wav::partial_path(&combined_path);
         
-        let mic_sample_rate = self.mic_config.sample_rate().0;
-        let mic_channels = self.mic_config.channels() as u16;
+        let mic_format = self.mic.format();
+        let sys_format = self.sys.as_ref().map(Input::format);
         
         // Determine output format - use higher sample rate, stereo
-        let (sys_sample_rate, sys_channels) = if let Some(config) = self.sys_config.as_ref() {
-            (config.sample_rate().0, config.channels() as u16)
-        } else {
-            (mic_sample_rate, 1)
-        };
-        
-        let output_sample_rate = mic_sample_rate.max(sys_sample_rate);
+        let output_sample_rate = mic_format.sample_rate.max(sys_format.map_or(0, |format| format.sample_rate));
         let output_channels = 2u16; // Always stereo for combined output
         
         let combined_spec = WavSpec {
@@ -108,267 +369,968 @@ impl Recorder {
             bits_per_sample: 16,
             sample_format: SampleFormat::Int,
         };
+        shared.sample_rate.store(output_sample_rate, Ordering::Relaxed);
         
-        // Create channels for sample data (callback doesn't hold WavWriter Arc)
-        let (mic_tx, mic_rx) = mpsc::channel::<Vec<i16>>();
-        let (sys_tx, sys_rx) = if self.sys_device.is_some() {
-            let (tx, rx) = mpsc::channel::<Vec<i16>>();
+        // Create bounded channels for sample data (callback doesn't hold WavWriter Arc).
+        // Buffers cycle between callbacks and the mixer through per-source pools.
+        let mic_pool = BufferPool::new(config.channel_capacity + 2, POOL_BUFFER_LEN);
+        let (mic_tx, mic_rx) = backpressure::bounded(
+            config.channel_capacity,
+            config.backpressure_policy,
+            "microphone",
+            mic_pool.clone(),
+        );
+        let mic_overruns = mic_tx.triggered();
+        let sys_pool = BufferPool::new(config.channel_capacity + 2, POOL_BUFFER_LEN);
+        let (sys_tx, sys_rx) = if self.sys.is_some() {
+            let (tx, rx) = backpressure::bounded(
+                config.channel_capacity,
+                config.backpressure_policy,
+                "system audio",
+                sys_pool.clone(),
+            );
             (Some(tx), Some(rx))
         } else {
             (None, None)
         };
+        let sys_overruns = sys_tx.as_ref().map(|tx| tx.triggered());
         
-        // Create single combined WAV writer
-        let combined_writer = WavWriter::create(&combined_filename, combined_spec)?;
+        // Make sure the meeting will fit before opening any streams
+        let min_free_space = config.min_free_space_bytes().filter(|_| self.wav_output);
+        if let Some(reserve) = min_free_space {
+            let available = disk::available_space(&config.output_directory)?;
+            if available <= reserve {
+                return Err(RecorderError::InsufficientSpace {
+                    directory: config.output_directory.clone(),
+                    available_mib: available / (1024 * 1024),
+                    minimum_mib: config.min_free_space_mb,
+                });
+            }
+            
+            let remaining = disk::time_remaining(available, reserve, &combined_spec);
+            if remaining.as_secs() < config.expected_meeting_minutes * 60 {
+                console_out!(
+                    "Warning: only about {} minutes of recording fit in the free space; \
+                     recording will stop automatically when it runs low",
+                    remaining.as_secs() / 60
+                );
+                self.events.emit(RecorderEvent::Error {
+                    message: format!("Only about {} minutes of recording fit in the free space", remaining.as_secs() / 60),
+                });
+            }
+        }
         
-        // Setup signal handler for Ctrl+C
-        let r = self.running.clone();
-        ctrlc::set_handler(move || {
-            println!("\n\nStopping recording...");
-            r.store(false, Ordering::SeqCst);
-        })?;
+        // Open the extra sinks first, so a failing one doesn't leave an empty WAV behind
+        let context = SinkContext {
+            recording_path: combined_path.clone(),
+            spec: combined_spec,
+            session_id: id,
+        };
+        let mut sinks = self.sinks.iter()
+            .map(|factory| factory(&context).map(|sink| OutputSink { sink, primary: false }))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(RecorderError::Sink)?;
+        let mut compressed_path = None;
+        // A copy in the recording's own format would only overwrite it
+        let copy_format = config.compressed_format
+            .filter(|format| !(self.wav_output && format.extension() == config.recording_extension()));
+        if self.wav_output && config.checksum {
+            sinks.push(OutputSink { sink: Box::new(ChecksumSink::new(&context)), primary: false });
+        }
+        if let Some(options) = &config.chunks {
+            if !options.is_valid() {
+                return Err(ConfigError::InvalidChunks.into());
+            }
+            let events = self.events.clone();
+            let sample_rate = context.spec.sample_rate;
+            let chunks = ChunkSink::files_then(&context, options, config.encryption.as_ref(), move |chunk, path| {
+                events.emit(RecorderEvent::SegmentRotated {
+                    index: chunk.index,
+                    path,
+                    start: chunk.start(sample_rate),
+                });
+            })
+            .map_err(|e| RecorderError::Sink(e.into()))?;
+            console_out!(
+                "Writing {}s chunks to {}",
+                options.length_secs,
+                chunking::chunk_directory(&combined_path).display()
+            );
+            sinks.push(OutputSink { sink: Box::new(chunks), primary: false });
+        }
+        if let Some(options) = &config.streaming {
+            sinks.push(OutputSink { sink: stream_sink(options, config, &context)?, primary: false });
+            console_out!("Streaming audio to {}", options.url);
+        }
+        if let Some(options) = &config.live {
+            sinks.push(OutputSink { sink: live_sink(options, config, &context)?, primary: false });
+            console_out!("Serving live audio at http://{}/", options.listen);
+        }
+        if let Some(options) = &config.collector {
+            let sink = CollectorSink::spawn(options, &context).map_err(RecorderError::Sink)?;
+            sinks.push(OutputSink { sink: Box::new(sink), primary: false });
+            console_out!("Sending audio to the collector at {}", options.address);
+        }
+        if let Some(format) = copy_format {
+            let encoder = EncoderSink::spawn(format, config.bitrate, &context).map_err(|e| RecorderError::Sink(e.into()))?;
+            compressed_path = Some(encoder.path().to_path_buf());
+            sinks.push(OutputSink { sink: Box::new(encoder), primary: false });
+        }
+        
+        // Create single combined recording writer behind a large buffer to cut syscall churn
+        if self.wav_output {
+            let buffer_size = config.write_buffer_kb * 1024;
+            let spawn_encoder = |format| {
+                EncoderSink::create(format, config.bitrate, &combined_spec, &part_path)
+                    .map_err(|e| RecorderError::Sink(e.into()))
+            };
+            let sink: Box<dyn Sink> = match config.format {
+                RecordingFormat::Wav => {
+                    let metadata = WavMetadata {
+                        title: self.title.clone().unwrap_or_else(|| stem.clone()),
+                        started: now,
+                        microphone: self.mic.name(),
+                        system_audio: self.sys.as_ref().map(Input::name),
+                        session_id: id,
+                    };
+                    Box::new(
+                        WavSink::create(&part_path, combined_spec, buffer_size, config.sync_on_flush)?
+                            .with_metadata(metadata),
+                    )
+                }
+                RecordingFormat::Flac => {
+                    Box::new(FlacSink::create(&part_path, combined_spec, buffer_size, config.sync_on_flush)?)
+                }
+                RecordingFormat::Opus => Box::new(spawn_encoder(CompressedFormat::Opus)?),
+                RecordingFormat::Mp3 => Box::new(spawn_encoder(CompressedFormat::Mp3)?),
+                RecordingFormat::External => {
+                    let encoder = config.external_encoder.as_ref().ok_or(ConfigError::MissingExternalEncoder)?;
+                    Box::new(
+                        EncoderSink::create_external(encoder, &combined_spec, &part_path)
+                            .map_err(|e| RecorderError::Sink(e.into()))?,
+                    )
+                }
+            };
+            sinks.insert(0, OutputSink { sink, primary: true });
+        }
         
         // Start mixer thread - mixes samples from both sources into single file
-        let mic_running = self.running.clone();
-        let mic_ch = mic_channels;
-        let sys_ch = sys_channels;
+        // Sources are brought to stereo at the output rate before mixing
+        let mut mixer = Mixer::new(mic_format, sys_format, output_sample_rate)
+        .with_backlog_limit(config.max_backlog_samples(&combined_spec), config.backlog_policy);
+        
+        let thread_priority = config.thread_priority;
+        let flush_interval = config.flush_interval();
+        let output_directory = config.output_directory.clone();
+        let mixer_running = self.running.clone();
+        let backlog_policy = config.backlog_policy;
+        let mixer_events = self.events.clone();
+        let mixer_frames = self.frames.clone();
+        let mixer_shared = shared.clone();
+        let mut mute_detector = config.mute_detector().filter(|_| sys_rx.is_some());
         
+        // The mixer and the stream error callbacks report back over this channel
+        let (status_tx, status_rx) = crossbeam_channel::unbounded::<Status>();
+        let mixer_status = status_tx.clone();
         let mixer_handle = thread::spawn(move || {
-            let mut writer = combined_writer;
-            let mut mic_buffer: Vec<i16> = Vec::new();
-            let mut sys_buffer: Vec<i16> = Vec::new();
+            if let Err(e) = priority::promote_current_thread(thread_priority) {
+                console_err!("Warning: mixer thread: {}", e);
+            }
+            
+            let mut mixed: Vec<i16> = Vec::with_capacity(POOL_BUFFER_LEN * 4);
             let mut mic_samples_received = 0u64;
             let mut sys_samples_received = 0u64;
             let mut samples_written = 0u64;
+            let mut write_error: Option<SinkError> = None;
+            let mut backlog_events = 0u64;
             
-            loop {
-                // Receive samples from both sources
-                let mut received_any = false;
+            // A source whose senders have all been dropped is swapped for a
+            // channel that never delivers, so select! only wakes for live ones
+            let never = crossbeam_channel::never::<Vec<i16>>();
+            let mut mic_source = &mic_rx;
+            let mut sys_source = sys_rx.as_ref().unwrap_or(&never);
+            let mut mic_open = true;
+            let mut sys_open = sys_rx.is_some();
+            let mut last_flush = Instant::now();
+            let mut last_disk_check = Instant::now();
+            let mut last_level_update = Instant::now();
+            
+            while mic_open || sys_open {
+                // Wake when periodic work is due even if the sources go quiet
+                let until_flush = flush_interval
+                    .map_or(Duration::MAX, |interval| interval.saturating_sub(last_flush.elapsed()));
+                let until_disk_check = min_free_space
+                    .map_or(Duration::MAX, |_| DISK_CHECK_INTERVAL.saturating_sub(last_disk_check.elapsed()));
+                let until_next_task = until_flush.min(until_disk_check);
+                
+                // Block until a source delivers samples or disconnects
+                crossbeam_channel::select! {
+                    recv(mic_source) -> msg => match msg {
+                        Ok(samples) => {
+                            mic_samples_received += samples.len() as u64;
+                            mixer.push(Source::Microphone, &samples);
+                            mic_pool.recycle(samples);
+                        }
+                        Err(_) => {
+                            mic_open = false;
+                            mic_source = &never;
+                            mixer.end(Source::Microphone);
+                        }
+                    },
+                    recv(sys_source) -> msg => match msg {
+                        Ok(samples) => {
+                            sys_samples_received += samples.len() as u64;
+                            mixer.push(Source::SystemAudio, &samples);
+                            sys_pool.recycle(samples);
+                        }
+                        Err(_) => {
+                            sys_open = false;
+                            sys_source = &never;
+                            mixer.end(Source::SystemAudio);
+                        }
+                    },
+                    default(until_next_task) => {}
+                }
                 
-                // Try to get mic samples
-                while let Ok(samples) = mic_rx.try_recv() {
-                    received_any = true;
+                // Pick up anything else that arrived meanwhile without blocking
+                while let Ok(samples) = mic_source.try_recv() {
                     mic_samples_received += samples.len() as u64;
-                    // Convert to stereo if needed
-                    let stereo_samples: Vec<i16> = if mic_ch == 1 {
-                        samples.iter().flat_map(|&s| [s, s]).collect()
-                    } else {
-                        samples
-                    };
-                    mic_buffer.extend(stereo_samples);
+                    mixer.push(Source::Microphone, &samples);
+                    mic_pool.recycle(samples);
+                }
+                while let Ok(samples) = sys_source.try_recv() {
+                    sys_samples_received += samples.len() as u64;
+                    mixer.push(Source::SystemAudio, &samples);
+                    sys_pool.recycle(samples);
                 }
                 
-                // Try to get system audio samples
-                if let Some(ref rx) = sys_rx {
-                    while let Ok(samples) = rx.try_recv() {
-                        received_any = true;
-                        sys_samples_received += samples.len() as u64;
-                        // Convert to stereo if needed
-                        let stereo_samples: Vec<i16> = if sys_ch == 1 {
-                            samples.iter().flat_map(|&s| [s, s]).collect()
-                        } else {
-                            samples
-                        };
-                        sys_buffer.extend(stereo_samples);
+                if last_level_update.elapsed() >= LEVEL_UPDATE_INTERVAL {
+                    let elapsed = last_level_update.elapsed();
+                    last_level_update = Instant::now();
+                    let levels = mixer.take_levels();
+                    let silent_for = mute_detector
+                        .as_mut()
+                        .and_then(|detector| detector.update(levels.mic, levels.sys, elapsed));
+                    if let Some(duration) = silent_for {
+                        console_err!(
+                            "\nWarning: the microphone has been silent for {} s while system audio is playing; is it muted?",
+                            duration.as_secs()
+                        );
+                        mixer_events.emit(RecorderEvent::MicSilent { duration });
+                    }
+                    if let Ok(mut latest) = mixer_shared.levels.lock() {
+                        *latest = levels;
+                    }
+                    if mixer_events.has_subscribers() {
+                        mixer_events.emit(RecorderEvent::LevelUpdate {
+                            mic: levels.mic,
+                            sys: levels.sys,
+                            mix: levels.mix,
+                        });
                     }
                 }
                 
-                // Mix and write samples - mix corresponding samples together
-                // For stereo: mix left with left, right with right
-                // Write as many samples as we can from both buffers
-                let min_len = mic_buffer.len().min(sys_buffer.len());
-                if min_len >= 2 {
-                    // Ensure we mix in stereo pairs (left, right)
-                    let pairs = min_len / 2;
-                    for i in 0..pairs {
-                        let mic_left = mic_buffer[i * 2];
-                        let mic_right = mic_buffer[i * 2 + 1];
-                        let sys_left = sys_buffer[i * 2];
-                        let sys_right = sys_buffer[i * 2 + 1];
-                        
-                        // Mix left channels
-                        let mixed_left = (mic_left as i32 + sys_left as i32)
-                            .clamp(i16::MIN as i32, i16::MAX as i32) as i16;
-                        // Mix right channels
-                        let mixed_right = (mic_right as i32 + sys_right as i32)
-                            .clamp(i16::MIN as i32, i16::MAX as i32) as i16;
-                        
-                        writer.write_sample(mixed_left).unwrap();
-                        writer.write_sample(mixed_right).unwrap();
-                        samples_written += 2;
-                    }
-                    mic_buffer.drain(0..pairs * 2);
-                    sys_buffer.drain(0..pairs * 2);
+                // After a write failure keep draining the sources, so callbacks
+                // never block on a full queue, until the streams are torn down
+                if write_error.is_some() {
+                    mixer.clear();
+                    continue;
                 }
                 
-                // If one buffer has more data than the other, write what we can
-                // This handles cases where one source is faster than the other
-                if mic_buffer.len() >= 2 && sys_buffer.is_empty() {
-                    // Only mic data available - write it
-                    let pairs = mic_buffer.len() / 2;
-                    for i in 0..pairs {
-                        writer.write_sample(mic_buffer[i * 2]).unwrap();
-                        writer.write_sample(mic_buffer[i * 2 + 1]).unwrap();
-                        samples_written += 2;
+                // Keep a stalled or runaway source from growing its buffer without bound
+                mixed.clear();
+                if mixer.mix(&mut mixed) > 0 {
+                    if backlog_events == 0 {
+                        console_err!(
+                            "\nWarning: one audio source is falling behind; applying backlog policy ({:?})",
+                            backlog_policy
+                        );
                     }
-                    mic_buffer.drain(0..pairs * 2);
-                } else if sys_buffer.len() >= 2 && mic_buffer.is_empty() {
-                    // Only system data available - write it
-                    let pairs = sys_buffer.len() / 2;
-                    for i in 0..pairs {
-                        writer.write_sample(sys_buffer[i * 2]).unwrap();
-                        writer.write_sample(sys_buffer[i * 2 + 1]).unwrap();
-                        samples_written += 2;
-                    }
-                    sys_buffer.drain(0..pairs * 2);
+                    backlog_events += 1;
+                    mixer_events.emit(RecorderEvent::Dropout { source: "mixer backlog", count: 1 });
                 }
                 
-                // Check if we should exit
-                if !mic_running.load(Ordering::SeqCst) && !received_any {
-                    // Drain remaining buffers - mix any remaining samples
-                    let max_len = mic_buffer.len().max(sys_buffer.len());
-                    let pairs = max_len / 2;
-                    for i in 0..pairs {
-                        let mic_left = mic_buffer.get(i * 2).copied().unwrap_or(0);
-                        let mic_right = mic_buffer.get(i * 2 + 1).copied().unwrap_or(0);
-                        let sys_left = sys_buffer.get(i * 2).copied().unwrap_or(0);
-                        let sys_right = sys_buffer.get(i * 2 + 1).copied().unwrap_or(0);
-                        
-                        let mixed_left = (mic_left as i32 + sys_left as i32)
-                            .clamp(i16::MIN as i32, i16::MAX as i32) as i16;
-                        let mixed_right = (mic_right as i32 + sys_right as i32)
-                            .clamp(i16::MIN as i32, i16::MAX as i32) as i16;
-                        
-                        writer.write_sample(mixed_left).unwrap();
-                        writer.write_sample(mixed_right).unwrap();
-                    }
-                    // Write any remaining unpaired samples
-                    if mic_buffer.len() > pairs * 2 {
-                        for &sample in mic_buffer.iter().skip(pairs * 2) {
-                            writer.write_sample(sample).unwrap();
-                        }
-                    }
-                    if sys_buffer.len() > pairs * 2 {
-                        for &sample in sys_buffer.iter().skip(pairs * 2) {
-                            writer.write_sample(sample).unwrap();
+                publish_frame(&mixer_frames, &mixed, samples_written, &combined_spec);
+                // Checkpoint the outputs (for WAV: update the header and push
+                // buffered audio to disk), so a crash loses at most one interval
+                let checkpoint_due = flush_interval.is_some_and(|interval| last_flush.elapsed() >= interval);
+                if checkpoint_due {
+                    last_flush = Instant::now();
+                }
+                if let Err(e) = write_sinks(&mut sinks, &mixed, checkpoint_due, &mixer_events) {
+                    console_err!("\nError writing recording: {}; stopping capture...", e);
+                    mixer_events.emit(RecorderEvent::Error {
+                        message: format!("Error writing recording: {}", e),
+                    });
+                    mixer_running.store(false, Ordering::SeqCst);
+                    write_error = Some(e);
+                    continue;
+                }
+                samples_written += mixed.len() as u64;
+                mixer_shared.frames_written.store(samples_written / combined_spec.channels as u64, Ordering::Relaxed);
+                
+                // Stop gracefully before the disk fills up; the streams are
+                // torn down by the main thread and the file finalized below
+                if let Some(reserve) = min_free_space {
+                    if last_disk_check.elapsed() >= DISK_CHECK_INTERVAL {
+                        last_disk_check = Instant::now();
+                        match disk::available_space(&output_directory) {
+                            Ok(available) if available <= reserve => {
+                                let _ = mixer_status.send(Status::LowDiskSpace);
+                                if mixer_running.swap(false, Ordering::SeqCst) {
+                                    console_err!(
+                                        "\nFree disk space is below {} MiB, stopping recording...",
+                                        reserve / (1024 * 1024)
+                                    );
+                                }
+                            }
+                            Ok(_) => {}
+                            Err(e) => console_err!("Warning: could not check free disk space: {}", e),
                         }
                     }
-                    break;
                 }
-                
-                if !received_any {
-                    thread::sleep(std::time::Duration::from_millis(10));
+            }
+            
+            // Finalize whatever made it to disk; a write error is still
+            // reported, but only after the header covers the written audio
+            if write_error.is_none() {
+                mixed.clear();
+                mixer.finish(&mut mixed);
+                publish_frame(&mixer_frames, &mixed, samples_written, &combined_spec);
+                match write_sinks(&mut sinks, &mixed, false, &mixer_events) {
+                    Ok(()) => samples_written += mixed.len() as u64,
+                    Err(e) => write_error = Some(e),
                 }
             }
+            // Markers set from here on would point past the end
+            let markers = mixer_shared.markers.lock().map(|markers| markers.clone()).unwrap_or_default();
+            for output in &mut sinks {
+                output.sink.set_markers(&markers);
+            }
+            let stats = MixerStats {
+                markers,
+                mic_samples: mic_samples_received,
+                sys_samples: sys_samples_received,
+                samples_written,
+                backlog_events,
+                mic_levels: mixer.levels(Source::Microphone),
+                sys_levels: mixer.levels(Source::SystemAudio),
+                mix_levels: mixer.mix_levels(),
+                mic_summary: mixer.summary(Source::Microphone),
+                sys_summary: mixer.summary(Source::SystemAudio),
+            };
+            let finalized = finalize_sinks(sinks, &mixer_events);
+            let result = match (write_error, finalized) {
+                (None, Ok(())) => Ok(stats),
+                (Some(cause), Ok(())) => Err(MixerError { cause, finalized: true, stats }),
+                (cause, Err(e)) => Err(MixerError { cause: cause.unwrap_or(e), finalized: false, stats }),
+            };
             
-            writer.finalize().unwrap();
-            eprintln!("Mixer stats: mic_samples={}, sys_samples={}, written={}", 
-                     mic_samples_received, sys_samples_received, samples_written);
+            // Only report once the file is safe
+            console_err!(
+                "Mixer stats: mic_samples={}, sys_samples={}, written={}, backlog_events={}",
+                mic_samples_received, sys_samples_received, samples_written, backlog_events
+            );
+            let _ = mixer_status.send(Status::MixerFinished(result));
         });
         
-        // Build microphone stream - callback sends to channel
-        let mic_tx_clone = mic_tx.clone();
-        let mic_running = self.running.clone();
+        // Start capturing from each input; streams and file threads send to the mixer
+        let mic_capture = self.start_capture(&self.mic, "Microphone", mic_tx, &status_tx, thread_priority)?;
+        let sys_capture = match (self.sys.as_ref(), sys_tx) {
+            (Some(input), Some(tx)) => Some(self.start_capture(input, "System audio", tx, &status_tx, thread_priority)?),
+            _ => None,
+        };
+        let mut live_inputs = 1 + usize::from(sys_capture.is_some());
+        let has_live_input = self.mic.is_live() || self.sys.as_ref().is_some_and(Input::is_live);
+        
+        // Start recording
+        console_out!("\n=== Recording Started ===");
+        if self.wav_output {
+            console_out!("Recording to: {}", combined_filename);
+        }
+        if let Some(path) = &compressed_path {
+            console_out!("Compressed copy: {}", path.display());
+        }
+        console_out!("Format: {} channels, {} Hz", output_channels, output_sample_rate);
+        console_out!("Microphone: {} channels, {} Hz", mic_format.channels, mic_format.sample_rate);
+        if let Some(format) = sys_format {
+            console_out!("System audio: {} channels, {} Hz", format.channels, format.sample_rate);
+        }
         
-        let mic_stream = self.mic_device.build_input_stream(
-            &self.mic_config.clone().into(),
-            move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                if !mic_running.load(Ordering::SeqCst) {
-                    return;
+        mic_capture.play()?;
+        if let Some(capture) = &sys_capture {
+            capture.play()?;
+        }
+        self.state.set(RecordingState::Recording);
+        let output_path = self.wav_output.then(|| combined_path.clone());
+        let _ = ready.send(output_path.clone());
+        self.events.emit(RecorderEvent::Started { id, path: output_path });
+        
+        // Only the streams and the mixer hold senders from here on, so the
+        // channel disconnects once they have all shut down
+        drop(status_tx);
+        
+        // Wait until stopped, reporting stream problems as they arrive
+        let mut stream_errors = 0u64;
+        let mut stream_failure: Option<cpal::StreamError> = None;
+        let mut stop_reason: Option<StopReason> = None;
+        let mut reported_overruns = (0u64, 0u64);
+        while self.running.load(Ordering::SeqCst) {
+            let deadline = shared.deadline.lock().ok().and_then(|deadline| *deadline);
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                console_out!("\nTime limit reached, stopping recording...");
+                stop_reason.get_or_insert(StopReason::TimeLimit);
+                self.running.store(false, Ordering::SeqCst);
+                break;
+            }
+            
+            // Report queue overruns since the last pass
+            let overruns = (
+                mic_overruns.load(Ordering::Relaxed),
+                sys_overruns.as_ref().map_or(0, |count| count.load(Ordering::Relaxed)),
+            );
+            if overruns.0 > reported_overruns.0 {
+                self.events.emit(RecorderEvent::Dropout {
+                    source: "microphone",
+                    count: overruns.0 - reported_overruns.0,
+                });
+            }
+            if overruns.1 > reported_overruns.1 {
+                self.events.emit(RecorderEvent::Dropout {
+                    source: "system audio",
+                    count: overruns.1 - reported_overruns.1,
+                });
+            }
+            reported_overruns = overruns;
+            
+            let poll_interval = deadline.map_or(STATUS_POLL_INTERVAL, |deadline| {
+                deadline.saturating_duration_since(Instant::now()).min(STATUS_POLL_INTERVAL)
+            });
+            match status_rx.recv_timeout(poll_interval) {
+                Ok(Status::StreamError { source, error: cpal::StreamError::DeviceNotAvailable }) => {
+                    console_err!("\n{} device is no longer available, stopping recording...", source);
+                    self.events.emit(RecorderEvent::Error {
+                        message: format!("{} device is no longer available", source),
+                    });
+                    stream_failure = Some(cpal::StreamError::DeviceNotAvailable);
+                    stop_reason.get_or_insert(StopReason::DeviceLost);
+                    self.running.store(false, Ordering::SeqCst);
                 }
-                
-                let samples: Vec<i16> = data.iter()
-                    .map(|&s| (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
-                    .collect();
-                
-                if let Err(e) = mic_tx_clone.send(samples) {
-                    eprintln!("Error sending mic samples: {}", e);
+                Ok(Status::StreamError { source, error }) => {
+                    console_err!("{} stream error: {}", source, error);
+                    self.events.emit(RecorderEvent::Error {
+                        message: format!("{} stream error: {}", source, error),
+                    });
+                    stream_errors += 1;
                 }
-            },
-            |err| eprintln!("Microphone stream error: {}", err),
-            None,
-        )?;
-        
-        // Build system audio stream if selected  
-        let sys_stream = if let (Some(dev), Some(config), Some(tx)) = 
-            (self.sys_device.as_ref(), self.sys_config.as_ref(), sys_tx.as_ref()) {
-            let sys_tx_clone = tx.clone();
-            let sys_running = self.running.clone();
-            
-            let stream = dev.build_input_stream(
-                &config.clone().into(),
-                move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                    if !sys_running.load(Ordering::SeqCst) {
-                        return;
+                Ok(Status::LowDiskSpace) => {
+                    stop_reason.get_or_insert(StopReason::LowDiskSpace);
+                }
+                Ok(Status::InputEnded { source, error }) => {
+                    if let Some(error) = error {
+                        console_err!("{} input could not be read: {}", source, error);
+                        self.events.emit(RecorderEvent::Error {
+                            message: format!("{} input could not be read: {}", source, error),
+                        });
                     }
-                    
-                    let samples: Vec<i16> = data.iter()
-                        .map(|&s| (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
-                        .collect();
-                    
-                    if let Err(e) = sys_tx_clone.send(samples) {
-                        eprintln!("Error sending system audio samples: {}", e);
+                    live_inputs -= 1;
+                    if live_inputs == 0 && !has_live_input {
+                        console_out!("\nEnd of input reached, stopping recording...");
+                        stop_reason.get_or_insert(StopReason::EndOfInput);
+                        self.running.store(false, Ordering::SeqCst);
                     }
-                },
-                |err| eprintln!("System audio stream error: {}", err),
-                None,
-            )?;
-            
-            Some(stream)
-        } else {
-            None
+                }
+                // The mixer only finishes once the streams are dropped below
+                Ok(Status::MixerFinished(_)) => {}
+                Err(_) => {}
+            }
+        }
+        
+        // Stop capturing; this drops the senders the streams and file threads
+        // own, which signals completion to the mixer
+        self.state.set(RecordingState::Stopping);
+        // A capture that fails to stop is still dropped, so the teardown goes
+        // on and the first error is returned once the output is in place
+        let mut stopped = mic_capture.stop();
+        if let Some(capture) = sys_capture {
+            stopped = stopped.and(capture.stop());
+        }
+        
+        // Wait for the mixer to finish and finalize
+        let mut mixer_result = None;
+        for status in status_rx.iter() {
+            match status {
+                Status::MixerFinished(result) => {
+                    mixer_result = Some(result);
+                    break;
+                }
+                Status::LowDiskSpace => {
+                    stop_reason.get_or_insert(StopReason::LowDiskSpace);
+                }
+                Status::StreamError { .. } | Status::InputEnded { .. } => {}
+            }
+        }
+        mixer_handle.join()
+            .map_err(|_| RecorderError::Thread("mixer thread panicked"))?;
+        let stats = match mixer_result.ok_or(RecorderError::Thread("mixer exited without reporting a result"))? {
+            Ok(stats) => stats,
+            // Without a finalized header the partial file is left for `repair`
+            Err(MixerError { cause, finalized: false, stats }) => {
+                return Err(self.incomplete(IncompleteRecording {
+                    id,
+                    path: part_path,
+                    reason: StopReason::WriteError,
+                    finalized: false,
+                    duration: stats.duration(&combined_spec),
+                    cause,
+                }));
+            }
+            Err(MixerError { cause, finalized: true, stats }) => {
+                std::fs::rename(&part_path, &combined_path)?;
+                return Err(self.incomplete(IncompleteRecording {
+                    id,
+                    path: self.encrypt(config, combined_path),
+                    reason: StopReason::WriteError,
+                    finalized: true,
+                    duration: stats.duration(&combined_spec),
+                    cause,
+                }));
+            }
         };
         
-        // Start recording
-        println!("\n=== Recording Started ===");
-        println!("Recording to: {}", combined_filename);
-        println!("Format: {} channels, {} Hz", output_channels, output_sample_rate);
-        println!("Microphone: {} channels, {} Hz", mic_channels, mic_sample_rate);
-        if let Some(config) = self.sys_config.as_ref() {
-            println!("System audio: {} channels, {} Hz", config.channels(), config.sample_rate().0);
+        // Without a file there is nothing to salvage from a lost device
+        if !self.wav_output {
+            if let Some(cause) = stream_failure {
+                self.events.emit(RecorderEvent::Stopped {
+                    reason: StopReason::DeviceLost,
+                    duration: stats.duration(&combined_spec),
+                });
+                return Err(StreamError::from(cause).into());
+            }
         }
-        println!("\nPress Ctrl+C to stop recording...\n");
         
-        mic_stream.play()?;
-        match &sys_stream {
-            Some(stream) => stream.play()?,
-            None => {}
+        // Publish the finished file under its final name in one atomic step
+        if self.wav_output {
+            std::fs::rename(&part_path, &combined_path)?;
         }
+        stopped?;
+        // Transcribe before encryption removes the plaintext
+        let transcripts = if self.wav_output && stream_failure.is_none() {
+            self.transcribe(config, &combined_path)
+        } else {
+            Vec::new()
+        };
+        let transcripts: Vec<PathBuf> = transcripts.into_iter().map(|path| self.encrypt(config, path)).collect();
+        let marker_exports = if self.wav_output {
+            let frames = stats.samples_written / combined_spec.channels as u64;
+            self.export_markers(config, &combined_path, &stats.markers, combined_spec.sample_rate, frames)
+        } else {
+            Vec::new()
+        };
+        let marker_exports: Vec<PathBuf> = marker_exports.into_iter().map(|path| self.encrypt(config, path)).collect();
+        let saved_path = if self.wav_output { self.encrypt(config, combined_path.clone()) } else { combined_path.clone() };
+        // A failed encoder was already reported when it was dropped
+        let compressed_path = compressed_path
+            .filter(|path| path.exists())
+            .map(|path| self.encrypt(config, path));
         
-        // Wait until Ctrl+C
-        while self.running.load(Ordering::SeqCst) {
-            std::thread::sleep(std::time::Duration::from_millis(100));
+        if let Some(cause) = stream_failure {
+            return Err(self.incomplete(IncompleteRecording {
+                id,
+                path: saved_path,
+                reason: StopReason::DeviceLost,
+                finalized: true,
+                duration: stats.duration(&combined_spec),
+                cause: Box::new(cause),
+            }));
         }
         
-        // Stop streams
-        mic_stream.pause()?;
-        match &sys_stream {
-            Some(stream) => stream.pause()?,
-            None => {}
+        console_out!("\n=== Recording Complete ===");
+        if self.wav_output {
+            console_out!("Saved recording: {}", saved_path.display());
+            
+            // Check file size
+            let file_size = std::fs::metadata(&saved_path)?.len();
+            console_out!("\nFile size: {} bytes ({:.2} KB)", file_size, file_size as f64 / 1024.0);
+        }
+        if let Some(path) = &compressed_path {
+            let file_size = std::fs::metadata(path)?.len();
+            console_out!("Saved compressed copy: {} ({:.2} KB)", path.display(), file_size as f64 / 1024.0);
+        }
+        console_out!(
+            "Duration: {:.1}s ({} frames at {} Hz)",
+            stats.duration(&combined_spec).as_secs_f64(),
+            stats.samples_written / combined_spec.channels as u64,
+            combined_spec.sample_rate
+        );
+        console_out!("Mix level: peak {:.2}, RMS {:.2}", stats.mix_levels.peak, stats.mix_levels.rms);
+        report_summary("Microphone", &stats.mic_summary);
+        if self.sys.is_some() {
+            report_summary("System audio", &stats.sys_summary);
         }
         
-        // Drop streams and channels to signal completion
-        drop(mic_stream);
-        drop(mic_tx);
-        drop(sys_stream);
-        if let Some(tx) = sys_tx {
-            drop(tx);
+        let backpressure_events = mic_overruns.load(Ordering::Relaxed)
+            + sys_overruns.map_or(0, |count| count.load(Ordering::Relaxed));
+        if backpressure_events > 0 {
+            console_out!(
+                "Backpressure policy ({:?}) triggered {} times",
+                config.backpressure_policy, backpressure_events
+            );
+        }
+        if stats.backlog_events > 0 {
+            console_out!(
+                "Backlog policy ({:?}) triggered {} times",
+                config.backlog_policy, stats.backlog_events
+            );
+        }
+        if stream_errors > 0 {
+            console_out!("Capture streams reported {} errors", stream_errors);
         }
         
-        // Wait for mixer thread to finish and finalize
-        mixer_handle.join()
-            .map_err(|_| "Failed to join mixer thread")?;
+        let stop_reason = stop_reason.unwrap_or(StopReason::Requested);
+        let mut result = RecordingResult {
+            id,
+            filename: self.wav_output.then(|| saved_path.to_string_lossy().into_owned()),
+            title: self.title.clone(),
+            stop_reason,
+            duration: stats.duration(&combined_spec),
+            spec: combined_spec,
+            mic_samples: stats.mic_samples,
+            sys_samples: stats.sys_samples,
+            samples_written: stats.samples_written,
+            frames_written: stats.samples_written / combined_spec.channels as u64,
+            mic_levels: stats.mic_levels,
+            sys_levels: self.sys.is_some().then_some(stats.sys_levels),
+            mix_levels: stats.mix_levels,
+            mic_summary: stats.mic_summary,
+            sys_summary: self.sys.is_some().then_some(stats.sys_summary),
+            backpressure_events,
+            backlog_events: stats.backlog_events,
+            stream_errors,
+            markers: stats.markers,
+            marker_exports,
+            transcripts,
+            upload: None,
+        };
         
-        println!("\n=== Recording Complete ===");
-        println!("Saved recording: {}", combined_filename);
+        // Upload everything but the sidecar first, so the sidecar can record how that went
+        if let Some(options) = &config.upload {
+            let mut files: Vec<PathBuf> = self.wav_output.then(|| saved_path.clone()).into_iter().collect();
+            files.extend(compressed_path);
+            files.extend(Some(checksum::checksum_path(&combined_path)).filter(|path| config.checksum && path.exists()));
+            files.extend(result.marker_exports.iter().cloned());
+            files.extend(result.transcripts.iter().cloned());
+            result.upload = Some(UploadReport {
+                destination: options.destination.location(),
+                files: self.upload(options, &files),
+            });
+        }
         
-        // Check file size
-        let file_size = std::fs::metadata(&combined_filename)?.len();
-        println!("\nFile size: {} bytes ({:.2} KB)", file_size, file_size as f64 / 1024.0);
+        // The recording is safe either way, so a sidecar that can't be written is only reported
+        if self.wav_output && config.sidecar {
+            let sidecar = sidecar::sidecar_json(&result, now, &self.mic, self.sys.as_ref(), config);
+            match sidecar::write_sidecar(&combined_path, &sidecar) {
+                Ok(path) => {
+                    console_out!("Saved metadata: {}", path.display());
+                    if let (Some(options), Some(report)) = (&config.upload, &mut result.upload) {
+                        report.files.extend(self.upload(options, &[path]));
+                    }
+                }
+                Err(e) => {
+                    console_err!("Warning: could not write metadata sidecar: {}", e);
+                    self.events.emit(RecorderEvent::Error {
+                        message: format!("Could not write metadata sidecar: {}", e),
+                    });
+                }
+            }
+        }
         
-        Ok(RecordingResult {
-            filename: combined_filename,
-        })
+        self.events.emit(RecorderEvent::Stopped {
+            reason: stop_reason,
+            duration: result.duration,
+        });
+        Ok(result)
+    }
+    
+    /// Open `input` and have it deliver audio to `tx`; it starts once [`Capture::play`] is called
+    ///
+    /// `source` names the input in messages.
+    fn start_capture(
+        &self,
+        input: &Input,
+        source: &'static str,
+        tx: SampleSender,
+        status: &crossbeam_channel::Sender<Status>,
+        thread_priority: ThreadPriority,
+    ) -> Result<Capture> {
+        let running = self.running.clone();
+        let state = self.state.clone();
+        match input {
+            Input::Device { device, config } => {
+                let error_status = status.clone();
+                let mut promoted = false;
+                let stream = device.build_input_stream(
+                    &config.clone().into(),
+                    move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                        if !running.load(Ordering::SeqCst) || state.get() == RecordingState::Paused {
+                            return;
+                        }
+                        
+                        // Callbacks run on a thread owned by the audio backend, so
+                        // the priority can only be applied from inside the callback
+                        if !promoted {
+                            promoted = true;
+                            if let Err(e) = priority::promote_current_thread(thread_priority) {
+                                console_err!("Warning: {} capture thread: {}", source, e);
+                            }
+                        }
+                        
+                        let mut samples = tx.take_buffer();
+                        samples.resize(data.len(), 0);
+                        dsp::convert_f32_to_i16(data, &mut samples);
+                        
+                        if let Err(e) = tx.send(samples) {
+                            console_err!("Error sending {} samples: {}", source, e);
+                        }
+                    },
+                    move |error| {
+                        let _ = error_status.send(Status::StreamError { source, error });
+                    },
+                    None,
+                ).map_err(StreamError::from)?;
+                Ok(Capture::Stream(stream))
+            }
+            Input::File(file) => {
+                let mut reader = file.reader()?;
+                let chunk_len = file.chunk_len();
+                let format = file.format();
+                let status = status.clone();
+                let (play_tx, play_rx) = crossbeam_channel::bounded::<()>(1);
+                let handle = thread::spawn(move || {
+                    if play_rx.recv().is_err() {
+                        return;
+                    }
+                    if let Err(e) = priority::promote_current_thread(thread_priority) {
+                        console_err!("Warning: {} file thread: {}", source, e);
+                    }
+                    
+                    // Deliver chunks at the file's own rate, like a device would
+                    let mut started = Instant::now();
+                    let mut frames_played = 0u64;
+                    let mut error = None;
+                    while running.load(Ordering::SeqCst) {
+                        if state.get() == RecordingState::Paused {
+                            thread::sleep(FILE_CHUNK);
+                            started = Instant::now();
+                            frames_played = 0;
+                            continue;
+                        }
+                        
+                        let mut samples = tx.take_buffer();
+                        if let Err(e) = reader.read_chunk(&mut samples, chunk_len) {
+                            error = Some(e);
+                            break;
+                        }
+                        if samples.is_empty() {
+                            break;
+                        }
+                        frames_played += (samples.len() / format.channels as usize) as u64;
+                        if tx.send(samples).is_err() {
+                            break;
+                        }
+                        
+                        let due = started + Duration::from_secs_f64(frames_played as f64 / format.sample_rate as f64);
+                        thread::sleep(due.saturating_duration_since(Instant::now()));
+                    }
+                    let _ = status.send(Status::InputEnded { source, error });
+                });
+                Ok(Capture::Thread { play: play_tx, handle })
+            }
+            Input::Rtp(rtp) => {
+                let socket = rtp.bind()?;
+                let mut decoder = rtp.decoder()?;
+                let status = status.clone();
+                let (play_tx, play_rx) = crossbeam_channel::bounded::<()>(1);
+                let handle = thread::spawn(move || {
+                    if play_rx.recv().is_err() {
+                        return;
+                    }
+                    if let Err(e) = priority::promote_current_thread(thread_priority) {
+                        console_err!("Warning: {} RTP thread: {}", source, e);
+                    }
+                    
+                    let mut packet = vec![0; rtp::MAX_PACKET];
+                    let mut malformed = 0u64;
+                    let mut error = None;
+                    while running.load(Ordering::SeqCst) {
+                        let len = match socket.recv(&mut packet) {
+                            Ok(len) => len,
+                            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => continue,
+                            Err(e) => {
+                                error = Some(e.into());
+                                break;
+                            }
+                        };
+                        if state.get() == RecordingState::Paused {
+                            decoder.resync();
+                            continue;
+                        }
+                        
+                        let mut samples = tx.take_buffer();
+                        if let Err(e) = decoder.decode(&packet[..len], &mut samples) {
+                            if malformed == 0 {
+                                console_err!("Warning: {} received a packet it could not decode: {}", source, e);
+                            }
+                            malformed += 1;
+                            continue;
+                        }
+                        if !samples.is_empty() && tx.send(samples).is_err() {
+                            break;
+                        }
+                    }
+                    if malformed > 1 {
+                        console_err!("{} dropped {} packets it could not decode", source, malformed);
+                    }
+                    let _ = status.send(Status::InputEnded { source, error });
+                });
+                Ok(Capture::Thread { play: play_tx, handle })
+            }
+        }
+    }
+    
+    /// Encrypt a finished file if configured, returning where it ended up
+    ///
+    /// A failure is reported and leaves the plaintext file in place.
+    fn encrypt(&self, config: &Config, path: PathBuf) -> PathBuf {
+        let Some(encryption) = &config.encryption else {
+            return path;
+        };
+        match encryption.encrypt_file(&path) {
+            Ok(encrypted) => encrypted,
+            Err(e) => {
+                console_err!("Warning: could not encrypt {}: {}", path.display(), e);
+                self.events.emit(RecorderEvent::Error {
+                    message: format!("Could not encrypt {}: {}", path.display(), e),
+                });
+                path
+            }
+        }
+    }
+    
+    /// Transcribe the finished recording at `path`, returning the transcripts written
+    ///
+    /// Failures are reported and leave the recording as it is.
+    #[cfg(feature = "whisper")]
+    fn transcribe(&self, config: &Config, path: &Path) -> Vec<PathBuf> {
+        let Some(options) = &config.transcription else {
+            return Vec::new();
+        };
+        console_out!("Transcribing {}...", path.display());
+        let result = transcription::transcribe(path, options).and_then(|segments| {
+            Ok(transcription::write_transcripts(path, &segments, &options.formats)?)
+        });
+        match result {
+            Ok(paths) => {
+                for path in &paths {
+                    console_out!("Saved transcript: {}", path.display());
+                }
+                paths
+            }
+            Err(e) => {
+                console_err!("Warning: could not transcribe {}: {}", path.display(), e);
+                self.events.emit(RecorderEvent::Error {
+                    message: format!("Could not transcribe {}: {}", path.display(), e),
+                });
+                Vec::new()
+            }
+        }
+    }
+
+    /// Nothing to transcribe: [`Config::validate`] refuses `transcription` in builds without `whisper`
+    #[cfg(not(feature = "whisper"))]
+    fn transcribe(&self, _config: &Config, _path: &Path) -> Vec<PathBuf> {
+        Vec::new()
+    }
+    
+    /// Export `markers` next to the recording at `path` in the configured formats, returning the files written
+    fn export_markers(&self, config: &Config, path: &Path, markers: &[Marker], sample_rate: u32, frames: u64) -> Vec<PathBuf> {
+        if markers.is_empty() || config.marker_exports.is_empty() {
+            return Vec::new();
+        }
+        match marker::write_exports(path, markers, sample_rate, frames, &config.marker_exports) {
+            Ok(paths) => {
+                for path in &paths {
+                    console_out!("Saved markers: {}", path.display());
+                }
+                paths
+            }
+            Err(e) => {
+                console_err!("Warning: could not export markers of {}: {}", path.display(), e);
+                self.events.emit(RecorderEvent::Error {
+                    message: format!("Could not export markers of {}: {}", path.display(), e),
+                });
+                Vec::new()
+            }
+        }
+    }
+    
+    /// Upload each of `paths` as `options` say, returning how each went
+    ///
+    /// Failures are reported and leave the local files as they are.
+    #[cfg(feature = "upload")]
+    fn upload(&self, options: &Upload, paths: &[PathBuf]) -> Vec<FileUpload> {
+        paths
+            .iter()
+            .map(|path| {
+                console_out!("Uploading {}...", path.display());
+                let file = upload::upload_file(options, path);
+                match &file.error {
+                    None => console_out!("Uploaded {}", file.url),
+                    Some(e) => {
+                        console_err!("Warning: could not upload {} to {}: {}", path.display(), file.url, e);
+                        self.events.emit(RecorderEvent::Error {
+                            message: format!("Could not upload {} to {}: {}", path.display(), file.url, e),
+                        });
+                    }
+                }
+                file
+            })
+            .collect()
+    }
+
+    /// Never has files to send, since a config with `upload` fails to validate without the feature
+    #[cfg(not(feature = "upload"))]
+    fn upload(&self, _options: &Upload, _paths: &[PathBuf]) -> Vec<FileUpload> {
+        Vec::new()
+    }
+    
+    /// Announce how session `id` ended to the configured webhook
+    ///
+    /// A webhook that can't be reached is reported, never an error of the recording.
+    #[cfg(feature = "webhook")]
+    fn notify(&self, config: &Config, id: Uuid, outcome: &Result<RecordingResult>) {
+        let Some(webhook) = &config.webhook else {
+            return;
+        };
+        if let Err(e) = webhook.send(&webhook.message(id, outcome)) {
+            console_err!("Warning: could not notify webhook {}: {}", webhook.url, e);
+            self.events.emit(RecorderEvent::Error {
+                message: format!("Could not notify webhook {}: {}", webhook.url, e),
+            });
+        }
+    }
+
+    /// Builds without `webhook` can't be configured with one, so there is no one to tell
+    #[cfg(not(feature = "webhook"))]
+    fn notify(&self, _config: &Config, _id: Uuid, _outcome: &Result<RecordingResult>) {}
+    
+    /// Announce a session that ended early and turn it into an error
+    fn incomplete(&self, recording: IncompleteRecording) -> RecorderError {
+        self.events.emit(RecorderEvent::Stopped {
+            reason: recording.reason,
+            duration: recording.duration,
+        });
+        recording.into()
     }
     
     /// Stop the recording
@@ -377,9 +1339,546 @@ impl Recorder {
     }
 }
 
+/// Handle to a recording running in the background
+///
+/// Dropping the handle without calling [`join`](Self::join) leaves the
+/// recording running until the recorder is stopped.
+pub struct RecordingSession {
+    id: Uuid,
+    path: Option<PathBuf>,
+    running: Arc<AtomicBool>,
+    state: SharedState,
+    shared: Arc<SessionShared>,
+    handle: thread::JoinHandle<Result<RecordingResult>>,
+}
+
+impl RecordingSession {
+    /// Unique ID of the session, also reported in its [`RecordingResult`]
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+    
+    /// Final path of the recording (written under a `.part` name until finalized),
+    /// unless WAV output is turned off
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+    
+    /// Ask the recording to stop; use [`join`](Self::join) to wait for it to finish
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::SeqCst);
+    }
+    
+    /// Stop and finalize the recording on its own at `deadline`
+    ///
+    /// Replaces any deadline set before.
+    pub fn stop_at(&self, deadline: Instant) {
+        if let Ok(mut current) = self.shared.deadline.lock() {
+            *current = Some(deadline);
+        }
+    }
+    
+    /// Stop and finalize the recording on its own once `duration` has passed
+    pub fn stop_after(&self, duration: Duration) {
+        self.stop_at(Instant::now() + duration);
+    }
+    
+    /// What the session is doing
+    pub fn state(&self) -> RecordingState {
+        self.state.get()
+    }
+    
+    /// Peak and RMS levels over the last tenth of a second or so, for live meters
+    pub fn levels(&self) -> MixLevels {
+        self.shared.levels.lock().map_or_else(|_| MixLevels::default(), |levels| *levels)
+    }
+    
+    /// Mark the current point of the recording with `label`
+    ///
+    /// The marker points at the end of the audio written so far. WAV
+    /// recordings carry markers as cue points with labels.
+    pub fn add_marker(&self, label: impl Into<String>) -> Marker {
+        let marker = Marker {
+            position: self.shared.frames_written.load(Ordering::Relaxed),
+            label: label.into(),
+        };
+        if let Ok(mut markers) = self.shared.markers.lock() {
+            markers.push(marker.clone());
+        }
+        marker
+    }
+    
+    /// Sample rate of the recording, which marker positions count frames of
+    pub fn sample_rate(&self) -> u32 {
+        self.shared.sample_rate.load(Ordering::Relaxed)
+    }
+    
+    /// Length of the audio written so far, which leaves out paused stretches
+    pub fn duration(&self) -> Duration {
+        let frames = self.shared.frames_written.load(Ordering::Relaxed);
+        Duration::from_secs_f64(frames as f64 / self.sample_rate().max(1) as f64)
+    }
+    
+    /// Suspend capture, discarding audio until [`resume`](Self::resume)
+    ///
+    /// Returns false if the session wasn't recording.
+    pub fn pause(&self) -> bool {
+        self.state.transition(RecordingState::Recording, RecordingState::Paused)
+    }
+    
+    /// Continue capturing after [`pause`](Self::pause)
+    ///
+    /// Returns false if the session wasn't paused.
+    pub fn resume(&self) -> bool {
+        self.state.transition(RecordingState::Paused, RecordingState::Recording)
+    }
+    
+    /// Whether the recording is still capturing audio
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst) && !self.handle.is_finished()
+    }
+    
+    /// Wait for the recording to stop and its file to be finalized
+    pub fn join(self) -> Result<RecordingResult> {
+        self.handle.join().map_err(|_| RecorderError::Thread("session thread panicked"))?
+    }
+}
+
+/// A running input of a session
+enum Capture {
+    /// A device stream, whose callback feeds the mixer
+    Stream(cpal::Stream),
+    /// A thread playing a file or receiving a network stream, waiting for the signal to start
+    Thread {
+        play: crossbeam_channel::Sender<()>,
+        handle: thread::JoinHandle<()>,
+    },
+}
+
+impl Capture {
+    fn play(&self) -> Result<()> {
+        match self {
+            Self::Stream(stream) => stream.play().map_err(StreamError::from)?,
+            Self::Thread { play, .. } => {
+                let _ = play.send(());
+            }
+        }
+        Ok(())
+    }
+    
+    /// Stop delivering audio, once the running flag has been cleared
+    fn stop(self) -> Result<()> {
+        match self {
+            Self::Stream(stream) => stream.pause().map_err(StreamError::from)?,
+            Self::Thread { play, handle } => {
+                drop(play);
+                handle.join().map_err(|_| RecorderError::Thread("input thread panicked"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Events reported to the recording thread while a session runs
+enum Status {
+    /// A capture stream reported an error
+    StreamError {
+        source: &'static str,
+        error: cpal::StreamError,
+    },
+    /// A file input was played through, or an input could not be read any further
+    InputEnded {
+        source: &'static str,
+        error: Option<hound::Error>,
+    },
+    /// The mixer stopped the recording because the disk is nearly full
+    LowDiskSpace,
+    /// The mixer has stopped and finalized the output file, or failed to
+    MixerFinished(Result<MixerStats, MixerError>),
+}
+
+/// Totals from a mixer run
+struct MixerStats {
+    markers: Vec<Marker>,
+    mic_samples: u64,
+    sys_samples: u64,
+    samples_written: u64,
+    backlog_events: u64,
+    mic_levels: dsp::Levels,
+    sys_levels: dsp::Levels,
+    mix_levels: dsp::Levels,
+    mic_summary: AudioSummary,
+    sys_summary: AudioSummary,
+}
+
+impl MixerStats {
+    /// Length of the audio written, for a stream of `spec`
+    fn duration(&self, spec: &WavSpec) -> Duration {
+        samples_duration(self.samples_written, spec)
+    }
+}
+
+/// A mixer run cut short by a write failure
+struct MixerError {
+    /// First error the WAV sink hit while writing, checkpointing or finalizing
+    cause: SinkError,
+    /// Whether the WAV header was still finalized to cover the written audio
+    finalized: bool,
+    stats: MixerStats,
+}
+
+/// An output the mixer writes to
+struct OutputSink {
+    sink: Box<dyn Sink>,
+    /// The session's WAV file, whose failure ends the recording
+    primary: bool,
+}
+
+/// Write mixed audio to every sink, checkpointing them if `checkpoint` is set
+///
+/// A failing secondary sink is reported and dropped, so it can't take the
+/// recording down with it; a failure of the primary sink is returned.
+fn write_sinks(
+    sinks: &mut Vec<OutputSink>,
+    samples: &[i16],
+    checkpoint: bool,
+    events: &EventBus,
+) -> std::result::Result<(), SinkError> {
+    let mut failure = None;
+    sinks.retain_mut(|output| {
+        let result = output.sink.write_frames(samples).and_then(|()| {
+            if checkpoint {
+                output.sink.checkpoint()
+            } else {
+                Ok(())
+            }
+        });
+        match result {
+            Ok(()) => true,
+            Err(e) if output.primary => {
+                failure = Some(e);
+                true
+            }
+            Err(e) => {
+                report_sink_failure(&e, events);
+                false
+            }
+        }
+    });
+    failure.map_or(Ok(()), Err)
+}
+
+/// `title` in lower case, with runs of anything but letters and digits
+/// turned into single dashes, cut to a length file systems take
+fn file_name_part(title: &str) -> String {
+    let mut name = String::new();
+    let mut chars = 0;
+    for c in title.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            name.push(c);
+            chars += 1;
+        } else if !name.is_empty() && !name.ends_with('-') {
+            name.push('-');
+            chars += 1;
+        }
+        if chars >= MAX_TITLE_CHARS {
+            break;
+        }
+    }
+    name.trim_end_matches('-').to_string()
+}
+
+/// Path for a recording named `stem`, numbered if an earlier session already took the name
+///
+/// Back-to-back sessions can start within the same minute.
+fn unused_recording_path(config: &Config, stem: &str, extension: &str) -> PathBuf {
+    let mut path = config.recording_path(&format!("{}.{}", stem, extension));
+    let mut number = 2;
+    while path.exists() || wav::partial_path(&path).exists() {
+        path = config.recording_path(&format!("{}-{}.{}", stem, number, extension));
+        number += 1;
+    }
+    path
+}
+
+/// Finalize every sink, returning the primary sink's error if it failed
+fn finalize_sinks(sinks: Vec<OutputSink>, events: &EventBus) -> std::result::Result<(), SinkError> {
+    let mut result = Ok(());
+    for output in sinks {
+        match output.sink.finalize() {
+            Ok(()) => {}
+            Err(e) if output.primary => result = Err(e),
+            Err(e) => report_sink_failure(&e, events),
+        }
+    }
+    result
+}
+
+fn report_sink_failure(error: &SinkError, events: &EventBus) {
+    console_err!("\nWarning: output sink failed and was dropped: {}", error);
+    events.emit(RecorderEvent::Error {
+        message: format!("Output sink failed and was dropped: {}", error),
+    });
+}
+
+/// Hand freshly mixed audio to frame subscribers, starting `offset` samples in
+fn publish_frame(frames: &Broadcast<Frame>, samples: &[i16], offset: u64, spec: &WavSpec) {
+    if samples.is_empty() || !frames.has_subscribers() {
+        return;
+    }
+    frames.emit(Frame {
+        timestamp: samples_duration(offset, spec),
+        samples: samples.to_vec(),
+        sample_rate: spec.sample_rate,
+        channels: spec.channels,
+    });
+}
+
+/// Sink streaming the session's audio to `options`' endpoint
+#[cfg(feature = "streaming")]
+fn stream_sink(options: &Streaming, config: &Config, context: &SinkContext) -> Result<Box<dyn Sink>> {
+    let sink = StreamSink::spawn(options, config.bitrate, context).map_err(RecorderError::Sink)?;
+    Ok(Box::new(sink))
+}
+
+/// Without the `streaming` feature there is nothing to stream with
+#[cfg(not(feature = "streaming"))]
+fn stream_sink(_options: &Streaming, _config: &Config, _context: &SinkContext) -> Result<Box<dyn Sink>> {
+    Err(ConfigError::StreamingUnavailable.into())
+}
+
+/// Sink serving the session's audio to WebSocket clients as `options` say
+#[cfg(feature = "live")]
+fn live_sink(options: &LiveServer, config: &Config, context: &SinkContext) -> Result<Box<dyn Sink>> {
+    let sink = LiveSink::spawn(options, config.bitrate, context).map_err(RecorderError::Sink)?;
+    Ok(Box::new(sink))
+}
+
+/// Without the `live` feature there is nothing to serve with
+#[cfg(not(feature = "live"))]
+fn live_sink(_options: &LiveServer, _config: &Config, _context: &SinkContext) -> Result<Box<dyn Sink>> {
+    Err(ConfigError::LiveUnavailable.into())
+}
+
+/// Print a source's summary, calling out a source that was too hot or effectively dead
+fn report_summary(name: &str, summary: &AudioSummary) {
+    console_out!("{}: {}", name, summary);
+    if summary.clipped() {
+        console_err!("Warning: {} clipped {} times; lower its input gain", name.to_lowercase(), summary.clipped_samples);
+    }
+    if summary.mostly_silent() {
+        console_err!(
+            "Warning: {} was silent for {:.0}% of the recording; check that it is connected and not muted",
+            name.to_lowercase(),
+            summary.silence * 100.0
+        );
+    }
+}
+
+/// Length of `samples` interleaved samples of a stream with `spec`
+fn samples_duration(samples: u64, spec: &WavSpec) -> Duration {
+    let frames = samples / spec.channels.max(1) as u64;
+    Duration::from_secs_f64(frames as f64 / spec.sample_rate.max(1) as f64)
+}
+
+/// Why a recording session ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StopReason {
+    /// Stopped by the user, a signal, or [`Recorder::stop`]
+    Requested,
+    /// A deadline set with [`RecordingSession::stop_at`] or [`Recorder::record_for`] passed
+    TimeLimit,
+    /// Free space on the output volume fell below the configured minimum
+    LowDiskSpace,
+    /// A capture device disappeared mid-recording
+    DeviceLost,
+    /// Writing the output file failed
+    WriteError,
+    /// Every input was a file, and all of them have been played through
+    EndOfInput,
+}
+
 /// Result of a recording session
 #[derive(Debug)]
 pub struct RecordingResult {
-    pub filename: String,
+    /// Unique ID of the session, for correlating files, transcripts and logs
+    pub id: Uuid,
+    /// Path of the saved recording, unless WAV output was turned off
+    pub filename: Option<String>,
+    /// What the recording was named after, if the recorder was given a title
+    pub title: Option<String>,
+    pub stop_reason: StopReason,
+    /// Length of the recorded audio
+    pub duration: Duration,
+    /// Format of the output
+    pub spec: WavSpec,
+    /// Samples received from the microphone, before upmixing or resampling
+    pub mic_samples: u64,
+    /// Samples received from system audio, before upmixing or resampling
+    pub sys_samples: u64,
+    /// Interleaved samples mixed into the output
+    pub samples_written: u64,
+    /// Sample frames (one sample per channel) mixed into the output
+    pub frames_written: u64,
+    /// Microphone levels over the whole session
+    pub mic_levels: dsp::Levels,
+    /// System audio levels over the whole session, if it was recorded
+    pub sys_levels: Option<dsp::Levels>,
+    /// Levels of the mixed output over the whole session
+    pub mix_levels: dsp::Levels,
+    /// Peak, loudness, clipping and silence of the microphone
+    pub mic_summary: AudioSummary,
+    /// Peak, loudness, clipping and silence of system audio, if it was recorded
+    pub sys_summary: Option<AudioSummary>,
+    /// Number of times a full source queue triggered the backpressure policy
+    pub backpressure_events: u64,
+    /// Number of times the mixer trimmed a source that ran too far ahead
+    pub backlog_events: u64,
+    /// Number of non-fatal errors reported by the capture streams
+    pub stream_errors: u64,
+    /// Markers set during the session, in order
+    pub markers: Vec<Marker>,
+    /// Marker exports written next to the recording, if `marker_exports` is set and there were markers
+    pub marker_exports: Vec<PathBuf>,
+    /// Transcripts written next to the recording, if transcription is configured
+    pub transcripts: Vec<PathBuf>,
+    /// What was uploaded where, if uploading is configured
+    pub upload: Option<UploadReport>,
+}
+
+impl RecordingResult {
+    /// Times audio was dropped or padded because a source or the mixer fell behind
+    pub fn dropouts(&self) -> u64 {
+        self.backpressure_events + self.backlog_events
+    }
 }
 
+/// A recording that stopped early, with whatever audio was saved
+///
+/// Returned as [`RecorderError::Incomplete`] from [`Recorder::record`] when a write failure or a lost
+/// device ends the session before it was stopped.
+#[derive(Debug)]
+pub struct IncompleteRecording {
+    /// Unique ID of the session
+    pub id: Uuid,
+    /// Where the audio captured before the failure ended up
+    pub path: PathBuf,
+    pub reason: StopReason,
+    /// Whether the file was finalized; if not, run `repair` on it
+    pub finalized: bool,
+    /// Length of the audio saved
+    pub duration: Duration,
+    /// What ended the recording
+    pub cause: Box<dyn std::error::Error + Send + Sync>,
+}
+
+impl fmt::Display for IncompleteRecording {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.finalized {
+            write!(
+                f,
+                "Recording stopped early: {}. {:.1}s of audio captured before the failure was saved to {}",
+                self.cause,
+                self.duration.as_secs_f64(),
+                self.path.display()
+            )
+        } else {
+            write!(
+                f,
+                "Recording stopped early: {}. The partial recording was left at {}; \
+                 run `meeting-recorder repair` on it once the problem is fixed",
+                self.cause,
+                self.path.display()
+            )
+        }
+    }
+}
+
+impl std::error::Error for IncompleteRecording {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.cause.as_ref())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Sink that fails after accepting `capacity` samples
+    struct LimitedSink {
+        capacity: usize,
+    }
+    
+    impl Sink for LimitedSink {
+        fn write_frames(&mut self, samples: &[i16]) -> std::result::Result<(), SinkError> {
+            self.capacity = self.capacity.checked_sub(samples.len()).ok_or("sink is full")?;
+            Ok(())
+        }
+        
+        fn finalize(self: Box<Self>) -> std::result::Result<(), SinkError> {
+            Ok(())
+        }
+    }
+    
+    #[test]
+    fn test_failing_secondary_sink_is_dropped() {
+        let events = EventBus::default();
+        let errors = events.subscribe();
+        let mut sinks = vec![
+            OutputSink { sink: Box::new(LimitedSink { capacity: 100 }), primary: true },
+            OutputSink { sink: Box::new(LimitedSink { capacity: 2 }), primary: false },
+        ];
+        
+        assert!(write_sinks(&mut sinks, &[0; 4], false, &events).is_ok());
+        assert_eq!(sinks.len(), 1);
+        assert!(matches!(errors.try_recv(), Ok(RecorderEvent::Error { .. })));
+        
+        assert!(write_sinks(&mut sinks, &[0; 200], false, &events).is_err());
+        assert_eq!(sinks.len(), 1);
+    }
+    
+    #[test]
+    fn test_incomplete_recording_points_at_repair_when_unfinalized() {
+        let error = IncompleteRecording {
+            id: Uuid::nil(),
+            path: PathBuf::from("meeting.wav.part"),
+            reason: StopReason::WriteError,
+            finalized: false,
+            duration: Duration::from_secs(90),
+            cause: "No space left on device".into(),
+        };
+        let message = error.to_string();
+        assert!(message.contains("meeting.wav.part"));
+        assert!(message.contains("repair"));
+    }
+    
+    #[test]
+    fn test_back_to_back_sessions_get_their_own_files() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = Config {
+            output_directory: temp_dir.path().to_string_lossy().into_owned(),
+            ..Config::default()
+        };
+        
+        let first = unused_recording_path(&config, "meeting", "wav");
+        assert_eq!(first, temp_dir.path().join("meeting.wav"));
+        std::fs::write(&first, b"").unwrap();
+        
+        // A session still writing under its temporary name also holds on to its name
+        let second = unused_recording_path(&config, "meeting", "wav");
+        assert_eq!(second, temp_dir.path().join("meeting-2.wav"));
+        std::fs::write(wav::partial_path(&second), b"").unwrap();
+        
+        assert_eq!(unused_recording_path(&config, "meeting", "wav"), temp_dir.path().join("meeting-3.wav"));
+    }
+    
+    #[test]
+    fn test_titles_become_file_names() {
+        assert_eq!(file_name_part("1:1 with Sam"), "1-1-with-sam");
+        assert_eq!(file_name_part("  Q3 Planning / Roadmap?! "), "q3-planning-roadmap");
+        assert_eq!(file_name_part("../.."), "");
+        assert_eq!(file_name_part(&"long ".repeat(40)).chars().count(), MAX_TITLE_CHARS - 1);
+    }
+}
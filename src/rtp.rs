@@ -0,0 +1,313 @@
+//! Audio received over RTP, as a recorder input
+//!
+//! An [`RtpSource`] listens on a UDP port for an RTP stream, such as one
+//! sent by conference hardware or by ffmpeg or GStreamer on another
+//! machine, and feeds it to the mixer like a capture device. Payloads are
+//! L16, big-endian 16-bit PCM as in RFC 3551, or, with the `opus` feature,
+//! Opus as in RFC 7587. Packets arriving late or twice are dropped, and
+//! audio missing from the stream is filled in with silence so the input
+//! keeps time with the others.
+
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+use crate::error::DeviceError;
+use crate::mixer::SourceFormat;
+
+/// How long a receive waits before the input checks whether to stop
+pub const RECEIVE_TIMEOUT: Duration = Duration::from_millis(100);
+/// Largest UDP datagram
+pub const MAX_PACKET: usize = 65536;
+/// Longest stretch of missing audio filled with silence; a bigger jump is
+/// taken for a restarted sender and skipped
+const MAX_GAP: Duration = Duration::from_secs(2);
+
+/// How an RTP stream's payload is encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtpCodec {
+    /// Big-endian 16-bit PCM
+    L16,
+    /// Opus, always clocked at 48 kHz
+    Opus,
+}
+
+/// An RTP stream used as a recorder input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RtpSource {
+    address: SocketAddr,
+    codec: RtpCodec,
+    format: SourceFormat,
+}
+
+impl RtpSource {
+    /// Listen on `address` for a stream of `codec` with the given format
+    pub fn new(address: SocketAddr, codec: RtpCodec, format: SourceFormat) -> Self {
+        Self { address, codec, format }
+    }
+
+    /// Parse `rtp://host:port`, optionally followed by `?codec=l16|opus&rate=48000&channels=2`
+    ///
+    /// The host is the local address to listen on, `0.0.0.0` for any. The
+    /// stream is L16 stereo at 48 kHz unless the query says otherwise.
+    pub fn from_url(url: &str) -> Result<Self, DeviceError> {
+        let invalid = || DeviceError::InvalidRtpUrl(url.to_string());
+        let rest = url.strip_prefix("rtp://").ok_or_else(invalid)?;
+        let (address, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let address = address
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addresses| addresses.next())
+            .ok_or_else(invalid)?;
+        let (mut codec, mut sample_rate, mut channels) = (RtpCodec::L16, 48000, 2);
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            match pair.split_once('=').ok_or_else(invalid)? {
+                ("codec", "l16" | "L16") => codec = RtpCodec::L16,
+                ("codec", "opus") => codec = RtpCodec::Opus,
+                ("rate", rate) => sample_rate = rate.parse().map_err(|_| invalid())?,
+                ("channels", count) => channels = count.parse().map_err(|_| invalid())?,
+                _ => return Err(invalid()),
+            }
+        }
+        let opus_format = matches!(channels, 1 | 2) && sample_rate == 48000;
+        if sample_rate == 0 || channels == 0 || (codec == RtpCodec::Opus && !opus_format) {
+            return Err(invalid());
+        }
+        if codec == RtpCodec::Opus && !cfg!(feature = "opus") {
+            return Err(DeviceError::OpusUnavailable);
+        }
+        Ok(Self::new(address, codec, SourceFormat { channels, sample_rate }))
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    pub fn codec(&self) -> RtpCodec {
+        self.codec
+    }
+
+    /// Channel count and sample rate of the stream
+    pub fn format(&self) -> SourceFormat {
+        self.format
+    }
+
+    /// The source as an `rtp://` URL, to describe it in recordings
+    pub fn url(&self) -> String {
+        let codec = match self.codec {
+            RtpCodec::L16 => "l16",
+            RtpCodec::Opus => "opus",
+        };
+        format!(
+            "rtp://{}?codec={}&rate={}&channels={}",
+            self.address, codec, self.format.sample_rate, self.format.channels
+        )
+    }
+
+    /// Open the port, with receives timing out after [`RECEIVE_TIMEOUT`]
+    pub fn bind(&self) -> io::Result<UdpSocket> {
+        let socket = UdpSocket::bind(self.address)
+            .map_err(|e| io::Error::new(e.kind(), format!("could not listen for RTP on {}: {}", self.address, e)))?;
+        socket.set_read_timeout(Some(RECEIVE_TIMEOUT))?;
+        Ok(socket)
+    }
+
+    /// A decoder for the stream's packets
+    pub fn decoder(&self) -> io::Result<RtpDecoder> {
+        RtpDecoder::new(self)
+    }
+}
+
+/// Turns RTP packets into interleaved 16-bit samples, in order and on time
+pub struct RtpDecoder {
+    channels: usize,
+    max_gap: u32,
+    /// Source, sequence number and timestamp expected next
+    next: Option<(u32, u16, u32)>,
+    #[cfg(feature = "opus")]
+    opus: Option<opus::Decoder>,
+}
+
+impl RtpDecoder {
+    fn new(source: &RtpSource) -> io::Result<Self> {
+        let format = source.format();
+        #[cfg(feature = "opus")]
+        let opus = match source.codec() {
+            RtpCodec::Opus => Some(opus::Decoder::new(format.channels)?),
+            RtpCodec::L16 => None,
+        };
+        #[cfg(not(feature = "opus"))]
+        if source.codec() == RtpCodec::Opus {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, DeviceError::OpusUnavailable.to_string()));
+        }
+        Ok(Self {
+            channels: format.channels.max(1) as usize,
+            max_gap: (MAX_GAP.as_millis() as u64 * format.sample_rate as u64 / 1000) as u32,
+            next: None,
+            #[cfg(feature = "opus")]
+            opus,
+        })
+    }
+
+    /// Forget the stream's position, so audio after a pause isn't preceded by silence
+    pub fn resync(&mut self) {
+        self.next = None;
+    }
+
+    /// Replace `samples` with the audio of `packet`, after silence for any
+    /// audio missing before it
+    ///
+    /// Leaves `samples` empty for a packet that arrived late or twice.
+    pub fn decode(&mut self, packet: &[u8], samples: &mut Vec<i16>) -> io::Result<()> {
+        samples.clear();
+        let header = parse_header(packet)?;
+        if let Some((ssrc, sequence, timestamp)) = self.next {
+            // A new source is a new stream; otherwise only newer packets count
+            if header.ssrc == ssrc {
+                if (header.sequence.wrapping_sub(sequence) as i16) < 0 {
+                    return Ok(());
+                }
+                let gap = header.timestamp.wrapping_sub(timestamp);
+                if gap > 0 && gap <= self.max_gap {
+                    samples.resize(gap as usize * self.channels, 0);
+                }
+            }
+        }
+        let start = samples.len();
+        self.decode_payload(&packet[header.payload], samples)?;
+        let frames = ((samples.len() - start) / self.channels) as u32;
+        self.next = Some((header.ssrc, header.sequence.wrapping_add(1), header.timestamp.wrapping_add(frames)));
+        Ok(())
+    }
+
+    fn decode_payload(&mut self, payload: &[u8], samples: &mut Vec<i16>) -> io::Result<()> {
+        #[cfg(feature = "opus")]
+        if let Some(decoder) = &mut self.opus {
+            return decoder.decode(payload, samples);
+        }
+        let whole_frames = payload.len() / (2 * self.channels) * 2 * self.channels;
+        samples.extend(payload[..whole_frames].chunks_exact(2).map(|pair| i16::from_be_bytes([pair[0], pair[1]])));
+        Ok(())
+    }
+}
+
+/// What the decoder needs from an RTP header
+struct Header {
+    sequence: u16,
+    timestamp: u32,
+    ssrc: u32,
+    payload: std::ops::Range<usize>,
+}
+
+fn parse_header(packet: &[u8]) -> io::Result<Header> {
+    let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidData, format!("not an RTP packet: {}", what));
+    if packet.len() < 12 {
+        return Err(invalid("too short"));
+    }
+    if packet[0] >> 6 != 2 {
+        return Err(invalid("wrong version"));
+    }
+    let mut start = 12 + 4 * (packet[0] & 0x0F) as usize;
+    if packet[0] & 0x10 != 0 {
+        let length = packet.get(start + 2..start + 4).ok_or_else(|| invalid("truncated extension"))?;
+        start += 4 + 4 * u16::from_be_bytes([length[0], length[1]]) as usize;
+    }
+    let mut end = packet.len();
+    if packet[0] & 0x20 != 0 {
+        end = end.saturating_sub(packet[end - 1] as usize);
+    }
+    if start > end {
+        return Err(invalid("truncated"));
+    }
+    Ok(Header {
+        sequence: u16::from_be_bytes([packet[2], packet[3]]),
+        timestamp: u32::from_be_bytes([packet[4], packet[5], packet[6], packet[7]]),
+        ssrc: u32::from_be_bytes([packet[8], packet[9], packet[10], packet[11]]),
+        payload: start..end,
+    })
+}
+
+#[cfg(feature = "opus")]
+mod opus {
+    use audiopus::coder::Decoder as OpusDecoder;
+    use audiopus::packet::Packet;
+    use audiopus::{Channels, MutSignals, SampleRate};
+    use std::io;
+
+    /// Longest Opus packet, 120 ms at 48 kHz
+    const MAX_FRAMES: usize = 5760;
+
+    pub struct Decoder {
+        decoder: OpusDecoder,
+        channels: usize,
+        buffer: Vec<i16>,
+    }
+
+    impl Decoder {
+        pub fn new(channels: u16) -> io::Result<Self> {
+            let layout = if channels == 1 { Channels::Mono } else { Channels::Stereo };
+            let decoder = OpusDecoder::new(SampleRate::Hz48000, layout).map_err(io::Error::other)?;
+            Ok(Self {
+                decoder,
+                channels: channels as usize,
+                buffer: vec![0; MAX_FRAMES * channels as usize],
+            })
+        }
+
+        pub fn decode(&mut self, payload: &[u8], samples: &mut Vec<i16>) -> io::Result<()> {
+            let packet = Packet::try_from(payload).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let output = MutSignals::try_from(&mut self.buffer[..]).map_err(io::Error::other)?;
+            let frames = self
+                .decoder
+                .decode(Some(packet), output, false)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            samples.extend_from_slice(&self.buffer[..frames * self.channels]);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(sequence: u16, timestamp: u32, samples: &[i16]) -> Vec<u8> {
+        let mut packet = vec![0x80, 96];
+        packet.extend_from_slice(&sequence.to_be_bytes());
+        packet.extend_from_slice(&timestamp.to_be_bytes());
+        packet.extend_from_slice(&0x1234u32.to_be_bytes());
+        packet.extend(samples.iter().flat_map(|sample| sample.to_be_bytes()));
+        packet
+    }
+
+    #[test]
+    fn test_parses_urls() {
+        let source = RtpSource::from_url("rtp://127.0.0.1:5004?rate=16000&channels=1").unwrap();
+        assert_eq!(source.address(), "127.0.0.1:5004".parse().unwrap());
+        assert_eq!(source.codec(), RtpCodec::L16);
+        assert_eq!(source.format(), SourceFormat { channels: 1, sample_rate: 16000 });
+        assert_eq!(RtpSource::from_url(&source.url()).unwrap(), source);
+        assert!(RtpSource::from_url("rtp://0.0.0.0:5004?codec=opus&rate=44100").is_err());
+        assert!(RtpSource::from_url("udp://0.0.0.0:5004").is_err());
+        assert!(RtpSource::from_url("rtp://0.0.0.0").is_err());
+    }
+
+    #[test]
+    fn test_decodes_l16_in_order_and_on_time() {
+        let format = SourceFormat { channels: 1, sample_rate: 8000 };
+        let source = RtpSource::new("127.0.0.1:0".parse().unwrap(), RtpCodec::L16, format);
+        let mut decoder = source.decoder().unwrap();
+        let mut samples = Vec::new();
+        decoder.decode(&packet(65534, 1000, &[1, -1]), &mut samples).unwrap();
+        assert_eq!(samples, [1, -1]);
+        // Packet 65535 (two frames) was lost, and the sequence number wraps
+        decoder.decode(&packet(0, 1004, &[2, 3]), &mut samples).unwrap();
+        assert_eq!(samples, [0, 0, 2, 3]);
+        // It turns up late and is dropped
+        decoder.decode(&packet(65535, 1002, &[9, 9]), &mut samples).unwrap();
+        assert!(samples.is_empty());
+        decoder.decode(&packet(1, 1006, &[4]), &mut samples).unwrap();
+        assert_eq!(samples, [4]);
+        assert!(decoder.decode(&[0x80, 96, 0, 1], &mut samples).is_err());
+    }
+}
@@ -0,0 +1,318 @@
+//! Installing the recorder as a service that starts on its own
+//!
+//! `install-service` writes a definition that runs `meeting-recorder record`
+//! with fixed devices and registers it with the platform's service manager:
+//!
+//! - Linux: a systemd unit (`Type=notify`), system-wide or, with `--user`,
+//!   in the user's session, where PulseAudio and PipeWire run.
+//! - macOS: a launchd property list, as a LaunchDaemon or, with `--user`,
+//!   a LaunchAgent, which is what gets microphone access.
+//! - Windows: a Task Scheduler task started at logon. A real Windows
+//!   service would run in session 0, which has no audio devices of the
+//!   signed-in user.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Seconds the service manager waits for a stopped recording to be finalized
+const STOP_TIMEOUT_SECS: u32 = 90;
+
+/// What to install
+pub struct ServiceOptions {
+    /// Name of the unit, launchd label or task
+    pub name: String,
+    /// Install for the current user instead of system-wide
+    pub user: bool,
+    /// The recorder binary
+    pub executable: PathBuf,
+    /// Arguments of the recording command, after the executable
+    pub args: Vec<String>,
+}
+
+/// A service manager and how to talk to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    Systemd,
+    Launchd,
+    TaskScheduler,
+}
+
+impl Platform {
+    /// The service manager of the platform the recorder was built for
+    pub fn current() -> Result<Self, Box<dyn std::error::Error>> {
+        if cfg!(target_os = "macos") {
+            Ok(Platform::Launchd)
+        } else if cfg!(windows) {
+            Ok(Platform::TaskScheduler)
+        } else if cfg!(target_os = "linux") {
+            Ok(Platform::Systemd)
+        } else {
+            Err("Installing a service is only supported on Linux (systemd), macOS and Windows".into())
+        }
+    }
+
+    /// Where the service definition is written, if the platform keeps one in a file
+    pub fn definition_path(self, name: &str, user: bool) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+        let home = || std::env::var_os("HOME").map(PathBuf::from).ok_or("HOME is not set");
+        Ok(match (self, user) {
+            (Platform::Systemd, false) => Some(Path::new("/etc/systemd/system").join(format!("{}.service", name))),
+            (Platform::Systemd, true) => {
+                let config = std::env::var_os("XDG_CONFIG_HOME").map(PathBuf::from);
+                let config = match config {
+                    Some(config) => config,
+                    None => home()?.join(".config"),
+                };
+                Some(config.join("systemd/user").join(format!("{}.service", name)))
+            }
+            (Platform::Launchd, false) => Some(Path::new("/Library/LaunchDaemons").join(format!("{}.plist", name))),
+            (Platform::Launchd, true) => Some(home()?.join("Library/LaunchAgents").join(format!("{}.plist", name))),
+            (Platform::TaskScheduler, _) => None,
+        })
+    }
+
+    /// The service definition for `options`
+    pub fn definition(self, options: &ServiceOptions) -> String {
+        match self {
+            Platform::Systemd => systemd_unit(options),
+            Platform::Launchd => launchd_plist(options),
+            Platform::TaskScheduler => command_line(options),
+        }
+    }
+
+    /// Commands that register and enable the installed service
+    fn register(self, options: &ServiceOptions, path: Option<&Path>) -> Vec<Command> {
+        match self {
+            Platform::Systemd => vec![
+                systemctl(options.user, &["daemon-reload"]),
+                systemctl(options.user, &["enable", &format!("{}.service", options.name)]),
+            ],
+            Platform::Launchd => vec![launchctl("load", path)],
+            Platform::TaskScheduler => {
+                let mut command = Command::new("schtasks");
+                command
+                    .args(["/Create", "/F", "/SC", "ONLOGON", "/RL", "LIMITED", "/TN", &options.name, "/TR"])
+                    .arg(command_line(options));
+                vec![command]
+            }
+        }
+    }
+
+    /// Commands that stop and unregister the service
+    fn unregister(self, name: &str, user: bool, path: Option<&Path>) -> Vec<Command> {
+        match self {
+            Platform::Systemd => vec![systemctl(user, &["disable", "--now", &format!("{}.service", name)])],
+            Platform::Launchd => vec![launchctl("unload", path)],
+            Platform::TaskScheduler => {
+                let mut command = Command::new("schtasks");
+                command.args(["/Delete", "/F", "/TN", name]);
+                vec![command]
+            }
+        }
+    }
+}
+
+fn systemctl(user: bool, args: &[&str]) -> Command {
+    let mut command = Command::new("systemctl");
+    if user {
+        command.arg("--user");
+    }
+    command.args(args);
+    command
+}
+
+fn launchctl(action: &str, path: Option<&Path>) -> Command {
+    let mut command = Command::new("launchctl");
+    command.args([action, "-w"]);
+    command.args(path);
+    command
+}
+
+/// Write and register the service, printing what was done
+pub fn install(platform: Platform, options: &ServiceOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let path = platform.definition_path(&options.name, options.user)?;
+    if let Some(path) = &path {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, platform.definition(options))
+            .map_err(|e| format!("Could not write {}: {}", path.display(), e))?;
+        println!("Wrote {}", path.display());
+    }
+    for command in platform.register(options, path.as_deref()) {
+        run(command)?;
+    }
+    match platform {
+        Platform::Systemd => println!(
+            "Installed {0}; start it with: systemctl {1}start {0}",
+            options.name,
+            if options.user { "--user " } else { "" }
+        ),
+        Platform::Launchd => println!("Installed and loaded {}", options.name),
+        Platform::TaskScheduler => println!("Installed {}; it starts at the next logon", options.name),
+    }
+    Ok(())
+}
+
+/// Stop and unregister the service, then remove its definition
+pub fn uninstall(platform: Platform, name: &str, user: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let path = platform.definition_path(name, user)?;
+    if path.as_ref().is_some_and(|path| !path.exists()) {
+        return Err(format!("{} is not installed", name).into());
+    }
+    for command in platform.unregister(name, user, path.as_deref()) {
+        run(command)?;
+    }
+    if let Some(path) = &path {
+        fs::remove_file(path).map_err(|e| format!("Could not remove {}: {}", path.display(), e))?;
+        println!("Removed {}", path.display());
+    }
+    if platform == Platform::Systemd {
+        run(systemctl(user, &["daemon-reload"]))?;
+    }
+    println!("Uninstalled {}", name);
+    Ok(())
+}
+
+fn run(mut command: Command) -> Result<(), Box<dyn std::error::Error>> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let status = command.status().map_err(|e| format!("Could not run `{}`: {}", program, e))?;
+    if !status.success() {
+        return Err(format!("`{}` exited with {}", program, status).into());
+    }
+    Ok(())
+}
+
+fn systemd_unit(options: &ServiceOptions) -> String {
+    let exec_start = std::iter::once(options.executable.to_string_lossy().into_owned())
+        .chain(options.args.iter().cloned())
+        .map(|word| systemd_quote(&word))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(
+        "[Unit]\n\
+         Description=Meeting recorder\n\
+         After=sound.target\n\
+         \n\
+         [Service]\n\
+         Type=notify\n\
+         ExecStart={}\n\
+         TimeoutStopSec={}\n\
+         \n\
+         [Install]\n\
+         WantedBy={}\n",
+        exec_start,
+        STOP_TIMEOUT_SECS,
+        if options.user { "default.target" } else { "multi-user.target" }
+    )
+}
+
+/// A word of a systemd command line, with specifiers and variables escaped
+fn systemd_quote(word: &str) -> String {
+    let escaped = word.replace('\\', "\\\\").replace('"', "\\\"").replace('%', "%%").replace('$', "$$");
+    if !word.is_empty() && word.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=:@".contains(c)) {
+        escaped
+    } else {
+        format!("\"{}\"", escaped)
+    }
+}
+
+fn launchd_plist(options: &ServiceOptions) -> String {
+    let arguments: String = std::iter::once(options.executable.to_string_lossy().into_owned())
+        .chain(options.args.iter().cloned())
+        .map(|word| format!("        <string>{}</string>\n", xml_escape(&word)))
+        .collect();
+    let log = match (options.user, std::env::var_os("HOME")) {
+        (true, Some(home)) => Path::new(&home).join("Library/Logs"),
+        _ => PathBuf::from("/Library/Logs"),
+    }
+    .join(format!("{}.log", options.name));
+    let log = xml_escape(&log.to_string_lossy());
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+         <plist version=\"1.0\">\n\
+         <dict>\n\
+         \x20   <key>Label</key>\n\
+         \x20   <string>{}</string>\n\
+         \x20   <key>ProgramArguments</key>\n\
+         \x20   <array>\n\
+         {}\
+         \x20   </array>\n\
+         \x20   <key>RunAtLoad</key>\n\
+         \x20   <true/>\n\
+         \x20   <key>ExitTimeOut</key>\n\
+         \x20   <integer>{}</integer>\n\
+         \x20   <key>StandardOutPath</key>\n\
+         \x20   <string>{}</string>\n\
+         \x20   <key>StandardErrorPath</key>\n\
+         \x20   <string>{}</string>\n\
+         </dict>\n\
+         </plist>\n",
+        xml_escape(&options.name),
+        arguments,
+        STOP_TIMEOUT_SECS,
+        log,
+        log
+    )
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// The recording command as one Windows command line
+fn command_line(options: &ServiceOptions) -> String {
+    std::iter::once(options.executable.to_string_lossy().into_owned())
+        .chain(options.args.iter().cloned())
+        .map(|word| {
+            if !word.is_empty() && !word.contains([' ', '\t', '"']) {
+                word
+            } else {
+                format!("\"{}\"", word.replace('"', "\\\""))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(user: bool) -> ServiceOptions {
+        ServiceOptions {
+            name: "meeting-recorder".to_string(),
+            user,
+            executable: PathBuf::from("/usr/local/bin/meeting-recorder"),
+            args: ["record", "--mic", "USB Mic (100%)", "--output", "json"].map(str::to_string).to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_systemd_unit() {
+        let unit = Platform::Systemd.definition(&options(false));
+        assert!(unit.contains(
+            "ExecStart=/usr/local/bin/meeting-recorder record --mic \"USB Mic (100%%)\" --output json\n"
+        ));
+        assert!(unit.contains("Type=notify\n") && unit.contains("WantedBy=multi-user.target\n"));
+        assert!(Platform::Systemd.definition(&options(true)).contains("WantedBy=default.target\n"));
+        assert_eq!(
+            Platform::Systemd.definition_path("meeting-recorder", false).unwrap().unwrap(),
+            Path::new("/etc/systemd/system/meeting-recorder.service")
+        );
+    }
+
+    #[test]
+    fn test_launchd_plist_and_task_command_line() {
+        let plist = Platform::Launchd.definition(&options(false));
+        assert!(plist.contains("<string>meeting-recorder</string>\n    <key>ProgramArguments</key>"));
+        assert!(plist.contains("        <string>USB Mic (100%)</string>\n"));
+        assert!(plist.contains("<string>/Library/Logs/meeting-recorder.log</string>"));
+
+        assert_eq!(
+            Platform::TaskScheduler.definition(&options(false)),
+            "/usr/local/bin/meeting-recorder record --mic \"USB Mic (100%)\" --output json"
+        );
+    }
+}
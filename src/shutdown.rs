@@ -0,0 +1,100 @@
+//! Stop-and-finalize handling for interrupts, termination and console events
+//!
+//! Ctrl+C, SIGTERM and SIGHUP (systemd stops, closed terminals) and the
+//! Windows console close, logoff and shutdown events all end the recording the
+//! same way: capture stops and the file is finalized before the process exits.
+
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Keeps the platform handler informed while a recording is being finalized
+///
+/// Dropping it signals that the output file is complete, releasing a console
+/// event that is waiting on it.
+#[must_use = "dropping the guard marks the recording as finalized"]
+pub struct ShutdownGuard {
+    _private: (),
+}
+
+impl Drop for ShutdownGuard {
+    fn drop(&mut self) {
+        platform::finished();
+    }
+}
+
+/// Clear `running` when the process is asked to stop
+pub fn install(running: Arc<AtomicBool>) -> Result<ShutdownGuard, ctrlc::Error> {
+    let stop = running.clone();
+    ctrlc::set_handler(move || {
+        // stderr may already be gone after a hangup, so never panic on it
+        let _ = writeln!(std::io::stderr(), "\n\nStopping recording...");
+        stop.store(false, Ordering::SeqCst);
+    })?;
+    platform::install(running);
+    Ok(ShutdownGuard { _private: () })
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex, Once};
+    use std::time::{Duration, Instant};
+    use windows_sys::Win32::Foundation::{BOOL, FALSE, TRUE};
+    use windows_sys::Win32::System::Console::{
+        SetConsoleCtrlHandler, CTRL_CLOSE_EVENT, CTRL_LOGOFF_EVENT, CTRL_SHUTDOWN_EVENT,
+    };
+
+    /// Windows kills the process about 5 s after a close, logoff or shutdown
+    /// event; leave a margin so the handler returns on its own
+    const FINALIZE_TIMEOUT: Duration = Duration::from_millis(4500);
+
+    static RUNNING: Mutex<Option<Arc<AtomicBool>>> = Mutex::new(None);
+    static FINISHED: AtomicBool = AtomicBool::new(false);
+    static REGISTER: Once = Once::new();
+
+    pub fn install(running: Arc<AtomicBool>) {
+        FINISHED.store(false, Ordering::SeqCst);
+        if let Ok(mut slot) = RUNNING.lock() {
+            *slot = Some(running);
+        }
+        // Registered after ctrlc's handler, so it sees console events first
+        REGISTER.call_once(|| unsafe {
+            SetConsoleCtrlHandler(Some(on_console_event), TRUE);
+        });
+    }
+
+    pub fn finished() {
+        FINISHED.store(true, Ordering::SeqCst);
+    }
+
+    unsafe extern "system" fn on_console_event(event: u32) -> BOOL {
+        match event {
+            CTRL_CLOSE_EVENT | CTRL_LOGOFF_EVENT | CTRL_SHUTDOWN_EVENT => {
+                if let Some(running) = RUNNING.lock().ok().and_then(|slot| slot.clone()) {
+                    running.store(false, Ordering::SeqCst);
+                }
+                // The process ends as soon as this returns, so hold it open
+                // until the recording has been finalized
+                let deadline = Instant::now() + FINALIZE_TIMEOUT;
+                while !FINISHED.load(Ordering::SeqCst) && Instant::now() < deadline {
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+                TRUE
+            }
+            // Ctrl+C and Ctrl+Break go on to the ctrlc handler
+            _ => FALSE,
+        }
+    }
+}
+
+#[cfg(not(windows))]
+mod platform {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+
+    // Signals don't force an exit once handled, so there is nothing to wait on
+    pub fn install(_running: Arc<AtomicBool>) {}
+
+    pub fn finished() {}
+}
@@ -0,0 +1,119 @@
+//! JSON metadata written next to each recording
+//!
+//! Transcription and archiving pipelines read the sidecar instead of
+//! guessing everything from the file name. It carries the session UUID, the
+//! inputs, the configuration, and the duration, levels, audio summary,
+//! dropouts, markers and upload status from the [`RecordingResult`], and is
+//! named after the recording with a `.json` extension.
+
+use serde_json::{json, Value};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::config::Config;
+use crate::dsp::Levels;
+use crate::recorder::RecordingResult;
+use crate::source::Input;
+use crate::wav;
+
+/// Path of the sidecar for the recording at `recording_path`
+pub fn sidecar_path(recording_path: impl AsRef<Path>) -> PathBuf {
+    recording_path.as_ref().with_extension("json")
+}
+
+/// Describe a finished session
+///
+/// `started` is when recording began; `mic` and `sys` are the recorder's inputs.
+pub fn sidecar_json(
+    result: &RecordingResult,
+    started: SystemTime,
+    mic: &Input,
+    sys: Option<&Input>,
+    config: &Config,
+) -> Value {
+    let (date, time) = wav::utc_date_time(started);
+    json!({
+        "session_id": result.id,
+        "file": result.filename,
+        "title": result.title,
+        "started": format!("{}T{}Z", date, time),
+        "stop_reason": result.stop_reason,
+        "duration_secs": result.duration.as_secs_f64(),
+        "format": {
+            "sample_rate": result.spec.sample_rate,
+            "channels": result.spec.channels,
+            "bits_per_sample": result.spec.bits_per_sample,
+        },
+        "devices": {
+            "microphone": input_json(mic),
+            "system_audio": sys.map(input_json),
+        },
+        "config": config,
+        "samples": {
+            "microphone": result.mic_samples,
+            "system_audio": result.sys_samples,
+            "written": result.samples_written,
+            "frames": result.frames_written,
+        },
+        "levels": {
+            "microphone": levels_json(&result.mic_levels),
+            "system_audio": result.sys_levels.as_ref().map(levels_json),
+            "mix": levels_json(&result.mix_levels),
+        },
+        "summary": {
+            "microphone": result.mic_summary,
+            "system_audio": result.sys_summary,
+        },
+        "dropouts": {
+            "total": result.dropouts(),
+            "backpressure_events": result.backpressure_events,
+            "backlog_events": result.backlog_events,
+            "stream_errors": result.stream_errors,
+        },
+        "marker_exports": result.marker_exports,
+        "transcripts": result.transcripts,
+        "upload": result.upload.as_ref().map(|report| json!({
+            "status": if report.succeeded() { "uploaded" } else { "failed" },
+            "destination": report.destination,
+            "files": report.files,
+        })),
+        "markers": result.markers.iter().map(|marker| json!({
+            "position": marker.position,
+            "time_secs": marker.time(result.spec.sample_rate).as_secs_f64(),
+            "label": marker.label,
+        })).collect::<Vec<_>>(),
+    })
+}
+
+/// Write `sidecar` next to the recording at `recording_path`
+///
+/// Like recordings, it is written under a `.part` name and renamed into
+/// place, so watchers never read half of it.
+pub fn write_sidecar(recording_path: impl AsRef<Path>, sidecar: &Value) -> io::Result<PathBuf> {
+    let path = sidecar_path(recording_path);
+    let part_path = wav::partial_path(&path);
+    fs::write(&part_path, serde_json::to_vec_pretty(sidecar)?)?;
+    fs::rename(&part_path, &path)?;
+    Ok(path)
+}
+
+fn input_json(input: &Input) -> Value {
+    let format = input.format();
+    let kind = match input {
+        Input::Device { .. } => "device",
+        Input::File(_) => "file",
+        Input::Rtp(_) => "rtp",
+    };
+    json!({
+        "kind": kind,
+        "name": input.name(),
+        "channels": format.channels,
+        "sample_rate": format.sample_rate,
+    })
+}
+
+fn levels_json(levels: &Levels) -> Value {
+    json!({ "peak": levels.peak, "rms": levels.rms })
+}
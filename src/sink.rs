@@ -0,0 +1,263 @@
+//! Outputs the mixer writes recorded audio to
+//!
+//! Every session writes to a set of [`Sink`]s: the WAV file (unless turned
+//! off with [`Recorder::with_wav_output`](crate::Recorder::with_wav_output))
+//! plus any added with [`Recorder::with_sink`](crate::Recorder::with_sink),
+//! such as encoders or network streams.
+
+use hound::WavSpec;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::marker::Marker;
+use crate::wav::{self, RecordingWriter, WavMetadata};
+
+/// Error reported by a sink
+pub type SinkError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Something that consumes the mixed audio of a recording
+///
+/// Sinks run on the mixer thread, so they should not block for long: a slow
+/// sink delays every other output and can make sources fall behind.
+pub trait Sink: Send {
+    /// Append interleaved samples in the session's [`SinkContext::spec`]
+    fn write_frames(&mut self, samples: &[i16]) -> Result<(), SinkError>;
+
+    /// Make the output written so far durable, on the configured flush interval
+    fn checkpoint(&mut self) -> Result<(), SinkError> {
+        Ok(())
+    }
+
+    /// Take note of the markers set during the session, just before [`finalize`](Self::finalize)
+    fn set_markers(&mut self, _markers: &[Marker]) {}
+
+    /// Complete the output once the recording has stopped
+    fn finalize(self: Box<Self>) -> Result<(), SinkError>;
+}
+
+/// What a sink is created for
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct SinkContext {
+    /// Path of the session's recording file; sinks writing files of their own
+    /// derive their names from it, e.g. with another extension
+    pub recording_path: PathBuf,
+    /// Format of the samples passed to [`Sink::write_frames`]
+    pub spec: WavSpec,
+    /// Unique ID of the recording session
+    pub session_id: Uuid,
+}
+
+/// Format of the file a session records to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RecordingFormat {
+    /// Uncompressed 16-bit PCM WAV
+    #[default]
+    Wav,
+    /// Lossless FLAC, about half the size of WAV for speech
+    Flac,
+    /// Opus in Ogg at the configured bitrate, encoded with `opusenc`
+    Opus,
+    /// MP3 at the configured bitrate, encoded with `lame`
+    Mp3,
+    /// Whatever the configured external encoder command produces
+    External,
+}
+
+impl RecordingFormat {
+    /// File extension of recordings in this format
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Wav => "wav",
+            Self::Flac => "flac",
+            Self::Opus => "opus",
+            Self::Mp3 => "mp3",
+            // Named by the external encoder's configuration
+            Self::External => "out",
+        }
+    }
+
+}
+
+/// Creates a sink for each session a recorder runs
+pub type SinkFactory = dyn Fn(&SinkContext) -> Result<Box<dyn Sink>, SinkError> + Send + Sync;
+
+/// Writes the recording to a WAV file
+pub struct WavSink {
+    writer: RecordingWriter,
+    sync_handle: File,
+    sync_on_checkpoint: bool,
+    metadata: Option<WavMetadata>,
+    markers: Vec<Marker>,
+}
+
+impl WavSink {
+    /// Create the WAV file at `path`, buffering `buffer_size` bytes in memory
+    ///
+    /// With `sync_on_checkpoint`, each checkpoint also forces the data to
+    /// stable storage.
+    pub fn create(
+        path: impl AsRef<Path>,
+        spec: WavSpec,
+        buffer_size: usize,
+        sync_on_checkpoint: bool,
+    ) -> hound::Result<Self> {
+        let (writer, sync_handle) = wav::create_recording_writer(path, spec, buffer_size)?;
+        Ok(Self {
+            writer,
+            sync_handle,
+            sync_on_checkpoint,
+            metadata: None,
+            markers: Vec::new(),
+        })
+    }
+
+    /// Describe the recording with `bext` and LIST-INFO chunks when it is finalized
+    pub fn with_metadata(mut self, metadata: WavMetadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+}
+
+impl Sink for WavSink {
+    fn write_frames(&mut self, samples: &[i16]) -> Result<(), SinkError> {
+        Ok(wav::write_samples(&mut self.writer, samples)?)
+    }
+
+    fn checkpoint(&mut self) -> Result<(), SinkError> {
+        Ok(wav::checkpoint(&mut self.writer, &self.sync_handle, self.sync_on_checkpoint)?)
+    }
+
+    fn set_markers(&mut self, markers: &[Marker]) {
+        self.markers = markers.to_vec();
+    }
+
+    fn finalize(self: Box<Self>) -> Result<(), SinkError> {
+        let spec = self.writer.spec();
+        self.writer.finalize()?;
+        let mut chunks = self.metadata.as_ref().map(|metadata| wav::metadata_chunks(&spec, metadata)).unwrap_or_default();
+        chunks.extend(wav::cue_chunks(&self.markers));
+        if !chunks.is_empty() {
+            let mut file = self.sync_handle;
+            wav::append_chunks(&mut file, &chunks)?;
+            if self.sync_on_checkpoint {
+                file.sync_data()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Cuts the mixed audio into chunks of a fixed duration, for sinks that send it on in pieces
+pub(crate) struct Chunker {
+    /// Interleaved samples per chunk
+    len: usize,
+    chunk_ms: u64,
+    channels: u64,
+    buffer: Vec<i16>,
+    /// Sample frame of the recording at the start of `buffer`
+    start_frame: u64,
+}
+
+/// Audio cut by a [`Chunker`]
+pub(crate) struct Chunk {
+    /// Sample frame of the recording the chunk starts at
+    pub start_frame: u64,
+    pub samples: Vec<i16>,
+}
+
+impl Chunker {
+    /// Cut audio of `spec` into chunks of `chunk_ms` milliseconds, and at least one frame
+    pub(crate) fn new(spec: &WavSpec, chunk_ms: u64) -> Self {
+        let samples_per_sec = spec.sample_rate as u64 * spec.channels as u64;
+        let len = (samples_per_sec * chunk_ms / 1000).max(spec.channels as u64) as usize;
+        Self {
+            len,
+            chunk_ms,
+            channels: spec.channels.max(1) as u64,
+            buffer: Vec::with_capacity(len),
+            start_frame: 0,
+        }
+    }
+
+    /// How many chunks make up `secs` seconds of audio, at least one
+    pub(crate) fn chunks_in(&self, secs: u64) -> usize {
+        (secs * 1000 / self.chunk_ms.max(1)).max(1) as usize
+    }
+
+    /// Buffer `samples` up to the end of the next chunk, returning the chunk once it is full
+    ///
+    /// Whatever isn't buffered is left in `samples`, so calling this until it
+    /// returns `None` takes all of them.
+    pub(crate) fn fill(&mut self, samples: &mut &[i16]) -> Option<Chunk> {
+        let take = samples.len().min(self.len - self.buffer.len());
+        self.buffer.extend_from_slice(&samples[..take]);
+        *samples = &samples[take..];
+        (self.buffer.len() == self.len).then(|| self.cut())
+    }
+
+    /// The last, shorter chunk, if any audio is left over
+    pub(crate) fn rest(&mut self) -> Option<Chunk> {
+        (!self.buffer.is_empty()).then(|| self.cut())
+    }
+
+    fn cut(&mut self) -> Chunk {
+        let samples = std::mem::replace(&mut self.buffer, Vec::with_capacity(self.len));
+        let chunk = Chunk { start_frame: self.start_frame, samples };
+        self.start_frame += chunk.samples.len() as u64 / self.channels;
+        chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hound::{SampleFormat, WavReader};
+
+    #[test]
+    fn test_wav_sink_writes_a_playable_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("sink.wav");
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 48000,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+
+        let mut sink: Box<dyn Sink> = Box::new(WavSink::create(&path, spec, 1024, false).unwrap());
+        sink.write_frames(&[1, 2, 3, 4]).unwrap();
+        sink.checkpoint().unwrap();
+        sink.write_frames(&[5, 6]).unwrap();
+        sink.finalize().unwrap();
+
+        let mut reader = WavReader::open(&path).unwrap();
+        let samples: Vec<i16> = reader.samples::<i16>().collect::<Result<_, _>>().unwrap();
+        assert_eq!(samples, vec![1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_chunker_cuts_across_writes() {
+        // Two frames of stereo per chunk
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 1000,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let mut chunker = Chunker::new(&spec, 2);
+        assert_eq!(chunker.chunks_in(1), 500);
+        let mut chunks = Vec::new();
+        for mut samples in [&[1, 2, 3][..], &[4, 5, 6, 7, 8, 9, 10, 11]] {
+            while let Some(chunk) = chunker.fill(&mut samples) {
+                chunks.push((chunk.start_frame, chunk.samples));
+            }
+        }
+        chunks.extend(chunker.rest().map(|chunk| (chunk.start_frame, chunk.samples)));
+        assert!(chunker.rest().is_none());
+        assert_eq!(chunks, [(0, vec![1, 2, 3, 4]), (2, vec![5, 6, 7, 8]), (4, vec![9, 10, 11])]);
+    }
+}
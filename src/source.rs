@@ -0,0 +1,196 @@
+//! Where a recorder's audio comes from
+//!
+//! Each of a [`Recorder`](crate::Recorder)'s inputs is a live capture
+//! device, a [`FileSource`], which plays an existing WAV file through the
+//! same pipeline, or an [`RtpSource`] receiving audio over the network.
+//! Files make it possible to re-mix an earlier microphone track with system
+//! audio, or to run a session without audio hardware.
+
+use cpal::traits::DeviceTrait;
+use cpal::SupportedStreamConfig;
+use hound::{SampleFormat, WavReader, WavSpec};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::dsp;
+use crate::mixer::SourceFormat;
+use crate::rtp::RtpSource;
+
+/// Length of the chunks a file is played in, like a device callback's
+pub const FILE_CHUNK: Duration = Duration::from_millis(10);
+
+/// One input of a recorder
+#[derive(Clone)]
+pub enum Input {
+    /// A capture device, opened in `config`
+    Device {
+        device: cpal::Device,
+        config: SupportedStreamConfig,
+    },
+    /// A WAV file played as if it were being captured
+    File(FileSource),
+    /// An RTP stream arriving over UDP
+    Rtp(RtpSource),
+}
+
+impl Input {
+    /// Channel count and sample rate of the audio this input delivers
+    pub fn format(&self) -> SourceFormat {
+        match self {
+            Self::Device { config, .. } => SourceFormat {
+                channels: config.channels(),
+                sample_rate: config.sample_rate().0,
+            },
+            Self::File(file) => file.format(),
+            Self::Rtp(rtp) => rtp.format(),
+        }
+    }
+
+    /// Whether the input delivers audio until stopped, rather than running out
+    pub fn is_live(&self) -> bool {
+        !matches!(self, Self::File(_))
+    }
+
+    /// Device name or file name, to describe the input in recordings
+    pub fn name(&self) -> String {
+        match self {
+            Self::Device { device, .. } => device.name().unwrap_or_else(|_| "unknown device".to_string()),
+            Self::File(file) => file.path().file_name().unwrap_or_default().to_string_lossy().into_owned(),
+            Self::Rtp(rtp) => rtp.url(),
+        }
+    }
+}
+
+impl From<FileSource> for Input {
+    fn from(file: FileSource) -> Self {
+        Self::File(file)
+    }
+}
+
+impl From<RtpSource> for Input {
+    fn from(rtp: RtpSource) -> Self {
+        Self::Rtp(rtp)
+    }
+}
+
+/// A WAV file used as a recorder input
+///
+/// The file plays at its own speed, like a device delivering it live, and
+/// is reopened for every session, so a recorder can replay it any number of
+/// times. Integer WAVs of up to 32 bits and 32-bit float WAVs are supported.
+///
+/// To process files faster than real time, read them with [`FileReader`]
+/// and feed a [`Mixer`](crate::mixer::Mixer) directly.
+#[derive(Debug, Clone)]
+pub struct FileSource {
+    path: PathBuf,
+    spec: WavSpec,
+}
+
+impl FileSource {
+    /// Use the WAV file at `path`, checking that it can be read
+    pub fn open(path: impl AsRef<Path>) -> hound::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let spec = WavReader::open(&path)?.spec();
+        if spec.channels == 0 || spec.sample_rate == 0 || !matches!(spec.bits_per_sample, 8..=32) {
+            return Err(hound::Error::Unsupported);
+        }
+        Ok(Self { path, spec })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Channel count and sample rate of the file
+    pub fn format(&self) -> SourceFormat {
+        SourceFormat {
+            channels: self.spec.channels,
+            sample_rate: self.spec.sample_rate,
+        }
+    }
+
+    /// Interleaved samples in one [`FILE_CHUNK`]
+    pub fn chunk_len(&self) -> usize {
+        let frames = (self.spec.sample_rate as u128 * FILE_CHUNK.as_millis() / 1000).max(1) as usize;
+        frames * self.spec.channels as usize
+    }
+
+    /// Start reading the file from the beginning
+    pub fn reader(&self) -> hound::Result<FileReader> {
+        Ok(FileReader {
+            reader: WavReader::open(&self.path)?,
+            scratch: Vec::new(),
+        })
+    }
+}
+
+/// Reads a [`FileSource`] in chunks of 16-bit samples
+pub struct FileReader {
+    reader: WavReader<BufReader<File>>,
+    scratch: Vec<f32>,
+}
+
+impl FileReader {
+    /// Replace `chunk` with up to `len` samples converted to 16 bits
+    ///
+    /// Leaves `chunk` empty at the end of the file.
+    pub fn read_chunk(&mut self, chunk: &mut Vec<i16>, len: usize) -> hound::Result<()> {
+        chunk.clear();
+        let spec = self.reader.spec();
+        match spec.sample_format {
+            SampleFormat::Float => {
+                self.scratch.clear();
+                for sample in self.reader.samples::<f32>().take(len) {
+                    self.scratch.push(sample?);
+                }
+                chunk.resize(self.scratch.len(), 0);
+                dsp::convert_f32_to_i16(&self.scratch, chunk);
+            }
+            SampleFormat::Int => {
+                let bits = spec.bits_per_sample as i32;
+                for sample in self.reader.samples::<i32>().take(len) {
+                    let sample = sample?;
+                    let scaled = if bits > 16 { sample >> (bits - 16) } else { sample << (16 - bits) };
+                    chunk.push(scaled as i16);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reads_24_bit_files_as_16_bit_chunks() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("track.wav");
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 8000,
+            bits_per_sample: 24,
+            sample_format: SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for sample in [0x7F_FFFF, -0x80_0000, 0x01_0000, 0] {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let source = FileSource::open(&path).unwrap();
+        assert_eq!(source.chunk_len(), 80);
+        let mut reader = source.reader().unwrap();
+        let mut chunk = Vec::new();
+        reader.read_chunk(&mut chunk, 3).unwrap();
+        assert_eq!(chunk, [i16::MAX, i16::MIN, 0x0100]);
+        reader.read_chunk(&mut chunk, 3).unwrap();
+        assert_eq!(chunk, [0]);
+        reader.read_chunk(&mut chunk, 3).unwrap();
+        assert!(chunk.is_empty());
+    }
+}
@@ -0,0 +1,80 @@
+//! Lifecycle of a recording session
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+/// What a recorder is doing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[repr(u8)]
+pub enum RecordingState {
+    /// No session has been started
+    Idle,
+    /// A session is opening its devices and output
+    Armed,
+    /// Audio is being captured
+    Recording,
+    /// Capture is suspended; audio arriving now is discarded
+    Paused,
+    /// Capture has stopped and the output is being finalized
+    Stopping,
+    /// The session is over, successfully or not
+    Finished,
+}
+
+impl RecordingState {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Self::Idle,
+            1 => Self::Armed,
+            2 => Self::Recording,
+            3 => Self::Paused,
+            4 => Self::Stopping,
+            _ => Self::Finished,
+        }
+    }
+}
+
+/// Recording state shared between a recorder, its session and the capture callbacks
+#[derive(Clone)]
+pub(crate) struct SharedState(Arc<AtomicU8>);
+
+impl Default for SharedState {
+    fn default() -> Self {
+        Self(Arc::new(AtomicU8::new(RecordingState::Idle as u8)))
+    }
+}
+
+impl SharedState {
+    pub fn get(&self) -> RecordingState {
+        RecordingState::from_u8(self.0.load(Ordering::SeqCst))
+    }
+
+    pub fn set(&self, state: RecordingState) {
+        self.0.store(state as u8, Ordering::SeqCst);
+    }
+
+    /// Move from `from` to `to`; false if the state was something else
+    pub fn transition(&self, from: RecordingState, to: RecordingState) -> bool {
+        self.0
+            .compare_exchange(from as u8, to as u8, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transition_only_from_the_expected_state() {
+        let state = SharedState::default();
+        assert_eq!(state.get(), RecordingState::Idle);
+        assert!(!state.transition(RecordingState::Recording, RecordingState::Paused));
+
+        state.set(RecordingState::Recording);
+        assert!(state.transition(RecordingState::Recording, RecordingState::Paused));
+        assert_eq!(state.get(), RecordingState::Paused);
+    }
+}
@@ -0,0 +1,483 @@
+//! Live streaming of the mixed audio to an HTTP or WebSocket endpoint
+//!
+//! With the `streaming` feature, a [`StreamSink`] sends the audio to a
+//! real-time captioning or transcription service while the local recording
+//! carries on. The audio is cut into short chunks, encoded as raw PCM, WAV
+//! or Opus, and sent from a thread of its own, so a slow or unreachable
+//! endpoint never holds up the mixer:
+//!
+//! - `http://` and `https://` URLs get one POST per chunk, described by
+//!   `X-Session-Id`, `X-Chunk-Index`, `X-Start-Frame`, `X-Sample-Rate` and
+//!   `X-Channels` headers.
+//! - `ws://` and `wss://` URLs get one connection per session: a JSON text
+//!   message describing the stream, a binary message per chunk, and a
+//!   closing `{"event":"end"}`. A dropped connection is reopened for the
+//!   next chunk.
+//!
+//! When the endpoint falls more than `max_queued_secs` behind, new chunks
+//! are dropped rather than held, and the loss is reported at the end.
+
+use hound::{WavSpec, WavWriter};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{self, Cursor, Read, Write};
+use std::process::Stdio;
+
+use crate::encoder::{Bitrate, CompressedFormat};
+
+/// Where and how the audio is streamed during a recording
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Streaming {
+    /// Endpoint: `http(s)://` to POST each chunk, `ws(s)://` for a WebSocket
+    pub url: String,
+    /// Encoding of each chunk
+    #[serde(default)]
+    pub format: StreamFormat,
+    /// Length of each chunk in milliseconds
+    #[serde(default = "default_chunk_ms")]
+    pub chunk_ms: u64,
+    /// Extra request headers, e.g. `Authorization`
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Most audio, in seconds, held while the endpoint is slow or down before chunks are dropped
+    #[serde(default = "default_max_queued_secs")]
+    pub max_queued_secs: u64,
+}
+
+fn default_chunk_ms() -> u64 {
+    500
+}
+
+fn default_max_queued_secs() -> u64 {
+    30
+}
+
+/// How streamed chunks are encoded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StreamFormat {
+    /// Raw interleaved 16-bit little-endian PCM
+    #[default]
+    Pcm,
+    /// A complete WAV file per chunk
+    Wav,
+    /// A complete Ogg Opus stream per chunk at the configured bitrate, encoded with `opusenc`
+    Opus,
+}
+
+impl StreamFormat {
+    /// Content type of a chunk in this format
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Pcm => "application/octet-stream",
+            Self::Wav => "audio/wav",
+            Self::Opus => "audio/ogg; codecs=opus",
+        }
+    }
+
+    /// Encode a chunk of interleaved `samples` of `spec`
+    pub fn encode(self, samples: &[i16], spec: &WavSpec, bitrate: Bitrate) -> io::Result<Vec<u8>> {
+        match self {
+            Self::Pcm => Ok(pcm_bytes(samples)),
+            Self::Wav => {
+                let mut bytes = Cursor::new(Vec::new());
+                let mut writer = WavWriter::new(&mut bytes, *spec).map_err(io::Error::other)?;
+                for &sample in samples {
+                    writer.write_sample(sample).map_err(io::Error::other)?;
+                }
+                writer.finalize().map_err(io::Error::other)?;
+                Ok(bytes.into_inner())
+            }
+            Self::Opus => encode_opus(samples, spec, bitrate),
+        }
+    }
+}
+
+/// Transport implied by an endpoint URL
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    Http,
+    WebSocket,
+}
+
+impl Streaming {
+    /// Transport for the configured URL, or `None` if its scheme isn't supported
+    pub fn transport(&self) -> Option<Transport> {
+        let scheme = self.url.split_once("://")?.0.to_ascii_lowercase();
+        match scheme.as_str() {
+            "http" | "https" => Some(Transport::Http),
+            "ws" | "wss" => Some(Transport::WebSocket),
+            _ => None,
+        }
+    }
+}
+
+fn pcm_bytes(samples: &[i16]) -> Vec<u8> {
+    samples.iter().flat_map(|sample| sample.to_le_bytes()).collect()
+}
+
+/// Run `opusenc` over one chunk, from raw PCM on stdin to Ogg Opus on stdout
+fn encode_opus(samples: &[i16], spec: &WavSpec, bitrate: Bitrate) -> io::Result<Vec<u8>> {
+    let format = CompressedFormat::Opus;
+    let mut child = format
+        .command(spec, bitrate, "-".as_ref())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| io::Error::new(e.kind(), format!("could not run `{}`: {}", format.program(), e)))?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    // Feed stdin while reading stdout, so neither pipe can fill up and stall the other
+    let output = std::thread::scope(|scope| {
+        let writer = scope.spawn(move || stdin.write_all(&pcm_bytes(samples)));
+        let mut output = Vec::new();
+        stdout.read_to_end(&mut output)?;
+        writer.join().expect("stdin writer panicked")?;
+        io::Result::Ok(output)
+    })?;
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(io::Error::other(format!("`{}` exited with {}", format.program(), status)));
+    }
+    Ok(output)
+}
+
+#[cfg(feature = "streaming")]
+pub use self::sink::StreamSink;
+
+#[cfg(feature = "streaming")]
+mod sink {
+    use crossbeam_channel::{Receiver, Sender, TrySendError};
+    use hound::WavSpec;
+    use std::net::TcpStream;
+    use std::thread::{self, JoinHandle};
+    use std::time::Duration;
+    use tungstenite::client::IntoClientRequest;
+    use tungstenite::http::{HeaderName, HeaderValue};
+    use tungstenite::stream::MaybeTlsStream;
+    use tungstenite::{Message, WebSocket};
+    use uuid::Uuid;
+
+    use super::{Streaming, Transport};
+    use crate::encoder::Bitrate;
+    use crate::sink::{Chunker, Sink, SinkContext, SinkError};
+
+    /// How long one HTTP request may take before it counts as failed
+    const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// A chunk of mixed audio waiting to be sent
+    struct Chunk {
+        index: u64,
+        start_frame: u64,
+        samples: Vec<i16>,
+    }
+
+    /// Chunks sent and lost by the sender thread
+    #[derive(Default)]
+    struct Delivery {
+        sent: u64,
+        failed: u64,
+    }
+
+    /// Sink that streams the recording to an HTTP or WebSocket endpoint
+    pub struct StreamSink {
+        url: String,
+        sender: Option<Sender<Chunk>>,
+        thread: Option<JoinHandle<Delivery>>,
+        chunker: Chunker,
+        index: u64,
+        dropped: u64,
+    }
+
+    impl StreamSink {
+        /// Start streaming the session's audio as `options` say
+        ///
+        /// Opus chunks are encoded at `bitrate`. Fails if the URL has no
+        /// supported scheme; the endpoint itself is only contacted once
+        /// the first chunk is ready.
+        pub fn spawn(options: &Streaming, bitrate: Bitrate, context: &SinkContext) -> Result<Self, SinkError> {
+            let transport = options
+                .transport()
+                .ok_or_else(|| format!("unsupported streaming URL {}", options.url))?;
+            let spec = context.spec;
+            let chunker = Chunker::new(&spec, options.chunk_ms);
+            let (sender, receiver) = crossbeam_channel::bounded(chunker.chunks_in(options.max_queued_secs));
+
+            let endpoint = Endpoint {
+                options: options.clone(),
+                bitrate,
+                spec,
+                session_id: context.session_id,
+            };
+            let thread = thread::Builder::new()
+                .name("stream-sender".to_string())
+                .spawn(move || match transport {
+                    Transport::Http => endpoint.run_http(receiver),
+                    Transport::WebSocket => endpoint.run_websocket(receiver),
+                })?;
+            Ok(Self {
+                url: options.url.clone(),
+                sender: Some(sender),
+                thread: Some(thread),
+                chunker,
+                index: 0,
+                dropped: 0,
+            })
+        }
+
+        /// Queue a chunk for sending, dropping it if the queue is full
+        fn queue(&mut self, start_frame: u64, samples: Vec<i16>) {
+            let chunk = Chunk {
+                index: self.index,
+                start_frame,
+                samples,
+            };
+            if let Some(sender) = &self.sender {
+                if let Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) = sender.try_send(chunk) {
+                    if self.dropped == 0 {
+                        console_err!("Warning: streaming to {} fell behind; dropping audio", self.url);
+                    }
+                    self.dropped += 1;
+                }
+            }
+            self.index += 1;
+        }
+    }
+
+    impl Sink for StreamSink {
+        fn write_frames(&mut self, mut samples: &[i16]) -> Result<(), SinkError> {
+            while let Some(chunk) = self.chunker.fill(&mut samples) {
+                self.queue(chunk.start_frame, chunk.samples);
+            }
+            Ok(())
+        }
+
+        fn finalize(mut self: Box<Self>) -> Result<(), SinkError> {
+            if let Some(chunk) = self.chunker.rest() {
+                self.queue(chunk.start_frame, chunk.samples);
+            }
+            // Hanging up lets the sender finish the queue and close the stream
+            self.sender = None;
+            let delivery = self
+                .thread
+                .take()
+                .expect("sender thread is only joined here")
+                .join()
+                .map_err(|_| "stream sender thread panicked")?;
+            let lost = delivery.failed + self.dropped;
+            if lost > 0 {
+                return Err(format!(
+                    "{} of {} chunks could not be streamed to {} ({} dropped while it fell behind)",
+                    lost,
+                    delivery.sent + lost,
+                    self.url,
+                    self.dropped
+                )
+                .into());
+            }
+            Ok(())
+        }
+    }
+
+    /// The sender thread's end of a stream
+    struct Endpoint {
+        options: Streaming,
+        bitrate: Bitrate,
+        spec: WavSpec,
+        session_id: Uuid,
+    }
+
+    impl Endpoint {
+        fn encode(&self, chunk: &Chunk) -> Result<Vec<u8>, SinkError> {
+            Ok(self.options.format.encode(&chunk.samples, &self.spec, self.bitrate)?)
+        }
+
+        /// Report the first failure of a run of them, and the recovery after
+        fn record(&self, result: Result<(), SinkError>, delivery: &mut Delivery, failing: &mut bool) {
+            match result {
+                Ok(()) => {
+                    if std::mem::take(failing) {
+                        console_err!("Streaming to {} recovered", self.options.url);
+                    }
+                    delivery.sent += 1;
+                }
+                Err(e) => {
+                    if !std::mem::replace(failing, true) {
+                        console_err!("Warning: streaming to {} failed: {}", self.options.url, e);
+                    }
+                    delivery.failed += 1;
+                }
+            }
+        }
+
+        /// POST each chunk on its own
+        fn run_http(self, chunks: Receiver<Chunk>) -> Delivery {
+            let agent = ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build();
+            let (mut delivery, mut failing) = (Delivery::default(), false);
+            for chunk in chunks {
+                let result = self.encode(&chunk).and_then(|body| {
+                    let mut request = agent
+                        .post(&self.options.url)
+                        .set("Content-Type", self.options.format.content_type())
+                        .set("X-Session-Id", &self.session_id.to_string())
+                        .set("X-Chunk-Index", &chunk.index.to_string())
+                        .set("X-Start-Frame", &chunk.start_frame.to_string())
+                        .set("X-Sample-Rate", &self.spec.sample_rate.to_string())
+                        .set("X-Channels", &self.spec.channels.to_string());
+                    for (name, value) in &self.options.headers {
+                        request = request.set(name, value);
+                    }
+                    request.send_bytes(&body)?;
+                    Ok(())
+                });
+                self.record(result, &mut delivery, &mut failing);
+            }
+            delivery
+        }
+
+        /// Send each chunk as a binary message over one connection, reconnecting as needed
+        fn run_websocket(self, chunks: Receiver<Chunk>) -> Delivery {
+            let (mut delivery, mut failing) = (Delivery::default(), false);
+            let mut socket = None;
+            for chunk in chunks {
+                let result = self.encode(&chunk).and_then(|body| {
+                    let open = match socket.as_mut() {
+                        Some(open) => open,
+                        None => socket.insert(self.connect()?),
+                    };
+                    open.send(Message::Binary(body))?;
+                    Ok(())
+                });
+                if result.is_err() {
+                    socket = None;
+                }
+                self.record(result, &mut delivery, &mut failing);
+            }
+            if let Some(mut socket) = socket {
+                let _ = socket.send(Message::Text(r#"{"event":"end"}"#.to_string()));
+                let _ = socket.close(None);
+                let _ = socket.flush();
+            }
+            delivery
+        }
+
+        /// Open the WebSocket and describe the stream
+        fn connect(&self) -> Result<WebSocket<MaybeTlsStream<TcpStream>>, SinkError> {
+            let mut request = self.options.url.as_str().into_client_request()?;
+            for (name, value) in &self.options.headers {
+                request
+                    .headers_mut()
+                    .insert(HeaderName::from_bytes(name.as_bytes())?, HeaderValue::from_str(value)?);
+            }
+            let (mut socket, _) = tungstenite::connect(request)?;
+            let start = serde_json::json!({
+                "event": "start",
+                "session_id": self.session_id,
+                "format": self.options.format,
+                "content_type": self.options.format.content_type(),
+                "sample_rate": self.spec.sample_rate,
+                "channels": self.spec.channels,
+            });
+            socket.send(Message::Text(start.to_string()))?;
+            Ok(socket)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hound::SampleFormat;
+
+    const SPEC: WavSpec = WavSpec {
+        channels: 2,
+        sample_rate: 48000,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    };
+
+    #[test]
+    fn test_chunk_encodings() {
+        let samples = [1i16, -2, 3, -4];
+        let pcm = StreamFormat::Pcm.encode(&samples, &SPEC, Bitrate::default()).unwrap();
+        assert_eq!(pcm, [1, 0, 0xFE, 0xFF, 3, 0, 0xFC, 0xFF]);
+
+        let wav = StreamFormat::Wav.encode(&samples, &SPEC, Bitrate::default()).unwrap();
+        let mut reader = hound::WavReader::new(Cursor::new(wav)).unwrap();
+        assert_eq!(reader.spec(), SPEC);
+        assert_eq!(reader.samples::<i16>().collect::<Result<Vec<_>, _>>().unwrap(), samples);
+    }
+
+    #[test]
+    fn test_transport_follows_the_url_scheme() {
+        let streaming = |url: &str| Streaming {
+            url: url.to_string(),
+            format: StreamFormat::Pcm,
+            chunk_ms: default_chunk_ms(),
+            headers: BTreeMap::new(),
+            max_queued_secs: default_max_queued_secs(),
+        };
+        assert_eq!(streaming("https://captions.example.com/audio").transport(), Some(Transport::Http));
+        assert_eq!(streaming("WSS://captions.example.com/live").transport(), Some(Transport::WebSocket));
+        assert_eq!(streaming("captions.example.com").transport(), None);
+        assert_eq!(streaming("ftp://captions.example.com").transport(), None);
+    }
+
+    #[cfg(feature = "streaming")]
+    #[test]
+    fn test_http_streaming_posts_every_chunk() {
+        use crate::sink::{Sink, SinkContext};
+        use std::io::{BufRead, BufReader};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/audio", listener.local_addr().unwrap());
+        // Accept three requests, answering each and keeping its chunk headers and body length
+        let server = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for _ in 0..3 {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut headers = BTreeMap::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        headers.insert(name.to_ascii_lowercase(), value.trim().to_string());
+                    }
+                }
+                let mut body = vec![0; headers["content-length"].parse().unwrap()];
+                reader.read_exact(&mut body).unwrap();
+                reader.get_mut().write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").unwrap();
+                requests.push((headers["x-chunk-index"].clone(), headers["x-start-frame"].clone(), headers["authorization"].clone(), body.len()));
+            }
+            requests
+        });
+
+        let options = Streaming {
+            url,
+            format: StreamFormat::Pcm,
+            chunk_ms: 10,
+            headers: BTreeMap::from([("Authorization".to_string(), "Bearer secret".to_string())]),
+            max_queued_secs: 30,
+        };
+        let context = SinkContext {
+            recording_path: "meeting.wav".into(),
+            spec: SPEC,
+            session_id: uuid::Uuid::new_v4(),
+        };
+        let mut sink: Box<dyn Sink> = Box::new(StreamSink::spawn(&options, Bitrate::default(), &context).unwrap());
+        // Two and a half chunks of 480 frames
+        sink.write_frames(&vec![7; 1500]).unwrap();
+        sink.write_frames(&vec![7; 900]).unwrap();
+        sink.finalize().unwrap();
+
+        let requests = server.join().unwrap();
+        let request = |index: &str, start: &str, len| (index.to_string(), start.to_string(), "Bearer secret".to_string(), len);
+        assert_eq!(requests, [request("0", "0", 1920), request("1", "480", 1920), request("2", "960", 960)]);
+    }
+}
@@ -0,0 +1,305 @@
+//! End-of-recording summary of each source's audio
+//!
+//! The mixer measures every chunk of each source as it arrives, in the
+//! source's own format, so a finished session reports peak level, integrated
+//! loudness, clipping and silence per source without another pass over the
+//! file. A microphone that was too hot shows up as clipping; one that was
+//! effectively dead as a recording that is almost all silence.
+
+use serde::Serialize;
+use std::fmt;
+use std::time::Duration;
+
+use crate::wav::{self, AnalyzeOptions};
+
+/// Blocks quieter than this (in LUFS) are left out of integrated loudness
+const ABSOLUTE_GATE: f64 = -70.0;
+/// Blocks this far below the ungated loudness are left out as well
+const RELATIVE_GATE: f64 = -10.0;
+/// Loudness is measured in 400 ms blocks, four of these steps each
+const LOUDNESS_STEP: Duration = Duration::from_millis(100);
+/// Share of silence above which a source counts as effectively dead
+const MOSTLY_SILENT: f32 = 0.95;
+
+/// Peak, loudness, clipping and silence of one source over a session
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct AudioSummary {
+    /// Peak level, from 0.0 (silence) to 1.0 (full scale)
+    pub peak: f32,
+    /// Integrated loudness in LUFS (ITU-R BS.1770), `None` if the audio was
+    /// too quiet or too short to measure
+    pub loudness: Option<f64>,
+    /// Samples at full scale, which were probably clipped
+    pub clipped_samples: u64,
+    /// Share of the audio that was silence, from 0.0 to 1.0
+    pub silence: f32,
+}
+
+impl AudioSummary {
+    /// Peak level in dBFS, negative infinity for digital silence
+    pub fn peak_dbfs(&self) -> f32 {
+        20.0 * self.peak.log10()
+    }
+
+    /// Whether the source clipped, e.g. because its gain was too high
+    pub fn clipped(&self) -> bool {
+        self.clipped_samples > 0
+    }
+
+    /// Whether the source was silent nearly all the time, e.g. muted or unplugged
+    pub fn mostly_silent(&self) -> bool {
+        self.silence >= MOSTLY_SILENT
+    }
+}
+
+impl fmt::Display for AudioSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "peak {:.1} dBFS, ", self.peak_dbfs())?;
+        match self.loudness {
+            Some(loudness) => write!(f, "loudness {:.1} LUFS, ", loudness)?,
+            None => write!(f, "loudness too low to measure, ")?,
+        }
+        write!(
+            f,
+            "{} clipped samples, {:.0}% silence",
+            self.clipped_samples,
+            self.silence * 100.0
+        )
+    }
+}
+
+/// Accumulates an [`AudioSummary`] over any number of chunks
+///
+/// Silence is measured in the same windows and against the same threshold
+/// as [`wav::analyze`]. Integrated loudness keeps one value per 100 ms of
+/// audio, about 3 MB for an eight-hour session.
+#[derive(Debug, Clone)]
+pub struct SummaryMeter {
+    filters: Vec<KWeighting>,
+    /// Channel of the next sample, so chunks may split frames
+    channel: usize,
+    peak: i16,
+    clipped_samples: u64,
+
+    step_len: u64,
+    step_frames: u64,
+    step_energy: f64,
+    /// Mean square of each complete step, summed over channels
+    steps: Vec<f64>,
+
+    window_len: u64,
+    window_frames: u64,
+    window_peak: i16,
+    frames: u64,
+    silent_frames: u64,
+}
+
+impl SummaryMeter {
+    /// Measure interleaved audio with `channels` channels at `sample_rate`
+    pub fn new(channels: u16, sample_rate: u32) -> Self {
+        let frames_in = |duration: Duration| (sample_rate as u128 * duration.as_millis() / 1000).max(1) as u64;
+        Self {
+            filters: vec![KWeighting::new(sample_rate); channels.max(1) as usize],
+            channel: 0,
+            peak: 0,
+            clipped_samples: 0,
+            step_len: frames_in(LOUDNESS_STEP),
+            step_frames: 0,
+            step_energy: 0.0,
+            steps: Vec::new(),
+            window_len: frames_in(wav::SILENCE_WINDOW),
+            window_frames: 0,
+            window_peak: 0,
+            frames: 0,
+            silent_frames: 0,
+        }
+    }
+
+    pub fn add(&mut self, samples: &[i16]) {
+        for &sample in samples {
+            let level = sample.saturating_abs();
+            self.peak = self.peak.max(level);
+            self.window_peak = self.window_peak.max(level);
+            self.clipped_samples += u64::from(sample == i16::MAX || sample == i16::MIN);
+            let weighted = self.filters[self.channel].process(sample as f64 / 32768.0);
+            self.step_energy += weighted * weighted;
+
+            self.channel += 1;
+            if self.channel == self.filters.len() {
+                self.channel = 0;
+                self.end_frame();
+            }
+        }
+    }
+
+    fn end_frame(&mut self) {
+        self.frames += 1;
+        self.step_frames += 1;
+        if self.step_frames == self.step_len {
+            self.steps.push(self.step_energy / self.step_len as f64);
+            self.step_frames = 0;
+            self.step_energy = 0.0;
+        }
+        self.window_frames += 1;
+        if self.window_frames == self.window_len {
+            self.end_window();
+        }
+    }
+
+    fn end_window(&mut self) {
+        if self.window_is_silent() {
+            self.silent_frames += self.window_frames;
+        }
+        self.window_frames = 0;
+        self.window_peak = 0;
+    }
+
+    fn window_is_silent(&self) -> bool {
+        level(self.window_peak) < AnalyzeOptions::default().silence_threshold
+    }
+
+    /// Summary of everything added so far
+    pub fn summary(&self) -> AudioSummary {
+        // A partial last window counts for its length
+        let silent_frames = self.silent_frames + if self.window_is_silent() { self.window_frames } else { 0 };
+        AudioSummary {
+            peak: level(self.peak),
+            loudness: integrated_loudness(&self.steps),
+            clipped_samples: self.clipped_samples,
+            silence: if self.frames == 0 { 0.0 } else { silent_frames as f32 / self.frames as f32 },
+        }
+    }
+}
+
+fn level(peak: i16) -> f32 {
+    (peak as f32 / i16::MAX as f32).min(1.0)
+}
+
+/// Gated loudness of overlapping 400 ms blocks, as in ITU-R BS.1770-4
+fn integrated_loudness(steps: &[f64]) -> Option<f64> {
+    let loudness = |energy: f64| -0.691 + 10.0 * energy.log10();
+    let blocks: Vec<f64> = steps.windows(4).map(|block| block.iter().sum::<f64>() / 4.0).collect();
+    let gated_mean = |threshold: f64| {
+        let gated: Vec<f64> = blocks.iter().copied().filter(|&energy| loudness(energy) > threshold).collect();
+        (!gated.is_empty()).then(|| gated.iter().sum::<f64>() / gated.len() as f64)
+    };
+    let relative_gate = loudness(gated_mean(ABSOLUTE_GATE)?) + RELATIVE_GATE;
+    gated_mean(relative_gate.max(ABSOLUTE_GATE)).map(loudness)
+}
+
+/// The K-weighting filter of BS.1770: a high shelf modelling the head,
+/// then a high-pass, with coefficients derived for any sample rate
+#[derive(Debug, Clone)]
+struct KWeighting {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+impl KWeighting {
+    fn new(sample_rate: u32) -> Self {
+        let rate = sample_rate.max(1) as f64;
+
+        let (gain_db, q, frequency) = (3.999_843_853_973_347, 0.707_175_236_955_419_3, 1_681.974_450_955_532);
+        let k = (std::f64::consts::PI * frequency / rate).tan();
+        let vh = 10f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.499_666_774_154_541_6);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad::new(
+            [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+
+        let (q, frequency) = (0.500_327_037_325_395_3, 38.135_470_876_139_82);
+        let k = (std::f64::consts::PI * frequency / rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass = Biquad::new([1.0, -2.0, 1.0], [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0]);
+
+        Self { shelf, high_pass }
+    }
+
+    fn process(&mut self, sample: f64) -> f64 {
+        self.high_pass.process(self.shelf.process(sample))
+    }
+}
+
+/// Second-order IIR filter, in transposed direct form II
+#[derive(Debug, Clone)]
+struct Biquad {
+    b: [f64; 3],
+    /// Feedback coefficients, normalized so a0 is 1
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self { b, a, state: [0.0; 2] }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.state[0];
+        self.state[0] = self.b[1] * x - self.a[0] * y + self.state[1];
+        self.state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `seconds` of a 997 Hz sine at `amplitude` (of full scale), mono at 48 kHz
+    fn sine(amplitude: f64, seconds: f64) -> Vec<i16> {
+        (0..(48000.0 * seconds) as usize)
+            .map(|i| {
+                let phase = 2.0 * std::f64::consts::PI * 997.0 * i as f64 / 48000.0;
+                (amplitude * 32768.0 * phase.sin()).round() as i16
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_k_weighting_matches_the_standard_at_48k() {
+        let filter = KWeighting::new(48000);
+        let close = |a: &[f64], b: &[f64]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-8);
+        assert!(close(&filter.shelf.b, &[1.535_124_859_586_97, -2.691_696_189_406_38, 1.198_392_810_852_85]));
+        assert!(close(&filter.shelf.a, &[-1.690_659_293_182_41, 0.732_480_774_215_85]));
+        assert!(close(&filter.high_pass.a, &[-1.990_047_454_833_98, 0.990_072_250_366_04]));
+    }
+
+    #[test]
+    fn test_half_scale_tone_then_silence() {
+        let mut meter = SummaryMeter::new(1, 48000);
+        // A half-scale sine is 6 dB below the -3.01 LUFS of a full-scale one
+        meter.add(&sine(0.5, 5.0));
+        let loudness = meter.summary().loudness.unwrap();
+        assert!((loudness + 9.03).abs() < 0.05, "got {} LUFS", loudness);
+
+        // Gating leaves the silence out of the loudness, all but the blocks at the edge
+        meter.add(&vec![0; 48000 * 5]);
+        let summary = meter.summary();
+        let loudness = summary.loudness.unwrap();
+        assert!((loudness + 9.03).abs() < 0.2, "got {} LUFS", loudness);
+        assert!((summary.peak_dbfs() + 6.02).abs() < 0.01);
+        assert!((summary.silence - 0.5).abs() < 0.01, "got {}", summary.silence);
+        assert!(!summary.clipped() && !summary.mostly_silent());
+    }
+
+    #[test]
+    fn test_clipping_and_dead_sources() {
+        let mut meter = SummaryMeter::new(2, 48000);
+        // Split mid-frame, so chunks don't need to hold whole frames
+        meter.add(&[i16::MAX, 0, i16::MIN]);
+        meter.add(&[0; 48000 * 2 - 3]);
+        let summary = meter.summary();
+        assert_eq!(summary.clipped_samples, 2);
+        assert_eq!(summary.peak, 1.0);
+
+        let mut dead = SummaryMeter::new(2, 48000);
+        dead.add(&[1; 48000 * 2]);
+        let summary = dead.summary();
+        assert_eq!(summary.loudness, None);
+        assert!(summary.mostly_silent());
+        assert_eq!(summary.to_string(), "peak -90.3 dBFS, loudness too low to measure, 0 clipped samples, 100% silence");
+    }
+}
@@ -0,0 +1,117 @@
+//! Synthetic audio sources for benchmarks and tests
+//!
+//! Each source produces interleaved f32 chunks shaped like the ones a cpal
+//! input callback delivers, without needing any audio hardware.
+
+use std::f32::consts::TAU;
+
+/// Signal produced by a [`SyntheticSource`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Waveform {
+    /// A sine tone at the given frequency in Hz
+    Sine(f32),
+    /// Uniform white noise
+    Noise,
+    Silence,
+}
+
+/// Generator of interleaved f32 audio at a fixed rate and channel count
+#[derive(Debug, Clone)]
+pub struct SyntheticSource {
+    waveform: Waveform,
+    sample_rate: u32,
+    channels: u16,
+    amplitude: f32,
+    phase: f32,
+    rng: u32,
+}
+
+impl SyntheticSource {
+    pub fn new(waveform: Waveform, sample_rate: u32, channels: u16) -> Self {
+        Self {
+            waveform,
+            sample_rate,
+            channels: channels.max(1),
+            amplitude: 0.8,
+            phase: 0.0,
+            rng: 0x9E37_79B9,
+        }
+    }
+
+    /// Peak amplitude in [0.0, 1.0] (0.8 by default)
+    pub fn with_amplitude(mut self, amplitude: f32) -> Self {
+        self.amplitude = amplitude;
+        self
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// Number of interleaved samples in a callback chunk of `duration_ms`
+    pub fn chunk_len(&self, duration_ms: u32) -> usize {
+        (self.sample_rate * duration_ms / 1000) as usize * self.channels as usize
+    }
+
+    /// Fill `output` with the next samples, continuing where the last call left off
+    ///
+    /// `output` should hold whole frames; every channel gets the same signal.
+    pub fn fill(&mut self, output: &mut [f32]) {
+        let channels = self.channels as usize;
+        for frame in output.chunks_mut(channels) {
+            let value = match self.waveform {
+                Waveform::Sine(freq) => {
+                    let value = self.phase.sin();
+                    self.phase = (self.phase + TAU * freq / self.sample_rate as f32) % TAU;
+                    value
+                }
+                Waveform::Noise => {
+                    // xorshift32 keeps the sequence deterministic across runs
+                    self.rng ^= self.rng << 13;
+                    self.rng ^= self.rng >> 17;
+                    self.rng ^= self.rng << 5;
+                    self.rng as f32 / u32::MAX as f32 * 2.0 - 1.0
+                }
+                Waveform::Silence => 0.0,
+            };
+            frame.fill(value * self.amplitude);
+        }
+    }
+
+    /// Generate the next callback chunk of `duration_ms`
+    pub fn chunk(&mut self, duration_ms: u32) -> Vec<f32> {
+        let mut chunk = vec![0.0; self.chunk_len(duration_ms)];
+        self.fill(&mut chunk);
+        chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_shape() {
+        let mut source = SyntheticSource::new(Waveform::Sine(440.0), 44100, 1);
+        assert_eq!(source.chunk(10).len(), 441);
+
+        let mut source = SyntheticSource::new(Waveform::Noise, 48000, 2);
+        let chunk = source.chunk(10);
+        assert_eq!(chunk.len(), 960);
+        assert!(chunk.chunks(2).all(|frame| frame[0] == frame[1]));
+        assert!(chunk.iter().all(|s| s.abs() <= 0.8));
+    }
+
+    #[test]
+    fn test_sine_is_continuous_across_chunks() {
+        let mut chunked = SyntheticSource::new(Waveform::Sine(1000.0), 48000, 1);
+        let mut whole = chunked.clone();
+        let mut joined = chunked.chunk(10);
+        joined.extend(chunked.chunk(10));
+        assert_eq!(joined, whole.chunk(20));
+    }
+}
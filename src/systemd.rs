@@ -0,0 +1,195 @@
+//! Readiness and status notifications for systemd services
+//!
+//! Under a `Type=notify` unit, systemd passes a socket in `$NOTIFY_SOCKET`
+//! and waits for `READY=1` before it considers the service started. The
+//! recorder sends it once capture is running, keeps `STATUS=` current with
+//! the elapsed time and file (shown by `systemctl status`), pings the
+//! watchdog if `WatchdogSec=` is set, and announces `STOPPING=1` when the
+//! unit is stopped, asking for more time while the file is finalized,
+//! transcribed or uploaded. Outside systemd nothing is sent.
+
+use meeting_recorder::events::RecorderEvent;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{Receiver, RecvTimeoutError};
+
+/// How often the status line is refreshed
+const STATUS_INTERVAL: Duration = Duration::from_secs(5);
+/// Extra stop time asked for while finalizing, renewed every status update
+const EXTEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Connection to systemd's notification socket
+pub struct Notifier {
+    #[cfg(unix)]
+    socket: std::os::unix::net::UnixDatagram,
+    #[cfg(unix)]
+    address: std::os::unix::net::SocketAddr,
+    /// How often systemd expects a watchdog ping, if at all
+    watchdog: Option<Duration>,
+}
+
+impl Notifier {
+    /// The notification socket systemd gave this process, if any
+    #[cfg(unix)]
+    pub fn from_env() -> Option<Self> {
+        use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+        let path = std::env::var_os("NOTIFY_SOCKET")?;
+        let path = path.to_string_lossy();
+        let address = match path.strip_prefix('@') {
+            #[cfg(target_os = "linux")]
+            Some(name) => {
+                use std::os::linux::net::SocketAddrExt;
+                SocketAddr::from_abstract_name(name).ok()?
+            }
+            #[cfg(not(target_os = "linux"))]
+            Some(_) => return None,
+            None => SocketAddr::from_pathname(&*path).ok()?,
+        };
+        Some(Self {
+            socket: UnixDatagram::unbound().ok()?,
+            address,
+            watchdog: watchdog_interval(),
+        })
+    }
+
+    /// systemd only runs on Unix
+    #[cfg(not(unix))]
+    pub fn from_env() -> Option<Self> {
+        None
+    }
+
+    /// Send `state`, e.g. `READY=1`; delivery failures are ignored, as a
+    /// missed status update must never stop a recording
+    pub fn notify(&self, state: &str) {
+        #[cfg(unix)]
+        let _ = self.socket.send_to_addr(state.as_bytes(), &self.address);
+        #[cfg(not(unix))]
+        let _ = state;
+    }
+
+    /// Keep systemd informed for the rest of the session from a thread of its own
+    ///
+    /// The thread follows `events` until the session stops, announcing the
+    /// stop as soon as `running` is cleared. Status and watchdog pings go out
+    /// once per interval, however often events arrive. It is never joined:
+    /// once the recording is finalized the process exits, which tells
+    /// systemd the rest.
+    pub fn follow(self, events: Receiver<RecorderEvent>, running: Arc<AtomicBool>) {
+        thread::spawn(move || {
+            let interval = self.watchdog.map_or(STATUS_INTERVAL, |watchdog| watchdog.min(STATUS_INTERVAL));
+            let mut started: Option<(Instant, String)> = None;
+            let mut stopping = false;
+            let mut last_update = Instant::now();
+            loop {
+                match events.recv_timeout(interval.saturating_sub(last_update.elapsed())) {
+                    Ok(RecorderEvent::Started { path, .. }) => {
+                        let file = path.map_or_else(|| "no WAV output".to_string(), |path| path.display().to_string());
+                        self.notify(&format!("READY=1\nSTATUS=Recording to {}", file));
+                        started = Some((Instant::now(), file));
+                    }
+                    Ok(RecorderEvent::Stopped { duration, .. }) => {
+                        self.notify(&format!("STATUS=Stopped after {}", minutes_text(duration)));
+                        return;
+                    }
+                    Ok(_) | Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+                let stop_announced = !stopping && !running.load(Ordering::SeqCst);
+                if stop_announced {
+                    self.notify("STOPPING=1\nSTATUS=Finalizing the recording");
+                    stopping = true;
+                }
+                // Level updates arrive every tenth of a second; systemd only needs one update per interval
+                if !stop_announced && last_update.elapsed() < interval {
+                    continue;
+                }
+                last_update = Instant::now();
+                if self.watchdog.is_some() {
+                    self.notify("WATCHDOG=1");
+                }
+                if stopping {
+                    self.notify(&format!("EXTEND_TIMEOUT_USEC={}", EXTEND_TIMEOUT.as_micros()));
+                } else if let Some((start, file)) = &started {
+                    self.notify(&format!("STATUS=Recording to {} for {}", file, minutes_text(start.elapsed())));
+                }
+            }
+        });
+    }
+}
+
+/// Watchdog interval systemd asked for, if it is meant for this process
+///
+/// Pings are sent at half the interval, as `sd_notify(3)` recommends.
+fn watchdog_interval() -> Option<Duration> {
+    if let Some(pid) = std::env::var("WATCHDOG_PID").ok().and_then(|pid| pid.parse::<u32>().ok()) {
+        if pid != std::process::id() {
+            return None;
+        }
+    }
+    let usec = std::env::var("WATCHDOG_USEC").ok()?.parse::<u64>().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec / 2))
+}
+
+/// `62m 03s`, as `status` reports running sessions
+fn minutes_text(duration: Duration) -> String {
+    format!("{}m {:02}s", duration.as_secs() / 60, duration.as_secs() % 60)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixDatagram;
+
+    #[test]
+    fn test_notifications_reach_the_socket() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("notify");
+        let socket = UnixDatagram::bind(&path).unwrap();
+        std::env::set_var("NOTIFY_SOCKET", &path);
+        let notifier = Notifier::from_env().unwrap();
+        std::env::remove_var("NOTIFY_SOCKET");
+
+        notifier.notify("READY=1\nSTATUS=Recording");
+        let mut buffer = [0; 64];
+        let len = socket.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], b"READY=1\nSTATUS=Recording");
+        assert_eq!(minutes_text(Duration::from_secs(3723)), "62m 03s");
+    }
+
+    #[test]
+    fn test_frequent_events_dont_flood_the_socket() {
+        use meeting_recorder::dsp::Levels;
+        use std::os::unix::net::SocketAddr;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("notify");
+        let socket = UnixDatagram::bind(&path).unwrap();
+        let notifier = Notifier {
+            socket: UnixDatagram::unbound().unwrap(),
+            address: SocketAddr::from_pathname(&path).unwrap(),
+            watchdog: Some(Duration::from_secs(10)),
+        };
+        let (tx, rx) = crossbeam_channel::unbounded();
+        notifier.follow(rx, Arc::new(AtomicBool::new(true)));
+
+        tx.send(RecorderEvent::Started { id: Default::default(), path: None }).unwrap();
+        let levels = Levels::default();
+        for _ in 0..20 {
+            tx.send(RecorderEvent::LevelUpdate { mic: levels, sys: None, mix: levels }).unwrap();
+        }
+        thread::sleep(Duration::from_millis(200));
+        drop(tx);
+
+        socket.set_nonblocking(true).unwrap();
+        let mut buffer = [0; 128];
+        let mut received = Vec::new();
+        while let Ok(len) = socket.recv(&mut buffer) {
+            received.push(String::from_utf8_lossy(&buffer[..len]).into_owned());
+        }
+        assert_eq!(received, ["READY=1\nSTATUS=Recording to no WAV output"]);
+    }
+}
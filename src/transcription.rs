@@ -0,0 +1,229 @@
+//! Transcription of finished recordings with Whisper
+//!
+//! With the `whisper` feature, a finished WAV recording is transcribed with
+//! [whisper.cpp](https://github.com/ggerganov/whisper.cpp) through
+//! `whisper-rs`, and the transcript is written next to it as plain text
+//! (`.txt`) and SubRip subtitles (`.srt`). The model is a ggml file such as
+//! `ggml-base.en.bin`, which has to be downloaded separately. Formatting and
+//! writing transcripts doesn't need the feature.
+
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::wav;
+
+/// How finished recordings are transcribed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transcription {
+    /// Path of the Whisper model, e.g. `/opt/whisper/ggml-base.en.bin`
+    pub model_path: String,
+    /// Spoken language as a code like `en` or `de`; detected when unset
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Transcript files written next to the recording
+    #[serde(default = "default_formats")]
+    pub formats: Vec<TranscriptFormat>,
+}
+
+fn default_formats() -> Vec<TranscriptFormat> {
+    vec![TranscriptFormat::Txt, TranscriptFormat::Srt]
+}
+
+/// A file format for transcripts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptFormat {
+    /// Plain text, one segment per line
+    Txt,
+    /// SubRip subtitles, with the time span of each segment
+    Srt,
+}
+
+impl TranscriptFormat {
+    /// File extension, without the dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            TranscriptFormat::Txt => "txt",
+            TranscriptFormat::Srt => "srt",
+        }
+    }
+
+    /// Render `segments` in this format
+    pub fn render(self, segments: &[Segment]) -> String {
+        match self {
+            TranscriptFormat::Txt => to_txt(segments),
+            TranscriptFormat::Srt => to_srt(segments),
+        }
+    }
+}
+
+/// A stretch of transcribed speech
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    /// Offset from the start of the recording
+    pub start: Duration,
+    pub end: Duration,
+    pub text: String,
+}
+
+/// Plain-text transcript, one segment per line
+pub fn to_txt(segments: &[Segment]) -> String {
+    segments.iter().fold(String::new(), |mut text, segment| {
+        let _ = writeln!(text, "{}", segment.text.trim());
+        text
+    })
+}
+
+/// SubRip subtitles, numbered from 1
+pub fn to_srt(segments: &[Segment]) -> String {
+    segments.iter().enumerate().fold(String::new(), |mut srt, (index, segment)| {
+        let _ = write!(
+            srt,
+            "{}\n{} --> {}\n{}\n\n",
+            index + 1,
+            srt_time(segment.start),
+            srt_time(segment.end),
+            segment.text.trim()
+        );
+        srt
+    })
+}
+
+/// `HH:MM:SS,mmm`, as SubRip writes times
+fn srt_time(time: Duration) -> String {
+    let millis = time.as_millis();
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// Path of the transcript of the recording at `recording_path` in `format`
+pub fn transcript_path(recording_path: impl AsRef<Path>, format: TranscriptFormat) -> PathBuf {
+    recording_path.as_ref().with_extension(format.extension())
+}
+
+/// Write `segments` next to the recording at `recording_path` in each of `formats`
+///
+/// Each file is written under a `.part` name and renamed into place.
+/// Returns the paths written.
+pub fn write_transcripts(
+    recording_path: impl AsRef<Path>,
+    segments: &[Segment],
+    formats: &[TranscriptFormat],
+) -> io::Result<Vec<PathBuf>> {
+    formats
+        .iter()
+        .map(|&format| {
+            let path = transcript_path(recording_path.as_ref(), format);
+            let part_path = wav::partial_path(&path);
+            fs::write(&part_path, format.render(segments))?;
+            fs::rename(&part_path, &path)?;
+            Ok(path)
+        })
+        .collect()
+}
+
+/// Sample rate Whisper models expect
+#[cfg(feature = "whisper")]
+const WHISPER_SAMPLE_RATE: u32 = 16_000;
+
+/// Transcribe the 16-bit WAV file at `path`
+///
+/// The whole recording is decoded into memory as 16 kHz mono, about 230 MB
+/// per hour, and transcribed in one pass, which takes a while for long
+/// meetings on a CPU.
+#[cfg(feature = "whisper")]
+pub fn transcribe(path: impl AsRef<Path>, options: &Transcription) -> Result<Vec<Segment>, Box<dyn std::error::Error>> {
+    use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+    let audio = load_audio(path.as_ref())?;
+    // Route whisper.cpp's own logging away from the terminal
+    whisper_rs::install_logging_hooks();
+    let context = WhisperContext::new_with_params(&options.model_path, WhisperContextParameters::default())
+        .map_err(|e| format!("could not load Whisper model {}: {}", options.model_path, e))?;
+    let mut state = context.create_state()?;
+
+    let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+    params.set_language(Some(options.language.as_deref().unwrap_or("auto")));
+    params.set_print_special(false);
+    params.set_print_progress(false);
+    params.set_print_realtime(false);
+    params.set_print_timestamps(false);
+    state.full(params, &audio)?;
+
+    // Segment times are in hundredths of a second
+    let time = |centis: i64| Duration::from_millis(centis.max(0) as u64 * 10);
+    (0..state.full_n_segments()?)
+        .map(|index| {
+            Ok(Segment {
+                start: time(state.full_get_segment_t0(index)?),
+                end: time(state.full_get_segment_t1(index)?),
+                text: state.full_get_segment_text_lossy(index)?,
+            })
+        })
+        .collect()
+}
+
+/// Samples of a WAV file as 16 kHz mono, the format Whisper takes
+#[cfg(feature = "whisper")]
+fn load_audio(path: &Path) -> hound::Result<Vec<f32>> {
+    use crate::dsp::{self, StereoResampler};
+
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    let mut resampler = StereoResampler::new(spec.sample_rate, WHISPER_SAMPLE_RATE);
+    let mut audio = Vec::with_capacity(reader.duration() as usize * WHISPER_SAMPLE_RATE as usize / spec.sample_rate.max(1) as usize);
+    let (mut chunk, mut stereo, mut resampled) = (Vec::new(), Vec::new(), Vec::new());
+    let mut samples = reader.samples::<i16>();
+    loop {
+        chunk.clear();
+        for sample in samples.by_ref().take(64 * 1024 * spec.channels as usize) {
+            chunk.push(sample?);
+        }
+        if chunk.is_empty() {
+            break;
+        }
+        stereo.clear();
+        dsp::append_stereo(&mut stereo, &chunk, spec.channels);
+        resampled.clear();
+        resampler.process(&stereo, &mut resampled);
+        audio.extend(resampled.chunks_exact(2).map(|frame| (frame[0] as f32 + frame[1] as f32) / 65536.0));
+    }
+    Ok(audio)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transcript_formats() {
+        let segments = [
+            Segment {
+                start: Duration::ZERO,
+                end: Duration::from_millis(2500),
+                text: " Let's get started.".to_string(),
+            },
+            Segment {
+                start: Duration::from_millis(3_723_040),
+                end: Duration::from_millis(3_725_000),
+                text: " Any questions?".to_string(),
+            },
+        ];
+        assert_eq!(to_txt(&segments), "Let's get started.\nAny questions?\n");
+        assert_eq!(
+            to_srt(&segments),
+            "1\n00:00:00,000 --> 00:00:02,500\nLet's get started.\n\n\
+             2\n01:02:03,040 --> 01:02:05,000\nAny questions?\n\n"
+        );
+        assert_eq!(transcript_path("/rec/meeting.wav", TranscriptFormat::Srt), Path::new("/rec/meeting.srt"));
+    }
+}
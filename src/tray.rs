@@ -0,0 +1,314 @@
+//! Tray icon mode
+//!
+//! `meeting-recorder tray --mic DEVICE` sits in the system tray (the
+//! notification area on Windows, the menu bar on macOS, an AppIndicator on
+//! Linux) instead of a terminal. The icon shows whether the recorder is idle
+//! (grey), recording (red) or paused (amber), and its menu starts, pauses,
+//! marks and stops recordings with the devices given on the command line,
+//! and opens the output folder. Each recording is finalized on a thread of
+//! its own, so the menu stays responsive while a long one is saved.
+
+use meeting_recorder::lock::SessionLock;
+use meeting_recorder::marker;
+use meeting_recorder::recorder::{RecordingResult, RecordingSession};
+use meeting_recorder::state::RecordingState;
+use meeting_recorder::{Config, DeviceManager};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tray_icon::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+use tray_icon::{Icon, TrayIconBuilder};
+
+use crate::meter::clock;
+use crate::{launch, select_device, shutdown, start_recording};
+
+/// How often the menu is checked and the status refreshed
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Side of the generated icons in pixels
+const ICON_SIZE: u32 = 32;
+
+/// What the icon shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TrayState {
+    Idle,
+    Recording,
+    Paused,
+}
+
+impl TrayState {
+    fn of(session: Option<&RecordingSession>) -> Self {
+        match session.map(RecordingSession::state) {
+            Some(RecordingState::Paused) => TrayState::Paused,
+            Some(_) => TrayState::Recording,
+            None => TrayState::Idle,
+        }
+    }
+
+    /// Fill color of the icon's dot
+    fn color(self) -> [u8; 3] {
+        match self {
+            TrayState::Idle => [0x9e, 0x9e, 0x9e],
+            TrayState::Recording => [0xe5, 0x39, 0x35],
+            TrayState::Paused => [0xff, 0xb3, 0x00],
+        }
+    }
+}
+
+/// The tray icon's menu
+struct TrayMenu {
+    status: MenuItem,
+    start: MenuItem,
+    pause: MenuItem,
+    marker: MenuItem,
+    stop: MenuItem,
+    open_folder: MenuItem,
+    quit: MenuItem,
+}
+
+impl TrayMenu {
+    fn new() -> Result<(Self, Menu), Box<dyn std::error::Error>> {
+        let items = Self {
+            status: MenuItem::new("Idle", false, None),
+            start: MenuItem::new("Start recording", true, None),
+            pause: MenuItem::new("Pause", false, None),
+            marker: MenuItem::new("Add marker", false, None),
+            stop: MenuItem::new("Stop recording", false, None),
+            open_folder: MenuItem::new("Open recordings folder", true, None),
+            quit: MenuItem::new("Quit", true, None),
+        };
+        let menu = Menu::new();
+        menu.append_items(&[
+            &items.status,
+            &PredefinedMenuItem::separator(),
+            &items.start,
+            &items.pause,
+            &items.marker,
+            &items.stop,
+            &PredefinedMenuItem::separator(),
+            &items.open_folder,
+            &items.quit,
+        ])?;
+        Ok((items, menu))
+    }
+
+    /// Enable the items that make sense in `state`
+    fn show(&self, state: TrayState) {
+        self.start.set_enabled(state == TrayState::Idle);
+        self.pause.set_enabled(state != TrayState::Idle);
+        self.pause.set_text(if state == TrayState::Paused { "Resume" } else { "Pause" });
+        self.marker.set_enabled(state == TrayState::Recording);
+        self.stop.set_enabled(state != TrayState::Idle);
+    }
+}
+
+/// Run the tray icon until Quit is chosen or a signal arrives, recording
+/// from `mic` and `system`
+pub fn run(mic: &str, system: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::load()?;
+    // Fail now rather than on the first click if a device is missing
+    let device_manager = DeviceManager::new()?;
+    select_device(&device_manager, mic)?;
+    if let Some(system) = system {
+        select_device(&device_manager, system)?;
+    }
+    drop(device_manager);
+
+    let quit = Arc::new(AtomicBool::new(true));
+    let _shutdown = shutdown::install(quit.clone())?;
+
+    platform::init()?;
+    let (menu, tray_menu) = TrayMenu::new()?;
+    let tray = TrayIconBuilder::new()
+        .with_menu(Box::new(tray_menu))
+        .with_tooltip("Meeting Recorder: idle")
+        .with_icon(icon(TrayState::Idle)?)
+        .build()?;
+    println!("Meeting Recorder is in the system tray; choose Quit from its menu or press Ctrl+C to exit");
+
+    let mut session: Option<RecordingSession> = None;
+    let mut lock: Option<SessionLock> = None;
+    let mut finalizing: Vec<thread::JoinHandle<()>> = Vec::new();
+    let mut shown = TrayState::Idle;
+    let mut shown_status = String::new();
+    let mut quitting = false;
+    while !quitting {
+        platform::wait(POLL_INTERVAL);
+        quitting = !quit.load(Ordering::SeqCst);
+
+        while let Ok(event) = MenuEvent::receiver().try_recv() {
+            if event.id == menu.start.id() && session.is_none() {
+                match start_recording(&config, mic, system, None) {
+                    Ok((started, session_lock)) => {
+                        println!("Recording to {}", started.path().map_or("no WAV file".into(), |path| path.display().to_string()));
+                        session = Some(started);
+                        lock = Some(session_lock);
+                    }
+                    Err(e) => eprintln!("Error: could not start recording: {}", e),
+                }
+            } else if event.id == menu.pause.id() {
+                if let Some(session) = &session {
+                    let _ = session.pause() || session.resume();
+                }
+            } else if event.id == menu.marker.id() {
+                if let Some(session) = &session {
+                    crate::control::mark(session, marker::DEFAULT_LABEL.to_string());
+                }
+            } else if event.id == menu.stop.id() {
+                if let Some(session) = &session {
+                    session.stop();
+                }
+            } else if event.id == menu.open_folder.id() {
+                if let Err(e) = launch::open(Path::new(&config.output_directory)) {
+                    eprintln!("Error: could not open {}: {}", config.output_directory, e);
+                }
+            } else if event.id == menu.quit.id() {
+                quitting = true;
+            }
+        }
+
+        if let (true, Some(session)) = (quitting, &session) {
+            session.stop();
+        }
+        // Hand a stopped session to a thread of its own to be finalized
+        if session.as_ref().is_some_and(|session| !session.is_running()) {
+            if let (Some(stopped), Some(session_lock)) = (session.take(), lock.take()) {
+                finalizing.push(thread::spawn(move || report(stopped.join(), session_lock)));
+            }
+        }
+
+        let state = TrayState::of(session.as_ref());
+        if state != shown {
+            tray.set_icon(Some(icon(state)?))?;
+            menu.show(state);
+            shown = state;
+        }
+        let status = match (&session, state) {
+            (Some(session), TrayState::Paused) => format!("Paused at {}", clock(session.duration())),
+            (Some(session), _) => format!("Recording {}", clock(session.duration())),
+            (None, _) => "Idle".to_string(),
+        };
+        if status != shown_status {
+            menu.status.set_text(&status);
+            tray.set_tooltip(Some(format!("Meeting Recorder: {}", status.to_lowercase())))?;
+            shown_status = status;
+        }
+    }
+
+    if !finalizing.is_empty() {
+        println!("Finalizing...");
+    }
+    for handle in finalizing {
+        let _ = handle.join();
+    }
+    Ok(())
+}
+
+/// Print how a finalized recording turned out, then release its lock
+fn report(result: meeting_recorder::error::Result<RecordingResult>, _session_lock: SessionLock) {
+    match result {
+        Ok(recording) => println!(
+            "Saved {} ({})",
+            recording.filename.as_deref().unwrap_or("the recording"),
+            clock(recording.duration)
+        ),
+        Err(e) => eprintln!("Error: {}", e),
+    }
+}
+
+/// A filled dot in the color of `state` on a transparent background
+fn icon(state: TrayState) -> Result<Icon, tray_icon::BadIcon> {
+    Icon::from_rgba(dot(state.color()), ICON_SIZE, ICON_SIZE)
+}
+
+fn dot([red, green, blue]: [u8; 3]) -> Vec<u8> {
+    let center = (ICON_SIZE as f32 - 1.0) / 2.0;
+    let radius = ICON_SIZE as f32 * 0.4;
+    let mut rgba = Vec::with_capacity((ICON_SIZE * ICON_SIZE * 4) as usize);
+    for y in 0..ICON_SIZE {
+        for x in 0..ICON_SIZE {
+            let distance = ((x as f32 - center).powi(2) + (y as f32 - center).powi(2)).sqrt();
+            // One pixel of antialiasing at the edge
+            let alpha = (radius + 0.5 - distance).clamp(0.0, 1.0);
+            rgba.extend_from_slice(&[red, green, blue, (alpha * 255.0) as u8]);
+        }
+    }
+    rgba
+}
+
+/// Running the platform's event loop, which the tray icon's menu lives on
+mod platform {
+    use std::time::Duration;
+
+    /// GTK must be running before the icon is created
+    #[cfg(target_os = "linux")]
+    pub fn init() -> Result<(), Box<dyn std::error::Error>> {
+        gtk::init().map_err(|e| format!("Could not start GTK for the tray icon: {}", e).into())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn init() -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    /// Handle pending GTK events, then wait
+    #[cfg(target_os = "linux")]
+    pub fn wait(duration: Duration) {
+        while gtk::events_pending() {
+            gtk::main_iteration_do(false);
+        }
+        std::thread::sleep(duration);
+    }
+
+    /// Dispatch the messages of the icon's hidden window
+    #[cfg(windows)]
+    pub fn wait(duration: Duration) {
+        use windows_sys::Win32::UI::WindowsAndMessaging::{
+            DispatchMessageW, PeekMessageW, TranslateMessage, MSG, PM_REMOVE,
+        };
+        unsafe {
+            let mut message: MSG = std::mem::zeroed();
+            while PeekMessageW(&mut message, std::ptr::null_mut(), 0, 0, PM_REMOVE) != 0 {
+                TranslateMessage(&message);
+                DispatchMessageW(&message);
+            }
+        }
+        std::thread::sleep(duration);
+    }
+
+    /// Run the main run loop, which serves the status item
+    #[cfg(target_os = "macos")]
+    pub fn wait(duration: Duration) {
+        use std::ffi::c_void;
+
+        #[link(name = "CoreFoundation", kind = "framework")]
+        extern "C" {
+            static kCFRunLoopDefaultMode: *const c_void;
+            fn CFRunLoopRunInMode(mode: *const c_void, seconds: f64, return_after_source_handled: u8) -> i32;
+        }
+        unsafe {
+            CFRunLoopRunInMode(kCFRunLoopDefaultMode, duration.as_secs_f64(), 1);
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux", windows, target_os = "macos")))]
+    pub fn wait(duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_icon_dot() {
+        let rgba = dot(TrayState::Recording.color());
+        assert_eq!(rgba.len(), (ICON_SIZE * ICON_SIZE * 4) as usize);
+        let pixel = |x: u32, y: u32| &rgba[((y * ICON_SIZE + x) * 4) as usize..][..4];
+        assert_eq!(pixel(16, 16), [0xe5, 0x39, 0x35, 255]);
+        assert_eq!(pixel(0, 0)[3], 0);
+        assert_eq!(TrayState::of(None), TrayState::Idle);
+    }
+}
@@ -0,0 +1,273 @@
+//! Full-screen terminal interface
+//!
+//! `meeting-recorder tui` picks the devices from lists and then shows, while
+//! recording, a live meter for each source and the mix, the elapsed time,
+//! the size of the file so far with its write rate and the recording time
+//! left on the volume, and any dropouts or errors, which the plain
+//! prompt only reports at the end. Space pauses and resumes, `m` adds a
+//! marker, and `q`, Esc or Ctrl+C stop. The terminal is handed back before
+//! the file is finalized, so the usual summary is printed afterwards.
+
+use meeting_recorder::dsp::Levels;
+use meeting_recorder::events::RecorderEvent;
+use meeting_recorder::lock::SessionLock;
+use meeting_recorder::marker;
+use meeting_recorder::recorder::{RecordingResult, RecordingSession};
+use meeting_recorder::state::RecordingState;
+use meeting_recorder::{console, Config, DeviceManager, Recorder};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Gauge, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::VecDeque;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::meter::{clock, db, file_size, meter_ratio, size_text, DiskUsage};
+use crate::shutdown;
+
+/// How often the screen is redrawn
+const REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+/// Events kept on screen
+const LOG_LINES: usize = 50;
+
+/// Pick devices and record until stopped, then finalize the recording
+pub fn run() -> Result<RecordingResult, Box<dyn std::error::Error>> {
+    let config = Config::load()?;
+    let _session_lock = SessionLock::acquire(&config.output_directory)?;
+    let device_manager = DeviceManager::new()?;
+    let names = (0..device_manager.device_count())
+        .map(|index| device_manager.device_name(index))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Signals still stop the recording; Ctrl+C arrives as a key press instead
+    let running = Arc::new(AtomicBool::new(true));
+    let _shutdown = shutdown::install(running.clone())?;
+
+    console::set_enabled(false);
+    let mut terminal = ratatui::init();
+    let session = record(&mut terminal, &config, device_manager, &names, running);
+    ratatui::restore();
+    console::set_enabled(true);
+
+    match session? {
+        Some(session) => {
+            println!("Finalizing {}...", session.path().map_or("the recording".into(), |path| path.display().to_string()));
+            Ok(session.join()?)
+        }
+        None => Err("No device selected".into()),
+    }
+}
+
+/// Run the device lists and the recording screen, returning the stopped session
+fn record(
+    terminal: &mut DefaultTerminal,
+    config: &Config,
+    mut device_manager: DeviceManager,
+    names: &[String],
+    running: Arc<AtomicBool>,
+) -> Result<Option<RecordingSession>, Box<dyn std::error::Error>> {
+    let Some(mic_idx) = select(terminal, "Select the microphone", names)? else {
+        return Ok(None);
+    };
+    let mut choices = vec!["No system audio".to_string()];
+    choices.extend(names.iter().cloned());
+    let Some(choice) = select(terminal, "Select the system audio device", &choices)? else {
+        return Ok(None);
+    };
+    let sys_idx = choice.checked_sub(1);
+
+    let mic_config = device_manager.device_config(mic_idx)?;
+    let sys_config = sys_idx.and_then(|idx| device_manager.device_config(idx).ok());
+    let mic_device = device_manager
+        .take_device(mic_idx)
+        .ok_or_else(|| format!("Failed to get microphone device at index {}", mic_idx))?;
+    let sys_device = sys_idx.and_then(|idx| device_manager.take_device(idx));
+    let recorder = Recorder::new(mic_device, mic_config, sys_device, sys_config).with_running_flag(running);
+    let events = recorder.subscribe();
+    let session = recorder.start(config)?;
+
+    let mut dashboard = Dashboard {
+        mic: names[mic_idx].clone(),
+        sys: sys_idx.map(|idx| names[idx].clone()),
+        log: VecDeque::new(),
+        dropouts: 0,
+        disk: DiskUsage::new(config),
+    };
+    while session.is_running() {
+        for event in events.try_iter() {
+            dashboard.note(event);
+        }
+        terminal.draw(|frame| dashboard.draw(frame, &session))?;
+        if !event::poll(REFRESH_INTERVAL)? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else { continue };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => session.stop(),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => session.stop(),
+            KeyCode::Char(' ') | KeyCode::Char('p') => {
+                let _ = session.pause() || session.resume();
+            }
+            KeyCode::Char('m') => {
+                let marker = session.add_marker(marker::DEFAULT_LABEL);
+                let time = marker.time(session.sample_rate().max(1));
+                dashboard.log(format!("Marker at {}", clock(time)));
+            }
+            _ => {}
+        }
+    }
+    Ok(Some(session))
+}
+
+/// Let the user pick one of `items`; None if they back out
+fn select(terminal: &mut DefaultTerminal, title: &str, items: &[String]) -> std::io::Result<Option<usize>> {
+    if items.is_empty() {
+        return Ok(None);
+    }
+    let mut state = ListState::default().with_selected(Some(0));
+    loop {
+        terminal.draw(|frame| {
+            let [list_area, help_area] = Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+            let list = List::new(items.iter().map(|item| ListItem::new(item.as_str())))
+                .block(Block::bordered().title(format!(" {} ", title)))
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+                .highlight_symbol("> ");
+            frame.render_stateful_widget(list, list_area, &mut state);
+            frame.render_widget(Paragraph::new("↑/↓ choose   Enter select   q quit"), help_area);
+        })?;
+        let Event::Key(key) = event::read()? else { continue };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => state.select_previous(),
+            KeyCode::Down | KeyCode::Char('j') => {
+                let next = state.selected().map_or(0, |selected| (selected + 1).min(items.len() - 1));
+                state.select(Some(next));
+            }
+            KeyCode::Enter => return Ok(state.selected()),
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(None),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(None),
+            _ => {}
+        }
+    }
+}
+
+/// What the recording screen shows besides the session's own state
+struct Dashboard {
+    mic: String,
+    sys: Option<String>,
+    /// Dropouts and errors, newest last
+    log: VecDeque<String>,
+    /// Audio chunks lost or delayed so far
+    dropouts: u64,
+    disk: DiskUsage,
+}
+
+impl Dashboard {
+    fn note(&mut self, event: RecorderEvent) {
+        match event {
+            RecorderEvent::Dropout { source, count } => {
+                self.dropouts += count;
+                self.log(format!("Dropout: {} lost {} chunk(s)", source, count));
+            }
+            RecorderEvent::MicSilent { duration } => self.log(format!(
+                "Microphone silent for {} s while system audio plays; is it muted?",
+                duration.as_secs()
+            )),
+            RecorderEvent::Error { message } => self.log(message),
+            _ => {}
+        }
+    }
+
+    fn log(&mut self, line: String) {
+        if self.log.len() == LOG_LINES {
+            self.log.pop_front();
+        }
+        self.log.push_back(line);
+    }
+
+    fn draw(&mut self, frame: &mut Frame, session: &RecordingSession) {
+        let [status_area, mic_area, sys_area, mix_area, log_area, help_area] = Layout::vertical([
+            Constraint::Length(4),
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let (state, state_color) = match session.state() {
+            RecordingState::Paused => ("PAUSED", Color::Yellow),
+            _ => ("● REC", Color::Red),
+        };
+        let file = session.path().map_or_else(|| "no WAV output".to_string(), |path| path.display().to_string());
+        let mut size = session.path().and_then(file_size).map_or_else(String::new, |bytes| format!("   {}", size_text(bytes)));
+        if let Some(usage) = self.disk.text(session) {
+            size.push_str(&format!(" @ {}", usage));
+        }
+        let status = vec![
+            Line::styled(
+                format!("{}   {}{}", state, clock(session.duration()), size),
+                Style::default().fg(state_color).add_modifier(Modifier::BOLD),
+            ),
+            Line::raw(file),
+        ];
+        let dropouts = if self.dropouts > 0 {
+            format!(" {} dropouts ", self.dropouts)
+        } else {
+            String::new()
+        };
+        let block = Block::bordered()
+            .title(" Meeting Recorder ")
+            .title_bottom(Line::styled(dropouts, Style::default().fg(Color::Yellow)).right_aligned());
+        frame.render_widget(Paragraph::new(status).block(block), status_area);
+
+        let levels = session.levels();
+        render_meter(frame, mic_area, &format!("Microphone: {}", self.mic), Some(levels.mic));
+        match &self.sys {
+            Some(name) => render_meter(frame, sys_area, &format!("System audio: {}", name), levels.sys),
+            None => render_meter(frame, sys_area, "System audio: not recorded", None),
+        }
+        render_meter(frame, mix_area, "Mix", Some(levels.mix));
+
+        let log: Vec<ListItem> = self
+            .log
+            .iter()
+            .rev()
+            .map(|line| ListItem::new(line.as_str()).style(Style::default().fg(Color::Yellow)))
+            .collect();
+        frame.render_widget(List::new(log).block(Block::bordered().title(" Events ")), log_area);
+        frame.render_widget(Paragraph::new("Space pause/resume   m marker   q stop"), help_area);
+    }
+}
+
+fn render_meter(frame: &mut Frame, area: Rect, title: &str, levels: Option<Levels>) {
+    let block = Block::bordered().title(format!(" {} ", title));
+    let Some(levels) = levels else {
+        frame.render_widget(block, area);
+        return;
+    };
+    let peak_db = db(levels.peak);
+    let color = if peak_db >= -1.0 {
+        Color::Red
+    } else if peak_db >= -6.0 {
+        Color::Yellow
+    } else {
+        Color::Green
+    };
+    let gauge = Gauge::default()
+        .block(block)
+        .gauge_style(Style::default().fg(color))
+        .ratio(meter_ratio(levels.peak))
+        .label(format!("peak {:.1} dBFS   RMS {:.1} dBFS", peak_db, db(levels.rms)));
+    frame.render_widget(gauge, area);
+}
@@ -0,0 +1,131 @@
+//! Recording whenever the system audio is active
+//!
+//! `meeting-recorder watch --mic DEVICE --system DEVICE` listens to the
+//! system audio device and, once sound has kept up for `start_after_secs`,
+//! records both devices until `stop_after_silence_secs` of silence. While
+//! waiting only the levels are measured; nothing is written. Lines typed
+//! while recording add markers.
+
+use meeting_recorder::activity::{Activity, ActivityDetector, ActivityWatch, LevelMonitor};
+use meeting_recorder::marker;
+use meeting_recorder::{Config, DeviceManager};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::meter::clock;
+use crate::{control, finish_recording, select_device, shutdown, start_recording, Current};
+
+/// How often the levels are checked
+const TICK: Duration = Duration::from_millis(100);
+/// How long to wait before opening a device that failed again
+const RETRY: Duration = Duration::from_secs(5);
+
+/// Record whenever `system` is active until a signal arrives, from `mic` and `system`
+pub fn run(mic: &str, system: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::load()?;
+    let options = config.activity_watch.clone().unwrap_or_default();
+    // Fail now rather than once sound starts if the microphone is missing
+    let device_manager = DeviceManager::new()?;
+    select_device(&device_manager, mic)?;
+    drop(device_manager);
+    let mut monitor = Some(listen(system)?);
+
+    let running = Arc::new(AtomicBool::new(true));
+    let _shutdown = shutdown::install(running.clone())?;
+    println!("Waiting for sound on {}; press Ctrl+C to stop", system);
+
+    let typed = control::stdin_lines();
+    let mut detector = options.detector();
+    let mut current: Option<Current> = None;
+    let mut retry_at: Option<Instant> = None;
+    let mut last_tick = Instant::now();
+    while running.load(Ordering::SeqCst) {
+        thread::sleep(TICK);
+        let elapsed = last_tick.elapsed();
+        last_tick = Instant::now();
+
+        if let Some(recording) = &current {
+            if let Ok(line) = typed.try_recv() {
+                control::mark(&recording.session, marker::typed_label(&line, &config.marker_labels));
+            }
+            let levels = recording.session.levels().sys.unwrap_or_default();
+            let stopped_itself = !recording.session.is_running();
+            if stopped_itself || detector.update(levels, elapsed) == Some(Activity::Stopped) {
+                if !stopped_itself {
+                    println!("Silent for {}; stopping", clock(Duration::from_secs(options.stop_after_silence_secs)));
+                }
+                finish_recording(current.take());
+                detector = waiting(&options, system, &mut monitor, &mut retry_at);
+            }
+            continue;
+        }
+        // Drain lines typed while waiting, so they don't mark the next recording
+        while typed.try_recv().is_ok() {}
+
+        let Some(levels_monitor) = &monitor else {
+            if retry_at.is_none_or(|at| Instant::now() >= at) {
+                detector = waiting(&options, system, &mut monitor, &mut retry_at);
+            }
+            continue;
+        };
+        let levels = match levels_monitor.take() {
+            Ok(levels) => levels,
+            Err(e) => {
+                eprintln!("Warning: lost {}: {}", system, e);
+                monitor = None;
+                retry_at = Some(Instant::now() + RETRY);
+                continue;
+            }
+        };
+        if detector.update(levels, elapsed) == Some(Activity::Started) {
+            // Let go of the device so the recording can open it
+            monitor = None;
+            match start_recording(&config, mic, Some(system), None) {
+                Ok((session, lock)) => {
+                    let path = session.path().map_or("no file".into(), |path| path.display().to_string());
+                    println!("Sound on {}; recording to {}", system, path);
+                    current = Some(Current { session, _lock: lock, name: None, state: () });
+                }
+                Err(e) => {
+                    eprintln!("Error: could not start recording: {}", e);
+                    detector = waiting(&options, system, &mut monitor, &mut retry_at);
+                }
+            }
+        }
+    }
+    finish_recording(current);
+    Ok(())
+}
+
+/// Open `system` to measure its levels
+fn listen(system: &str) -> Result<LevelMonitor, Box<dyn std::error::Error>> {
+    let device_manager = DeviceManager::new()?;
+    let index = select_device(&device_manager, system)?;
+    let config = device_manager.device_config(index)?;
+    let device = device_manager
+        .get_device(index)
+        .ok_or_else(|| format!("Failed to get system audio device at index {}", index))?;
+    Ok(LevelMonitor::start(device, &config)?)
+}
+
+/// Go back to waiting for sound, listening to `system` again
+fn waiting(
+    options: &ActivityWatch,
+    system: &str,
+    monitor: &mut Option<LevelMonitor>,
+    retry_at: &mut Option<Instant>,
+) -> ActivityDetector {
+    match listen(system) {
+        Ok(listening) => {
+            *monitor = Some(listening);
+            *retry_at = None;
+        }
+        Err(e) => {
+            eprintln!("Warning: could not listen to {}: {}", system, e);
+            *retry_at = Some(Instant::now() + RETRY);
+        }
+    }
+    options.detector()
+}
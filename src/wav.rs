@@ -1,45 +1,706 @@
-use std::fs;
-use std::io::Read;
+use hound::{SampleFormat, WavSpec, WavWriter};
+use std::fmt;
+use std::ops::Range;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
 
-/// Validates that a file is a proper WAV file with valid structure
-pub fn validate_wav_file(path: &str) -> Result<(), Box<dyn std::error::Error>> {
+use crate::dsp::Levels;
+use crate::marker::Marker;
+
+/// WAV writer used for recordings, buffered in front of the output file
+pub type RecordingWriter = WavWriter<BufWriter<File>>;
+
+/// Create a buffered WAV writer for a recording
+///
+/// Also returns a second handle to the same file, used by [`checkpoint`] to
+/// sync data to disk while the writer owns the buffered handle.
+pub fn create_recording_writer(
+    path: impl AsRef<Path>,
+    spec: WavSpec,
+    buffer_size: usize,
+) -> hound::Result<(RecordingWriter, File)> {
+    let file = File::create(path)?;
+    let sync_handle = file.try_clone()?;
+    let writer = WavWriter::new(BufWriter::with_capacity(buffer_size, file), spec)?;
+    Ok((writer, sync_handle))
+}
+
+/// Extension appended to a recording's path while it is still being written
+pub const PARTIAL_EXTENSION: &str = "part";
+
+/// Temporary path a recording is written to before being renamed into place
+pub fn partial_path(path: impl AsRef<Path>) -> PathBuf {
+    let mut partial = path.as_ref().as_os_str().to_owned();
+    partial.push(".");
+    partial.push(PARTIAL_EXTENSION);
+    PathBuf::from(partial)
+}
+
+/// Final path of a recording left behind under its temporary name, if `path` is one
+pub fn completed_path(path: impl AsRef<Path>) -> Option<PathBuf> {
+    let path = path.as_ref();
+    (path.extension()? == PARTIAL_EXTENSION).then(|| path.with_extension(""))
+}
+
+/// Append interleaved samples to a recording
+pub fn write_samples(writer: &mut RecordingWriter, samples: &[i16]) -> hound::Result<()> {
+    for &sample in samples {
+        writer.write_sample(sample)?;
+    }
+    Ok(())
+}
+
+/// Make everything written so far playable from disk
+///
+/// Rewrites the RIFF and data chunk sizes to cover the samples written so
+/// far and flushes the buffer, so a crash leaves a valid file up to this
+/// point. With `sync` the data is also forced to stable storage, which
+/// protects against power loss as well as process crashes.
+pub fn checkpoint(
+    writer: &mut RecordingWriter,
+    sync_handle: &File,
+    sync: bool,
+) -> hound::Result<()> {
+    writer.flush()?;
+    if sync {
+        sync_handle.sync_data()?;
+    }
+    Ok(())
+}
+
+/// Name and version of the recorder, as written into file metadata
+pub const SOFTWARE: &str = concat!("meeting-recorder ", env!("CARGO_PKG_VERSION"));
+
+/// What a recording is, for its LIST-INFO and Broadcast Wave (`bext`) chunks
+#[derive(Debug, Clone)]
+pub struct WavMetadata {
+    /// Title of the recording
+    pub title: String,
+    /// When recording started
+    pub started: SystemTime,
+    /// Name of the microphone input
+    pub microphone: String,
+    /// Name of the system audio input, if there was one
+    pub system_audio: Option<String>,
+    /// Unique ID of the recording session
+    pub session_id: Uuid,
+}
+
+/// `bext` and LIST-INFO chunks describing a recording
+pub fn metadata_chunks(spec: &WavSpec, metadata: &WavMetadata) -> Vec<u8> {
+    let mut chunks = Vec::new();
+    push_chunk(&mut chunks, b"bext", &bext_chunk(spec, metadata));
+    push_chunk(&mut chunks, b"LIST", &info_chunk(metadata));
+    chunks
+}
+
+/// `cue ` and LIST-adtl chunks placing `markers` on the timeline, with their labels
+///
+/// Empty without markers.
+pub fn cue_chunks(markers: &[Marker]) -> Vec<u8> {
+    let mut chunks = Vec::new();
+    if markers.is_empty() {
+        return chunks;
+    }
+
+    let mut cue = (markers.len() as u32).to_le_bytes().to_vec();
+    let mut adtl = b"adtl".to_vec();
+    for (id, marker) in (1u32..).zip(markers) {
+        let position = u32::try_from(marker.position).unwrap_or(u32::MAX);
+        cue.extend_from_slice(&id.to_le_bytes());
+        cue.extend_from_slice(&position.to_le_bytes());
+        cue.extend_from_slice(b"data");
+        // Chunk and block start are zero for uncompressed data in a single data chunk
+        cue.extend_from_slice(&[0; 8]);
+        cue.extend_from_slice(&position.to_le_bytes());
+
+        let mut label = id.to_le_bytes().to_vec();
+        label.extend_from_slice(marker.label.as_bytes());
+        label.push(0);
+        push_chunk(&mut adtl, b"labl", &label);
+    }
+    push_chunk(&mut chunks, b"cue ", &cue);
+    push_chunk(&mut chunks, b"LIST", &adtl);
+    chunks
+}
+
+/// Append `chunks` to a finalized WAV file and extend its RIFF size over them
+///
+/// The chunks go after the audio, so the recording never has to be moved to
+/// make room for them. Files too close to the 4 GiB RIFF limit are left as
+/// they are.
+pub fn append_chunks<F: Write + Seek>(file: &mut F, chunks: &[u8]) -> io::Result<()> {
+    let end = file.seek(SeekFrom::End(0))?;
+    let Ok(riff_size) = u32::try_from(end + chunks.len() as u64 - 8) else {
+        return Ok(());
+    };
+    file.write_all(chunks)?;
+    file.seek(SeekFrom::Start(4))?;
+    file.write_all(&riff_size.to_le_bytes())?;
+    file.flush()
+}
+
+/// Broadcast Wave extension chunk, version 1
+fn bext_chunk(spec: &WavSpec, metadata: &WavMetadata) -> Vec<u8> {
+    let (date, time) = utc_date_time(metadata.started);
+    let secs_since_midnight = metadata.started.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() % 86400;
+
+    let mut bext = Vec::with_capacity(602);
+    push_fixed(&mut bext, &metadata.title, 256);
+    push_fixed(&mut bext, "meeting-recorder", 32);
+    push_fixed(&mut bext, &metadata.session_id.simple().to_string(), 32);
+    push_fixed(&mut bext, &date, 10);
+    push_fixed(&mut bext, &time, 8);
+    bext.extend_from_slice(&(secs_since_midnight * spec.sample_rate as u64).to_le_bytes());
+    bext.extend_from_slice(&1u16.to_le_bytes());
+    // UMID, loudness and reserved bytes stay zero
+    bext.resize(602, 0);
+    let mode = if spec.channels == 1 { "mono" } else { "stereo" };
+    bext.extend_from_slice(
+        format!("A=PCM,F={},W={},M={},T={}\r\n", spec.sample_rate, spec.bits_per_sample, mode, SOFTWARE).as_bytes(),
+    );
+    bext
+}
+
+/// LIST chunk of type INFO with the title, date, software and inputs
+fn info_chunk(metadata: &WavMetadata) -> Vec<u8> {
+    let mut inputs = format!("Microphone: {}", metadata.microphone);
+    if let Some(system_audio) = &metadata.system_audio {
+        inputs.push_str(&format!("; System audio: {}", system_audio));
+    }
+
+    let mut info = b"INFO".to_vec();
+    for (id, value) in [
+        (b"INAM", metadata.title.as_str()),
+        (b"ICRD", &utc_date_time(metadata.started).0),
+        (b"ISFT", SOFTWARE),
+        (b"ICMT", &inputs),
+    ] {
+        let mut text = value.as_bytes().to_vec();
+        text.push(0);
+        push_chunk(&mut info, id, &text);
+    }
+    info
+}
+
+/// Append a chunk with its header and padding byte
+fn push_chunk(out: &mut Vec<u8>, id: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(id);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+/// Append `text` as a NUL-padded field of `len` bytes, cut at a character boundary
+fn push_fixed(out: &mut Vec<u8>, text: &str, len: usize) {
+    let mut end = text.len().min(len);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    out.extend_from_slice(&text.as_bytes()[..end]);
+    out.resize(out.len() + len - end, 0);
+}
+
+/// `yyyy-mm-dd` and `hh:mm:ss` in UTC
+pub(crate) fn utc_date_time(time: SystemTime) -> (String, String) {
+    let secs = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let (days, secs_in_day) = ((secs / 86400) as i64, secs % 86400);
+
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (
+        format!("{:04}-{:02}-{:02}", year, month, day),
+        format!("{:02}:{:02}:{:02}", secs_in_day / 3600, secs_in_day % 3600 / 60, secs_in_day % 60),
+    )
+}
+
+/// A chunk of a RIFF file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkInfo {
+    /// Four-character chunk ID, e.g. `fmt ` or `data`
+    pub id: String,
+    /// Offset of the chunk header from the start of the file
+    pub offset: u64,
+    /// Size of the chunk body as recorded in its header
+    pub size: u32,
+}
+
+/// What [`validate_wav_file`] found in a WAV file
+#[derive(Debug, Clone, PartialEq)]
+pub struct WavReport {
+    /// Sample format from the `fmt ` chunk
+    pub spec: WavSpec,
+    /// Length of the audio in the data chunk
+    pub duration: Duration,
+    /// Bytes of audio in the data chunk
+    pub data_size: u64,
+    /// Top-level chunks in file order
+    pub chunks: Vec<ChunkInfo>,
+    /// Problems that don't stop the file from being read
+    pub warnings: Vec<String>,
+}
+
+impl fmt::Display for WavReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format = match self.spec.sample_format {
+            SampleFormat::Int => "integer",
+            SampleFormat::Float => "float",
+        };
+        writeln!(
+            f,
+            "Format: {} channels, {} Hz, {}-bit {}",
+            self.spec.channels, self.spec.sample_rate, self.spec.bits_per_sample, format
+        )?;
+        writeln!(f, "Duration: {:.3}s", self.duration.as_secs_f64())?;
+        writeln!(f, "Data size: {} bytes", self.data_size)?;
+        writeln!(f, "Chunks:")?;
+        for chunk in &self.chunks {
+            writeln!(f, "  {:<4} at {:>10}, {} bytes", chunk.id, chunk.offset, chunk.size)?;
+        }
+        if self.warnings.is_empty() {
+            write!(f, "No problems found")
+        } else {
+            write!(f, "Warnings:")?;
+            for warning in &self.warnings {
+                write!(f, "\n  {}", warning)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Top-level chunks of a RIFF/WAVE file, as found by [`walk_chunks`]
+struct RiffLayout {
+    chunks: Vec<ChunkInfo>,
+    /// Where the walk stopped: the end of the last chunk, or the start of
+    /// bytes that don't form a chunk header
+    end: u64,
+}
+
+/// Whether `id` can be a chunk ID: four printable ASCII characters
+fn is_chunk_id(id: &[u8]) -> bool {
+    id.iter().all(|byte| (0x20..0x7F).contains(byte))
+}
+
+/// Read the chunk header at `offset`, if there is a plausible one
+fn read_chunk_header<R: Read + Seek>(reader: &mut R, offset: u64, file_len: u64) -> io::Result<Option<([u8; 4], u32)>> {
+    if offset + 8 > file_len {
+        return Ok(None);
+    }
+    let mut header = [0u8; 8];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut header)?;
+    let id: [u8; 4] = header[0..4].try_into().unwrap();
+    Ok(is_chunk_id(&id).then(|| (id, u32::from_le_bytes(header[4..8].try_into().unwrap()))))
+}
+
+/// List the chunks after the 12-byte RIFF header, in any order
+///
+/// Chunks are word aligned, but some writers leave out the padding byte
+/// after an odd-sized chunk; the unpadded position is used when only it
+/// holds a chunk header. The walk stops at the end of the file or at bytes
+/// that can't be a chunk header, such as audio past a stale data size.
+fn walk_chunks<R: Read + Seek>(reader: &mut R, file_len: u64) -> io::Result<RiffLayout> {
+    let mut chunks = Vec::new();
+    let mut offset = 12;
+    while let Some((id, size)) = read_chunk_header(reader, offset, file_len)? {
+        chunks.push(ChunkInfo {
+            id: String::from_utf8_lossy(&id).into_owned(),
+            offset,
+            size,
+        });
+        let unpadded = offset + 8 + size as u64;
+        offset = unpadded + (size as u64 & 1);
+        if offset != unpadded
+            && read_chunk_header(reader, offset, file_len)?.is_none()
+            && read_chunk_header(reader, unpadded, file_len)?.is_some()
+        {
+            offset = unpadded;
+        }
+    }
+    Ok(RiffLayout { chunks, end: offset })
+}
+
+/// Check that a file is a WAV file with a valid structure and describe what it holds
+///
+/// The `fmt ` and `data` chunks are found wherever they are, among any
+/// LIST, fact, JUNK or other chunks.
+pub fn validate_wav_file(path: impl AsRef<Path>) -> Result<WavReport, Box<dyn std::error::Error>> {
     let mut file = fs::File::open(path)?;
-    let mut buffer = [0u8; 44]; // Read at least the header
-    
-    let bytes_read = file.read(&mut buffer)?;
-    
-    if bytes_read < 12 {
+    let file_len = file.metadata()?.len();
+    let mut header = [0u8; 12];
+    if file_len < 12 {
         return Err("File too small to be a valid WAV file".into());
     }
+    file.read_exact(&mut header)?;
     
     // Check RIFF header (bytes 0-3)
-    if &buffer[0..4] != b"RIFF" {
-        return Err(format!("Invalid RIFF header: expected 'RIFF', got '{:?}'", &buffer[0..4]).into());
+    if &header[0..4] != b"RIFF" {
+        return Err(format!("Invalid RIFF header: expected 'RIFF', got '{:?}'", &header[0..4]).into());
     }
     
     // Check WAVE identifier (bytes 8-11)
-    if &buffer[8..12] != b"WAVE" {
-        return Err(format!("Invalid WAVE identifier: expected 'WAVE', got '{:?}'", &buffer[8..12]).into());
+    if &header[8..12] != b"WAVE" {
+        return Err(format!("Invalid WAVE identifier: expected 'WAVE', got '{:?}'", &header[8..12]).into());
     }
     
-    // Check format chunk (bytes 12-15 should be "fmt ")
-    if bytes_read >= 16 && &buffer[12..16] != b"fmt " {
-        return Err("Format chunk identifier not found".into());
+    let mut warnings = Vec::new();
+    let riff_size = u32::from_le_bytes(header[4..8].try_into().unwrap()) as u64;
+    if riff_size + 8 != file_len {
+        warnings.push(format!(
+            "RIFF size covers {} bytes but the file has {}; run `repair` if it was not finalized",
+            riff_size + 8,
+            file_len
+        ));
     }
     
-    // Verify file has data beyond headers
-    let metadata = fs::metadata(path)?;
-    if metadata.len() <= 44 {
+    let RiffLayout { chunks, end } = walk_chunks(&mut file, file_len)?;
+    let format_chunk = chunks.iter().find(|chunk| chunk.id == "fmt ").ok_or("Format chunk not found")?;
+    file.seek(SeekFrom::Start(format_chunk.offset + 8))?;
+    let spec = read_format(&mut file, format_chunk.size, &mut warnings)?;
+    let data_chunk = chunks.iter().find(|chunk| chunk.id == "data").ok_or("No data chunk found")?;
+    
+    let data_start = data_chunk.offset + 8;
+    let mut data_size = data_chunk.size as u64;
+    if end > file_len {
+        let last = chunks.last().map_or("", |chunk| chunk.id.as_str());
+        warnings.push(format!("The '{}' chunk runs {} bytes past the end of the file", last, end - file_len));
+        data_size = data_size.min(file_len - data_start);
+    } else if end < file_len {
+        warnings.push(format!(
+            "{} bytes at offset {} are not part of any chunk; run `repair` if the recording was not finalized",
+            file_len - end,
+            end
+        ));
+    }
+    if data_size == 0 && end >= file_len {
         return Err("File contains only headers, no audio data".into());
     }
     
-    Ok(())
+    let block_align = spec.channels as u64 * spec.bits_per_sample.div_ceil(8) as u64;
+    if !data_size.is_multiple_of(block_align) {
+        warnings.push(format!("The data ends with {} bytes of an incomplete frame", data_size % block_align));
+    }
+    let frames = data_size / block_align;
+    
+    Ok(WavReport {
+        spec,
+        duration: Duration::from_secs_f64(frames as f64 / spec.sample_rate as f64),
+        data_size,
+        chunks,
+        warnings,
+    })
+}
+
+/// Read the body of a `fmt ` chunk of `size` bytes
+///
+/// Supports integer PCM of 8 to 32 bits and 32- or 64-bit float, and notes
+/// header fields that disagree with the format in `warnings`.
+fn read_format<R: Read>(
+    file: &mut R,
+    size: u32,
+    warnings: &mut Vec<String>,
+) -> Result<WavSpec, Box<dyn std::error::Error>> {
+    if size < 16 {
+        return Err(format!("Format chunk is {} bytes, too short", size).into());
+    }
+    let mut fmt = vec![0u8; size.min(40) as usize];
+    file.read_exact(&mut fmt)?;
+    let mut format_tag = u16::from_le_bytes([fmt[0], fmt[1]]);
+    // WAVE_FORMAT_EXTENSIBLE names the real format in its subformat GUID
+    if format_tag == 0xFFFE && fmt.len() >= 26 {
+        format_tag = u16::from_le_bytes([fmt[24], fmt[25]]);
+    }
+    let sample_format = match format_tag {
+        1 => SampleFormat::Int,
+        3 => SampleFormat::Float,
+        tag => return Err(format!("Unsupported format tag {:#06x}", tag).into()),
+    };
+    let spec = WavSpec {
+        channels: u16::from_le_bytes([fmt[2], fmt[3]]),
+        sample_rate: u32::from_le_bytes(fmt[4..8].try_into().unwrap()),
+        bits_per_sample: u16::from_le_bytes([fmt[14], fmt[15]]),
+        sample_format,
+    };
+    if spec.channels == 0 || spec.sample_rate == 0 || spec.bits_per_sample == 0 {
+        return Err("Format chunk has no channels, sample rate or sample size".into());
+    }
+    let supported = match sample_format {
+        SampleFormat::Int => (8..=32).contains(&spec.bits_per_sample),
+        SampleFormat::Float => matches!(spec.bits_per_sample, 32 | 64),
+    };
+    if !supported {
+        return Err(format!("Unsupported sample size of {} bits", spec.bits_per_sample).into());
+    }
+    
+    // Players trust these over the format, so a mismatch plays at the wrong speed or garbled
+    let block_align = u16::from_le_bytes([fmt[12], fmt[13]]);
+    let expected_align = spec.channels * spec.bits_per_sample.div_ceil(8);
+    if block_align != expected_align {
+        warnings.push(format!("Block align is {} but the format needs {}", block_align, expected_align));
+    }
+    let byte_rate = u32::from_le_bytes(fmt[8..12].try_into().unwrap());
+    let expected_rate = spec.sample_rate as u64 * expected_align as u64;
+    if byte_rate as u64 != expected_rate {
+        warnings.push(format!("Byte rate is {} but the format needs {}", byte_rate, expected_rate));
+    }
+    Ok(spec)
+}
+
+/// Settings for [`analyze_with`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnalyzeOptions {
+    /// Peak level, from 0.0 to 1.0, below which audio counts as silence
+    pub silence_threshold: f32,
+    /// Shortest stretch of silence worth reporting
+    pub min_silence: Duration,
+}
+
+impl Default for AnalyzeOptions {
+    fn default() -> Self {
+        Self {
+            // About -60 dBFS
+            silence_threshold: 0.001,
+            min_silence: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Audio statistics of a WAV file, from [`analyze`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioStats {
+    pub spec: WavSpec,
+    /// Length of the audio
+    pub duration: Duration,
+    /// Peak and RMS level over all channels
+    pub levels: Levels,
+    /// Samples at full scale, which were probably clipped
+    pub clipped_samples: u64,
+    /// Stretches of silence at least [`AnalyzeOptions::min_silence`] long
+    pub silence: Vec<Range<Duration>>,
+}
+
+impl AudioStats {
+    /// Total length of the reported silence
+    pub fn silent_duration(&self) -> Duration {
+        self.silence.iter().map(|range| range.end - range.start).sum()
+    }
+}
+
+impl fmt::Display for AudioStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Duration: {:.3}s", self.duration.as_secs_f64())?;
+        writeln!(f, "Peak: {:.3}, RMS: {:.3}", self.levels.peak, self.levels.rms)?;
+        writeln!(f, "Clipped samples: {}", self.clipped_samples)?;
+        write!(f, "Silence: {:.1}s in {} stretches", self.silent_duration().as_secs_f64(), self.silence.len())?;
+        for range in &self.silence {
+            write!(f, "\n  {:.1}s - {:.1}s", range.start.as_secs_f64(), range.end.as_secs_f64())?;
+        }
+        Ok(())
+    }
+}
+
+/// Length of the windows silence is measured in
+pub(crate) const SILENCE_WINDOW: Duration = Duration::from_millis(50);
+
+/// Measure a WAV file's levels, clipping and silence with default settings
+pub fn analyze(path: impl AsRef<Path>) -> hound::Result<AudioStats> {
+    analyze_with(path, &AnalyzeOptions::default())
+}
+
+/// Measure a WAV file's levels, clipping and silence
+///
+/// Streams through the file a window at a time, so recordings of any
+/// length are analyzed in constant memory. Supports integer PCM of up to 32
+/// bits, measured at full precision, and 32-bit float, where samples beyond
+/// full scale count as clipped.
+pub fn analyze_with(path: impl AsRef<Path>, options: &AnalyzeOptions) -> hound::Result<AudioStats> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    match spec.sample_format {
+        SampleFormat::Float if spec.bits_per_sample == 32 => {
+            let samples = reader.samples::<f32>().map(|sample| sample.map(|s| (s, s.abs() >= 1.0)));
+            analyze_samples(samples, spec, options)
+        }
+        SampleFormat::Int if (8..=32).contains(&spec.bits_per_sample) => {
+            let full_scale = (1i64 << (spec.bits_per_sample - 1)) as f64;
+            let max = (1i64 << (spec.bits_per_sample - 1)) - 1;
+            let samples = reader.samples::<i32>().map(move |sample| {
+                sample.map(|s| ((s as f64 / full_scale) as f32, s as i64 >= max || (s as i64) < -max))
+            });
+            analyze_samples(samples, spec, options)
+        }
+        _ => Err(hound::Error::Unsupported),
+    }
+}
+
+/// Measure `samples` of `spec`, each normalized to full scale and flagged if clipped
+fn analyze_samples<I>(mut samples: I, spec: WavSpec, options: &AnalyzeOptions) -> hound::Result<AudioStats>
+where
+    I: Iterator<Item = hound::Result<(f32, bool)>>,
+{
+    let window_len = (spec.sample_rate as u128 * SILENCE_WINDOW.as_millis() / 1000).max(1) as usize
+        * spec.channels as usize;
+    let frame_time = |frames: u64| Duration::from_secs_f64(frames as f64 / spec.sample_rate as f64);
+    
+    let mut peak = 0f32;
+    let mut sum_squares = 0f64;
+    let mut count = 0u64;
+    let mut clipped_samples = 0;
+    let mut silence = Vec::new();
+    let mut silent_since = None;
+    let mut frames = 0u64;
+    loop {
+        let mut window_peak = 0f32;
+        let mut window_samples = 0;
+        for sample in samples.by_ref().take(window_len) {
+            let (sample, clipped) = sample?;
+            window_peak = window_peak.max(sample.abs());
+            sum_squares += sample as f64 * sample as f64;
+            clipped_samples += u64::from(clipped);
+            window_samples += 1;
+        }
+        if window_samples == 0 {
+            break;
+        }
+        peak = peak.max(window_peak);
+        count += window_samples as u64;
+        
+        let start = frames;
+        frames += (window_samples / spec.channels as usize) as u64;
+        if window_peak < options.silence_threshold {
+            silent_since.get_or_insert(start);
+        } else if let Some(since) = silent_since.take() {
+            silence.push(frame_time(since)..frame_time(start));
+        }
+    }
+    if let Some(since) = silent_since {
+        silence.push(frame_time(since)..frame_time(frames));
+    }
+    silence.retain(|range| range.end - range.start >= options.min_silence);
+    
+    Ok(AudioStats {
+        spec,
+        duration: frame_time(frames),
+        levels: Levels {
+            peak: peak.min(1.0),
+            rms: ((sum_squares / count.max(1) as f64).sqrt() as f32).min(1.0),
+        },
+        clipped_samples,
+        silence,
+    })
+}
+
+/// What [`repair`] found and changed in a WAV file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepairSummary {
+    /// Data chunk size recorded in the header before repair
+    pub original_data_size: u32,
+    /// Data chunk size written by the repair
+    pub repaired_data_size: u32,
+    /// Trailing bytes of an incomplete sample frame that were cut off
+    pub truncated_bytes: u64,
+}
+
+impl RepairSummary {
+    /// Whether the file needed any changes
+    pub fn changed(&self) -> bool {
+        self.original_data_size != self.repaired_data_size || self.truncated_bytes > 0
+    }
+}
+
+/// Rebuild the header of a truncated or unfinalized WAV file in place
+///
+/// Recordings interrupted by a crash keep their audio but carry a header
+/// claiming less data than the file holds (often none). This walks the
+/// chunks to find `fmt ` and `data`, recomputes the data and RIFF sizes from
+/// the actual file length, and drops any trailing partial sample frame.
+pub fn repair(path: impl AsRef<Path>) -> Result<RepairSummary, Box<dyn std::error::Error>> {
+    let mut file = fs::OpenOptions::new().read(true).write(true).open(path)?;
+    let file_len = file.metadata()?.len();
+    
+    let mut header = [0u8; 12];
+    if file_len < 12 || file.read_exact(&mut header).is_err() {
+        return Err("File too small to be a valid WAV file".into());
+    }
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Err("Not a RIFF/WAVE file".into());
+    }
+    
+    // Find the data chunk and the frame size from fmt, wherever they are
+    let RiffLayout { chunks, .. } = walk_chunks(&mut file, file_len)?;
+    let data_chunk = chunks.iter().find(|chunk| chunk.id == "data").ok_or("No data chunk found")?;
+    let (data_offset, original_data_size) = (data_chunk.offset, data_chunk.size);
+    let mut block_align: Option<u16> = None;
+    if let Some(format_chunk) = chunks.iter().find(|chunk| chunk.id == "fmt " && chunk.size >= 16) {
+        let mut fmt = [0u8; 16];
+        file.seek(SeekFrom::Start(format_chunk.offset + 8))?;
+        file.read_exact(&mut fmt)?;
+        block_align = Some(u16::from_le_bytes([fmt[12], fmt[13]]));
+    }
+    
+    let block_align = match block_align {
+        Some(align) if align > 0 => align as u64,
+        _ => return Err("Format chunk missing or invalid".into()),
+    };
+    
+    // A finalized file's sizes already cover it, including any metadata
+    // chunks after the audio
+    let data_start = data_offset + 8;
+    let riff_size = u32::from_le_bytes(header[4..8].try_into().unwrap()) as u64;
+    if riff_size + 8 == file_len && data_start + original_data_size as u64 <= file_len {
+        return Ok(RepairSummary {
+            original_data_size,
+            repaired_data_size: original_data_size,
+            truncated_bytes: 0,
+        });
+    }
+
+    // A crashed recording's data runs to the end of the file; drop any
+    // incomplete frame and cap at what a 32-bit size field can describe
+    let available = file_len - data_start;
+    let max_data = (u32::MAX as u64 - (data_start - 8)) / block_align * block_align;
+    let data_size = (available / block_align * block_align).min(max_data);
+    let truncated_bytes = available - data_size;
+    
+    let summary = RepairSummary {
+        original_data_size,
+        repaired_data_size: data_size as u32,
+        truncated_bytes,
+    };
+    if !summary.changed() {
+        return Ok(summary);
+    }
+    
+    if truncated_bytes > 0 {
+        file.set_len(data_start + data_size)?;
+    }
+    file.seek(SeekFrom::Start(4))?;
+    file.write_all(&((data_start + data_size - 8) as u32).to_le_bytes())?;
+    file.seek(SeekFrom::Start(data_offset + 4))?;
+    file.write_all(&(data_size as u32).to_le_bytes())?;
+    file.sync_all()?;
+    
+    Ok(summary)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use hound::{WavWriter, WavSpec, SampleFormat};
 
     #[test]
     fn test_wav_file_validation() {
@@ -59,7 +720,126 @@ mod tests {
             writer.finalize().unwrap();
         }
         
-        assert!(validate_wav_file(test_file).is_ok());
+        let report = validate_wav_file(test_file).unwrap();
+        assert_eq!(report.spec, spec);
+        assert_eq!(report.data_size, 2000);
+        assert_eq!(report.duration, Duration::from_secs_f64(1000.0 / 44100.0));
+        let ids: Vec<_> = report.chunks.iter().map(|chunk| chunk.id.as_str()).collect();
+        assert_eq!(ids, ["fmt ", "data"]);
+        assert!(report.warnings.is_empty());
+        
+        // An unfinalized header is reported, not rejected
+        let mut bytes = fs::read(test_file).unwrap();
+        bytes[4..8].copy_from_slice(&36u32.to_le_bytes());
+        fs::write(test_file, &bytes).unwrap();
+        let report = validate_wav_file(test_file).unwrap();
+        assert_eq!(report.warnings.len(), 1);
+        assert!(report.warnings[0].contains("repair"));
+        
+        fs::remove_file(test_file).unwrap();
+    }
+
+    #[test]
+    fn test_wav_file_validation_finds_chunks_anywhere() {
+        let test_file = "test_validation_chunk_order.wav";
+        let mut body = b"WAVE".to_vec();
+        push_chunk(&mut body, b"JUNK", &[0; 28]);
+        // An odd-sized chunk written without its padding byte
+        body.extend_from_slice(b"LIST\x05\x00\x00\x00INFOx");
+        let fmt = [1, 0, 1, 0, 0x40, 0x1F, 0, 0, 0x80, 0x3E, 0, 0, 2, 0, 16, 0];
+        push_chunk(&mut body, b"fmt ", &fmt);
+        push_chunk(&mut body, b"fact", &4u32.to_le_bytes());
+        push_chunk(&mut body, b"data", &[0; 8]);
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&body);
+        fs::write(test_file, &bytes).unwrap();
+        
+        let report = validate_wav_file(test_file).unwrap();
+        let ids: Vec<_> = report.chunks.iter().map(|chunk| chunk.id.as_str()).collect();
+        assert_eq!(ids, ["JUNK", "LIST", "fmt ", "fact", "data"]);
+        assert_eq!(report.spec.sample_rate, 8000);
+        assert_eq!(report.data_size, 8);
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        assert!(!repair(test_file).unwrap().changed());
+        
+        fs::remove_file(test_file).unwrap();
+    }
+
+    #[test]
+    fn test_analyze_finds_clipping_and_silence() {
+        let test_file = "test_analyze.wav";
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 1000,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        // A second of tone with two clipped samples, three of silence, then half a second of tone
+        let mut writer = WavWriter::create(test_file, spec).unwrap();
+        for i in 0..4500 {
+            let sample = match i {
+                10 => i16::MAX,
+                20 => i16::MIN,
+                1000..4000 => 0,
+                _ => 1000,
+            };
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+        
+        let stats = analyze(test_file).unwrap();
+        assert_eq!(stats.duration, Duration::from_millis(4500));
+        assert_eq!(stats.levels.peak, 1.0);
+        assert_eq!(stats.clipped_samples, 2);
+        assert_eq!(stats.silence, vec![Duration::from_secs(1)..Duration::from_secs(4)]);
+        
+        fs::remove_file(test_file).unwrap();
+    }
+
+    #[test]
+    fn test_analyze_24_bit_and_float_at_full_precision() {
+        let test_file = "test_analyze_formats.wav";
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 1000,
+            bits_per_sample: 24,
+            sample_format: SampleFormat::Int,
+        };
+        // Below 16-bit resolution, but not silent at 24 bits
+        let mut writer = WavWriter::create(test_file, spec).unwrap();
+        for _ in 0..100 {
+            writer.write_sample(0x40).unwrap();
+        }
+        writer.write_sample(0x7F_FFFF).unwrap();
+        writer.finalize().unwrap();
+        let options = AnalyzeOptions {
+            silence_threshold: 1e-6,
+            min_silence: Duration::ZERO,
+        };
+        let stats = analyze_with(test_file, &options).unwrap();
+        assert_eq!(stats.clipped_samples, 1);
+        assert!(stats.silence.is_empty());
+        
+        let spec = WavSpec { bits_per_sample: 32, sample_format: SampleFormat::Float, ..spec };
+        let mut writer = WavWriter::create(test_file, spec).unwrap();
+        for sample in [0.5f32, -1.5, 0.25] {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+        let stats = analyze(test_file).unwrap();
+        assert_eq!(stats.clipped_samples, 1);
+        assert_eq!(stats.levels.peak, 1.0);
+        assert!(validate_wav_file(test_file).unwrap().warnings.is_empty());
+        
+        // A block align that disagrees with the format is flagged
+        let mut bytes = fs::read(test_file).unwrap();
+        let fmt = bytes.windows(4).position(|id| id == b"fmt ").unwrap();
+        bytes[fmt + 20..fmt + 22].copy_from_slice(&2u16.to_le_bytes());
+        fs::write(test_file, &bytes).unwrap();
+        let warnings = validate_wav_file(test_file).unwrap().warnings;
+        assert_eq!(warnings, ["Block align is 2 but the format needs 4"]);
+        
         fs::remove_file(test_file).unwrap();
     }
 
@@ -139,5 +919,120 @@ mod tests {
         
         fs::remove_file(test_file).unwrap();
     }
+
+    #[test]
+    fn test_repair_unfinalized_file() {
+        let test_file = "test_repair_unfinalized.wav";
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 48000,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        
+        {
+            let mut writer = WavWriter::create(test_file, spec).unwrap();
+            for i in 0..4000 {
+                writer.write_sample((i % 1000) as i16).unwrap();
+            }
+            writer.finalize().unwrap();
+        }
+        
+        // Zero the sizes as a crash before finalize would, and add half a frame
+        let mut bytes = fs::read(test_file).unwrap();
+        bytes[4..8].copy_from_slice(&36u32.to_le_bytes());
+        bytes[40..44].copy_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&[1, 2]);
+        fs::write(test_file, &bytes).unwrap();
+        
+        let summary = repair(test_file).unwrap();
+        assert!(summary.changed());
+        assert_eq!(summary.original_data_size, 0);
+        assert_eq!(summary.repaired_data_size, 8000);
+        assert_eq!(summary.truncated_bytes, 2);
+        
+        let reader = hound::WavReader::open(test_file).unwrap();
+        assert_eq!(reader.duration(), 2000);
+        
+        // A second pass has nothing left to fix
+        assert!(!repair(test_file).unwrap().changed());
+        
+        fs::remove_file(test_file).unwrap();
+    }
+
+    #[test]
+    fn test_metadata_chunks_follow_the_audio() {
+        let test_file = "test_metadata_chunks.wav";
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 48000,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let (mut writer, mut file) = create_recording_writer(test_file, spec, 1024).unwrap();
+        write_samples(&mut writer, &[1, 2, 3, 4]).unwrap();
+        writer.finalize().unwrap();
+        let metadata = WavMetadata {
+            title: "Weekly sync".to_string(),
+            // 2024-01-25 14:30:15 UTC
+            started: UNIX_EPOCH + std::time::Duration::from_secs(1_706_193_015),
+            microphone: "USB Mic".to_string(),
+            system_audio: None,
+            session_id: Uuid::nil(),
+        };
+        let markers = [Marker { position: 1, label: "decision".to_string() }];
+        let mut chunks = metadata_chunks(&spec, &metadata);
+        chunks.extend(cue_chunks(&markers));
+        append_chunks(&mut file, &chunks).unwrap();
+        drop(file);
+
+        let bytes = fs::read(test_file).unwrap();
+        assert_eq!(u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize, bytes.len() - 8);
+        let bext = &bytes[52..];
+        assert_eq!(&bext[..4], b"bext");
+        assert_eq!(&bext[8..19], b"Weekly sync");
+        assert_eq!(&bext[8 + 320..8 + 338], b"2024-01-2514:30:15");
+        assert_eq!(u64::from_le_bytes(bext[8 + 338..8 + 346].try_into().unwrap()), 52_215 * 48000);
+        let list = &bytes[52 + 8 + u32::from_le_bytes(bext[4..8].try_into().unwrap()) as usize..];
+        assert_eq!(&list[..4], b"LIST");
+        assert_eq!(&list[8..12], b"INFO");
+        assert!(list.windows(20).any(|text| text == b"Microphone: USB Mic\0"));
+        let cue = &list[8 + u32::from_le_bytes(list[4..8].try_into().unwrap()) as usize..];
+        assert_eq!(&cue[..4], b"cue ");
+        assert_eq!(u32::from_le_bytes(cue[8..12].try_into().unwrap()), 1);
+        assert_eq!(&cue[20..24], b"data");
+        assert_eq!(u32::from_le_bytes(cue[32..36].try_into().unwrap()), 1);
+        assert_eq!(&cue[36..40], b"LIST");
+        assert_eq!(&cue[44..56], b"adtllabl\x0d\0\0\0");
+        assert_eq!(&cue[60..69], b"decision\0");
+
+        // The audio is unaffected and the file needs no repair
+        let mut reader = hound::WavReader::open(test_file).unwrap();
+        let samples: Vec<i16> = reader.samples::<i16>().collect::<Result<_, _>>().unwrap();
+        assert_eq!(samples, vec![1, 2, 3, 4]);
+        assert!(!repair(test_file).unwrap().changed());
+
+        fs::remove_file(test_file).unwrap();
+    }
+
+    #[test]
+    fn test_partial_path_round_trip() {
+        let final_path = Path::new("/tmp/recordings/01-25-2024-14-30-recording.wav");
+        let partial = partial_path(final_path);
+        
+        assert_eq!(partial, Path::new("/tmp/recordings/01-25-2024-14-30-recording.wav.part"));
+        assert_eq!(completed_path(&partial).as_deref(), Some(final_path));
+        assert_eq!(completed_path(final_path), None);
+    }
+
+    #[test]
+    fn test_repair_rejects_non_wav() {
+        let test_file = "test_repair_not_wav.wav";
+        fs::write(test_file, b"this is definitely not a wav file").unwrap();
+        
+        assert!(repair(test_file).is_err());
+        
+        fs::remove_file(test_file).unwrap();
+    }
 }
 
@@ -0,0 +1,272 @@
+//! Overview waveform images of recordings
+//!
+//! Renders a WAV file as a PNG thumbnail, so a catalog or UI can show at a
+//! glance whether a recording holds a conversation or an hour of silence.
+//! The file is streamed once, so recordings of any length render in constant
+//! memory. Each column shows the peak range of its stretch of audio, with
+//! the RMS level drawn darker inside it.
+
+use std::fs;
+use std::path::Path;
+
+use crate::source::FileSource;
+
+/// Palette index of the background
+const BACKGROUND: u8 = 0;
+/// Palette index of the peak envelope
+const PEAK: u8 = 1;
+/// Palette index of the RMS band
+const RMS: u8 = 2;
+/// Background, peak and RMS colors
+const PALETTE: [[u8; 3]; 3] = [[0xFF, 0xFF, 0xFF], [0x7F, 0xA7, 0xD9], [0x1F, 0x4E, 0x8C]];
+
+/// Largest block deflate can store uncompressed
+const MAX_STORED_BLOCK: usize = 65_535;
+
+/// How to render a waveform
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WaveformOptions {
+    /// Image width in pixels, one column of audio each
+    pub width: u32,
+    /// Image height in pixels
+    pub height: u32,
+    /// Draw each channel in its own lane instead of one combined lane
+    pub per_channel: bool,
+}
+
+impl Default for WaveformOptions {
+    fn default() -> Self {
+        Self {
+            width: 1200,
+            height: 200,
+            per_channel: false,
+        }
+    }
+}
+
+/// Peak range and RMS of one column of one lane, from -1.0 to 1.0
+#[derive(Debug, Clone, Copy, Default)]
+struct Column {
+    min: f32,
+    max: f32,
+    sum_squares: f64,
+    samples: u64,
+}
+
+impl Column {
+    fn add(&mut self, sample: f32) {
+        self.min = self.min.min(sample);
+        self.max = self.max.max(sample);
+        self.sum_squares += sample as f64 * sample as f64;
+        self.samples += 1;
+    }
+
+    fn rms(&self) -> f32 {
+        (self.sum_squares / self.samples.max(1) as f64).sqrt() as f32
+    }
+}
+
+/// Render the WAV file at `input` as a PNG at `output`
+pub fn render_png(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    options: &WaveformOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if options.width == 0 || options.height == 0 {
+        return Err("Waveform images need a width and height of at least one pixel".into());
+    }
+    let lanes = measure(input.as_ref(), options)?;
+    let pixels = draw(&lanes, options);
+    fs::write(output, encode_png(options.width, options.height, &pixels))?;
+    Ok(())
+}
+
+/// Columns of each lane, streaming through the file once
+fn measure(path: &Path, options: &WaveformOptions) -> Result<Vec<Vec<Column>>, Box<dyn std::error::Error>> {
+    let source = FileSource::open(path)?;
+    let channels = source.format().channels as usize;
+    let total_frames = hound::WavReader::open(path)?.duration() as u64;
+    let width = options.width as usize;
+    let lane_count = if options.per_channel { channels } else { 1 };
+    let mut lanes = vec![vec![Column::default(); width]; lane_count];
+
+    let mut reader = source.reader()?;
+    let mut chunk = Vec::new();
+    let mut frame = 0u64;
+    loop {
+        reader.read_chunk(&mut chunk, source.chunk_len())?;
+        if chunk.is_empty() {
+            break;
+        }
+        for samples in chunk.chunks_exact(channels) {
+            let column = ((frame * width as u64) / total_frames.max(1)).min(width as u64 - 1) as usize;
+            for (channel, &sample) in samples.iter().enumerate() {
+                let lane = if options.per_channel { channel } else { 0 };
+                lanes[lane][column].add(sample as f32 / 32768.0);
+            }
+            frame += 1;
+        }
+    }
+    Ok(lanes)
+}
+
+/// Palette-indexed pixels of the image, row by row
+fn draw(lanes: &[Vec<Column>], options: &WaveformOptions) -> Vec<u8> {
+    let (width, height) = (options.width as usize, options.height as usize);
+    let mut pixels = vec![BACKGROUND; width * height];
+    let lane_height = height / lanes.len();
+    for (index, lane) in lanes.iter().enumerate() {
+        let top = index * lane_height;
+        let half = lane_height as f32 / 2.0;
+        let center = top as f32 + half;
+        // Row of a level, clamped to the lane
+        let row = |level: f32| ((center - level.clamp(-1.0, 1.0) * half) as usize).clamp(top, top + lane_height - 1);
+        for (x, column) in lane.iter().enumerate() {
+            // Even silence gets a line through the middle
+            let (max, min) = if column.samples == 0 { (0.0, 0.0) } else { (column.max, column.min) };
+            for y in row(max)..=row(min) {
+                pixels[y * width + x] = PEAK;
+            }
+            let rms = column.rms();
+            for y in row(rms)..=row(-rms) {
+                pixels[y * width + x] = RMS;
+            }
+        }
+    }
+    pixels
+}
+
+/// Encode palette-indexed `pixels` as a PNG
+///
+/// The image data is stored uncompressed in the zlib stream, which keeps the
+/// encoder small; thumbnails are only a few hundred kilobytes either way.
+fn encode_png(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8-bit palette indices, no interlacing
+    header.extend_from_slice(&[8, 3, 0, 0, 0]);
+    push_png_chunk(&mut png, b"IHDR", &header);
+    push_png_chunk(&mut png, b"PLTE", PALETTE.as_flattened());
+
+    // Each row starts with filter type 0 (none)
+    let mut raw = Vec::with_capacity(pixels.len() + height as usize);
+    for row in pixels.chunks_exact(width as usize) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    let mut zlib = vec![0x78, 0x01];
+    let block_count = raw.len().div_ceil(MAX_STORED_BLOCK).max(1);
+    for (index, block) in raw.chunks(MAX_STORED_BLOCK).enumerate() {
+        zlib.push(u8::from(index + 1 == block_count));
+        zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+    push_png_chunk(&mut png, b"IDAT", &zlib);
+    push_png_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn push_png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// CRC-32 (ISO 3309) as used by PNG chunks
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Adler-32 checksum that ends a zlib stream
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65_521;
+        b %= 65_521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hound::{SampleFormat, WavSpec, WavWriter};
+
+    /// Palette indices of a PNG written by [`encode_png`], row by row
+    fn decode_stored_png(png: &[u8], width: usize) -> Vec<Vec<u8>> {
+        let idat = png.windows(4).position(|kind| kind == b"IDAT").unwrap();
+        let len = u32::from_be_bytes(png[idat - 4..idat].try_into().unwrap()) as usize;
+        let mut zlib = &png[idat + 6..idat + 4 + len - 4];
+        let mut raw = Vec::new();
+        while !zlib.is_empty() {
+            let block_len = u16::from_le_bytes([zlib[1], zlib[2]]) as usize;
+            raw.extend_from_slice(&zlib[5..5 + block_len]);
+            zlib = &zlib[5 + block_len..];
+        }
+        raw.chunks_exact(width + 1).map(|row| row[1..].to_vec()).collect()
+    }
+
+    #[test]
+    fn test_known_checksums() {
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn test_renders_loud_and_silent_halves() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let input = temp_dir.path().join("meeting.wav");
+        let output = temp_dir.path().join("meeting.png");
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 8000,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        // Loud on the left channel for the first half, then silence
+        let mut writer = WavWriter::create(&input, spec).unwrap();
+        for frame in 0..8000 {
+            let left = if frame < 4000 { if frame % 2 == 0 { 16000 } else { -16000 } } else { 0 };
+            writer.write_sample(left as i16).unwrap();
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let options = WaveformOptions {
+            width: 100,
+            height: 40,
+            per_channel: true,
+        };
+        render_png(&input, &output, &options).unwrap();
+        let png = fs::read(&output).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        let rows = decode_stored_png(&png, 100);
+        assert_eq!(rows.len(), 40);
+
+        // The left lane spans half its height while loud, then flattens to a line
+        let drawn = |x: usize, lane: std::ops::Range<usize>| rows[lane].iter().filter(|row| row[x] != BACKGROUND).count();
+        assert_eq!(drawn(10, 0..20), 10);
+        assert_eq!(drawn(90, 0..20), 1);
+        // The silent right channel is a line throughout
+        assert_eq!(drawn(10, 20..40), 1);
+    }
+}
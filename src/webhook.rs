@@ -0,0 +1,238 @@
+//! Notifications when a recording finishes or fails
+//!
+//! With the `webhook` feature, the recorder POSTs a JSON message to a
+//! configured URL at the end of every session, so a team channel learns
+//! about each recording without anyone checking the machine that made it.
+//! The message is either a description of the session for your own
+//! services, or a `{"text": ...}` message in the format Slack, Mattermost
+//! and similar chat tools accept on their incoming webhooks. A webhook that
+//! can't be reached is reported, never an error of the recording.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::error::RecorderError;
+use crate::recorder::RecordingResult;
+
+/// Where and how to announce finished sessions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Webhook {
+    /// `http://` or `https://` URL the message is POSTed to
+    pub url: String,
+    /// Shape of the message
+    #[serde(default)]
+    pub format: WebhookFormat,
+    /// Extra request headers, e.g. `Authorization`
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+/// Shape of a webhook message
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookFormat {
+    /// The session's ID, file, duration, levels and summary, or its error
+    #[default]
+    Json,
+    /// A chat message for Slack-compatible incoming webhooks
+    Slack,
+}
+
+impl Webhook {
+    /// Whether the URL is one the webhook can POST to
+    pub fn has_valid_url(&self) -> bool {
+        let url = self.url.to_ascii_lowercase();
+        url.starts_with("http://") || url.starts_with("https://")
+    }
+
+    /// Message announcing how session `id` ended
+    pub fn message(&self, id: Uuid, outcome: &Result<RecordingResult, RecorderError>) -> Value {
+        match self.format {
+            WebhookFormat::Json => json_message(id, outcome),
+            WebhookFormat::Slack => json!({ "text": slack_text(outcome) }),
+        }
+    }
+}
+
+fn json_message(id: Uuid, outcome: &Result<RecordingResult, RecorderError>) -> Value {
+    match outcome {
+        Ok(result) => json!({
+            "event": "recording_completed",
+            "session_id": result.id,
+            "file": result.filename,
+            "stop_reason": result.stop_reason,
+            "duration_secs": result.duration.as_secs_f64(),
+            "levels": {
+                "microphone": { "peak": result.mic_levels.peak, "rms": result.mic_levels.rms },
+                "system_audio": result.sys_levels.map(|levels| json!({ "peak": levels.peak, "rms": levels.rms })),
+                "mix": { "peak": result.mix_levels.peak, "rms": result.mix_levels.rms },
+            },
+            "summary": {
+                "microphone": result.mic_summary,
+                "system_audio": result.sys_summary,
+            },
+            "dropouts": result.dropouts(),
+        }),
+        Err(RecorderError::Incomplete(incomplete)) => json!({
+            "event": "recording_failed",
+            "session_id": incomplete.id,
+            "file": incomplete.path,
+            "stop_reason": incomplete.reason,
+            "duration_secs": incomplete.duration.as_secs_f64(),
+            "finalized": incomplete.finalized,
+            "error": incomplete.to_string(),
+        }),
+        Err(e) => json!({
+            "event": "recording_failed",
+            "session_id": id,
+            "file": null,
+            "error": e.to_string(),
+        }),
+    }
+}
+
+fn slack_text(outcome: &Result<RecordingResult, RecorderError>) -> String {
+    match outcome {
+        Ok(result) => {
+            let mut text = format!(
+                ":white_check_mark: Recording saved: `{}` ({})",
+                result.filename.as_deref().unwrap_or("no WAV file"),
+                duration_text(result.duration)
+            );
+            let _ = write!(text, "\nMicrophone: {}", result.mic_summary);
+            if let Some(summary) = &result.sys_summary {
+                let _ = write!(text, "\nSystem audio: {}", summary);
+            }
+            let summaries = [Some(&result.mic_summary), result.sys_summary.as_ref()];
+            if summaries.iter().flatten().any(|summary| summary.clipped() || summary.mostly_silent()) {
+                text.push_str("\n:warning: Check the levels: a source clipped or was almost silent");
+            }
+            text
+        }
+        Err(e @ RecorderError::Incomplete(_)) => format!(":warning: {}", e),
+        Err(e) => format!(":x: Recording failed: {}", e),
+    }
+}
+
+/// `1h 02m 03s`, or `2m 03s` under an hour
+pub(crate) fn duration_text(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}h {:02}m {:02}s", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    }
+}
+
+/// How long delivering the message may take
+#[cfg(feature = "webhook")]
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[cfg(feature = "webhook")]
+impl Webhook {
+    /// POST `message` to the webhook
+    pub fn send(&self, message: &Value) -> Result<(), Box<ureq::Error>> {
+        let agent = ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build();
+        let mut request = agent.post(&self.url).set("Content-Type", "application/json");
+        for (name, value) in &self.headers {
+            request = request.set(name, value);
+        }
+        request.send_string(&message.to_string())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::Levels;
+    use crate::recorder::{IncompleteRecording, StopReason};
+    use crate::summary::AudioSummary;
+    use hound::{SampleFormat, WavSpec};
+
+    fn result() -> RecordingResult {
+        let summary = AudioSummary {
+            peak: 0.5,
+            loudness: Some(-23.0),
+            clipped_samples: 0,
+            silence: 0.1,
+        };
+        RecordingResult {
+            id: Uuid::nil(),
+            filename: Some("/rec/01-25-2024-14-30-recording.wav".to_string()),
+            title: None,
+            stop_reason: StopReason::Requested,
+            duration: Duration::from_secs(3723),
+            spec: WavSpec {
+                channels: 2,
+                sample_rate: 48000,
+                bits_per_sample: 16,
+                sample_format: SampleFormat::Int,
+            },
+            mic_samples: 0,
+            sys_samples: 0,
+            samples_written: 0,
+            frames_written: 0,
+            mic_levels: Levels { peak: 0.5, rms: 0.1 },
+            sys_levels: None,
+            mix_levels: Levels { peak: 0.5, rms: 0.1 },
+            mic_summary: summary,
+            sys_summary: None,
+            backpressure_events: 0,
+            backlog_events: 0,
+            stream_errors: 0,
+            markers: Vec::new(),
+            marker_exports: Vec::new(),
+            transcripts: Vec::new(),
+            upload: None,
+        }
+    }
+
+    fn webhook(format: WebhookFormat) -> Webhook {
+        Webhook {
+            url: "https://hooks.slack.com/services/T000/B000/XXXX".to_string(),
+            format,
+            headers: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_slack_messages() {
+        let slack = webhook(WebhookFormat::Slack);
+        assert_eq!(
+            slack.message(Uuid::nil(), &Ok(result())),
+            json!({ "text": ":white_check_mark: Recording saved: `/rec/01-25-2024-14-30-recording.wav` (1h 02m 03s)\n\
+                             Microphone: peak -6.0 dBFS, loudness -23.0 LUFS, 0 clipped samples, 10% silence" })
+        );
+
+        let incomplete = RecorderError::Incomplete(IncompleteRecording {
+            id: Uuid::nil(),
+            path: "/rec/meeting.wav".into(),
+            reason: StopReason::DeviceLost,
+            finalized: true,
+            duration: Duration::from_secs(90),
+            cause: "device unplugged".into(),
+        });
+        let text = slack.message(Uuid::nil(), &Err(incomplete))["text"].as_str().unwrap().to_string();
+        assert!(text.starts_with(":warning: Recording stopped early: device unplugged."), "{}", text);
+    }
+
+    #[test]
+    fn test_json_messages() {
+        let json = webhook(WebhookFormat::Json);
+        let message = json.message(Uuid::nil(), &Ok(result()));
+        assert_eq!(message["event"], "recording_completed");
+        assert_eq!(message["duration_secs"], 3723.0);
+        assert_eq!(message["summary"]["microphone"]["loudness"], -23.0);
+        assert_eq!(message["levels"]["system_audio"], Value::Null);
+
+        let message = json.message(Uuid::nil(), &Err(RecorderError::Busy));
+        assert_eq!(message["event"], "recording_failed");
+        assert_eq!(message["session_id"], Uuid::nil().to_string());
+        assert!(json.has_valid_url() && !Webhook { url: "hooks.example.com".into(), ..json }.has_valid_url());
+    }
+}
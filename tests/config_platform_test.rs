@@ -41,6 +41,7 @@ fn test_windows_path_handling() {
     {
         let config = Config {
             output_directory: "C:\\Recordings\\Meetings".to_string(),
+            ..Default::default()
         };
         
         let path = config.recording_path("test.wav");
@@ -57,6 +58,7 @@ fn test_unix_path_handling() {
     {
         let config = Config {
             output_directory: "/var/recordings/meetings".to_string(),
+            ..Default::default()
         };
         
         let path = config.recording_path("test.wav");
@@ -1,6 +1,9 @@
 // Integration test for configuration functionality
 
-use meeting_recorder::Config;
+use meeting_recorder::{Config, RecorderError};
+use meeting_recorder::error::ConfigError;
+use meeting_recorder::transcription::TranscriptFormat;
+use meeting_recorder::upload::Destination;
 use std::fs;
 use tempfile::TempDir;
 
@@ -75,6 +78,19 @@ fn test_config_invalid_yaml() {
     assert!(result.is_err());
 }
 
+#[test]
+fn test_config_errors_can_be_matched() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_file = temp_dir.path().join("config.yaml");
+    
+    let result = Config::load_from_path(&config_file);
+    assert!(matches!(result, Err(RecorderError::Config(ConfigError::NotFound(_)))));
+    
+    fs::write(&config_file, "invalid: yaml: content: [unclosed").unwrap();
+    let result = Config::load_from_path(&config_file);
+    assert!(matches!(result, Err(RecorderError::Config(ConfigError::Parse { .. }))));
+}
+
 #[test]
 fn test_config_missing_output_directory_field() {
     let temp_dir = TempDir::new().unwrap();
@@ -160,3 +176,261 @@ fn test_config_with_file_as_output_directory() {
     assert!(error_msg.contains("not a directory"), "Error should mention it's not a directory");
 }
 
+
+#[test]
+fn test_config_external_format_needs_an_encoder() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_file = temp_dir.path().join("config.yaml");
+    let config_content = format!(
+        "output_directory: {}\nformat: external\n",
+        temp_dir.path().to_string_lossy()
+    );
+    fs::write(&config_file, &config_content).unwrap();
+    let error = Config::load_from_path(&config_file).unwrap_err();
+    assert!(matches!(error, RecorderError::Config(ConfigError::MissingExternalEncoder)));
+
+    let config_content = format!(
+        "{}external_encoder:\n  extension: m4a\n  command: [ffmpeg, -i, \"-\", \"{{output}}\"]\n",
+        config_content
+    );
+    fs::write(&config_file, config_content).unwrap();
+    let config = Config::load_from_path(&config_file).unwrap();
+    assert_eq!(config.recording_extension(), "m4a");
+}
+
+#[test]
+fn test_config_transcription() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_file = temp_dir.path().join("config.yaml");
+    let config_content = format!(
+        "output_directory: {}\ntranscription:\n  model_path: /opt/whisper/ggml-base.en.bin\n  language: en\n",
+        temp_dir.path().to_string_lossy()
+    );
+    fs::write(&config_file, &config_content).unwrap();
+    let result = Config::load_from_path(&config_file);
+    if cfg!(feature = "whisper") {
+        let transcription = result.unwrap().transcription.unwrap();
+        assert_eq!(transcription.language.as_deref(), Some("en"));
+        assert_eq!(transcription.formats, [TranscriptFormat::Txt, TranscriptFormat::Srt]);
+    } else {
+        assert!(matches!(result, Err(RecorderError::Config(ConfigError::TranscriptionUnavailable))));
+    }
+
+    fs::write(&config_file, format!("{}format: flac\n", config_content)).unwrap();
+    let error = Config::load_from_path(&config_file).unwrap_err();
+    assert!(matches!(
+        error,
+        RecorderError::Config(ConfigError::TranscriptionUnavailable | ConfigError::TranscriptionNeedsWav)
+    ));
+}
+
+#[test]
+fn test_config_checksum_needs_plain_wav() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_file = temp_dir.path().join("config.yaml");
+    let config_content = format!("output_directory: {}\nchecksum: true\n", temp_dir.path().to_string_lossy());
+    fs::write(&config_file, &config_content).unwrap();
+    assert!(Config::load_from_path(&config_file).unwrap().checksum);
+
+    fs::write(&config_file, format!("{}format: flac\n", config_content)).unwrap();
+    let error = Config::load_from_path(&config_file).unwrap_err();
+    assert!(matches!(error, RecorderError::Config(ConfigError::ChecksumNeedsWav)));
+
+    fs::write(&config_file, format!("{}encryption:\n  recipients: [age1example]\n", config_content)).unwrap();
+    let error = Config::load_from_path(&config_file).unwrap_err();
+    assert!(matches!(error, RecorderError::Config(ConfigError::ChecksumWithEncryption)));
+}
+
+#[test]
+fn test_config_upload() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_file = temp_dir.path().join("config.yaml");
+    let config_content = format!(
+        "output_directory: {}\nupload:\n  s3:\n    bucket: meetings\n    endpoint: http://minio.internal:9000\n",
+        temp_dir.path().to_string_lossy()
+    );
+    fs::write(&config_file, config_content).unwrap();
+    let result = Config::load_from_path(&config_file);
+    if cfg!(feature = "upload") {
+        let upload = result.unwrap().upload.unwrap();
+        assert_eq!(upload.retries, 3);
+        let Destination::S3(s3) = upload.destination else {
+            panic!("expected an S3 destination");
+        };
+        assert_eq!(s3.bucket, "meetings");
+        assert_eq!(s3.region, "us-east-1");
+        assert_eq!(s3.endpoint.as_deref(), Some("http://minio.internal:9000"));
+    } else {
+        assert!(matches!(result, Err(RecorderError::Config(ConfigError::UploadUnavailable))));
+    }
+
+    let config_content = format!(
+        "output_directory: {}\nupload:\n  rsync:\n    host: archive@files.internal\n    directory: /srv/meetings\n  retries: 5\n",
+        temp_dir.path().to_string_lossy()
+    );
+    fs::write(&config_file, config_content).unwrap();
+    let result = Config::load_from_path(&config_file);
+    if cfg!(feature = "upload") {
+        let upload = result.unwrap().upload.unwrap();
+        assert_eq!(upload.retries, 5);
+        assert!(matches!(upload.destination, Destination::Rsync(remote) if remote.port.is_none()));
+    } else {
+        assert!(matches!(result, Err(RecorderError::Config(ConfigError::UploadUnavailable))));
+    }
+}
+
+#[test]
+fn test_config_hotkeys() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_file = temp_dir.path().join("config.yaml");
+    let config_content = format!(
+        "output_directory: {}\nhotkeys:\n  start_stop: ctrl+alt+R\n  marker: ctrl+alt+M\n",
+        temp_dir.path().to_string_lossy()
+    );
+    fs::write(&config_file, config_content).unwrap();
+    let result = Config::load_from_path(&config_file);
+    if cfg!(feature = "hotkeys") {
+        let hotkeys = result.unwrap().hotkeys.unwrap();
+        assert_eq!(hotkeys.marker.as_deref(), Some("ctrl+alt+M"));
+        assert_eq!(hotkeys.marker_label, "marker");
+    } else {
+        assert!(matches!(result, Err(RecorderError::Config(ConfigError::HotkeysUnavailable))));
+    }
+
+    let config_content = format!(
+        "output_directory: {}\nhotkeys:\n  start_stop: ctrl+alt+Rr\n",
+        temp_dir.path().to_string_lossy()
+    );
+    fs::write(&config_file, config_content).unwrap();
+    if cfg!(feature = "hotkeys") {
+        assert!(matches!(
+            Config::load_from_path(&config_file),
+            Err(RecorderError::Config(ConfigError::InvalidHotkey(shortcut))) if shortcut == "ctrl+alt+Rr"
+        ));
+    }
+}
+
+#[test]
+fn test_config_midi() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_file = temp_dir.path().join("config.yaml");
+    let config_content = format!(
+        "output_directory: {}\nmidi:\n  start_stop: note 36\n  pause: cc 20 channel 2\n",
+        temp_dir.path().to_string_lossy()
+    );
+    fs::write(&config_file, config_content).unwrap();
+    let result = Config::load_from_path(&config_file);
+    if cfg!(feature = "midi") {
+        let midi = result.unwrap().midi.unwrap();
+        assert_eq!(midi.pause.as_deref(), Some("cc 20 channel 2"));
+        assert_eq!((midi.port, midi.marker_label.as_str()), (None, "marker"));
+    } else {
+        assert!(matches!(result, Err(RecorderError::Config(ConfigError::MidiUnavailable))));
+    }
+
+    let config_content = format!("output_directory: {}\nmidi:\n  marker: note 36 channel 17\n", temp_dir.path().to_string_lossy());
+    fs::write(&config_file, config_content).unwrap();
+    if cfg!(feature = "midi") {
+        assert!(matches!(
+            Config::load_from_path(&config_file),
+            Err(RecorderError::Config(ConfigError::InvalidMidiControl(_)))
+        ));
+    }
+}
+
+#[test]
+fn test_config_notifications() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_file = temp_dir.path().join("config.yaml");
+    let config_content = format!("output_directory: {}\nnotifications: true\n", temp_dir.path().to_string_lossy());
+    fs::write(&config_file, config_content).unwrap();
+    let result = Config::load_from_path(&config_file);
+    if cfg!(feature = "notifications") {
+        assert!(result.unwrap().notifications);
+    } else {
+        assert!(matches!(result, Err(RecorderError::Config(ConfigError::NotificationsUnavailable))));
+    }
+}
+
+#[test]
+fn test_config_live() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_file = temp_dir.path().join("config.yaml");
+    let config_content = format!("output_directory: {}\nlive:\n  listen: localhost\n", temp_dir.path().to_string_lossy());
+    fs::write(&config_file, config_content).unwrap();
+    let result = Config::load_from_path(&config_file);
+    if cfg!(feature = "live") {
+        assert!(matches!(result, Err(RecorderError::Config(ConfigError::InvalidLiveAddress(_)))));
+    } else {
+        assert!(matches!(result, Err(RecorderError::Config(ConfigError::LiveUnavailable))));
+    }
+}
+
+#[test]
+fn test_config_collector_address() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_file = temp_dir.path().join("config.yaml");
+    for (address, valid) in [("recordings.example.com:9100", true), ("10.0.0.5", false), (":9100", false)] {
+        let config_content =
+            format!("output_directory: {}\ncollector:\n  address: \"{}\"\n", temp_dir.path().to_string_lossy(), address);
+        fs::write(&config_file, config_content).unwrap();
+        let result = Config::load_from_path(&config_file);
+        if valid {
+            assert_eq!(result.unwrap().collector.unwrap().max_queued_secs, 300);
+        } else {
+            assert!(matches!(result, Err(RecorderError::Config(ConfigError::InvalidCollectorAddress))));
+        }
+    }
+}
+
+#[test]
+fn test_config_calendar() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_file = temp_dir.path().join("config.yaml");
+    let config_content = format!(
+        "output_directory: {}\ncalendar:\n  url: https://calendar.example.com/me.ics\n  rules:\n    - title_contains: \"1:1\"\n    - location_contains: zoom.us\n      organizer_contains: example.com\n",
+        temp_dir.path().to_string_lossy()
+    );
+    fs::write(&config_file, config_content).unwrap();
+    let calendar = Config::load_from_path(&config_file).unwrap().calendar.unwrap();
+    assert_eq!(calendar.rules.len(), 2);
+    assert_eq!(calendar.rules[1].organizer_contains.as_deref(), Some("example.com"));
+    assert_eq!(calendar.rules[1].title_contains, None);
+    assert_eq!((calendar.refresh_minutes, calendar.start_early_secs, calendar.stop_late_secs), (5, 60, 300));
+
+    // Without rules nothing would ever be recorded
+    let config_content =
+        format!("output_directory: {}\ncalendar:\n  url: me.ics\n", temp_dir.path().to_string_lossy());
+    fs::write(&config_file, config_content).unwrap();
+    let result = Config::load_from_path(&config_file);
+    assert!(matches!(result, Err(RecorderError::Config(ConfigError::IncompleteCalendar))));
+}
+
+#[test]
+fn test_config_meeting_apps() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_file = temp_dir.path().join("config.yaml");
+    let config_content = format!(
+        "output_directory: {}\nmeeting_apps:\n  apps: [zoom, slack]\n  prompt: true\n",
+        temp_dir.path().to_string_lossy()
+    );
+    fs::write(&config_file, config_content).unwrap();
+    let apps = Config::load_from_path(&config_file).unwrap().meeting_apps.unwrap();
+    assert_eq!(apps.apps, ["zoom", "slack"]);
+    assert!(apps.prompt);
+    assert_eq!((apps.poll_secs, apps.stop_delay_secs), (2, 10));
+}
+
+#[test]
+fn test_config_activity_watch() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_file = temp_dir.path().join("config.yaml");
+    let config_content = format!(
+        "output_directory: {}\nactivity_watch:\n  threshold_db: -50\n",
+        temp_dir.path().to_string_lossy()
+    );
+    fs::write(&config_file, config_content).unwrap();
+    let watch = Config::load_from_path(&config_file).unwrap().activity_watch.unwrap();
+    assert_eq!(watch.threshold_db, -50.0);
+    assert_eq!((watch.start_after_secs, watch.stop_after_silence_secs), (3, 120));
+}
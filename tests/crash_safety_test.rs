@@ -0,0 +1,62 @@
+// Test that periodic checkpoints leave a playable WAV if the recorder dies
+// before finalizing
+
+use hound::{SampleFormat, WavReader, WavSpec};
+use meeting_recorder::wav::{checkpoint, create_recording_writer};
+use tempfile::TempDir;
+
+fn stereo_spec() -> WavSpec {
+    WavSpec {
+        channels: 2,
+        sample_rate: 48000,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    }
+}
+
+#[test]
+fn test_checkpoint_survives_crash() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("crashed.wav");
+
+    let (mut writer, sync_handle) = create_recording_writer(&path, stereo_spec(), 64 * 1024).unwrap();
+
+    // One second of audio, then a checkpoint
+    for i in 0..96000 {
+        writer.write_sample((i % 1000) as i16).unwrap();
+    }
+    checkpoint(&mut writer, &sync_handle, true).unwrap();
+
+    // More audio that never gets checkpointed
+    for i in 0..1000 {
+        writer.write_sample((i % 1000) as i16).unwrap();
+    }
+
+    // Simulate a crash: the writer's destructor (which would finalize) never runs
+    std::mem::forget(writer);
+
+    let mut reader = WavReader::open(&path).expect("Crashed file should still open");
+    assert_eq!(reader.spec(), stereo_spec());
+    assert_eq!(reader.duration(), 48000, "Header should cover audio up to the checkpoint");
+
+    let samples: Vec<i16> = reader.samples::<i16>().collect::<Result<_, _>>().unwrap();
+    assert_eq!(samples.len(), 96000);
+    assert_eq!(samples[999], 999);
+}
+
+#[test]
+fn test_unflushed_writer_leaves_empty_header() {
+    // Without a checkpoint the header still claims zero-length data, which is
+    // what periodic checkpoints protect against
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("unflushed.wav");
+
+    let (mut writer, _sync_handle) = create_recording_writer(&path, stereo_spec(), 1024).unwrap();
+    for i in 0..96000 {
+        writer.write_sample((i % 1000) as i16).unwrap();
+    }
+    std::mem::forget(writer);
+
+    let reader = WavReader::open(&path).unwrap();
+    assert_eq!(reader.duration(), 0);
+}
@@ -0,0 +1,179 @@
+// Runs whole recording sessions from WAV files instead of audio devices
+
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+use meeting_recorder::recorder::StopReason;
+use meeting_recorder::checksum;
+use meeting_recorder::chunking::{self, ChunkOptions};
+use meeting_recorder::events::RecorderEvent;
+use meeting_recorder::sidecar;
+use meeting_recorder::sink::RecordingFormat;
+use meeting_recorder::source::{FileSource, Input};
+use meeting_recorder::{Config, Recorder};
+use std::path::Path;
+
+fn write_track(path: &Path, channels: u16, sample_rate: u32, frames: usize, value: i16) {
+    let spec = WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    };
+    let mut writer = WavWriter::create(path, spec).unwrap();
+    for _ in 0..frames * channels as usize {
+        writer.write_sample(value).unwrap();
+    }
+    writer.finalize().unwrap();
+}
+
+#[test]
+fn test_session_from_files_stops_at_end_of_input() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let mic_path = temp_dir.path().join("mic.wav");
+    let sys_path = temp_dir.path().join("sys.wav");
+    // A quarter second of each, at different formats
+    write_track(&mic_path, 1, 16000, 4000, 1000);
+    write_track(&sys_path, 2, 48000, 12000, 2000);
+
+    let output_dir = temp_dir.path().join("out");
+    std::fs::create_dir(&output_dir).unwrap();
+    let config = Config {
+        output_directory: output_dir.to_string_lossy().into_owned(),
+        min_free_space_mb: 0,
+        ..Config::default()
+    };
+
+    let recorder = Recorder::from_inputs(
+        Input::from(FileSource::open(&mic_path).unwrap()),
+        Some(Input::from(FileSource::open(&sys_path).unwrap())),
+    );
+    let result = recorder.record(&config).unwrap();
+
+    assert_eq!(result.stop_reason, StopReason::EndOfInput);
+    assert_eq!(result.mic_samples, 4000);
+    assert_eq!(result.sys_samples, 24000);
+    assert_eq!(result.spec.sample_rate, 48000);
+
+    let mut reader = WavReader::open(result.filename.unwrap()).unwrap();
+    let samples: Vec<i16> = reader.samples::<i16>().collect::<Result<_, _>>().unwrap();
+    assert_eq!(samples.len() as u64, result.samples_written);
+    assert_eq!(samples.len(), 24000);
+    assert!(samples.contains(&3000), "the tracks were never mixed");
+}
+
+#[test]
+fn test_session_records_to_flac() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let mic_path = temp_dir.path().join("mic.wav");
+    write_track(&mic_path, 1, 48000, 4800, 1000);
+
+    let config = Config {
+        output_directory: temp_dir.path().to_string_lossy().into_owned(),
+        min_free_space_mb: 0,
+        format: RecordingFormat::Flac,
+        ..Config::default()
+    };
+    let recorder = Recorder::from_inputs(Input::from(FileSource::open(&mic_path).unwrap()), None);
+    let result = recorder.record(&config).unwrap();
+
+    let path = result.filename.unwrap();
+    assert!(path.ends_with(".flac"));
+    let mut reader = claxon::FlacReader::open(&path).unwrap();
+    assert_eq!(reader.streaminfo().samples, Some(4800));
+    // The mono track is upmixed to both channels
+    assert!(reader.samples().all(|sample| sample.unwrap() == 1000));
+}
+
+#[test]
+fn test_markers_become_cue_points() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let mic_path = temp_dir.path().join("mic.wav");
+    write_track(&mic_path, 1, 16000, 4000, 1000);
+
+    let config = Config {
+        output_directory: temp_dir.path().to_string_lossy().into_owned(),
+        min_free_space_mb: 0,
+        ..Config::default()
+    };
+    let recorder = Recorder::from_inputs(Input::from(FileSource::open(&mic_path).unwrap()), None);
+    let session = recorder.start(&config).unwrap();
+    let marker = session.add_marker("decision");
+    let result = session.join().unwrap();
+
+    assert_eq!(result.markers, vec![marker.clone()]);
+    assert!(marker.position <= result.frames_written);
+    let path = result.filename.unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    assert!(bytes.windows(4).any(|id| id == b"cue "));
+    assert!(bytes.windows(9).any(|label| label == b"decision\0"));
+
+    // The sidecar describes the session, markers included
+    let sidecar: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(sidecar::sidecar_path(&path)).unwrap()).unwrap();
+    assert_eq!(sidecar["session_id"], result.id.to_string());
+    assert_eq!(sidecar["devices"]["microphone"]["name"], "mic.wav");
+    assert_eq!(sidecar["devices"]["system_audio"], serde_json::Value::Null);
+    assert_eq!(sidecar["markers"][0]["label"], "decision");
+    assert_eq!(sidecar["markers"][0]["position"], marker.position);
+}
+
+#[test]
+fn test_checksum_covers_the_recorded_audio() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let mic_path = temp_dir.path().join("mic.wav");
+    write_track(&mic_path, 1, 16000, 1600, 1000);
+
+    let config = Config {
+        output_directory: temp_dir.path().to_string_lossy().into_owned(),
+        min_free_space_mb: 0,
+        checksum: true,
+        ..Config::default()
+    };
+    let recorder = Recorder::from_inputs(Input::from(FileSource::open(&mic_path).unwrap()), None);
+    let path = recorder.record(&config).unwrap().filename.unwrap();
+    assert!(checksum::verify(&path).unwrap());
+
+    // Change one sample at the end of the audio
+    let mut bytes = std::fs::read(&path).unwrap();
+    let data_end = 44 + u32::from_le_bytes(bytes[40..44].try_into().unwrap()) as usize;
+    bytes[data_end - 1] ^= 1;
+    std::fs::write(&path, bytes).unwrap();
+    assert!(!checksum::verify(&path).unwrap());
+}
+
+#[test]
+fn test_chunks_are_written_while_recording() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let mic_path = temp_dir.path().join("mic.wav");
+    // Three and a half seconds
+    write_track(&mic_path, 1, 16000, 56000, 1000);
+
+    let config = Config {
+        output_directory: temp_dir.path().to_string_lossy().into_owned(),
+        min_free_space_mb: 0,
+        chunks: Some(ChunkOptions { length_secs: 2, overlap_secs: 1 }),
+        ..Config::default()
+    };
+    let recorder = Recorder::from_inputs(Input::from(FileSource::open(&mic_path).unwrap()), None);
+    let events = recorder.subscribe();
+    let path = recorder.record(&config).unwrap().filename.unwrap();
+
+    let directory = chunking::chunk_directory(&path);
+    let mut chunks: Vec<_> = std::fs::read_dir(&directory).unwrap().map(|entry| entry.unwrap().path()).collect();
+    chunks.sort();
+    let stem = Path::new(&path).file_stem().unwrap().to_string_lossy().into_owned();
+    assert_eq!(chunks.len(), 3);
+    assert_eq!(chunks[2].file_name().unwrap().to_string_lossy(), format!("{}-chunk-0002.wav", stem));
+    // Two seconds each, then the second and a half from the two-second mark on
+    let frames: Vec<u32> = chunks.iter().map(|chunk| WavReader::open(chunk).unwrap().duration()).collect();
+    assert_eq!(frames, [32000, 32000, 24000]);
+
+    let rotated: Vec<_> = events
+        .try_iter()
+        .filter_map(|event| match event {
+            RecorderEvent::SegmentRotated { index, path, start } => Some((index, path, start.as_secs())),
+            _ => None,
+        })
+        .collect();
+    let expected: Vec<_> = chunks.iter().enumerate().map(|(i, chunk)| (i as u64, chunk.clone(), i as u64)).collect();
+    assert_eq!(rotated, expected);
+}
@@ -1,41 +1,54 @@
 // Test to validate audio mixing logic
 
+use meeting_recorder::dsp;
+use meeting_recorder::mixer::{Mixer, Source, SourceFormat};
+
 #[test]
 fn test_stereo_mixing() {
     // Test that mixing two stereo samples works correctly
-    let mic_left = 1000i16;
-    let mic_right = 2000i16;
-    let sys_left = 3000i16;
-    let sys_right = 4000i16;
+    let mic = [1000i16, 2000i16];
+    let sys = [3000i16, 4000i16];
+    let mut mixed = [0i16; 2];
     
-    let mixed_left = (mic_left as i32 + sys_left as i32)
-        .clamp(i16::MIN as i32, i16::MAX as i32) as i16;
-    let mixed_right = (mic_right as i32 + sys_right as i32)
-        .clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+    dsp::mix_saturating(&mic, &sys, &mut mixed);
     
-    assert_eq!(mixed_left, 4000);
-    assert_eq!(mixed_right, 6000);
+    assert_eq!(mixed[0], 4000);
+    assert_eq!(mixed[1], 6000);
 }
 
 #[test]
 fn test_mixing_with_clipping() {
     // Test that mixing prevents clipping
-    let mic = 20000i16;
-    let sys = 20000i16;
+    let mut mixed = [0i16; 1];
     
-    let mixed = (mic as i32 + sys as i32)
-        .clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+    dsp::mix_saturating(&[20000], &[20000], &mut mixed);
     
-    assert_eq!(mixed, i16::MAX); // Should clamp to max
+    assert_eq!(mixed[0], i16::MAX); // Should clamp to max
 }
 
 #[test]
 fn test_mono_to_stereo_conversion() {
     // Test mono to stereo conversion
     let mono_samples = vec![1000i16, 2000i16, 3000i16];
-    let stereo: Vec<i16> = mono_samples.iter().flat_map(|&s| [s, s]).collect();
+    let mut stereo = Vec::new();
+    dsp::append_stereo(&mut stereo, &mono_samples, 1);
     
     assert_eq!(stereo.len(), 6);
     assert_eq!(stereo, vec![1000, 1000, 2000, 2000, 3000, 3000]);
 }
 
+#[test]
+fn test_mixer_combines_mono_mic_with_stereo_system_audio() {
+    let mut mixer = Mixer::new(
+        SourceFormat { channels: 1, sample_rate: 48000 },
+        Some(SourceFormat { channels: 2, sample_rate: 48000 }),
+        48000,
+    );
+    mixer.push(Source::Microphone, &[1000, 2000]);
+    mixer.push(Source::SystemAudio, &[3000, 4000, 20000, -5000]);
+    
+    let mut mixed = Vec::new();
+    mixer.mix(&mut mixed);
+    
+    assert_eq!(mixed, vec![4000, 5000, 22000, -3000]);
+}
@@ -104,6 +104,7 @@ fn test_cross_platform_path_join() {
     // Test that PathBuf.join works correctly on all platforms
     let config = Config {
         output_directory: "/tmp/test".to_string(),
+        ..Default::default()
     };
     
     let path = config.recording_path("file.wav");
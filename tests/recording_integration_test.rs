@@ -2,6 +2,7 @@
 // Simulates recording from microphone and system audio, mixing them into a single WAV file
 
 use hound::{WavReader, WavSpec, SampleFormat};
+use meeting_recorder::mixer::{Mixer, Source, SourceFormat};
 use std::fs;
 use std::sync::mpsc;
 use std::thread;
@@ -57,8 +58,12 @@ fn test_full_recording_workflow() {
     // Start mixer thread (simulating the recorder's mixer)
     let mixer_handle = thread::spawn(move || {
         let mut writer = hound::WavWriter::create(test_file, spec).unwrap();
-        let mut mic_buffer: Vec<i16> = Vec::new();
-        let mut sys_buffer: Vec<i16> = Vec::new();
+        let mut mixer = Mixer::new(
+            SourceFormat { channels: 1, sample_rate: 48000 },
+            Some(SourceFormat { channels: 2, sample_rate: 48000 }),
+            48000,
+        );
+        let mut mixed: Vec<i16> = Vec::new();
         let mut running = true;
         let mut iterations = 0;
         
@@ -66,49 +71,23 @@ fn test_full_recording_workflow() {
             iterations += 1;
             let mut received_any = false;
             
-            // Receive mic samples (mono -> convert to stereo)
+            // Receive mic samples (mono, upmixed to stereo by the mixer)
             while let Ok(samples) = mic_rx.try_recv() {
                 received_any = true;
-                let stereo: Vec<i16> = samples.iter().flat_map(|&s| [s, s]).collect();
-                mic_buffer.extend(stereo);
+                mixer.push(Source::Microphone, &samples);
             }
             
             // Receive system audio samples (already stereo)
             while let Ok(samples) = sys_rx.try_recv() {
                 received_any = true;
-                sys_buffer.extend(samples);
+                mixer.push(Source::SystemAudio, &samples);
             }
             
             // Mix and write
-            let min_len = mic_buffer.len().min(sys_buffer.len());
-            if min_len > 0 {
-                let pairs = min_len / 2;
-                for i in 0..pairs {
-                    let mic_left = mic_buffer[i * 2];
-                    let mic_right = mic_buffer[i * 2 + 1];
-                    let sys_left = sys_buffer[i * 2];
-                    let sys_right = sys_buffer[i * 2 + 1];
-                    
-                    let mixed_left = (mic_left as i32 + sys_left as i32)
-                        .clamp(i16::MIN as i32, i16::MAX as i32) as i16;
-                    let mixed_right = (mic_right as i32 + sys_right as i32)
-                        .clamp(i16::MIN as i32, i16::MAX as i32) as i16;
-                    
-                    writer.write_sample(mixed_left).unwrap();
-                    writer.write_sample(mixed_right).unwrap();
-                }
-                mic_buffer.drain(0..pairs * 2);
-                sys_buffer.drain(0..pairs * 2);
-            } else if !mic_buffer.is_empty() {
-                for &sample in &mic_buffer {
-                    writer.write_sample(sample).unwrap();
-                }
-                mic_buffer.clear();
-            } else if !sys_buffer.is_empty() {
-                for &sample in &sys_buffer {
-                    writer.write_sample(sample).unwrap();
-                }
-                sys_buffer.clear();
+            mixed.clear();
+            mixer.mix(&mut mixed);
+            for &sample in &mixed {
+                writer.write_sample(sample).unwrap();
             }
             
             if !received_any {
@@ -122,21 +101,10 @@ fn test_full_recording_workflow() {
         }
         
         // Drain remaining
-        let max_len = mic_buffer.len().max(sys_buffer.len());
-        let pairs = max_len / 2;
-        for i in 0..pairs {
-            let mic_left = mic_buffer.get(i * 2).copied().unwrap_or(0);
-            let mic_right = mic_buffer.get(i * 2 + 1).copied().unwrap_or(0);
-            let sys_left = sys_buffer.get(i * 2).copied().unwrap_or(0);
-            let sys_right = sys_buffer.get(i * 2 + 1).copied().unwrap_or(0);
-            
-            let mixed_left = (mic_left as i32 + sys_left as i32)
-                .clamp(i16::MIN as i32, i16::MAX as i32) as i16;
-            let mixed_right = (mic_right as i32 + sys_right as i32)
-                .clamp(i16::MIN as i32, i16::MAX as i32) as i16;
-            
-            writer.write_sample(mixed_left).unwrap();
-            writer.write_sample(mixed_right).unwrap();
+        mixed.clear();
+        mixer.finish(&mut mixed);
+        for &sample in &mixed {
+            writer.write_sample(sample).unwrap();
         }
         
         writer.finalize().unwrap();
@@ -195,7 +163,6 @@ fn test_full_recording_workflow() {
 #[test]
 fn test_mixing_different_sample_rates() {
     // Test that mixing works even when sources have different sample rates
-    // (In practice, we'd resample, but for now we test the mixing logic)
     let test_file = "test_mixed_rates.wav";
     
     let spec = WavSpec {
@@ -207,19 +174,26 @@ fn test_mixing_different_sample_rates() {
     
     let mut writer = hound::WavWriter::create(test_file, spec).unwrap();
     
-    // Simulate mic samples (mono, 48kHz) - 100 samples
+    // Simulate mic samples (mono, 24kHz) - 100 samples, resampled to 48kHz
     let mic_samples: Vec<i16> = (0..100).map(|i| (i * 100) as i16).collect();
-    let mic_stereo: Vec<i16> = mic_samples.iter().flat_map(|&s| [s, s]).collect();
     
     // Simulate system samples (stereo, 48kHz) - 100 samples (50 stereo pairs)
     let sys_samples: Vec<i16> = (0..100).map(|i| (i * 50) as i16).collect();
     
     // Mix them
-    let min_len = mic_stereo.len().min(sys_samples.len());
-    for i in 0..min_len {
-        let mixed = (mic_stereo[i] as i32 + sys_samples[i] as i32)
-            .clamp(i16::MIN as i32, i16::MAX as i32) as i16;
-        writer.write_sample(mixed).unwrap();
+    let mut mixer = Mixer::new(
+        SourceFormat { channels: 1, sample_rate: 24000 },
+        Some(SourceFormat { channels: 2, sample_rate: 48000 }),
+        48000,
+    );
+    mixer.push(Source::Microphone, &mic_samples);
+    mixer.push(Source::SystemAudio, &sys_samples);
+    let mut mixed = Vec::new();
+    mixer.mix(&mut mixed);
+    mixer.finish(&mut mixed);
+    let min_len = sys_samples.len();
+    for &sample in &mixed {
+        writer.write_sample(sample).unwrap();
     }
     
     writer.finalize().unwrap();
@@ -250,8 +224,13 @@ fn test_mixing_with_one_source_missing() {
     let mic_samples: Vec<i16> = (0..1000).map(|i| ((i % 100) * 10) as i16).collect();
     let mic_stereo: Vec<i16> = mic_samples.iter().flat_map(|&s| [s, s]).collect();
     
-    // Write mic samples (no system audio to mix)
-    for &sample in &mic_stereo {
+    // Mix mic samples (no system audio to mix)
+    let mut mixer = Mixer::new(SourceFormat { channels: 1, sample_rate: 48000 }, None, 48000);
+    mixer.push(Source::Microphone, &mic_samples);
+    let mut mixed = Vec::new();
+    mixer.mix(&mut mixed);
+    mixer.finish(&mut mixed);
+    for &sample in &mixed {
         writer.write_sample(sample).unwrap();
     }
     
@@ -51,6 +51,7 @@ fn test_filename_with_timestamp_format() {
     // Test that filenames with the new format work correctly with Config
     let config = Config {
         output_directory: "/tmp/recordings".to_string(),
+        ..Default::default()
     };
     
     // Test with the new timestamp format: mm-dd-yyyy-24h-m-recording.wav